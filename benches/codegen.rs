@@ -0,0 +1,77 @@
+//! Codegen throughput benchmarks. Loads the assemblies captured in the corpus directory(`benches/corpus`, or `RCCLR_BENCH_CORPUS`), and measures
+//! deserialization, optimization, serialization and export, reporting the throughput of each phase in ops/s, and the peak memory usage.
+//!
+//! To capture a corpus, build any crate using the backend with `RCCLR_CAPTURE_DIR=benches/corpus` set.
+//! Type translation and method lowering can only run inside rustc: build a crate with `RCCLR_TIMINGS=1` to measure them.
+use rustc_codegen_clr::{
+    assembly::Assembly,
+    assembly_exporter::{ilasm_exporter::ILASMExporter, AssemblyExporter},
+    timings::Timings,
+};
+use std::path::PathBuf;
+fn corpus() -> Vec<(String, Vec<u8>)> {
+    let dir: PathBuf = std::env::var("RCCLR_BENCH_CORPUS")
+        .unwrap_or_else(|_| "benches/corpus".into())
+        .into();
+    let mut corpus: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap_or_else(|_| panic!("Could not read the benchmark corpus at {dir:?}"))
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "bc" {
+                return None;
+            }
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            Some((name, std::fs::read(path).ok()?))
+        })
+        .collect();
+    corpus.sort();
+    corpus
+}
+fn deserialize(bytes: &[u8]) -> Assembly {
+    rustc_codegen_clr::serialization::deserialize(bytes)
+        .expect("Could not deserialize a captured assembly!")
+}
+/// Generates the ILASM text of `asm`, without running ILASM.
+fn export(asm: &Assembly) {
+    let mut exporter = ILASMExporter::init("bench");
+    for (asm_name, asm_ref) in asm.extern_refs() {
+        exporter.add_extern_ref(asm_name, asm_ref);
+    }
+    for tpe in asm.types() {
+        exporter.add_type(tpe);
+    }
+    for method in asm.methods() {
+        exporter.add_method(method);
+    }
+    for (name, tpe) in asm.globals() {
+        exporter.add_global(tpe, name);
+    }
+    std::hint::black_box(exporter);
+}
+fn main() {
+    let iters: usize = std::env::var("RCCLR_BENCH_ITERS")
+        .ok()
+        .and_then(|iters| iters.parse().ok())
+        .unwrap_or(10);
+    let corpus = corpus();
+    if corpus.is_empty() {
+        eprintln!("The benchmark corpus is empty. Capture it using `RCCLR_CAPTURE_DIR`.");
+        return;
+    }
+    for (name, bytes) in &corpus {
+        let mut timings = Timings::new();
+        let ops = deserialize(bytes).op_count();
+        for _ in 0..iters {
+            let mut asm = timings.time("deserialize", ops, || deserialize(bytes));
+            timings.time("opt", ops, || asm.opt());
+            let ops = asm.op_count();
+            let serialized = timings.time("serialize", ops, || asm.to_bytes());
+            std::hint::black_box(serialized);
+            timings.time("export", ops, || export(&asm));
+        }
+        println!(
+            "{name}({ops} ops, {iters} iterations):\n{report}",
+            report = timings.report()
+        );
+    }
+}
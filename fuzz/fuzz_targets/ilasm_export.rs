@@ -0,0 +1,20 @@
+//! Generates arbitrary methods, exports them using ILASM, and verifies them using ILVerify(if `RCCLR_ILVERIFY_REFS` is set).
+//! Failing methods are shrunk before being reported. Run with `cargo fuzz run ilasm_export`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rustc_codegen_clr::fuzz::{check_method, Entropy, MethodGenerator};
+use std::path::Path;
+
+fuzz_target!(|data: &[u8]| {
+    let dir = Path::new("work").join(std::process::id().to_string());
+    let generated = MethodGenerator::new(Entropy::new(data)).generate();
+    if let Err(err) = check_method(&generated.to_method("fuzzed"), &dir) {
+        let shrunk = generated.shrink(|method| check_method(method, &dir).is_err());
+        let method = shrunk.to_method("fuzzed");
+        let reason = check_method(&method, &dir).unwrap_err();
+        panic!(
+            "Method failed to export or verify with error:\n{err}\nShrunk method:\n{ops:#?}\nShrunk error:\n{reason}",
+            ops = method.get_ops()
+        );
+    }
+});
@@ -78,10 +78,22 @@ pub struct RustcCLRInteropManagedChar {
 }
 #[derive(Clone, Copy)]
 #[repr(C)]
+pub struct RustcCLRInteropManagedRune {
+    value: u32,
+}
+#[derive(Clone, Copy)]
+#[repr(C)]
 struct RustcCLRInteropManagedArray<T, const DIMENSIONS: usize> {
     object_ref: usize,
     pd: core::marker::PhantomData<T>,
 }
+/// Replaced by the backend with the IL text `IL`, inserted verbatim. `MAX_STACK` is the maximum number of values the IL pushes onto the
+/// evaluation stack. Use the `clr_inline_il!` macro instead of calling this directly.
+#[allow(dead_code)]
+#[inline(never)]
+pub(crate) fn rustc_clr_inline_il<const IL: &'static str, const MAX_STACK: usize>() {
+    core::intrinsics::abort();
+}
 //Calls
 #[allow(unused_variables)]
 #[inline(never)]
@@ -231,6 +243,16 @@ impl From<u16> for RustcCLRInteropManagedChar {
         }
     }
 }
+impl From<char> for RustcCLRInteropManagedRune {
+    fn from(value: char) -> RustcCLRInteropManagedRune {
+        unsafe { core::mem::transmute::<char, RustcCLRInteropManagedRune>(value) }
+    }
+}
+impl From<RustcCLRInteropManagedRune> for char {
+    fn from(rune: RustcCLRInteropManagedRune) -> char {
+        unsafe { core::mem::transmute::<RustcCLRInteropManagedRune, char>(rune) }
+    }
+}
 /*
 impl TryFrom<char> for RustcCLRInteropManagedChar{
     type Error = (RustcCLRInteropManagedChar,RustcCLRInteropManagedChar);
@@ -6,6 +6,16 @@
 #![allow(internal_features, incomplete_features)]
 #![feature(core_intrinsics, adt_const_params)]
 
+/// Inserts raw IL into the current method, for hand-optimizing hot code. The IL must leave the evaluation stack as it found it, and may only
+/// refer to the arguments of the method. `max_stack` is the maximum number of values the IL pushes onto the stack. Only usable within this
+/// crate: the backend rejects inline IL anywhere else.
+macro_rules! clr_inline_il {
+    ($max_stack:literal, $il:literal) => {
+        $crate::intrinsics::rustc_clr_inline_il::<$il, $max_stack>()
+    };
+}
+// Shims may also be written entirely in IL, as functions with a `#[link_section = "clr_il_body:MAX_STACK:IL"]` attribute. The backend ignores
+// their Rust body, and uses the IL following the stack depth as their whole body. This is also only allowed within this crate.
 /// Very low-level interop stuff. Don't use unless you need to.
 pub mod intrinsics;
 /// Reimplementation of some Rust std APIs
@@ -14,6 +24,8 @@ pub mod std;
 pub mod system;
 /// C# `char` type
 pub type DotNetChar = crate::intrinsics::RustcCLRInteropManagedChar;
+/// C# `System.Text.Rune` type
+pub type DotNetRune = crate::intrinsics::RustcCLRInteropManagedRune;
 
 #[macro_export]
 macro_rules! panic_handler {
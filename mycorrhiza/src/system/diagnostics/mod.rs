@@ -28,3 +28,14 @@ impl Stopwatch {
         Self::virt0::<"get_ElapsedMilliseconds", i64>(self)
     }
 }
+/// Signals a breakpoint to an attached debugger(`System.Diagnostics.Debugger.Break`).
+#[inline(always)]
+pub fn debugger_break() {
+    clr_inline_il!(0, "call void [System.Runtime]System.Diagnostics.Debugger::Break()");
+}
+/// Checks if a debugger is attached to the process(`System.Diagnostics.Debugger.IsAttached`).
+#[inline(never)]
+#[link_section = "clr_il_body:1:call bool [System.Runtime]System.Diagnostics.Debugger::get_IsAttached()\nret"]
+pub fn is_debugger_attached() -> bool {
+    false
+}
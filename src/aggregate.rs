@@ -9,7 +9,7 @@ use crate::{
 use rustc_index::IndexVec;
 use rustc_middle::mir::{AggregateKind, Operand, Place};
 use rustc_middle::ty::{AdtDef, AdtKind, GenericArg, Instance, List, ParamEnv, Ty, TyCtxt, TyKind};
-use rustc_target::abi::FieldIdx;
+use rustc_target::abi::{FieldIdx, VariantIdx};
 /// Returns the CIL ops to create the aggreagate value specifed by `aggregate_kind` at `target_location`. Uses indivlidual values specifed by `value_index`
 pub fn handle_aggregate<'tyctx>(
     tyctx: TyCtxt<'tyctx>,
@@ -223,6 +223,15 @@ fn aggregate_adt<'tyctx>(
             );
             let mut ops: Vec<CILOp> = Vec::with_capacity(fields.len() * 2);
             for field in fields {
+                // ZST fields are not stored.
+                let field_ty = adt
+                    .all_fields()
+                    .nth(field.0 as usize)
+                    .expect("No field with provided index!")
+                    .ty(tyctx, subst);
+                if crate::utilis::is_zst(field_ty, tyctx) {
+                    continue;
+                }
                 ops.extend(obj_getter.iter().cloned());
                 ops.extend(field.1);
                 let field_desc = crate::utilis::field_descrptor(
@@ -270,6 +279,9 @@ fn aggregate_adt<'tyctx>(
                 .nth(variant_idx as usize)
                 .expect("Can't get variant index");
             for (field, field_value) in enum_variant.fields.iter().zip(fields.iter()) {
+                if crate::utilis::is_zst(field.ty(tyctx, subst), tyctx) {
+                    continue;
+                }
                 ops.extend(variant_address.clone());
                 ops.extend(field_value.1.clone());
                 let field_name = field.name.to_string();
@@ -285,18 +297,15 @@ fn aggregate_adt<'tyctx>(
                     field_name,
                 ))));
             }
-            // Set tag
-            {
-                ops.extend(adt_adress_ops);
-                ops.push(CILOp::LdcI32(variant_idx as i32));
-                let field_name = "_tag".into();
-                let field = Type::U8;
-                ops.push(CILOp::STField(Box::new(FieldDescriptor::new(
-                    adt_type_ref,
-                    field,
-                    field_name,
-                ))));
-            }
+            // Set the tag after the fields, since they may overlap with it.
+            ops.extend(crate::discriminant::set_discr(
+                adt_adress_ops,
+                adt_type,
+                VariantIdx::from_u32(variant_idx),
+                tyctx,
+                method_instance,
+                type_cache,
+            ));
             ops.extend(crate::place::place_get(
                 target_location,
                 tyctx,
@@ -316,14 +325,17 @@ fn aggregate_adt<'tyctx>(
             );
             let mut ops: Vec<CILOp> = Vec::with_capacity(fields.len() * 2);
             for field in fields {
-                ops.extend(obj_getter.iter().cloned());
-                ops.extend(field.1);
                 let field_def = adt
                     .all_fields()
                     .nth(field.0 as usize)
                     .expect("Could not find field!");
                 let field_type = field_def.ty(tyctx, subst);
                 let field_type = crate::utilis::monomorphize(&method_instance, field_type, tyctx);
+                if crate::utilis::is_zst(field_type, tyctx) {
+                    continue;
+                }
+                ops.extend(obj_getter.iter().cloned());
+                ops.extend(field.1);
                 let field_type =
                     type_cache.type_from_cache(field_type, tyctx, Some(method_instance));
                 let field_name = field_name(adt_type, field.0);
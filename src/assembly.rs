@@ -1,23 +1,24 @@
 use crate::{
     access_modifier::AccessModifer,
-    cil::{CILOp, CallSite},
+    cil::{CILOp, CallSite, SourceSpan},
     codegen_error::CodegenError,
     codegen_error::MethodCodegenError,
+    codegen_error::UnsupportedKind,
     function_sig::FnSig,
-    method::Method,
+    method::{ImplFlags, Method},
     r#type::TyCache,
     r#type::Type,
     r#type::TypeDef,
     IString,
 };
+use indexmap::IndexMap;
 use rustc_middle::mir::{
-    interpret::{AllocId, GlobalAlloc},
+    interpret::{read_target_uint, AllocId, ConstAllocation, GlobalAlloc},
     mono::MonoItem,
     Local, LocalDecl, Statement, Terminator,
 };
 use rustc_middle::ty::{Instance, ParamEnv, TyCtxt, TyKind};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
 #[derive(Clone, Copy, Serialize, Deserialize, Debug)]
 /// Data representing a reference to an external assembly.
 pub struct AssemblyExternRef {
@@ -35,17 +36,40 @@ impl AssemblyExternRef {
 }
 #[derive(Serialize, Deserialize, Debug)]
 /// Representation of a .NET assembly.
+/// All items are kept in the order they were added in, so the same input always results in the same output.
 pub struct Assembly {
-    /// List of types desined within the assembly.
-    types: HashSet<TypeDef>,
+    /// List of types desined within the assembly, keyed by their names.
+    #[serde(with = "type_list")]
+    types: IndexMap<IString, TypeDef>,
     /// List of functions defined within this assembly.
-    functions: HashMap<CallSite, Method>,
+    functions: IndexMap<CallSite, Method>,
     /// Callsite representing the entrypoint of this assebmly if any present.
     entrypoint: Option<CallSite>,
     /// List of references to external assemblies
-    extern_refs: HashMap<IString, AssemblyExternRef>,
+    extern_refs: IndexMap<IString, AssemblyExternRef>,
     /// List of all static fields within the assembly
-    static_fields: HashMap<IString, Type>,
+    static_fields: IndexMap<IString, Type>,
+}
+/// Serializes the types of an assembly as a list, since their names are already stored within them.
+mod type_list {
+    use crate::{r#type::TypeDef, IString};
+    use indexmap::IndexMap;
+    use serde::{Deserialize, Deserializer, Serializer};
+    pub fn serialize<S: Serializer>(
+        types: &IndexMap<IString, TypeDef>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(types.values())
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<IndexMap<IString, TypeDef>, D::Error> {
+        let types = Vec::<TypeDef>::deserialize(deserializer)?;
+        Ok(types
+            .into_iter()
+            .map(|tpe| (tpe.name().into(), tpe))
+            .collect())
+    }
 }
 impl Assembly {
     /// Returns iterator over all global fields
@@ -61,18 +85,27 @@ impl Assembly {
             true,
         ))
     }
+    /// Returns the entrypoint of this assembly, if it has one.
+    pub fn entrypoint(&self) -> Option<&CallSite> {
+        self.entrypoint.as_ref()
+    }
+    /// Returns a JSON summary of the structure of this assembly. The schema of the summary is described by [`crate::summary::AssemblySummary`].
+    pub fn to_json_summary(&self) -> String {
+        serde_json::to_string_pretty(&crate::summary::AssemblySummary::new(self))
+            .expect("Could not serialize the assembly summary!")
+    }
     /// Returns the external assembly reference
-    pub fn extern_refs(&self) -> &HashMap<IString, AssemblyExternRef> {
+    pub fn extern_refs(&self) -> &IndexMap<IString, AssemblyExternRef> {
         &self.extern_refs
     }
     /// Creates a new, empty assembly.
     pub fn empty() -> Self {
         let mut res = Self {
-            types: HashSet::new(),
-            functions: HashMap::new(),
+            types: IndexMap::new(),
+            functions: IndexMap::new(),
             entrypoint: None,
-            extern_refs: HashMap::new(),
-            static_fields: HashMap::new(),
+            extern_refs: IndexMap::new(),
+            static_fields: IndexMap::new(),
         };
         let dotnet_ver = AssemblyExternRef {
             version: (6, 12, 0, 0),
@@ -81,12 +114,42 @@ impl Assembly {
         //res.extern_refs.insert("mscorlib".into(),dotnet_ver);
         res.extern_refs
             .insert("System.Runtime.InteropServices".into(), dotnet_ver);
+        res.extern_refs
+            .insert("System.Runtime.Intrinsics".into(), dotnet_ver);
         res
     }
+    /// Loads the assembly serialized in the file at `path`. The file may either be a single serialized assembly(`.bc`),
+    /// or an archive(eg. an `.rlib`), in which case all the assemblies it contains are joined together.
+    pub fn load(path: &std::path::Path) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        if !bytes.starts_with(b"!<arch>\n") {
+            return Ok(crate::serialization::deserialize(&bytes)?);
+        }
+        let mut final_assembly = Self::empty();
+        let mut archive = ar::Archive::new(bytes.as_slice());
+        while let Some(entry) = archive.next_entry() {
+            let mut entry = entry?;
+            let name = String::from_utf8_lossy(entry.header().identifier());
+            if name.contains(".bc") {
+                let mut asm_bytes = Vec::with_capacity(0x100);
+                std::io::Read::read_to_end(&mut entry, &mut asm_bytes)?;
+                let assembly = crate::serialization::deserialize(&asm_bytes)?;
+                final_assembly = final_assembly.join(assembly);
+            }
+        }
+        Ok(final_assembly)
+    }
+    /// Serializes this assembly. The format is described in [`crate::serialization`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        crate::serialization::serialize(self)
+    }
     /// Joins 2 assemblies together.
     pub fn join(self, other: Self) -> Self {
         let static_initializer = link_static_initializers(self.cctor(), other.cctor());
-        let types = self.types.union(&other.types).cloned().collect();
+        let mut types = self.types;
+        for (name, tpe) in other.types {
+            types.entry(name).or_insert(tpe);
+        }
         let mut functions = self.functions;
         functions.extend(other.functions);
         if let Some(static_initializer) = static_initializer {
@@ -126,35 +189,35 @@ impl Assembly {
             }
             return Some(td);
         }
-        self.types().find(|&tpe| tpe.name() == path)
+        self.types.get(path)
     }
-    /// Turns a terminator into ops, if ABORT_ON_ERROR set to false, will handle and recover from errors.
+    /// Turns a terminator into ops, handling errors according to the error policy of the terminator.
+    /// Unsupported terminators are reported at their span, and, unless the policy is `Abort`, replaced with code throwing an exception.
     pub fn terminator_to_ops<'tcx>(
         term: &Terminator<'tcx>,
         mir: &'tcx rustc_middle::mir::Body<'tcx>,
         tcx: TyCtxt<'tcx>,
         instance: Instance<'tcx>,
+        name: &str,
         type_cache: &mut TyCache,
     ) -> Vec<CILOp> {
-        let mut terminator = if crate::ABORT_ON_ERROR {
+        let mut terminator = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
             crate::terminator::handle_terminator(term, mir, tcx, mir, instance, type_cache)
-        } else {
-            match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                crate::terminator::handle_terminator(term, mir, tcx, mir, instance, type_cache)
-            })) {
-                Ok(ok) => ok,
-                Err(payload) => {
-                    type_cache.recover_from_panic();
-                    let msg = if let Some(msg) = payload.downcast_ref::<&str>() {
-                        rustc_middle::ty::print::with_no_trimmed_paths! {
-                        format!("Tried to execute terminator {term:?} whose compialtion message {msg:?}!")}
-                    } else {
-                        eprintln!("handle_terminator panicked with a non-string message!");
-                        rustc_middle::ty::print::with_no_trimmed_paths! {
-                        format!("Tried to execute terminator {term:?} whose compialtion failed with a no-string message!")
-                        }
-                    };
-                    CILOp::throw_msg(&msg).into()
+        })) {
+            Ok(ok) => ok,
+            Err(payload) => {
+                type_cache.recover_from_panic();
+                let msg = crate::codegen_error::panic_message(&*payload)
+                    .unwrap_or("handle_terminator panicked with a non-string message");
+                crate::codegen_error::report_unsupported(
+                    tcx,
+                    term.source_info.span,
+                    UnsupportedKind::of_terminator(term, tcx),
+                    name,
+                    msg,
+                );
+                rustc_middle::ty::print::with_no_trimmed_paths! {
+                CILOp::throw_msg(&format!("Tried to execute terminator {term:?} whose compialtion message {msg:?}!")).into()
                 }
             }
         };
@@ -170,7 +233,7 @@ impl Assembly {
         }
         terminator
     }
-    /// Turns a statement into ops, if ABORT_ON_ERROR set to false, will handle and recover from errors.
+    /// Turns a statement into ops, catching any errors. The caller handles them according to the error policy for statements.
     pub fn statement_to_ops<'tcx>(
         statement: &Statement<'tcx>,
         tcx: TyCtxt<'tcx>,
@@ -178,25 +241,14 @@ impl Assembly {
         instance: Instance<'tcx>,
         type_cache: &mut TyCache,
     ) -> Result<Vec<CILOp>, CodegenError> {
-        if crate::ABORT_ON_ERROR {
-            Ok(crate::statement::handle_statement(
-                statement, tcx, mir, instance, type_cache,
-            ))
-        } else {
-            match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                crate::statement::handle_statement(statement, tcx, mir, instance, type_cache)
-            })) {
-                Ok(success) => Ok(success),
-                Err(payload) => {
-                    if let Some(msg) = payload.downcast_ref::<&str>() {
-                        Err(crate::codegen_error::CodegenError::from_panic_message(msg))
-                    } else {
-                        Err(crate::codegen_error::CodegenError::from_panic_message(
-                            "statement_to_ops panicked with a non-string message!",
-                        ))
-                    }
-                }
-            }
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            crate::statement::handle_statement(statement, tcx, mir, instance, type_cache)
+        })) {
+            Ok(success) => Ok(success),
+            Err(payload) => Err(crate::codegen_error::CodegenError::from_panic_message(
+                crate::codegen_error::panic_message(&*payload)
+                    .unwrap_or("statement_to_ops panicked with a non-string message!"),
+            )),
         }
     }
     /// This is used *ONLY* to catch uncaught errors.
@@ -212,15 +264,33 @@ impl Assembly {
         })) {
             Ok(success) => success,
             Err(payload) => {
+                // Errors raised because of an `Abort` error policy must stop the compilation.
+                if payload.is::<rustc_span::fatal_error::FatalErrorMarker>() {
+                    std::panic::resume_unwind(payload);
+                }
                 cache.recover_from_panic();
-                if let Some(msg) = payload.downcast_ref::<&str>() {
-                    eprintln!("could not compile method {name}. fn_add panicked with unhandled message: {msg:?}");
-                    //self.add_method(Method::missing_because(format!("could not compile method {name}. fn_add panicked with unhandled message: {msg:?}")));
-                    Ok(())
-                } else {
-                    eprintln!("could not compile method {name}. fn_add panicked with no message.");
-                    Ok(())
+                let msg = crate::codegen_error::panic_message(&*payload)
+                    .unwrap_or("add_fn panicked with a non-string message");
+                // Statements and terminators catch their own errors, so this panic comes from lowering the types(eg. of locals) or layouts
+                // the function uses.
+                crate::codegen_error::report_unsupported(
+                    tcx,
+                    tcx.def_span(instance.def_id()),
+                    UnsupportedKind::Type,
+                    name,
+                    msg,
+                );
+                if let Ok(sig) = FnSig::sig_from_instance_(instance, tcx, cache) {
+                    let mut method = Method::new(AccessModifer::Public, true, sig, name, vec![]);
+                    method.set_ops(
+                        CILOp::throw_msg(&format!(
+                            "Tried to call {name}, which failed to compile with message {msg:?}!"
+                        ))
+                        .into(),
+                    );
+                    self.add_method(method);
                 }
+                Ok(())
             }
         }
     }
@@ -251,6 +321,17 @@ impl Assembly {
             return Ok(());
         }
 
+        let _context = rustc_middle::ty::print::with_no_trimmed_paths! {
+            crate::codegen_context::enter_function(name, format!("{instance:?}"))
+        };
+        if let Some(cached) = crate::incremental::load(tcx, instance, name) {
+            for tpe in cached.types {
+                self.add_typedef(tpe);
+            }
+            self.add_method(cached.method);
+            return Ok(());
+        }
+        let unsupported_before = crate::codegen_error::unsupported_count();
         let mir = tcx.optimized_mir(instance.def_id());
         // Check if function is public or not.
         // FIXME: figure out the source of the bug causing visibility to not be read propely.
@@ -260,27 +341,65 @@ impl Assembly {
         let sig = match FnSig::sig_from_instance_(instance, tcx, cache) {
             Ok(sig) => sig,
             Err(err) => {
-                eprintln!("Could not get the signature of function {name} because {err:?}");
+                crate::codegen_error::report_unsupported(
+                    tcx,
+                    tcx.def_span(instance.def_id()),
+                    UnsupportedKind::Type,
+                    name,
+                    &format!("could not get the signature of the function because {err:?}"),
+                );
                 return Ok(());
             }
         };
-
+        // Shims written in the support library may replace their whole body with IL.
+        if let Some(ops) = crate::inline_il::il_body(tcx, instance.def_id()) {
+            let mut method = Method::new(access_modifier, true, sig, name, vec![]);
+            method.set_ops(ops);
+            self.add_method(method);
+            return Ok(());
+        }
         // Get locals
         //eprintln!("method")
         let locals = locals_from_mir(&mir.local_decls, tcx, mir.arg_count, &instance, cache);
         // Create method prototype
         let mut method = Method::new(access_modifier, true, sig, name, locals);
+        method.set_impl_flags(impl_flags(tcx, instance));
+        if let Some((entry_point, call_conv)) = crate::pinvoke::unmanaged_export(tcx, instance) {
+            method.add_attribute(crate::method::Attribute::UnmanagedCallersOnly {
+                entry_point,
+                call_conv,
+            });
+        }
         let mut ops = Vec::new();
-        if crate::TRACE_CALLS {
+        if crate::config::config().trace_calls && crate::config::config().traced(name) {
             ops.extend(CILOp::debug_msg(&format!("Called {name}.")));
         }
 
         let blocks = &mir.basic_blocks;
+        // Labels of the basic blocks are their indices.
+        crate::codegen_context::reserve_labels(blocks.len() as u32);
         let does_return_void: bool = *method.sig().output() == Type::Void;
+        // Blocks which can't be reached from the entry block don't need to be compiled.
+        let reachable = rustc_middle::mir::traversal::reachable_as_bitset(mir);
+        // With `-C panic=abort`, panics never unwind, so cleanup blocks(run only while unwinding) are skipped.
+        let skip_cleanup = crate::utilis::panic_aborts(tcx);
         for (last_bb_id, block_data) in blocks.into_iter().enumerate() {
+            if !reachable.contains(rustc_middle::mir::BasicBlock::from_usize(last_bb_id)) {
+                continue;
+            }
+            if skip_cleanup && block_data.is_cleanup {
+                continue;
+            }
             ops.push(CILOp::Label(last_bb_id as u32));
-            for statement in &block_data.statements {
-                if crate::INSERT_MIR_DEBUG_COMMENTS {
+            crate::codegen_context::set_block(last_bb_id as u32);
+            for (statement_id, statement) in block_data.statements.iter().enumerate() {
+                crate::codegen_context::set_statement(Some(statement_id));
+                if crate::config::config().source_spans {
+                    ops.push(CILOp::SourceSpan(
+                        SourceSpan::from_span(statement.source_info.span, tcx).into(),
+                    ));
+                }
+                if crate::config::config().insert_mir_debug_comments {
                     rustc_middle::ty::print::with_no_trimmed_paths! {ops.push(CILOp::Comment(format!("{statement:?}").into()))};
                 }
                 let statement_ops = match Self::statement_to_ops(
@@ -289,24 +408,34 @@ impl Assembly {
                     Ok(ops) => ops,
                     Err(err) => {
                         cache.recover_from_panic();
-                        rustc_middle::ty::print::with_no_trimmed_paths! {eprintln!(
-                            "Method \"{name}\" failed to compile statement {statement:?} with message {err:?}"
-                        )};
+                        crate::codegen_error::report_unsupported(
+                            tcx,
+                            statement.source_info.span,
+                            UnsupportedKind::Statement,
+                            name,
+                            &err.to_string(),
+                        );
                         rustc_middle::ty::print::with_no_trimmed_paths! {CILOp::throw_msg(&format!("Tired to run a statement {statement:?} which failed to compile with error message {err:?}.")).into()}
                     }
                 };
                 crate::utilis::check_debugable(&statement_ops, statement, does_return_void);
                 ops.extend(statement_ops);
-                if crate::INSERT_MIR_DEBUG_COMMENTS {
+                if crate::config::config().insert_mir_debug_comments {
                     ops.push(CILOp::Comment("STATEMENT END.".into()));
                 }
             }
+            crate::codegen_context::set_statement(None);
             match &block_data.terminator {
                 Some(term) => {
-                    if crate::INSERT_MIR_DEBUG_COMMENTS {
+                    if crate::config::config().source_spans {
+                        ops.push(CILOp::SourceSpan(
+                            SourceSpan::from_span(term.source_info.span, tcx).into(),
+                        ));
+                    }
+                    if crate::config::config().insert_mir_debug_comments {
                         rustc_middle::ty::print::with_no_trimmed_paths! {ops.push(CILOp::Comment(format!("{term:?}").into()))};
                     }
-                    let term_ops = Self::terminator_to_ops(term, mir, tcx, instance, cache);
+                    let term_ops = Self::terminator_to_ops(term, mir, tcx, instance, name, cache);
                     if term_ops != [CILOp::Ret] {
                         crate::utilis::check_debugable(&term_ops, term, does_return_void);
                     }
@@ -315,19 +444,41 @@ impl Assembly {
                 None => (),
             }
         }
+        // Methods referring to global allocations depend on the allocation ids of this session, and methods with unsupported constructs should be reported again.
+        // When lowering in parallel, an unsupported construct in another function may also prevent caching. That only makes the cache miss more often.
+        let cacheable = crate::codegen_error::unsupported_count() == unsupported_before
+            && !ops
+                .iter()
+                .any(|op| matches!(op, CILOp::LoadGlobalAllocPtr { .. }));
         #[allow(clippy::single_match)]
         // This will be slowly expanded with support for new types of allocations.
         ops.iter_mut().for_each(|op| match op {
             CILOp::LoadGlobalAllocPtr { alloc_id } => {
-                *op = CILOp::LDStaticField(self.add_allocation(*alloc_id, tcx).into());
+                *op = CILOp::LDStaticField(self.add_allocation(*alloc_id, tcx, cache).into());
             }
             _ => (),
         });
 
         method.set_ops(ops);
+        // Loads and stores of ZSTs are elided, so ZST locals are not needed anymore.
+        let zst_locals = zst_locals(&mir.local_decls, tcx, mir.arg_count, &instance);
+        method.remove_unused_locals(|local| zst_locals[local]);
         // Do some basic checks on the method as a whole.
         crate::utilis::check_debugable(method.get_ops(), &method, does_return_void);
-        self.types.extend(cache.defs().cloned());
+        crate::utilis::check_bool_normalization(&method);
+        // The peephole optimizer needs the temporaries allocated, so that it sees all the loads and stores of locals.
+        if crate::config::config().optimize_cil() {
+            method.allocate_temporaries();
+            crate::opt::peephole::optimize_method(&mut method, tcx.sess.opts.optimize);
+        }
+        for tpe in cache.take_new_defs() {
+            self.types
+                .entry(tpe.name().into())
+                .or_insert_with(|| tpe.clone());
+        }
+        if cacheable {
+            crate::incremental::store(tcx, instance, name, &method, cache);
+        }
         println!("Compiled method {name}");
         self.add_method(method);
         Ok(())
@@ -337,12 +488,53 @@ impl Assembly {
     pub fn add_static(&mut self, tpe: Type, name: &str) {
         self.static_fields.insert(name.into(), tpe);
     }
+    /// Adds a global static field named *name* of type *tpe*, initialized by the static constructor to the value `init` leaves on the stack.
+    pub fn add_static_with_init(
+        &mut self,
+        tpe: Type,
+        name: &str,
+        init: impl IntoIterator<Item = CILOp>,
+    ) {
+        let field = crate::cil::StaticFieldDescriptor::boxed(None, tpe.clone(), name.into());
+        self.add_static(tpe, name);
+        self.extend_cctor(init.into_iter().chain([CILOp::STStaticField(field)]));
+    }
 
+    /// Appends `ops` to the static constructor(`.cctor`) of this assembly, creating it if needed.
+    pub fn extend_cctor(&mut self, new_ops: impl IntoIterator<Item = CILOp>) {
+        let cctor = self
+            .functions
+            .entry(CallSite::new(
+                None,
+                ".cctor".into(),
+                FnSig::new(&[], &Type::Void),
+                true,
+            ))
+            .or_insert_with(|| {
+                Method::new(
+                    AccessModifer::Public,
+                    true,
+                    FnSig::new(&[], &Type::Void),
+                    ".cctor",
+                    vec![
+                        (None, Type::Ptr(Type::U8.into())),
+                        (None, Type::Ptr(Type::U8.into())),
+                    ],
+                )
+            });
+        let ops: &mut Vec<CILOp> = cctor.ops_mut();
+        if !ops.is_empty() && ops[ops.len() - 1] == CILOp::Ret {
+            ops.pop();
+        }
+        ops.extend(new_ops);
+        ops.push(CILOp::Ret);
+    }
     /// Adds a static field and initialized for allocation represented by `alloc_id`.
-    fn add_allocation(
+    fn add_allocation<'tcx>(
         &mut self,
         alloc_id: u64,
-        tcx: TyCtxt<'_>,
+        tcx: TyCtxt<'tcx>,
+        cache: &mut TyCache,
     ) -> crate::cil::StaticFieldDescriptor {
         let const_allocation =
             match tcx.global_alloc(AllocId(alloc_id.try_into().expect("0 alloc id?"))) {
@@ -368,47 +560,70 @@ impl Assembly {
             alloc_fld.clone(),
         );
         if self.static_fields.get(&alloc_fld).is_none() {
-            let cctor = self
-                .functions
-                .entry(CallSite::new(
-                    None,
-                    ".cctor".into(),
-                    FnSig::new(&[], &Type::Void),
-                    true,
-                ))
-                .or_insert_with(|| {
-                    Method::new(
-                        AccessModifer::Public,
-                        true,
-                        FnSig::new(&[], &Type::Void),
-                        ".cctor",
-                        vec![
-                            (None, Type::Ptr(Type::U8.into())),
-                            (None, Type::Ptr(Type::U8.into())),
-                        ],
-                    )
-                });
-
-            let ops: &mut Vec<CILOp> = cctor.ops_mut();
-            if !ops.is_empty() && ops[ops.len() - 1] == CILOp::Ret {
-                ops.pop();
-            }
-            let init_method = allocation_initializer_method(bytes, &alloc_fld, tcx);
-            ops.extend([
-                CILOp::Call(CallSite::boxed(
-                    None,
-                    init_method.name().into(),
-                    init_method.sig().clone(),
-                    true,
-                )),
+            // The static is added before handling relocations, so that allocations refering to themselves do not cause infinite recursion.
+            self.add_static(Type::Ptr(Type::U8.into()), &alloc_fld);
+            let init_method = allocation_initializer_method(bytes, &field_desc, tcx);
+            self.extend_cctor([
+                CILOp::Call(init_method.call_site().into()),
                 CILOp::STStaticField(field_desc.clone().into()),
-                CILOp::Ret,
             ]);
             self.add_method(init_method);
-            self.add_static(Type::Ptr(Type::U8.into()), &alloc_fld);
+            // Pointers inside the allocation only hold offsets into their target allocations. The real addresses are only known at runtime,
+            // so they are patched in by the static constructor, after both allocations are initialized.
+            let ptr_size = tcx.data_layout.pointer_size.bytes() as usize;
+            for (offset, prov) in const_allocation.provenance().ptrs().iter() {
+                let offset = offset.bytes() as usize;
+                let target_offset =
+                    read_target_uint(tcx.data_layout.endian, &bytes[offset..(offset + ptr_size)])
+                        .expect("Could not read a pointer from an allocation!");
+                let target_ops = self.relocation_target(prov.alloc_id(), tcx, cache);
+                let mut fixup = vec![
+                    CILOp::LDStaticField(field_desc.clone().into()),
+                    CILOp::LdcI64(offset as i64),
+                    CILOp::ConvISize(false),
+                    CILOp::Add,
+                ];
+                fixup.extend(target_ops);
+                fixup.extend([
+                    CILOp::LdcI64(target_offset as u64 as i64),
+                    CILOp::ConvISize(false),
+                    CILOp::Add,
+                    CILOp::STIndISize,
+                ]);
+                self.extend_cctor(fixup);
+            }
         }
         field_desc
     }
+    /// Returns the ops loading the address of `target`, the target of a relocation.
+    fn relocation_target<'tcx>(
+        &mut self,
+        target: AllocId,
+        tcx: TyCtxt<'tcx>,
+        cache: &mut TyCache,
+    ) -> Vec<CILOp> {
+        match tcx.global_alloc(target) {
+            GlobalAlloc::Memory(_) | GlobalAlloc::Static(_) => {
+                let target_field =
+                    self.add_allocation(crate::utilis::alloc_id_to_u64(target), tcx, cache);
+                vec![CILOp::LDStaticField(target_field.into())]
+            }
+            // Function pointers are obtained with `ldftn`. The function itself is a mono item, so it will be compiled on its own.
+            GlobalAlloc::Function(instance) => {
+                let name = crate::utilis::function_name(tcx.symbol_name(instance));
+                let sig = FnSig::sig_from_instance_(instance, tcx, cache)
+                    .expect("Could not get the signature of a function refered to by a static!");
+                vec![CILOp::LDFtn(CallSite::boxed(None, name, sig, true))]
+            }
+            // Vtables are allocations built by rustc, with relocations pointing to the methods.
+            GlobalAlloc::VTable(ty, trait_ref) => {
+                let vtable = tcx.vtable_allocation((ty, trait_ref));
+                let target_field =
+                    self.add_allocation(crate::utilis::alloc_id_to_u64(vtable), tcx, cache);
+                vec![CILOp::LDStaticField(target_field.into())]
+            }
+        }
+    }
     /// Returns true if assembly contains function named `name`
     pub fn contains_fn_named(&self, name: &str) -> bool {
         //FIXME:This is inefficient.
@@ -418,6 +633,7 @@ impl Assembly {
     pub fn add_method(&mut self, mut method: Method) {
         method.allocate_temporaries();
         method.ensure_valid();
+        crate::assembly_exporter::ilasm_exporter::dump_method(&method);
         self.functions.insert(method.call_site(), method);
     }
     /// Returns the list of all calls within the method. Calls may repeat.
@@ -428,13 +644,29 @@ impl Assembly {
     pub fn methods(&self) -> impl Iterator<Item = &Method> {
         self.functions.values()
     }
+    /// Returns an interator over mutable references to all methods within the assembly.
+    pub fn methods_mut(&mut self) -> impl Iterator<Item = &mut Method> {
+        self.functions.values_mut()
+    }
+    /// Removes the method behind `site`, keeping the order of the remaining methods.
+    pub fn remove_method(&mut self, site: &CallSite) -> Option<Method> {
+        self.functions.shift_remove(site)
+    }
+    /// Returns the total number of ops in all methods of the assembly.
+    pub fn op_count(&self) -> usize {
+        self.methods().map(|method| method.get_ops().len()).sum()
+    }
     /// Returns an iterator over all types witin the assembly.
     pub fn types(&self) -> impl Iterator<Item = &TypeDef> {
-        self.types.iter()
+        self.types.values()
+    }
+    /// Returns an iterator over mutable references to all types witin the assembly.
+    pub fn types_mut(&mut self) -> impl Iterator<Item = &mut TypeDef> {
+        self.types.values_mut()
     }
     /// Optimizes all the methods witin the assembly.
     pub fn opt(&mut self) {
-        let functions: HashMap<_, _> = self
+        let functions: IndexMap<_, _> = self
             .functions
             .iter()
             .map(|method| {
@@ -446,9 +678,37 @@ impl Assembly {
             .collect();
         self.functions = functions;
     }
+    /// Optimizes all methods of the assembly except the static initializer(which is extended by later items), writes them to `spill`,
+    /// and removes them from the assembly.
+    pub fn spill_methods(&mut self, spill: &crate::spill::MethodSpill) {
+        let cctor = self.cctor().map(Method::call_site);
+        let sites: Vec<CallSite> = self
+            .functions
+            .keys()
+            .filter(|site| Some(*site) != cctor.as_ref())
+            .cloned()
+            .collect();
+        for site in sites {
+            let mut method = self.functions[&site].clone();
+            // Done twice for inlining, like in `Assembly::opt`.
+            crate::opt::opt_method(&mut method, self);
+            crate::opt::opt_method(&mut method, self);
+            spill.spill(&method);
+            self.functions.shift_remove(&site);
+        }
+    }
+    /// Adds methods read from the method sections of a serialized assembly. Unlike [`Self::add_method`], does not prepare them for export,
+    /// since they were prepared before being spilled.
+    pub(crate) fn add_spilled_methods(&mut self, methods: impl IntoIterator<Item = Method>) {
+        self.functions.extend(
+            methods
+                .into_iter()
+                .map(|method| (method.call_site(), method)),
+        );
+    }
     /// Adds a definition of a type to the assembly.
     pub fn add_typedef(&mut self, type_def: TypeDef) {
-        self.types.insert(type_def);
+        self.types.entry(type_def.name().into()).or_insert(type_def);
     }
     /// Adds a MIR item (method,inline assembly code, etc.) to the assembly.
     pub fn add_item<'tcx>(
@@ -480,18 +740,137 @@ impl Assembly {
                 Ok(())
             }
             MonoItem::Static(stotic) => {
-                let alloc = tcx.eval_static_initializer(stotic).unwrap();
+                let alloc = match tcx.eval_static_initializer(stotic) {
+                    Ok(alloc) => alloc,
+                    Err(err) => {
+                        let name = item.symbol_name(tcx);
+                        crate::codegen_error::report_unsupported(
+                            tcx,
+                            tcx.def_span(stotic),
+                            UnsupportedKind::Static,
+                            name.name,
+                            &format!("could not evaluate the initializer because {err:?}"),
+                        );
+                        return Ok(());
+                    }
+                };
+                if let Some(priority) = tcx
+                    .codegen_fn_attrs(stotic)
+                    .link_section
+                    .and_then(|section| crate::entrypoint::init_array_priority(section.as_str()))
+                {
+                    self.add_init_array_caller(item, alloc, priority, tcx, cache);
+                }
                 let alloc_id = tcx.reserve_and_set_memory_alloc(alloc);
-                self.add_allocation(crate::utilis::alloc_id_to_u64(alloc_id), tcx);
+                self.add_allocation(crate::utilis::alloc_id_to_u64(alloc_id), tcx, cache);
                 //eprintln!("Unsuported item - Static:{stotic:?}");
                 Ok(())
             }
         }
     }
-    /// Sets the entrypoint of the assembly to the method behind `CallSite`.
-    pub fn set_entrypoint(&mut self, entrypoint: CallSite) {
+    /// Adds a method calling the function pointed to by static `item`, placed in an `.init_array` section with priority `priority`.
+    fn add_init_array_caller<'tcx>(
+        &mut self,
+        item: MonoItem<'tcx>,
+        alloc: ConstAllocation<'tcx>,
+        priority: u16,
+        tcx: TyCtxt<'tcx>,
+        cache: &mut TyCache,
+    ) {
+        let name = item.symbol_name(tcx);
+        let Some(GlobalAlloc::Function(instance)) = alloc
+            .inner()
+            .provenance()
+            .ptrs()
+            .iter()
+            .next()
+            .map(|(_, prov)| tcx.global_alloc(prov.alloc_id()))
+        else {
+            eprintln!("WARNING: {name} is placed in `.init_array`, but does not point to a function. Ignoring it.");
+            return;
+        };
+        let sig = FnSig::sig_from_instance_(instance, tcx, cache)
+            .expect("Could not get the signature of an `.init_array` function!");
+        let init = CallSite::new(
+            None,
+            crate::utilis::function_name(tcx.symbol_name(instance)),
+            sig,
+            true,
+        );
+        self.add_method(crate::entrypoint::init_array_caller(
+            name.name, priority, init,
+        ));
+    }
+    /// Makes the entrypoint wrapper call the functions placed in `.init_array`(by all joined crates) in order of priority, before anything else.
+    /// Called by the linker, since those functions and the entrypoint usually come from different crates.
+    pub fn call_init_array(&mut self) {
+        let mut callers: Vec<CallSite> = self
+            .methods()
+            .filter(|method| {
+                method
+                    .name()
+                    .starts_with(crate::entrypoint::INIT_ARRAY_PREFIX)
+            })
+            .map(Method::call_site)
+            .collect();
+        callers.sort_by(|a, b| a.name().cmp(b.name()));
+        let Some(wrapper) = self.methods_mut().find(|method| method.is_entrypoint()) else {
+            return;
+        };
+        wrapper.ops_mut().splice(
+            0..0,
+            callers.into_iter().map(|caller| CILOp::Call(caller.into())),
+        );
+    }
+    /// Removes the private methods nothing refers to. Those are mostly the runtime shims(see [`crate::ffi`]), which are inserted into every crate,
+    /// but only some of which a program uses. Called by the linker, since only the whole program shows which shims are used. Returns the number of removed methods.
+    /// Inline IL may call any method by name, so no method is removed from assemblies containing it.
+    pub fn remove_unreferenced_private_methods(&mut self) -> usize {
+        let has_inline_il = self
+            .methods()
+            .chain(self.types().flat_map(TypeDef::methods))
+            .flat_map(Method::get_ops)
+            .any(|op| matches!(op, CILOp::InlineIL(..)));
+        if has_inline_il {
+            return 0;
+        }
+        let mut removed = 0;
+        loop {
+            let referenced: std::collections::HashSet<&CallSite> = self
+                .call_sites()
+                .chain(
+                    self.types()
+                        .flat_map(TypeDef::methods)
+                        .flat_map(Method::calls),
+                )
+                .collect();
+            let unreferenced: Vec<CallSite> = self
+                .methods()
+                .filter(|method| {
+                    method.access() == AccessModifer::Private
+                        && !method.is_entrypoint()
+                        && !referenced.contains(&method.call_site())
+                })
+                .map(Method::call_site)
+                .collect();
+            // Removing a method may leave the methods only it called unreferenced.
+            if unreferenced.is_empty() {
+                return removed;
+            }
+            removed += unreferenced.len();
+            for site in &unreferenced {
+                self.remove_method(site);
+            }
+        }
+    }
+    /// Sets the entrypoint of the assembly to the method behind `CallSite`. The program exits with the code obtained as described by `exit_code`.
+    pub fn set_entrypoint(
+        &mut self,
+        entrypoint: CallSite,
+        exit_code: &crate::entrypoint::ExitCodeSource,
+    ) {
         assert!(self.entrypoint.is_none(), "ERROR: Multiple entrypoints");
-        let wrapper = crate::entrypoint::wrapper(&entrypoint);
+        let wrapper = crate::entrypoint::wrapper(&entrypoint, exit_code);
         self.functions.insert(wrapper.call_site(), wrapper);
         self.entrypoint = Some(entrypoint);
     }
@@ -512,6 +891,14 @@ fn link_static_initializers(a: Option<&Method>, b: Option<&Method>) -> Option<Me
         }
     }
 }
+/// Returns the implementation flags matching the `#[inline]` attribute of `instance`. `#[inline]` hints are left to the JIT.
+fn impl_flags<'tcx>(tcx: TyCtxt<'tcx>, instance: Instance<'tcx>) -> ImplFlags {
+    match tcx.codegen_fn_attrs(instance.def_id()).inline {
+        rustc_attr::InlineAttr::Always => ImplFlags::AggressiveInlining,
+        rustc_attr::InlineAttr::Never => ImplFlags::NoInlining,
+        _ => ImplFlags::None,
+    }
+}
 /// Returns the list of all local variables within MIR of a function, and converts them to the internal type represenation `Type`
 fn locals_from_mir<'tyctx>(
     locals: &rustc_index::IndexVec<Local, LocalDecl<'tyctx>>,
@@ -524,7 +911,7 @@ fn locals_from_mir<'tyctx>(
     for (local_id, local) in locals.iter().enumerate() {
         if local_id == 0 || local_id > argc {
             let ty = crate::utilis::monomorphize(method_instance, local.ty, tyctx);
-            if crate::PRINT_LOCAL_TYPES {
+            if crate::config::config().print_local_types {
                 println!(
                     "Local type {ty:?},non-morphic: {non_morph}",
                     non_morph = local.ty
@@ -537,38 +924,80 @@ fn locals_from_mir<'tyctx>(
     }
     local_types
 }
-fn allocation_initializer_method(bytes: &[u8], name: &str, tyctx: TyCtxt) -> Method {
+/// Returns, for each local returned by [`locals_from_mir`], true if it is a zero-sized type.
+fn zst_locals<'tyctx>(
+    locals: &rustc_index::IndexVec<Local, LocalDecl<'tyctx>>,
+    tyctx: TyCtxt<'tyctx>,
+    argc: usize,
+    method_instance: &Instance<'tyctx>,
+) -> Vec<bool> {
+    locals
+        .iter()
+        .enumerate()
+        .filter(|(local_id, _)| *local_id == 0 || *local_id > argc)
+        .map(|(_, local)| {
+            let ty = crate::utilis::monomorphize(method_instance, local.ty, tyctx);
+            crate::utilis::is_zst(ty, tyctx)
+        })
+        .collect()
+}
+/// Creates the method allocating and filling the allocation stored in the static `field`. If the allocation was already initialized, the method returns it instead.
+/// Codegen units are lowered separately, so the static constructor may try to initialize the same allocation multiple times.
+/// Allocations can be big(eg. the unicode tables of `core` take tens of kilobytes), so the allocation is zeroed, and only its non-zero 8 byte words are written.
+fn allocation_initializer_method(
+    bytes: &[u8],
+    field: &crate::cil::StaticFieldDescriptor,
+    tyctx: TyCtxt,
+) -> Method {
+    let name = field.name();
     let mut ops = Vec::new();
     ops.extend([
+        CILOp::LDStaticField(field.clone().into()),
+        CILOp::Dup,
+        CILOp::BTrue(0),
+        CILOp::Pop,
         CILOp::LdcI64(bytes.len() as u64 as i64),
         CILOp::ConvISize(false),
         CILOp::Call(CallSite::malloc(tyctx).into()),
-        CILOp::Dup,
         CILOp::STLoc(0),
-        CILOp::STLoc(1),
+        CILOp::Comment(name.clone().into()),
+        CILOp::LDLoc(0),
+        CILOp::LdcI32(0),
+        CILOp::LdcI64(bytes.len() as u64 as i64),
+        CILOp::ConvU32(false),
+        CILOp::InitBlk,
     ]);
-    for byte in bytes {
-        ops.extend([
-            CILOp::LDLoc(0),
-            CILOp::LdcI32(*byte as i32),
-            CILOp::STIndI8,
+    let address = |offset: usize| {
+        [
             CILOp::LDLoc(0),
-            CILOp::LdcI32(1),
+            CILOp::LdcI64(offset as i64),
+            CILOp::ConvISize(false),
             CILOp::Add,
-            CILOp::STLoc(0),
-            CILOp::Comment(name.clone().into()),
-        ]);
+        ]
+    };
+    let words = bytes.chunks_exact(8);
+    let tail_start = bytes.len() - words.remainder().len();
+    for (idx, word) in words.enumerate() {
+        // .NET runs only on little endian targets.
+        let word = u64::from_le_bytes(word.try_into().unwrap());
+        if word != 0 {
+            ops.extend(address(idx * 8));
+            ops.extend([CILOp::LdcI64(word as i64), CILOp::STIndI64]);
+        }
+    }
+    for (offset, byte) in bytes.iter().enumerate().skip(tail_start) {
+        if *byte != 0 {
+            ops.extend(address(offset));
+            ops.extend([CILOp::LdcI32(i32::from(*byte)), CILOp::STIndI8]);
+        }
     }
-    ops.extend([CILOp::LDLoc(1), CILOp::Ret]);
+    ops.extend([CILOp::LDLoc(0), CILOp::Label(0), CILOp::Ret]);
     let mut method = Method::new(
         AccessModifer::Private,
         true,
         FnSig::new(&[], &Type::Ptr(Type::U8.into())),
         &format!("init_{name}"),
-        vec![
-            (Some("curr".into()), Type::Ptr(Type::U8.into())),
-            (Some("alloc_ptr".into()), Type::Ptr(Type::U8.into())),
-        ],
+        vec![(Some("alloc_ptr".into()), Type::Ptr(Type::U8.into()))],
     );
     method.set_ops(ops);
     method
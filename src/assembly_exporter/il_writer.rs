@@ -0,0 +1,185 @@
+//! A writer of ILASM text, writing ops straight into the output buffer.
+//!
+//! Most ops are either plain mnemonics, or mnemonics followed by a label, an index, an integer or a string. [`ILWriter`] writes those
+//! without formatting them into temporary [`String`]s first. All other ops(calls, field accesses, type-based ops) are written using
+//! [`op_cli`], and the text written for any op is always the same as the text returned by [`op_cli`].
+use super::ilasm_op::op_cli;
+use crate::cil::CILOp;
+/// Writes ILASM text into a byte buffer.
+pub(crate) struct ILWriter<'a> {
+    out: &'a mut Vec<u8>,
+}
+impl<'a> ILWriter<'a> {
+    /// Creates a writer appending to `out`.
+    pub fn new(out: &'a mut Vec<u8>) -> Self {
+        Self { out }
+    }
+    /// Writes `text` as-is.
+    pub fn str(&mut self, text: &str) {
+        self.out.extend_from_slice(text.as_bytes());
+    }
+    /// Writes `value` in decimal.
+    pub fn uint(&mut self, mut value: u64) {
+        let mut digits = [0_u8; 20];
+        let mut start = digits.len();
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (value % 10) as u8;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+        self.out.extend_from_slice(&digits[start..]);
+    }
+    /// Writes `value` in decimal.
+    pub fn int(&mut self, value: i64) {
+        if value < 0 {
+            self.out.push(b'-');
+        }
+        self.uint(value.unsigned_abs());
+    }
+    /// Writes the name of label `id`.
+    pub fn label(&mut self, id: u32) {
+        self.str("bb_");
+        self.uint(u64::from(id));
+    }
+    /// Writes a mnemonic followed by an operand.
+    fn with_uint(&mut self, mnemonic: &str, value: u32) {
+        self.str(mnemonic);
+        self.uint(u64::from(value));
+    }
+    /// Writes `text` as a quoted string literal. Strings containing characters other than printable ASCII are escaped the slow way.
+    pub fn quoted(&mut self, text: &str) {
+        if !text.bytes().all(|byte| (b' '..=b'~').contains(&byte)) {
+            self.str(&format!("{text:?}").replace('\'', "\\\'"));
+            return;
+        }
+        self.out.push(b'"');
+        for byte in text.bytes() {
+            if matches!(byte, b'"' | b'\\' | b'\'') {
+                self.out.push(b'\\');
+            }
+            self.out.push(byte);
+        }
+        self.out.push(b'"');
+    }
+    /// Writes the ILASM text of `op`, without a trailing new line.
+    pub fn op(&mut self, op: &CILOp) {
+        match op {
+            CILOp::Label(id) => {
+                self.label(*id);
+                self.out.push(b':');
+            }
+            CILOp::GoTo(id) => self.branch("br ", *id),
+            CILOp::BEq(id) => self.branch("beq ", *id),
+            CILOp::BNe(id) => self.branch("bne.un ", *id),
+            CILOp::BGe(id) => self.branch("bge ", *id),
+            CILOp::BLt(id) => self.branch("blt ", *id),
+            CILOp::BLe(id) => self.branch("ble ", *id),
+            CILOp::BLtUn(id) => self.branch("blt.un ", *id),
+            CILOp::BGeUn(id) => self.branch("bge.un ", *id),
+            CILOp::BLeUn(id) => self.branch("ble.un ", *id),
+            CILOp::BZero(id) => self.branch("brzero ", *id),
+            CILOp::BTrue(id) => self.branch("brtrue ", *id),
+            CILOp::Leave(id) => self.branch("leave ", *id),
+            CILOp::Switch(targets) => {
+                self.str("switch (");
+                for (idx, target) in targets.iter().enumerate() {
+                    if idx != 0 {
+                        self.out.push(b',');
+                    }
+                    self.label(*target);
+                }
+                self.out.push(b')');
+            }
+            CILOp::LDArg(idx) => self.short_form("ldarg", *idx, true),
+            CILOp::LDArgA(idx) => self.short_form("ldarga", *idx, false),
+            CILOp::STArg(idx) => self.short_form("starg", *idx, false),
+            CILOp::LDLoc(idx) => self.short_form("ldloc", *idx, true),
+            CILOp::LDLocA(idx) => self.short_form("ldloca", *idx, false),
+            CILOp::STLoc(idx) => self.short_form("stloc", *idx, true),
+            CILOp::LdcI32(value) => self.ldc_i4(i64::from(*value)),
+            CILOp::LdcI64(value) => {
+                if i32::try_from(*value).is_ok() {
+                    self.ldc_i4(*value);
+                } else {
+                    self.str("ldc.i8 ");
+                    self.int(*value);
+                }
+            }
+            CILOp::LdStr(text) => {
+                self.str("ldstr ");
+                self.quoted(text);
+            }
+            CILOp::Comment(comment) => {
+                self.str("//");
+                self.str(comment);
+            }
+            _ => self.str(&op_cli(op)),
+        }
+    }
+    fn branch(&mut self, mnemonic: &str, target: u32) {
+        self.str(mnemonic);
+        self.label(target);
+    }
+    /// Writes an op taking an argument or local index, using its shortest form. Ops with `has_tiny_form` have forms encoding indices below 4 in the opcode.
+    fn short_form(&mut self, mnemonic: &str, idx: u32, has_tiny_form: bool) {
+        self.str(mnemonic);
+        if has_tiny_form && idx < 4 {
+            self.with_uint(".", idx);
+        } else if u8::try_from(idx).is_ok() {
+            self.with_uint(".s ", idx);
+        } else {
+            self.with_uint(" ", idx);
+        }
+    }
+    /// Writes `ldc.i4`, using its shortest form. `value` must fit within an `i32`.
+    fn ldc_i4(&mut self, value: i64) {
+        if value == -1 {
+            self.str("ldc.i4.m1");
+            return;
+        }
+        if (0..=8).contains(&value) {
+            self.str("ldc.i4.");
+        } else if i8::try_from(value).is_ok() {
+            self.str("ldc.i4.s ");
+        } else {
+            self.str("ldc.i4 ");
+        }
+        self.int(value);
+    }
+}
+#[test]
+fn matches_op_cli() {
+    let ops = [
+        CILOp::Label(17),
+        CILOp::GoTo(0),
+        CILOp::BNe(4_000_000_000),
+        CILOp::Switch([1, 20, 300].into()),
+        CILOp::Leave(5),
+        CILOp::BGeUn(6),
+        CILOp::EndFinally,
+        CILOp::LDArg(3),
+        CILOp::LDArg(255),
+        CILOp::LDArg(256),
+        CILOp::LDArgA(0),
+        CILOp::STLoc(70_000),
+        CILOp::LdcI32(-1),
+        CILOp::LdcI32(8),
+        CILOp::LdcI32(-128),
+        CILOp::LdcI32(i32::MIN),
+        CILOp::LdcI64(100),
+        CILOp::LdcI64(i64::MIN),
+        CILOp::LdStr("plain \"quoted\" 'text' \\".into()),
+        CILOp::LdStr("new\nline, ünicode\0".into()),
+        CILOp::Comment("comment".into()),
+        CILOp::Add,
+        CILOp::Ret,
+    ];
+    for op in ops {
+        let mut out = Vec::new();
+        ILWriter::new(&mut out).op(&op);
+        assert_eq!(String::from_utf8(out).unwrap(), op_cli(&op), "{op:?}");
+    }
+}
@@ -1,11 +1,11 @@
-use super::{ilasm_op::dotnet_type_ref_cli, AssemblyExporter};
+use super::{il_writer::ILWriter, ilasm_op::dotnet_type_ref_cli, AssemblyExporter};
 use crate::{
     access_modifier::AccessModifer,
     assembly_exporter::{
         ilasm_op::{non_void_type_cil, type_cil},
         AssemblyExportError,
     },
-    method::Method,
+    method::{ExceptionHandler, HandlerKind, ImplFlags, Method},
     r#type::TypeDef,
     r#type::{DotnetTypeRef, Type},
 };
@@ -23,6 +23,12 @@ impl std::io::Write for ILASMExporter {
         self.encoded_asm.flush()
     }
 }
+impl ILASMExporter {
+    /// Returns the ILASM text of the exported assembly, without assembling it.
+    pub fn into_il(self) -> Vec<u8> {
+        self.encoded_asm
+    }
+}
 impl AssemblyExporter for ILASMExporter {
     fn add_global(&mut self, tpe: &Type, name: &str) {
         writeln!(
@@ -61,17 +67,8 @@ impl AssemblyExporter for ILASMExporter {
         final_path: &std::path::Path,
         is_dll: bool,
     ) -> Result<(), AssemblyExportError> {
-        let directory = absolute_path(final_path)
-            .map_err(|io| AssemblyExportError::CouldNotCanonalizePath(io, final_path.to_owned()))?
-            .parent()
-            .expect("Can't get the target directory")
-            .to_owned();
-
-        let mut out_path = directory.clone();
-        out_path.set_file_name(final_path.file_name().expect("Target file has no name!"));
-        if let Some(ext) = final_path.extension() {
-            out_path = out_path.with_extension(ext);
-        }
+        let out_path = output_path(final_path)
+            .map_err(|io| AssemblyExportError::CouldNotCanonalizePath(io, final_path.to_owned()))?;
         //final_path.expect("Could not canonialize path!");
 
         let cil_path = out_path.with_extension("il");
@@ -90,7 +87,9 @@ impl AssemblyExporter for ILASMExporter {
             target,
             cil_path.clone().to_string_lossy().to_string(),
         ];
-        let out = std::process::Command::new("ilasm")
+        let profile = crate::config::config().runtime.profile();
+        let out = std::process::Command::new(profile.ilasm())
+            .args(profile.ilasm_flags())
             .args(args)
             .output()
             .expect("failed run ilasm process");
@@ -107,15 +106,18 @@ impl AssemblyExporter for ILASMExporter {
     }
 }
 fn type_def_cli(
-    w: &mut impl Write,
+    w: &mut Vec<u8>,
     tpe: &TypeDef,
     is_nested: bool,
 ) -> Result<(), super::AssemblyExportError> {
     let name = tpe.name();
-    assert!(
-        tpe.gargc() == 0,
-        "Generic typedefs not supported yet. tpe:{tpe:?}"
-    );
+    // Generic parameters are only ever referred to by their index(`!0`), so their names don't matter.
+    let generic_params = if tpe.gargc() == 0 {
+        String::new()
+    } else {
+        let params: Vec<_> = (0..tpe.gargc()).map(|idx| format!("G{idx}")).collect();
+        format!("<{}>", params.join(","))
+    };
     let extends = if let Some(extended) = tpe.extends() {
         todo!("Can't handle inheretence yet. Typedef inherits from {extended:?}!");
     } else {
@@ -137,7 +139,10 @@ fn type_def_cli(
         ""
     };
     let nested = if is_nested { "nested" } else { "" };
-    writeln!(w,".class {nested} {access} {explicit} ansi {sealed} beforefieldinit {name} extends {extends}{{")?;
+    writeln!(w,".class {nested} {access} {explicit} ansi {sealed} beforefieldinit {name}{generic_params} extends {extends}{{")?;
+    if let Some(size) = tpe.explicit_size() {
+        writeln!(w, "\t.size {size}")?;
+    }
     for inner_type in tpe.inner_types() {
         type_def_cli(w, inner_type, true)?;
     }
@@ -158,28 +163,51 @@ fn type_def_cli(
             )?;
         }
     }
+    for (field_name, field_type) in tpe.static_fields() {
+        writeln!(
+            w,
+            "\t.field public static {field_type_name} {field_name}",
+            field_type_name = non_void_type_cil(field_type)
+        )?;
+    }
     for method in tpe.methods() {
         method_cil(w, method)?;
     }
     writeln!(w, "}}")?;
     Ok(())
 }
-fn method_cil(w: &mut impl Write, method: &Method) -> std::io::Result<()> {
+fn method_cil(w: &mut Vec<u8>, method: &Method) -> std::io::Result<()> {
     let access = if let AccessModifer::Private = method.access() {
         "private"
     } else {
         "public"
     };
-    let static_inst = if method.is_static() {
-        "static"
+    let static_inst = match (method.is_static(), method.sig().is_vararg()) {
+        (true, false) => "static",
+        (true, true) => "static vararg",
+        (false, false) => "instance",
+        (false, true) => "instance vararg",
+    };
+    // Constructors must be marked as special, or the runtime will not recognize them.
+    let special = if method.name() == ".cctor" || method.name() == ".ctor" {
+        "specialname rtspecialname "
     } else {
-        "instance"
+        ""
     };
+    // P/Invoke methods are implemented by a native library, and have no body.
+    let pinvoke = method.pinvoke().map_or(String::new(), |pinvoke| {
+        format!(
+            "pinvokeimpl(\"{module}\" as \"{entry_point}\" {call_conv}) ",
+            module = pinvoke.module(),
+            entry_point = pinvoke.entry_point(),
+            call_conv = pinvoke.call_conv().name()
+        )
+    });
     let output = type_cil(method.sig().output());
     let name = method.name();
     write!(
         w,
-        ".method {access} hidebysig {static_inst} {output} {name}("
+        ".method {access} hidebysig {special}{pinvoke}{static_inst} {output} {name}("
     )?;
     let mut input_iter = method.explicit_inputs().iter();
     if let Some(input) = input_iter.next() {
@@ -188,11 +216,31 @@ fn method_cil(w: &mut impl Write, method: &Method) -> std::io::Result<()> {
     for input in input_iter {
         write!(w, ",{}", non_void_type_cil(input))?;
     }
-    writeln!(w, "){{")?;
+    if method.pinvoke().is_some() {
+        return writeln!(w, ") preservesig{{}}");
+    }
+    let impl_flags = match method.impl_flags() {
+        ImplFlags::None => "",
+        ImplFlags::AggressiveInlining => " aggressiveinlining",
+        ImplFlags::NoInlining => " noinlining",
+    };
+    writeln!(w, "){impl_flags}{{")?;
     if method.is_entrypoint() {
         writeln!(w, ".entrypoint")?;
     }
-    if crate::ALWAYS_INIT_LOCALS {
+    if let Some((entry_point, call_conv)) = method.unmanaged_export() {
+        let blob: Vec<String> = crate::pinvoke::unmanaged_callers_only_blob(entry_point, call_conv)
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+        writeln!(
+            w,
+            "\t.custom instance void [System.Runtime]System.Runtime.InteropServices.UnmanagedCallersOnlyAttribute::.ctor() = ({blob})",
+            blob = blob.join(" ")
+        )?;
+    }
+    writeln!(w, "\t.maxstack {}", method.max_stack())?;
+    if crate::config::config().always_init_locals {
         writeln!(w, "\t.locals init(")?;
     } else {
         writeln!(w, "\t.locals (")?;
@@ -213,11 +261,86 @@ fn method_cil(w: &mut impl Write, method: &Method) -> std::io::Result<()> {
         )?;
     }
     writeln!(w, "\n\t)")?;
+    // Ops make up most of the text, so they are written without any intermediate formatting.
+    let mut writer = ILWriter::new(w);
     for op in method.get_ops() {
-        writeln!(w, "\t{op_cli}", op_cli = super::ilasm_op::op_cli(op))?;
+        writer.str("\t");
+        writer.op(op);
+        writer.str("\n");
+    }
+    for handler in method.handlers() {
+        writeln!(w, "\t{}", handler_cil(handler))?;
     }
     writeln!(w, "}}")
 }
+// The ILASM text of a method, used for dumping it in a readable form, which can be pasted into an `.il` file.
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut il = Vec::new();
+        method_cil(&mut il, self).map_err(|_| std::fmt::Error)?;
+        f.write_str(&String::from_utf8_lossy(&il))
+    }
+}
+/// Returns the ILASM clause describing `handler`, using the label form(eg. `.try bb_1 to bb_2 finally handler bb_2 to bb_3`).
+fn handler_cil(handler: &ExceptionHandler) -> String {
+    let (try_start, try_end) = handler.try_range();
+    let (handler_start, handler_end) = handler.handler_range();
+    let kind = match handler.kind() {
+        HandlerKind::Catch(class) => format!("catch {}", dotnet_type_ref_cli(class)),
+        HandlerKind::Finally => "finally".into(),
+        HandlerKind::Fault => "fault".into(),
+    };
+    format!(
+        ".try bb_{try_start} to bb_{try_end} {kind} handler bb_{handler_start} to bb_{handler_end}"
+    )
+}
+/// If `method` matches the [`dump_il`](crate::config::BackendConfig::dump_il) filter, writes its ILASM text to `{name}.il` and its ops to `{name}.ops` in the dump directory.
+/// If it matches the [`dump_cfg`](crate::config::BackendConfig::dump_cfg) filter, writes its control flow graph to `{name}.dot`.
+pub(crate) fn dump_method(method: &Method) {
+    let config = crate::config::config();
+    let dir = &config.dump_il_dir;
+    if let Some(filter) = &config.dump_cfg {
+        if method.name().contains(filter.as_str()) {
+            std::fs::create_dir_all(dir).expect("Could not create the CIL dump directory!");
+            std::fs::write(
+                dir.join(format!("{}.dot", method.name())),
+                method.dump_cfg_dot(),
+            )
+            .expect("Could not dump the control flow graph of a method!");
+        }
+    }
+    let Some(filter) = &config.dump_il else {
+        return;
+    };
+    if !method.name().contains(filter.as_str()) {
+        return;
+    }
+    std::fs::create_dir_all(dir).expect("Could not create the CIL dump directory!");
+    let mut il = Vec::new();
+    method_cil(&mut il, method).expect("Could not write the CIL of a method!");
+    std::fs::write(dir.join(format!("{}.il", method.name())), il)
+        .expect("Could not dump the CIL of a method!");
+    let ops: String = method
+        .get_ops()
+        .iter()
+        .map(|op| format!("{op:?}\n"))
+        .collect();
+    std::fs::write(dir.join(format!("{}.ops", method.name())), ops)
+        .expect("Could not dump the ops of a method!");
+}
+/// Returns the path the assembly exported to `final_path` is written to. Note that it is placed in the parent of the directory containing `final_path`.
+pub fn output_path(final_path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+    let directory = absolute_path(final_path)?
+        .parent()
+        .expect("Can't get the target directory")
+        .to_owned();
+    let mut out_path = directory;
+    out_path.set_file_name(final_path.file_name().expect("Target file has no name!"));
+    if let Some(ext) = final_path.extension() {
+        out_path = out_path.with_extension(ext);
+    }
+    Ok(out_path)
+}
 fn absolute_path(path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
     if path.has_root() {
         Ok(path.to_owned())
@@ -227,3 +350,106 @@ fn absolute_path(path: &std::path::Path) -> std::io::Result<std::path::PathBuf>
         Ok(abs_path)
     }
 }
+#[test]
+fn handler_clauses() {
+    let catch = ExceptionHandler::new(
+        1,
+        2,
+        HandlerKind::Catch(Box::new(crate::utilis::exception_class())),
+        2,
+        3,
+    );
+    assert_eq!(
+        handler_cil(&catch),
+        ".try bb_1 to bb_2 catch class [System.Runtime]System.Exception handler bb_2 to bb_3"
+    );
+    let finally = ExceptionHandler::new(0, 4, HandlerKind::Finally, 4, 5);
+    assert_eq!(
+        handler_cil(&finally),
+        ".try bb_0 to bb_4 finally handler bb_4 to bb_5"
+    );
+}
+#[test]
+fn method_display() {
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        crate::function_sig::FnSig::new(&[Type::I32], &Type::I32),
+        "inc",
+        vec![],
+    );
+    method.set_ops(vec![
+        crate::cil::CILOp::LDArg(0),
+        crate::cil::CILOp::LdcI32(1),
+        crate::cil::CILOp::Add,
+        crate::cil::CILOp::Ret,
+    ]);
+    assert_eq!(method.get_ops()[0].to_string(), "ldarg.0");
+    let text = method.to_string();
+    assert!(text.starts_with(".method public hidebysig static int32 inc(int32){"));
+    assert!(text.contains("\tldarg.0\n\tldc.i4.1\n\tadd\n\tret\n"));
+}
+#[test]
+fn type_cctor() {
+    let mut tpe = TypeDef::nameonly("Counter");
+    tpe.add_static_field_with_init("count".into(), Type::I32, [crate::cil::CILOp::LdcI32(1)]);
+    tpe.extend_cctor([crate::cil::CILOp::Nop]);
+    let mut w = Vec::new();
+    type_def_cli(&mut w, &tpe, false).expect("Could not export the type!");
+    let text = String::from_utf8(w).expect("Exported IL is not UTF-8!");
+    assert!(text.contains("\t.field public static int32 count\n"));
+    assert!(
+        text.contains(".method private hidebysig specialname rtspecialname static void .cctor(){")
+    );
+    assert!(text.contains("Counter::count\n\tnop\n\tret\n"));
+}
+#[test]
+fn generic_type_def() {
+    let mut tpe = TypeDef::nameonly("Wrapper`1");
+    tpe.set_generic_count(1);
+    tpe.add_field("inner".into(), Type::GenericArg(0));
+    tpe.add_field("next".into(), Type::Ptr(Type::GenericArg(0).into()));
+    let mut w = Vec::new();
+    type_def_cli(&mut w, &tpe, false).expect("Could not export the type!");
+    let text = String::from_utf8(w).expect("Exported IL is not UTF-8!");
+    assert!(text.contains(" Wrapper`1<G0> extends "));
+    assert!(text.contains("\t.field public !0 inner\n"));
+    assert!(text.contains("\t.field public !0* next\n"));
+    let mut instance = crate::r#type::DotnetTypeRef::new(None, "Wrapper`1");
+    instance.set_generics([Type::I32]);
+    assert_eq!(crate::r#type::mangle(&instance.into()), "Wrapper_g1_i32");
+}
+#[test]
+fn method_impl_flags() {
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        crate::function_sig::FnSig::new(&[], &Type::Void),
+        "shim",
+        vec![],
+    );
+    method.set_ops(vec![crate::cil::CILOp::Ret]);
+    method.set_impl_flags(ImplFlags::AggressiveInlining);
+    assert!(method
+        .to_string()
+        .starts_with(".method public hidebysig static void shim() aggressiveinlining{"));
+    method.set_impl_flags(ImplFlags::NoInlining);
+    assert!(method.to_string().contains("shim() noinlining{"));
+}
+#[test]
+fn pinvoke_decl() {
+    use crate::pinvoke::{pinvoke_method, CallConv, PInvoke};
+    let method = pinvoke_method(
+        "sqlite3_libversion_number",
+        crate::function_sig::FnSig::new(&[], &Type::I32),
+        PInvoke::new(
+            "sqlite3".into(),
+            "sqlite3_libversion_number".into(),
+            CallConv::Cdecl,
+        ),
+    );
+    assert_eq!(
+        method.to_string(),
+        ".method public hidebysig pinvokeimpl(\"sqlite3\" as \"sqlite3_libversion_number\" cdecl) static int32 sqlite3_libversion_number() preservesig{}\n"
+    );
+}
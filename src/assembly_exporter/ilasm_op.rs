@@ -1,9 +1,11 @@
 use std::borrow::Cow;
 
-use crate::r#type::{DotnetTypeRef, Type};
+use crate::{
+    cil::{CILOp, CallSite, FieldDescriptor},
+    r#type::{DotnetTypeRef, Type},
+};
 
-pub fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
-    use crate::cil::CILOp;
+pub fn op_cli(op: &CILOp) -> Cow<'static, str> {
     match op {
         //Control flow
         CILOp::Ret => "ret".into(),
@@ -14,72 +16,46 @@ pub fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         CILOp::BGe(id) => format!("bge bb_{id}").into(),
         CILOp::BLt(id) => format!("blt bb_{id}").into(),
         CILOp::BLe(id) => format!("ble bb_{id}").into(),
+        CILOp::BLtUn(id) => format!("blt.un bb_{id}").into(),
+        CILOp::BGeUn(id) => format!("bge.un bb_{id}").into(),
+        CILOp::BLeUn(id) => format!("ble.un bb_{id}").into(),
         CILOp::BZero(id) => format!("brzero bb_{id}").into(),
         CILOp::BTrue(id) => format!("brtrue bb_{id}").into(),
+        CILOp::Leave(id) => format!("leave bb_{id}").into(),
+        CILOp::EndFinally => "endfinally".into(),
+        CILOp::CallI(sig) => {
+            let inputs: Vec<_> = sig.inputs().iter().map(non_void_type_cil).collect();
+            format!(
+                "calli {output}({inputs})",
+                output = type_cil(sig.output()),
+                inputs = inputs.join(",")
+            )
+            .into()
+        }
+        CILOp::Switch(targets) => {
+            let targets: Vec<_> = targets.iter().map(|id| format!("bb_{id}")).collect();
+            format!("switch ({targets})", targets = targets.join(",")).into()
+        }
         CILOp::Call(call_site) => {
             if call_site.is_nop() {
                 "".into()
             } else {
-                //assert!(sig.inputs.is_empty());
-                let mut inputs_iter = call_site.explicit_inputs().iter();
-                let mut input_string = String::new();
-                if let Some(firts_arg) = inputs_iter.next() {
-                    input_string.push_str(&non_void_type_cil(firts_arg));
-                }
-                for arg in inputs_iter {
-                    input_string.push(',');
-                    input_string.push_str(&non_void_type_cil(arg));
-                }
-                let prefix = if call_site.is_static() {
-                    ""
-                } else {
-                    "instance"
-                };
-                let owner_name = match call_site.class() {
-                    Some(owner) => {
-                        format!("{}::", type_cil(&owner.clone().into()))
-                    }
-                    None => String::new(),
-                };
-                format!(
-                    "call {prefix} {output} {owner_name} {function_name}({input_string})",
-                    function_name = call_site.name(),
-                    output = type_cil(call_site.signature().output())
-                )
-                .into()
+                format!("call {}", call_site_cil(call_site)).into()
             }
         }
+        CILOp::LDFtn(call_site) | CILOp::LDVirtFtn(call_site) => {
+            let op_name = if let CILOp::LDVirtFtn(_) = op {
+                "ldvirtftn"
+            } else {
+                "ldftn"
+            };
+            format!("{op_name} {}", call_site_cil(call_site)).into()
+        }
         CILOp::CallVirt(call_site) => {
             if call_site.is_nop() {
                 "".into()
             } else {
-                //assert!(sig.inputs.is_empty());
-                let mut inputs_iter = call_site.explicit_inputs().iter();
-                let mut input_string = String::new();
-                if let Some(firts_arg) = inputs_iter.next() {
-                    input_string.push_str(&non_void_type_cil(firts_arg));
-                }
-                for arg in inputs_iter {
-                    input_string.push(',');
-                    input_string.push_str(&non_void_type_cil(arg));
-                }
-                let prefix = if call_site.is_static() {
-                    ""
-                } else {
-                    "instance"
-                };
-                let owner_name = match call_site.class() {
-                    Some(owner) => {
-                        format!("{}::", type_cil(&owner.clone().into()))
-                    }
-                    None => String::new(),
-                };
-                format!(
-                    "callvirt {prefix} {output} {owner_name} {function_name}({input_string})",
-                    function_name = call_site.name(),
-                    output = type_cil(call_site.signature().output())
-                )
-                .into()
+                format!("callvirt {}", call_site_cil(call_site)).into()
             }
         }
         //Arthmetics
@@ -104,8 +80,10 @@ pub fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         CILOp::Shr => "shr".into(),
         //Comparisons
         CILOp::Gt => "cgt".into(),
+        CILOp::GtUn => "cgt.un".into(),
         CILOp::Eq => "ceq".into(),
         CILOp::Lt => "clt".into(),
+        CILOp::LtUn => "clt.un".into(),
         //Arguments
         CILOp::LDArg(argnum) => {
             if *argnum < 4 {
@@ -174,7 +152,7 @@ pub fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
             } else if *value <= 8 && *value >= 0 {
                 format!("ldc.i4.{value}").into()
             } else if i8::try_from(*value).is_ok() {
-                format!("ldc.i4.s {value}\n\t").into()
+                format!("ldc.i4.s {value}").into()
             } else if i32::try_from(*value).is_ok() {
                 format!("ldc.i4 {value}").into()
             } else {
@@ -202,6 +180,7 @@ pub fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         }
         //Debug
         CILOp::Comment(comment) => format!("//{comment}").into(),
+        CILOp::SourceSpan(span) => format!("// {span}").into(),
         //Convertions
         CILOp::ConvISize(checked) => {
             if *checked {
@@ -287,6 +266,7 @@ pub fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
                 "conv.r8".into()
             }
         }
+        CILOp::ConvRUn => "conv.r.un".into(),
         // Pointer stuff
         CILOp::LDIndI8 => "ldind.i1".into(),
         CILOp::LDIndI16 => "ldind.i2".into(),
@@ -306,6 +286,15 @@ pub fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         CILOp::LocAlloc => "localloc".into(),
         //OOP
         CILOp::SizeOf(tpe) => format!("sizeof {tpe}", tpe = type_cil(tpe)).into(),
+        CILOp::Box(tpe) => format!("box {tpe}", tpe = type_cil(tpe)).into(),
+        CILOp::UnboxAny(tpe) => format!("unbox.any {tpe}", tpe = type_cil(tpe)).into(),
+        CILOp::InitObj(tpe) => format!("initobj {tpe}", tpe = type_cil(tpe)).into(),
+        CILOp::InitBlk => "initblk".into(),
+        CILOp::LDTypeToken(tpe) => format!("ldtoken {tpe}", tpe = type_cil(tpe)).into(),
+        CILOp::IsInst(class) => format!("isinst {class}", class = dotnet_type_ref_cli(class)).into(),
+        CILOp::CastClass(class) => {
+            format!("castclass {class}", class = dotnet_type_ref_cli(class)).into()
+        }
         CILOp::Throw => "throw".into(),
         CILOp::Rethrow => "rethrow".into(),
         CILOp::LdStr(str) => format!("ldstr {str:?}").replace('\'',"\\\'").into(),
@@ -319,28 +308,15 @@ pub fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
             tpe = type_cil(&obj.as_ref().clone())
         )
         .into(),
-        CILOp::LDField(descr) => format!(
-            "ldfld {prefixed_type} {owner}::{field_name}",
-            prefixed_type = type_cil(descr.tpe()),
-            owner = type_cil(&descr.owner().clone().into()),
-            field_name = descr.name()
-        )
-        .into(),
-        CILOp::LDFieldAdress(descr) => format!(
-            "ldflda {prefixed_type} {owner}::{field_name}",
-            prefixed_type = type_cil(descr.tpe()),
-            owner = type_cil(&descr.owner().clone().into()),
-            field_name = descr.name()
-        )
-        .into(),
-        CILOp::STField(descr) => format!(
-            "stfld {prefixed_type} {owner}::{field_name}",
-            prefixed_type = type_cil(descr.tpe()),
-            owner = type_cil(&descr.owner().clone().into()),
-            field_name = descr.name()
-        )
-        .into(),
+        CILOp::LDField(descr) => format!("ldfld {}", field_descriptor_cil(descr)).into(),
+        CILOp::LDFieldAdress(descr) => format!("ldflda {}", field_descriptor_cil(descr)).into(),
+        CILOp::STField(descr) => format!("stfld {}", field_descriptor_cil(descr)).into(),
         CILOp::CpBlk=>"cpblk".into(),
+        CILOp::Unaligned(alignment) => format!("unaligned. {alignment}").into(),
+        CILOp::Volatile => "volatile.".into(),
+        CILOp::Tail => "tail.".into(),
+        CILOp::Constrained(tpe) => format!("constrained. {tpe}", tpe = type_cil(tpe)).into(),
+        CILOp::InlineIL(il, _) => il.to_string().into(),
         CILOp::NewObj(call_site) => {
             if call_site.is_nop() {
                 "".into()
@@ -380,18 +356,92 @@ pub fn op_cli(op: &crate::cil::CILOp) -> Cow<'static, str> {
         CILOp::Dup => "dup".into(),
         CILOp::LDStaticField(static_field) => {
             match static_field.owner(){
-                Some(_owner)=>todo!("Can't load static field {static_field:?}"),
+                Some(owner)=>format!("ldsfld {tpe} {owner}::{name}",tpe = type_cil(static_field.tpe()), owner = type_cil(&owner.clone().into()), name = static_field.name()).into(),
                 None=>format!("ldsfld {tpe} {name}",tpe = type_cil(static_field.tpe()), name = static_field.name()).into(),
             }
         }
         CILOp::STStaticField(static_field) => {
             match static_field.owner(){
-                Some(_owner)=>todo!("Can't load static field {static_field:?}"),
+                Some(owner)=>format!("stsfld {tpe} {owner}::{name}",tpe = type_cil(static_field.tpe()), owner = type_cil(&owner.clone().into()), name = static_field.name()).into(),
                 None=>format!("stsfld {tpe} {name}",tpe = type_cil(static_field.tpe()), name = static_field.name()).into(),
             }
         }
     }
 }
+/// Returns the ILASM text referring to the method `call_site`(eg. `int32 Foo::Bar(int32)`), as used by the `call` op. The signature of generic
+/// methods refers to their generic arguments(eg. `!!0 Foo::Bar<int32>(!!0)`).
+pub fn call_site_cil(call_site: &CallSite) -> String {
+    //assert!(sig.inputs.is_empty());
+    let mut inputs_iter = call_site.explicit_inputs().iter();
+    let mut input_string = String::new();
+    if let Some(firts_arg) = inputs_iter.next() {
+        input_string.push_str(&non_void_type_cil(firts_arg));
+    }
+    for arg in inputs_iter {
+        input_string.push(',');
+        input_string.push_str(&non_void_type_cil(arg));
+    }
+    // The types of variadic arguments follow the sentinel(`...`).
+    let varargs = call_site.signature().varargs();
+    if !varargs.is_empty() {
+        if !input_string.is_empty() {
+            input_string.push(',');
+        }
+        input_string.push_str("...");
+        for arg in varargs {
+            input_string.push(',');
+            input_string.push_str(&non_void_type_cil(arg));
+        }
+    }
+    let prefix = match (call_site.is_static(), call_site.signature().is_vararg()) {
+        (true, false) => "",
+        (true, true) => "vararg",
+        (false, false) => "instance",
+        (false, true) => "instance vararg",
+    };
+    let owner_name = match call_site.class() {
+        Some(owner) => {
+            format!("{}::", type_cil(&owner.clone().into()))
+        }
+        None => String::new(),
+    };
+    let generics = if call_site.generics().is_empty() {
+        String::new()
+    } else {
+        let generics: Vec<_> = call_site.generics().iter().map(type_cil).collect();
+        format!("<{}>", generics.join(","))
+    };
+    format!(
+        "{prefix} {output} {owner_name} {function_name}{generics}({input_string})",
+        function_name = call_site.name(),
+        output = type_cil(call_site.signature().output())
+    )
+}
+/// Returns the ILASM text referring to the field `descr`(eg. `int32 Foo::bar`), as used by the field access ops.
+pub fn field_descriptor_cil(descr: &FieldDescriptor) -> String {
+    format!(
+        "{prefixed_type} {owner}::{field_name}",
+        prefixed_type = type_cil(descr.tpe()),
+        owner = type_cil(&descr.owner().clone().into()),
+        field_name = descr.name()
+    )
+}
+// The ILASM text of ops, call sites and fields, used for dumping them in a readable form.
+impl std::fmt::Display for CILOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&op_cli(self))
+    }
+}
+impl std::fmt::Display for CallSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&call_site_cil(self))
+    }
+}
+impl std::fmt::Display for FieldDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&field_descriptor_cil(self))
+    }
+}
 pub fn non_void_type_cil(tpe: &Type) -> Cow<'static, str> {
     match tpe {
         Type::Void => "valuetype RustVoid".into(),
@@ -408,7 +458,7 @@ pub fn type_cil(tpe: &Type) -> Cow<'static, str> {
         Type::U16 => "uint16".into(),
         Type::F32 => "float32".into(),
         Type::I32 => "int32".into(),
-        Type::U32 => "uint32".into(),
+        Type::U32 | Type::Char => "uint32".into(),
         Type::F64 => "float64".into(),
         Type::I64 => "int64".into(),
         Type::U64 => "uint64".into(),
@@ -417,13 +467,18 @@ pub fn type_cil(tpe: &Type) -> Cow<'static, str> {
         Type::ISize => "native int".into(),
         Type::USize => "native uint".into(),
         Type::Ptr(inner) => format!("{inner}*", inner = type_cil(inner)).into(),
+        Type::ManagedReference(inner) => format!("{inner}&", inner = type_cil(inner)).into(),
         Type::DotnetType(dotnet_type) => dotnet_type_ref_cli(dotnet_type).into(),
         //Special type
         Type::Unresolved => "valuetype Unresolved".into(),
         Type::Bool => "bool".into(),
         Type::DotnetChar => "char".into(),
         Type::GenericArg(idx) => format!("!{idx}").into(),
+        Type::MethodGenericArg(idx) => format!("!!{idx}").into(),
         Type::Foreign => "valuetype Foreign".into(),
+        Type::Vector64(_) | Type::Vector128(_) | Type::Vector256(_) => {
+            dotnet_type_ref_cli(&tpe.simd_class().expect("Not a SIMD vector type!")).into()
+        }
         Type::DotnetArray(array) => {
             let arr = if array.dimensions > 0 {
                 (0..(array.dimensions - 1)).map(|_| ",").collect::<String>()
@@ -466,3 +521,18 @@ fn generics_str(generics: &[Type]) -> Cow<'static, str> {
         format!("<{garg_string}>").into()
     }
 }
+#[test]
+fn vararg_call_site() {
+    let printf = CallSite::new(
+        None,
+        "printf".into(),
+        crate::function_sig::FnSig::new_vararg(&[Type::Ptr(Type::I8.into())], &Type::I32)
+            .with_varargs(vec![Type::I32, Type::F64]),
+        true,
+    );
+    assert_eq!(
+        call_site_cil(&printf),
+        "vararg int32  printf(int8*,...,int32,float64)"
+    );
+    assert_eq!(CILOp::Call(printf.into()).stack_diff(), -2);
+}
@@ -8,9 +8,11 @@ use crate::{
     r#type::{DotnetTypeRef, Type, TypeDef},
     IString,
 };
+/// Fast writer of the ILASM text of ops.
+pub(crate) mod il_writer;
 /// ILASM-based assembly exporter.
 pub mod ilasm_exporter;
-mod ilasm_op;
+pub(crate) mod ilasm_op;
 /// This trait represents an interface implemented by all .NET assembly exporters. (Currently only ilasm)
 pub trait AssemblyExporter: Sized {
     /// Initializes an assembly exporter.
@@ -0,0 +1,416 @@
+//! Atomic intrinsics are lowered like this:
+//!
+//! | Rust                         | CIL                                                        |
+//! |------------------------------|------------------------------------------------------------|
+//! | `atomic_load_*`              | `volatile.` load. A volatile read has acquire semantics.   |
+//! | `atomic_store_*`             | `volatile.` store. A volatile write has release semantics. |
+//! | `atomic_store_seqcst`        | `volatile.` store followed by a full memory barrier.       |
+//! | `atomic_fence_*`             | `System.Threading.Interlocked.MemoryBarrier`               |
+//! | `atomic_xchg_*`              | `System.Threading.Interlocked.Exchange`                    |
+//! | `atomic_cxchg(weak)_*`       | `System.Threading.Interlocked.CompareExchange`             |
+//! | `atomic_xadd_*`,`atomic_xsub_*` | `System.Threading.Interlocked.Add`                      |
+//! | `atomic_and_*`,`atomic_or_*` | `System.Threading.Interlocked.And`/`Or`                    |
+//! | `atomic_nand_*`,`atomic_xor_*`, `atomic_(u)max_*`,`atomic_(u)min_*` | `System.Threading.Interlocked.CompareExchange` loop |
+//!
+//! All `Interlocked` operations imply a full memory barrier, so they satisfy any Rust ordering.
+//! Since every access is `volatile.`, the JIT will never cache the value of a static(or any other memory location) accessed atomically.
+//! Rust statics live in unmanaged memory, pointed to by static fields. Loads of those fields are `volatile.` too, when they are used to access a
+//! static atomically.
+//! The same mapping is used on CoreCLR and Mono, since both implement the ECMA-335 memory model(I.12.6).
+//! `Interlocked` does not support 8 and 16 bit values, so read-modify-write operations on them are `CompareExchange` loops on the aligned 4 byte
+//! word containing the value(see [`narrow_cas_loop`]).
+use crate::{
+    cil::{CILOp, CallSite, FieldDescriptor},
+    function_sig::FnSig,
+    r#type::{DotnetTypeRef, Type},
+};
+/// The `System.Threading.Interlocked` class.
+fn interlocked() -> DotnetTypeRef {
+    DotnetTypeRef::new(Some("System.Threading"), "System.Threading.Interlocked")
+        .with_valuetype(false)
+}
+/// Op emitting a full memory barrier.
+pub(crate) fn memory_barrier() -> CILOp {
+    CILOp::Call(CallSite::boxed(
+        Some(interlocked()),
+        "MemoryBarrier".into(),
+        FnSig::new(&[], &Type::Void),
+        true,
+    ))
+}
+/// Returns the type used when calling `Interlocked` methods operating on `tpe`, or `None` if `Interlocked` does not support `tpe`.
+/// Signed and unsigned intigers of the same size are the same on the evaluation stack, so only the signed overloads are used.
+fn interlocked_type(tpe: &Type) -> Option<Type> {
+    match tpe {
+        Type::I32 | Type::U32 => Some(Type::I32),
+        Type::I64 | Type::U64 => Some(Type::I64),
+        Type::ISize | Type::USize | Type::Ptr(_) => Some(Type::ISize),
+        _ => None,
+    }
+}
+/// Calls the method `name` of `Interlocked`, which takes a reference to `tpe` and `argc` values of type `tpe`, returning `tpe`.
+fn interlocked_call(name: &str, tpe: Type, argc: usize) -> CILOp {
+    let mut inputs = vec![Type::ManagedReference(tpe.clone().into())];
+    inputs.extend((0..argc).map(|_| tpe.clone()));
+    CILOp::Call(CallSite::boxed(
+        Some(interlocked()),
+        name.into(),
+        FnSig::new(&inputs, &tpe),
+        true,
+    ))
+}
+/// Returns the ops loading and storing a value of type `tpe` from/to a pointer. Unsigned 8 and 16 bit values are zero extended after loading them,
+/// so that they can be compared with other values of their type.
+fn ind_ops(tpe: &Type) -> (Vec<CILOp>, CILOp) {
+    match tpe {
+        Type::I8 => (vec![CILOp::LDIndI8], CILOp::STIndI8),
+        Type::U8 | Type::Bool => (vec![CILOp::LDIndI8, CILOp::ConvU8(false)], CILOp::STIndI8),
+        Type::I16 => (vec![CILOp::LDIndI16], CILOp::STIndI16),
+        Type::U16 => (
+            vec![CILOp::LDIndI16, CILOp::ConvU16(false)],
+            CILOp::STIndI16,
+        ),
+        Type::I32 | Type::U32 => (vec![CILOp::LDIndI32], CILOp::STIndI32),
+        Type::I64 | Type::U64 => (vec![CILOp::LDIndI64], CILOp::STIndI64),
+        Type::ISize | Type::USize | Type::Ptr(_) => (vec![CILOp::LDIndISize], CILOp::STIndISize),
+        _ => (
+            vec![CILOp::LdObj(tpe.clone().into())],
+            CILOp::STObj(tpe.clone().into()),
+        ),
+    }
+}
+/// Preforms a read-modify-write operation on the 8 or 16 bit value of type `tpe` behind `ptr`, which `Interlocked` does not support. The new value
+/// is stored into the aligned 4 byte word containing the value using `CompareExchange`, retrying until no other thread changed the word in between.
+/// `vals` load the operands, and `update` returns the ops computing the new value, out of the ops loading the old value and the operands.
+/// Returns the old value, or `None` if `tpe` is not an 8 or 16 bit type.
+fn narrow_cas_loop(
+    tpe: &Type,
+    ptr: Vec<CILOp>,
+    vals: Vec<Vec<CILOp>>,
+    update: impl FnOnce(&CILOp, &[CILOp]) -> Vec<CILOp>,
+) -> Option<Vec<CILOp>> {
+    let (mask, normalize) = match tpe {
+        Type::I8 => (0xFF, CILOp::ConvI8(false)),
+        Type::U8 | Type::Bool => (0xFF, CILOp::ConvU8(false)),
+        Type::I16 => (0xFFFF, CILOp::ConvI16(false)),
+        Type::U16 => (0xFFFF, CILOp::ConvU16(false)),
+        _ => return None,
+    };
+    // TMP locals: pointer, address of the word, shift of the value within the word, operands, old word, old value.
+    let count = 5 + vals.len();
+    let local = |idx: usize| CILOp::LoadUnderTMPLocal((count - 1 - idx) as u8);
+    let (word, shift, old_word, old) = (local(1), local(2), local(count - 2), local(count - 1));
+    let operands: Vec<_> = (0..vals.len()).map(|idx| local(3 + idx)).collect();
+    let mut ops = ptr;
+    ops.extend([
+        CILOp::NewTMPLocal(Type::ISize.into()),
+        CILOp::SetTMPLocal,
+        // word = ptr & !3
+        CILOp::LoadTMPLocal,
+        CILOp::LdcI32(!3),
+        CILOp::ConvISize(false),
+        CILOp::And,
+        CILOp::NewTMPLocal(Type::ISize.into()),
+        CILOp::SetTMPLocal,
+        // shift = (ptr & 3) * 8. All supported targets are little endian, so the value starts `ptr & 3` bytes into the word.
+        CILOp::LoadUnderTMPLocal(1),
+        CILOp::LdcI32(3),
+        CILOp::ConvISize(false),
+        CILOp::And,
+        CILOp::ConvI32(false),
+        CILOp::LdcI32(3),
+        CILOp::Shl,
+        CILOp::NewTMPLocal(Type::I32.into()),
+        CILOp::SetTMPLocal,
+    ]);
+    for val in vals {
+        ops.extend(val);
+        ops.extend([CILOp::NewTMPLocal(tpe.clone().into()), CILOp::SetTMPLocal]);
+    }
+    let label = crate::codegen_context::fresh_label();
+    ops.extend([
+        CILOp::NewTMPLocal(Type::I32.into()),
+        CILOp::NewTMPLocal(tpe.clone().into()),
+        CILOp::Label(label),
+        // old_word = *word
+        CILOp::LoadAdressUnderTMPLocal(1),
+        word.clone(),
+        CILOp::Volatile,
+        CILOp::LDIndI32,
+        CILOp::STIndI32,
+        // old = old_word >> shift, normalized
+        old_word.clone(),
+        shift.clone(),
+        CILOp::Shr,
+        normalize,
+        CILOp::SetTMPLocal,
+        // CompareExchange(word, (old_word & !(mask << shift)) | ((new & mask) << shift), old_word)
+        word,
+        old_word.clone(),
+        CILOp::LdcI32(mask),
+        shift.clone(),
+        CILOp::Shl,
+        CILOp::Not,
+        CILOp::And,
+    ]);
+    ops.extend(update(&old, &operands));
+    ops.extend([
+        CILOp::LdcI32(mask),
+        CILOp::And,
+        shift,
+        CILOp::Shl,
+        CILOp::Or,
+        old_word.clone(),
+        interlocked_call("CompareExchange", Type::I32, 2),
+        // Another thread changed the word since it was loaded, so try again.
+        old_word,
+        CILOp::BNe(label),
+        old,
+    ]);
+    ops.extend((0..count).map(|_| CILOp::FreeTMPLocal));
+    Some(ops)
+}
+/// Returns the ops computing the result of the read-modify-write operation `op` from the old value(loaded by `old`) and the operand(loaded by `val`),
+/// or `None` if `op` is not a read-modify-write operation.
+fn rmw_update(op: &str, tpe: &Type, old: &CILOp, val: &CILOp) -> Option<Vec<CILOp>> {
+    let cmp = match op {
+        "and" => return Some(vec![old.clone(), val.clone(), CILOp::And]),
+        "or" => return Some(vec![old.clone(), val.clone(), CILOp::Or]),
+        "xor" => return Some(vec![old.clone(), val.clone(), CILOp::XOr]),
+        "nand" => return Some(vec![old.clone(), val.clone(), CILOp::And, CILOp::Not]),
+        "max" => CILOp::Gt,
+        "min" => CILOp::Lt,
+        "umax" => CILOp::GtUn,
+        "umin" => CILOp::LtUn,
+        _ => return None,
+    };
+    // 8 and 16 bit operands may be sign extended(eg. `u8`s loaded with `ldind.i1`), so they have to be normalized before comparing them.
+    let normalize = match tpe {
+        Type::I8 => Some(CILOp::ConvI8(false)),
+        Type::U8 | Type::Bool => Some(CILOp::ConvU8(false)),
+        Type::I16 => Some(CILOp::ConvI16(false)),
+        Type::U16 => Some(CILOp::ConvU16(false)),
+        _ => None,
+    };
+    // The mask(all ones if the old value should be kept) must be as big as the values.
+    let widen = match tpe {
+        Type::I64 | Type::U64 => Some(CILOp::ConvI64(false)),
+        Type::ISize | Type::USize | Type::Ptr(_) => Some(CILOp::ConvISize(false)),
+        _ => None,
+    };
+    // val ^ ((old ^ val) & -(old cmp val)) is `old` if the comparison holds, and `val` otherwise.
+    let mut ops = vec![
+        val.clone(),
+        old.clone(),
+        val.clone(),
+        CILOp::XOr,
+        old.clone(),
+    ];
+    ops.extend(normalize.clone());
+    ops.push(val.clone());
+    ops.extend(normalize);
+    ops.extend([cmp, CILOp::Neg]);
+    ops.extend(widen);
+    ops.extend([CILOp::And, CILOp::XOr]);
+    Some(ops)
+}
+/// Preforms the read-modify-write operation `op`(see [`rmw_update`]) on the value of type `tpe` behind `ptr`. Returns the old value.
+/// The new value is stored using `CompareExchange`, retrying until no other thread changed the value in between. 8 and 16 bit values are
+/// updated trough the word containing them, like in [`narrow_cas_loop`].
+fn cas_loop_rmw(op: &str, tpe: &Type, ptr: Vec<CILOp>, val: Vec<CILOp>) -> Option<Vec<CILOp>> {
+    let Some(itpe) = interlocked_type(tpe) else {
+        // Checks if `op` is supported before building the loop.
+        rmw_update(op, tpe, &CILOp::Nop, &CILOp::Nop)?;
+        return narrow_cas_loop(tpe, ptr, vec![val], |old, vals| {
+            rmw_update(op, tpe, old, &vals[0]).expect("Unsupported read-modify-write operation!")
+        });
+    };
+    // TMP locals: pointer, operand, old value.
+    let update = rmw_update(op, tpe, &CILOp::LoadTMPLocal, &CILOp::LoadUnderTMPLocal(1))?;
+    let (load, _) = ind_ops(tpe);
+    let mut ops = ptr;
+    ops.extend([
+        CILOp::NewTMPLocal(Type::Ptr(tpe.clone().into()).into()),
+        CILOp::SetTMPLocal,
+    ]);
+    ops.extend(val);
+    let label = crate::codegen_context::fresh_label();
+    ops.extend([
+        CILOp::NewTMPLocal(tpe.clone().into()),
+        CILOp::SetTMPLocal,
+        CILOp::NewTMPLocal(tpe.clone().into()),
+        CILOp::Label(label),
+        CILOp::LoadUnderTMPLocal(2),
+        CILOp::Volatile,
+    ]);
+    ops.extend(load);
+    ops.extend([CILOp::SetTMPLocal, CILOp::LoadUnderTMPLocal(2)]);
+    ops.extend(update);
+    ops.extend([
+        CILOp::LoadTMPLocal,
+        interlocked_call("CompareExchange", itpe, 2),
+        // Another thread changed the value since it was loaded, so try again.
+        CILOp::LoadTMPLocal,
+        CILOp::BNe(label),
+        CILOp::LoadTMPLocal,
+        CILOp::FreeTMPLocal,
+        CILOp::FreeTMPLocal,
+        CILOp::FreeTMPLocal,
+    ]);
+    Some(ops)
+}
+/// Prefixes the loads of static fields within `ptr` with `volatile.`. Those fields point to the memory of Rust statics, and the JIT must not cache
+/// them when a static is accessed atomically.
+pub(crate) fn volatile_statics(ptr: Vec<CILOp>) -> Vec<CILOp> {
+    let mut ops = Vec::with_capacity(ptr.len());
+    for op in ptr {
+        if matches!(
+            op,
+            CILOp::LoadGlobalAllocPtr { .. } | CILOp::LDStaticField(_)
+        ) {
+            ops.push(CILOp::Volatile);
+        }
+        ops.push(op);
+    }
+    ops
+}
+/// Handles the atomic intrinsic `name`, operating on values of type `tpe`, with arguments `args`.
+/// Returns the ops calculating the return value of the intrinsic, or `None` if the intrinsic is not supported.
+pub(crate) fn atomic_intrinsic(
+    name: &str,
+    mut args: Vec<Vec<CILOp>>,
+    tpe: &Type,
+) -> Option<Vec<CILOp>> {
+    let name = name.strip_prefix("atomic_")?;
+    let (op, ordering) = name.split_once('_').unwrap_or((name, ""));
+    let (load, store) = ind_ops(tpe);
+    match op {
+        "load" => {
+            let mut ops = args.remove(0);
+            ops.push(CILOp::Volatile);
+            ops.extend(load);
+            Some(ops)
+        }
+        "store" => {
+            let mut ops = args.remove(0);
+            ops.extend(args.remove(0));
+            ops.extend([CILOp::Volatile, store]);
+            if ordering == "seqcst" {
+                ops.push(memory_barrier());
+            }
+            Some(ops)
+        }
+        // A single thread fence only has to prevent the JIT from reordering memory accesses, but a full barrier is used anyway.
+        "fence" | "singlethreadfence" => Some(vec![memory_barrier()]),
+        "xchg" => {
+            let ptr = args.remove(0);
+            let val = args.remove(0);
+            match interlocked_type(tpe) {
+                Some(itpe) => {
+                    Some([ptr, val, vec![interlocked_call("Exchange", itpe, 1)]].concat())
+                }
+                None => narrow_cas_loop(tpe, ptr, vec![val], |_, vals| vec![vals[0].clone()]),
+            }
+        }
+        "xadd" | "xsub" => {
+            let ptr = args.remove(0);
+            let val = args.remove(0);
+            let arthm = if op == "xadd" { CILOp::Add } else { CILOp::Sub };
+            let Some(itpe) = interlocked_type(tpe) else {
+                return narrow_cas_loop(tpe, ptr, vec![val], |old, vals| {
+                    vec![old.clone(), vals[0].clone(), arthm]
+                });
+            };
+            // `Interlocked.Add` returns the new value, while Rust expects the old one.
+            let mut ops = val;
+            ops.extend([CILOp::NewTMPLocal(tpe.clone().into()), CILOp::SetTMPLocal]);
+            ops.extend(ptr);
+            ops.push(CILOp::LoadTMPLocal);
+            if op == "xsub" {
+                ops.push(CILOp::Neg);
+            }
+            ops.push(interlocked_call("Add", itpe, 1));
+            ops.push(CILOp::LoadTMPLocal);
+            ops.push(if op == "xadd" { CILOp::Sub } else { CILOp::Add });
+            ops.push(CILOp::FreeTMPLocal);
+            Some(ops)
+        }
+        "and" | "or" => {
+            let ptr = args.remove(0);
+            let val = args.remove(0);
+            match interlocked_type(tpe) {
+                Some(itpe @ (Type::I32 | Type::I64)) => {
+                    let name = if op == "and" { "And" } else { "Or" };
+                    Some([ptr, val, vec![interlocked_call(name, itpe, 1)]].concat())
+                }
+                _ => cas_loop_rmw(op, tpe, ptr, val),
+            }
+        }
+        "nand" | "xor" | "max" | "min" | "umax" | "umin" => {
+            let ptr = args.remove(0);
+            let val = args.remove(0);
+            cas_loop_rmw(op, tpe, ptr, val)
+        }
+        "cxchg" | "cxchgweak" => {
+            let ptr = args.remove(0);
+            let expected = args.remove(0);
+            let new = args.remove(0);
+            let tuple = crate::r#type::simple_tuple(&[tpe.clone(), Type::Bool]);
+            let mut ops = expected;
+            ops.extend([CILOp::NewTMPLocal(tpe.clone().into()), CILOp::SetTMPLocal]);
+            if let Some(itpe) = interlocked_type(tpe) {
+                ops.extend(ptr);
+                ops.extend(new);
+                ops.push(CILOp::LoadTMPLocal);
+                ops.push(interlocked_call("CompareExchange", itpe, 2));
+            } else {
+                // Stores `old ^ ((old ^ new) & -(old == expected))`, which is `new` if `old == expected` and `old` otherwise. The expected
+                // value is under the TMP locals of the loop: the pointer, the address of the word and the shift.
+                let update = |old: &CILOp, vals: &[CILOp]| {
+                    vec![
+                        old.clone(),
+                        old.clone(),
+                        vals[1].clone(),
+                        CILOp::XOr,
+                        old.clone(),
+                        vals[0].clone(),
+                        CILOp::Eq,
+                        CILOp::Neg,
+                        CILOp::And,
+                        CILOp::XOr,
+                    ]
+                };
+                ops.extend(narrow_cas_loop(
+                    tpe,
+                    ptr,
+                    vec![vec![CILOp::LoadUnderTMPLocal(3)], new],
+                    update,
+                )?);
+            }
+            // Stack: old value, TMP locals: expected value
+            ops.extend([
+                CILOp::NewTMPLocal(tpe.clone().into()),
+                CILOp::SetTMPLocal,
+                CILOp::NewTMPLocal(Type::from(tuple.clone()).into()),
+                CILOp::LoadAddresOfTMPLocal,
+                CILOp::LoadUnderTMPLocal(1),
+                CILOp::STField(FieldDescriptor::boxed(
+                    tuple.clone(),
+                    tpe.clone(),
+                    "Item1".into(),
+                )),
+                CILOp::LoadAddresOfTMPLocal,
+                CILOp::LoadUnderTMPLocal(1),
+                CILOp::LoadUnderTMPLocal(2),
+                CILOp::Eq,
+                CILOp::STField(FieldDescriptor::boxed(tuple, Type::Bool, "Item2".into())),
+                CILOp::LoadTMPLocal,
+                CILOp::FreeTMPLocal,
+                CILOp::FreeTMPLocal,
+                CILOp::FreeTMPLocal,
+            ]);
+            Some(ops)
+        }
+        _ => None,
+    }
+}
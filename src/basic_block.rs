@@ -1,92 +1,225 @@
-// This is a WIP module an is currently unused
-#![allow(unused)]
-#![allow(clippy::all)]
-use crate::cil::CILOp;
-use rustc_middle::mir::UnwindAction;
-#[derive(Debug)]
-struct BasicBlock {
-    unwind_action: UnwindAction,
+//! Basic-block representation of method bodies.
+//!
+//! Methods store their ops as a flat sequence with numeric labels, since that is what the exporter writes out. Passes reasoning about control flow
+//! work on a [`MethodBody`] instead: a list of [`BasicBlock`]s, each with an optional label, straight-line ops, and an optional terminator(the branch
+//! or diverging op ending it), together with the exception handlers of the method. [`MethodBody::from_ops`] and [`MethodBody::into_ops`] convert
+//! between the 2 forms without changing the ops, so a pass can freely convert a method, change it, and lower it back to flat ops.
+use crate::{cil::CILOp, method::ExceptionHandler};
+use std::collections::HashMap;
+/// A sequence of ops, only entered at its start, and only left at its end.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BasicBlock {
+    label: Option<u32>,
     ops: Vec<CILOp>,
+    terminator: Option<CILOp>,
 }
 impl BasicBlock {
-    pub fn new(ops: Vec<CILOp>, unwind_action: UnwindAction) -> Self {
-        Self { unwind_action, ops }
-    }
-    pub fn may_merge(&self, other: &Self) -> bool {
-        match (self.unwind_action, other.unwind_action) {
-            (UnwindAction::Continue, UnwindAction::Continue) => true,
-            (UnwindAction::Continue, UnwindAction::Unreachable)
-            | (UnwindAction::Unreachable, UnwindAction::Continue) => true,
-            (UnwindAction::Unreachable, UnwindAction::Unreachable) => true,
-            (UnwindAction::Cleanup(a), UnwindAction::Cleanup(b)) => a == b,
-            (UnwindAction::Terminate(_), UnwindAction::Terminate(_)) => true,
-            (_, _) => false,
+    /// Creates a block starting at `label`, executing `ops`, and ending with `terminator`. `ops` must not contain any labels, branches or
+    /// diverging ops.
+    #[must_use]
+    pub fn new(label: Option<u32>, ops: Vec<CILOp>, terminator: Option<CILOp>) -> Self {
+        Self {
+            label,
+            ops,
+            terminator,
         }
     }
-    pub fn into_ops(bbs: &[Self]) -> Vec<CILOp> {
-        todo!("Can't convert basic blocks {bbs:?} into ops yet!");
+    /// Returns the label this block starts at, if any.
+    pub fn label(&self) -> Option<u32> {
+        self.label
+    }
+    /// Returns the ops of this block, without its label and terminator.
+    pub fn ops(&self) -> &[CILOp] {
+        &self.ops
+    }
+    /// Returns a mutable reference to the ops of this block, without its label and terminator.
+    pub fn ops_mut(&mut self) -> &mut Vec<CILOp> {
+        &mut self.ops
+    }
+    /// Returns the branch or diverging op ending this block, if any.
+    pub fn terminator(&self) -> Option<&CILOp> {
+        self.terminator.as_ref()
+    }
+    /// Returns the labels the terminator of this block may jump to.
+    pub fn targets(&self) -> &[u32] {
+        self.terminator.as_ref().map_or(&[], CILOp::branch_targets)
+    }
+    /// Returns true if execution may continue to the block after this one.
+    pub fn falls_through(&self) -> bool {
+        !self.terminator.as_ref().is_some_and(CILOp::is_diverging)
+    }
+    /// Returns true if this block has no label, ops or terminator.
+    fn is_empty(&self) -> bool {
+        self.label.is_none() && self.ops.is_empty() && self.terminator.is_none()
+    }
+}
+/// The body of a method: its basic blocks, in order, and its exception handlers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MethodBody {
+    blocks: Vec<BasicBlock>,
+    handlers: Vec<ExceptionHandler>,
+}
+impl MethodBody {
+    /// Splits `ops` into basic blocks. A block starts at each label, and after each branch or diverging op.
+    #[must_use]
+    pub fn from_ops(ops: &[CILOp], handlers: &[ExceptionHandler]) -> Self {
+        let mut blocks = Vec::new();
+        let mut current = BasicBlock::default();
+        for op in ops {
+            match op {
+                CILOp::Label(label) => {
+                    if !current.is_empty() {
+                        blocks.push(std::mem::take(&mut current));
+                    }
+                    current.label = Some(*label);
+                }
+                op if !op.branch_targets().is_empty() || op.is_diverging() => {
+                    current.terminator = Some(op.clone());
+                    blocks.push(std::mem::take(&mut current));
+                }
+                op => current.ops.push(op.clone()),
+            }
+        }
+        if !current.is_empty() {
+            blocks.push(current);
+        }
+        Self {
+            blocks,
+            handlers: handlers.into(),
+        }
+    }
+    /// Lowers the blocks back to flat ops.
+    #[must_use]
+    pub fn into_ops(self) -> Vec<CILOp> {
+        let mut ops = Vec::new();
+        for block in self.blocks {
+            ops.extend(block.label.map(CILOp::Label));
+            ops.extend(block.ops);
+            ops.extend(block.terminator);
+        }
+        ops
+    }
+    /// Returns the basic blocks, in order.
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+    /// Returns a mutable reference to the basic blocks.
+    pub fn blocks_mut(&mut self) -> &mut Vec<BasicBlock> {
+        &mut self.blocks
+    }
+    /// Returns the exception handlers, innermost first.
+    pub fn handlers(&self) -> &[ExceptionHandler] {
+        &self.handlers
+    }
+    /// Returns the index of the block starting at `label`.
+    pub fn block_of_label(&self, label: u32) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.label == Some(label))
+    }
+    /// Returns the indices of the blocks execution may continue to after block `block`.
+    pub fn successors(&self, block: usize) -> Vec<usize> {
+        let labels = self.label_map();
+        self.successors_with(block, &labels)
+    }
+    fn label_map(&self) -> HashMap<u32, usize> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, block)| Some((block.label?, idx)))
+            .collect()
+    }
+    fn successors_with(&self, block: usize, labels: &HashMap<u32, usize>) -> Vec<usize> {
+        let mut successors: Vec<usize> = self.blocks[block]
+            .targets()
+            .iter()
+            .filter_map(|target| labels.get(target).copied())
+            .collect();
+        if self.blocks[block].falls_through() && block + 1 < self.blocks.len() {
+            successors.push(block + 1);
+        }
+        successors
+    }
+    /// Returns the labels delimiting protected regions and handlers. Blocks starting at them must be kept.
+    fn handler_labels(&self) -> Vec<u32> {
+        self.handlers
+            .iter()
+            .flat_map(|handler| {
+                let (try_start, try_end) = handler.try_range();
+                let (handler_start, handler_end) = handler.handler_range();
+                [try_start, try_end, handler_start, handler_end]
+            })
+            .collect()
+    }
+    /// Returns, for each block, whether it can be reached from the start of the method, or from one of the handlers.
+    pub fn reachable(&self) -> Vec<bool> {
+        let labels = self.label_map();
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut pending: Vec<usize> = self
+            .handlers
+            .iter()
+            .filter_map(|handler| labels.get(&handler.handler_range().0).copied())
+            .collect();
+        if !self.blocks.is_empty() {
+            pending.push(0);
+        }
+        while let Some(block) = pending.pop() {
+            if std::mem::replace(&mut reachable[block], true) {
+                continue;
+            }
+            pending.extend(self.successors_with(block, &labels));
+        }
+        reachable
+    }
+    /// Removes the blocks which can't be reached, except for the ones starting at labels used by the handlers. Returns the number of removed
+    /// blocks.
+    pub fn remove_unreachable_blocks(&mut self) -> usize {
+        let reachable = self.reachable();
+        let handler_labels = self.handler_labels();
+        let len = self.blocks.len();
+        let mut idx = 0;
+        self.blocks.retain(|block| {
+            let keep = reachable[idx]
+                || block
+                    .label
+                    .is_some_and(|label| handler_labels.contains(&label));
+            idx += 1;
+            keep
+        });
+        len - self.blocks.len()
     }
 }
 #[test]
-fn exceptions() {
-    use crate::{cil::CallSite, function_sig::FnSig, r#type::Type};
-    let bb0 = BasicBlock::new(
-        vec![
+fn ops_roundtrip() {
+    let ops = vec![
+        CILOp::LDArg(0),
+        CILOp::BZero(1),
+        CILOp::LdcI32(1),
+        CILOp::Ret,
+        // Unreachable, and not labeled.
+        CILOp::Pop,
+        CILOp::Label(2),
+        CILOp::Label(1),
+        CILOp::LdcI32(0),
+        CILOp::Ret,
+    ];
+    let body = MethodBody::from_ops(&ops, &[]);
+    assert_eq!(body.blocks().len(), 5);
+    assert_eq!(body.successors(0), [4, 1]);
+    assert_eq!(body.reachable(), [true, true, false, false, true]);
+    assert_eq!(body.clone().into_ops(), ops);
+    let mut body = body;
+    assert_eq!(body.remove_unreachable_blocks(), 2);
+    assert_eq!(
+        body.into_ops(),
+        [
             CILOp::LDArg(0),
-            CILOp::LDArg(1),
-            CILOp::Add,
-            CILOp::Dup,
-            CILOp::STLoc(0),
-            CILOp::Call(CallSite::boxed(
-                None,
-                "malloc".into(),
-                FnSig::new(&[Type::ISize], &Type::Ptr(Type::Void.into())),
-                true,
-            )),
-            CILOp::GoTo(1),
-        ],
-        UnwindAction::Continue,
-    );
-    let bb1 = BasicBlock::new(
-        vec![
-            CILOp::STLoc(1),
-            CILOp::LDLoc(1),
-            CILOp::Call(CallSite::boxed(
-                None,
-                "do_sth_with_a_buff".into(),
-                FnSig::new(&[Type::Ptr(Type::Void.into())], &Type::Void),
-                true,
-            )),
-            CILOp::GoTo(2),
-        ],
-        UnwindAction::Cleanup(3_u32.into()),
-    );
-    let bb2 = BasicBlock::new(
-        vec![
-            CILOp::LDLoc(1),
-            CILOp::Call(CallSite::boxed(
-                None,
-                "free".into(),
-                FnSig::new(&[Type::Ptr(Type::Void.into())], &Type::Void),
-                true,
-            )),
+            CILOp::BZero(1),
+            CILOp::LdcI32(1),
             CILOp::Ret,
-        ],
-        UnwindAction::Continue,
-    );
-    let bb3 = BasicBlock::new(
-        vec![
-            CILOp::LDLoc(1),
-            CILOp::Call(CallSite::boxed(
-                None,
-                "free".into(),
-                FnSig::new(&[Type::Ptr(Type::Void.into())], &Type::Void),
-                true,
-            )),
-            CILOp::Rethrow,
-        ],
-        UnwindAction::Continue,
+            CILOp::Label(1),
+            CILOp::LdcI32(0),
+            CILOp::Ret,
+        ]
     );
-    let bbs = vec![bb0, bb1, bb2, bb3];
-    let ops = BasicBlock::into_ops(&bbs);
 }
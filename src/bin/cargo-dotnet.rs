@@ -0,0 +1,169 @@
+//! `cargo dotnet` - builds, runs and tests crates using `rustc_codegen_clr`.
+//!
+//! Usage: `cargo dotnet <build|run|test> [cargo arguments...]`
+//!
+//! The backend and linker are looked up next to this executable, or at the paths in `RUSTC_CODEGEN_CLR_BACKEND` and `RUSTC_CODEGEN_CLR_LINKER`.
+//! Programs are run using `dotnet`, or `mono` if `CARGO_DOTNET_RUNTIME=mono` is set.
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+/// Subcommand used by cargo to run the built assemblies. Not meant to be invoked directly.
+const EXEC_SUBCOMMAND: &str = "exec";
+fn backend_file_name() -> &'static str {
+    if cfg!(target_os = "linux") {
+        "librustc_codegen_clr.so"
+    } else if cfg!(target_os = "windows") {
+        "rustc_codegen_clr.dll"
+    } else if cfg!(target_os = "macos") {
+        "librustc_codegen_clr.dylib"
+    } else {
+        panic!("Unsupported target OS");
+    }
+}
+fn linker_file_name() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "linker.exe"
+    } else {
+        "linker"
+    }
+}
+/// Returns the path of `file_name`, which is either set by the environment variable `env_var`, or placed next to this executable.
+fn locate(env_var: &str, file_name: &str) -> PathBuf {
+    if let Ok(path) = std::env::var(env_var) {
+        return PathBuf::from(path);
+    }
+    let exe = std::env::current_exe().expect("Could not get the path of cargo-dotnet!");
+    let path = exe
+        .parent()
+        .expect("cargo-dotnet is not inside a directory!")
+        .join(file_name);
+    if !path.exists() {
+        eprintln!(
+            "ERROR: Could not find {file_name} next to cargo-dotnet. Build it with `cargo build`, or set {env_var}."
+        );
+        std::process::exit(1);
+    }
+    path
+}
+/// Returns the target triple of the host, used to set the cargo runner and the target.
+fn host_triple() -> String {
+    let out = Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .expect("Could not run `rustc -vV`");
+    let info = String::from_utf8(out.stdout).expect("rustc version info is not UTF8!");
+    info.lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .expect("rustc did not report a host triple!")
+        .trim()
+        .to_owned()
+}
+/// Returns the `RUSTFLAGS` selecting the backend and linker, appended to any flags the user already set.
+fn rustflags(backend: &Path, linker: &Path) -> String {
+    let user_flags = std::env::var("RUSTFLAGS").unwrap_or_default();
+    format!(
+        "{user_flags} -Z codegen-backend={backend} -C linker={linker}",
+        backend = backend.display(),
+        linker = linker.display()
+    )
+    .trim()
+    .to_owned()
+}
+/// Runs the assembly at `path` with `args`, returning its exit code.
+fn exec(path: &str, args: &[String]) -> ExitCode {
+    let runtime = if std::env::var("CARGO_DOTNET_RUNTIME").is_ok_and(|runtime| runtime == "mono") {
+        rustc_codegen_clr::config::Runtime::Mono
+    } else {
+        rustc_codegen_clr::config::config().runtime
+    };
+    let profile = runtime.profile();
+    let Some(host) = profile.host() else {
+        eprintln!(
+            "Assemblies targeting the {runtime} runtime can't be run directly.",
+            runtime = profile.name()
+        );
+        return ExitCode::FAILURE;
+    };
+    if profile.needs_runtime_config() {
+        let tfm = rustc_codegen_clr::config::config().tfm;
+        if !tfm.is_runnable() {
+            eprintln!(
+                "Assemblies targeting {tfm} can't be run. Select `net7.0` or `net8.0` using the `tfm` option.",
+                tfm = tfm.name()
+            );
+            return ExitCode::FAILURE;
+        }
+        let config_path = format!("{path}.runtimeconfig.json");
+        if !Path::new(&config_path).exists() {
+            std::fs::write(&config_path, rustc_codegen_clr::runtime::runtime_config())
+                .expect("Could not write the runtime config!");
+        }
+    }
+    let status = Command::new(host)
+        .arg(path)
+        .args(args)
+        .status()
+        .expect("Could not start the .NET runtime!");
+    exit_code(status)
+}
+fn exit_code(status: std::process::ExitStatus) -> ExitCode {
+    match status.code() {
+        Some(code) => ExitCode::from(code as u8),
+        None => ExitCode::FAILURE,
+    }
+}
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // When invoked as `cargo dotnet`, cargo passes the name of the subcommand as the first argument.
+    if args.first().is_some_and(|arg| arg == "dotnet") {
+        args.remove(0);
+    }
+    let Some(subcommand) = args.first().cloned() else {
+        eprintln!("Usage: cargo dotnet <build|run|test> [cargo arguments...]");
+        return ExitCode::FAILURE;
+    };
+    let cargo_args = &args[1..];
+    match subcommand.as_str() {
+        EXEC_SUBCOMMAND => {
+            let Some(path) = cargo_args.first() else {
+                eprintln!("ERROR: No assembly to run!");
+                return ExitCode::FAILURE;
+            };
+            exec(path, &cargo_args[1..])
+        }
+        "build" | "run" | "test" => {
+            let backend = locate("RUSTC_CODEGEN_CLR_BACKEND", backend_file_name());
+            let linker = locate("RUSTC_CODEGEN_CLR_LINKER", linker_file_name());
+            let this = std::env::current_exe().expect("Could not get the path of cargo-dotnet!");
+            // Cargo runs the produced executables(including tests) using this runner.
+            let triple = host_triple();
+            let runner_var = format!(
+                "CARGO_TARGET_{triple}_RUNNER",
+                triple = triple.to_uppercase().replace(['-', '.'], "_")
+            );
+            let runner = format!("{this} {EXEC_SUBCOMMAND}", this = this.display());
+            let mut cargo = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+            cargo.arg(&subcommand).args(cargo_args);
+            // With an explicit `--target`, cargo does not pass `RUSTFLAGS` to crates built for the host(proc-macros and build scripts), so they
+            // are built with the default backend.
+            if !cargo_args
+                .iter()
+                .any(|arg| arg == "--target" || arg.starts_with("--target="))
+            {
+                cargo.args(["--target", &triple]);
+            }
+            cargo
+                .env("RUSTFLAGS", rustflags(&backend, &linker))
+                .env(runner_var, runner);
+            // Threads are not supported yet, so libtest runs the tests one by one, on the main thread.
+            if subcommand == "test" && std::env::var_os("RUST_TEST_THREADS").is_none() {
+                cargo.env("RUST_TEST_THREADS", "1");
+            }
+            let status = cargo.status().expect("Could not run cargo!");
+            exit_code(status)
+        }
+        _ => {
+            eprintln!("Unknown subcommand {subcommand:?}. Usage: cargo dotnet <build|run|test> [cargo arguments...]");
+            ExitCode::FAILURE
+        }
+    }
+}
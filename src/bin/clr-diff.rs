@@ -0,0 +1,102 @@
+//! Compares two serialized assemblies(`.bc` files or rlibs produced by the backend), and prints which methods were added, removed or changed.
+//! Methods are matched by their name and signature. Changed methods are printed with a diff of their ops.
+//!
+//! Usage: `clr-diff <old> <new>`.
+use rustc_codegen_clr::{assembly::Assembly, cil::CILOp, function_sig::FnSig, method::Method};
+use std::collections::BTreeMap;
+/// Above this number of op pairs, changed ops are not diffed precisely, to limit the memory usage.
+const MAX_DIFF_CELLS: usize = 1 << 22;
+/// Maps the name and signature of each method in `asm` to the method.
+fn methods_by_sig(asm: &Assembly) -> BTreeMap<(String, String), (&FnSig, &Method)> {
+    asm.methods()
+        .map(|method| {
+            (
+                (method.name().to_owned(), format!("{:?}", method.sig())),
+                (method.sig(), method),
+            )
+        })
+        .collect()
+}
+/// Prints a diff of ops `old` and `new`. Lines only in `old` are prefixed with `-`, and lines only in `new` with `+`.
+fn print_op_diff(old: &[CILOp], new: &[CILOp]) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+    println!("\t@@ op {prefix} @@");
+    if old.len() * new.len() > MAX_DIFF_CELLS {
+        old.iter().for_each(|op| println!("\t-{op:?}"));
+        new.iter().for_each(|op| println!("\t+{op:?}"));
+        return;
+    }
+    // Longest common subsequence of the ops, computed from the back, so that the diff can be printed from the front.
+    let mut lcs = vec![vec![0_u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            println!("\t {op:?}", op = old[i]);
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            println!("\t+{op:?}", op = new[j]);
+            j += 1;
+        } else {
+            println!("\t-{op:?}", op = old[i]);
+            i += 1;
+        }
+    }
+}
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [old_path, new_path] = args.as_slice() else {
+        eprintln!("Usage: clr-diff <old> <new>");
+        std::process::exit(1);
+    };
+    let old = Assembly::load(old_path.as_ref()).expect("ERROR: Could not load the old assembly!");
+    let new = Assembly::load(new_path.as_ref()).expect("ERROR: Could not load the new assembly!");
+    let old_methods = methods_by_sig(&old);
+    let new_methods = methods_by_sig(&new);
+    let (mut added, mut removed, mut changed, mut unchanged) = (0, 0, 0, 0);
+    for (key, (sig, _)) in &old_methods {
+        if !new_methods.contains_key(key) {
+            println!("removed: {name}{sig:?}", name = key.0);
+            removed += 1;
+        }
+    }
+    for (key, (sig, new_method)) in &new_methods {
+        let name = &key.0;
+        let Some((_, old_method)) = old_methods.get(key) else {
+            println!("added: {name}{sig:?}");
+            added += 1;
+            continue;
+        };
+        if old_method == new_method {
+            unchanged += 1;
+            continue;
+        }
+        changed += 1;
+        println!("changed: {name}{sig:?}");
+        if old_method.locals() != new_method.locals() {
+            println!("\tlocals: {:?}", old_method.locals());
+            println!("\t     -> {:?}", new_method.locals());
+        }
+        if old_method.get_ops() != new_method.get_ops() {
+            print_op_diff(old_method.get_ops(), new_method.get_ops());
+        }
+    }
+    println!("{added} added, {removed} removed, {changed} changed, {unchanged} unchanged.");
+}
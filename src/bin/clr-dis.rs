@@ -0,0 +1,44 @@
+//! Disassembles a serialized assembly(a `.bc` file produced by the backend) into ILASM text, without linking it.
+//! Unlike the linker, it does not patch missing methods or add any statics, so the output shows exactly what the backend produced.
+//!
+//! Usage: `clr-dis <file> [-o <output>]`. The IL is written to stdout, unless an output file is given.
+use rustc_codegen_clr::{
+    assembly::Assembly,
+    assembly_exporter::{ilasm_exporter::ILASMExporter, AssemblyExporter},
+};
+use std::io::Write;
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        eprintln!("Usage: clr-dis <file> [-o <output>]");
+        std::process::exit(1);
+    };
+    let output = args.iter().position(|arg| arg == "-o").map(|idx| {
+        args.get(idx + 1)
+            .expect("ERROR: \"-o\" provided, but no output file set!")
+    });
+    let asm = Assembly::load(path.as_ref()).expect("ERROR: Could not load the assembly file!");
+    let asm_name = std::path::Path::new(path)
+        .file_stem()
+        .map_or("asm".into(), |stem| stem.to_string_lossy());
+    let mut exporter = ILASMExporter::init(&asm_name);
+    for (asm_name, asm_ref) in asm.extern_refs() {
+        exporter.add_extern_ref(asm_name, asm_ref);
+    }
+    for tpe in asm.types() {
+        exporter.add_type(tpe);
+    }
+    for method in asm.methods() {
+        exporter.add_method(method);
+    }
+    for (name, tpe) in asm.globals() {
+        exporter.add_global(tpe, name);
+    }
+    let il = exporter.into_il();
+    match output {
+        Some(output) => std::fs::write(output, il).expect("ERROR: Could not write the IL!"),
+        None => std::io::stdout()
+            .write_all(&il)
+            .expect("ERROR: Could not write the IL!"),
+    }
+}
@@ -0,0 +1,140 @@
+//! Inspects serialized assemblies(`.bc` files and rlibs produced by the backend).
+//!
+//! Usage: `clr-inspect <file> [command]`, where command is one of:
+//! * `stats` - prints size statistics of the assembly. The default.
+//! * `types` - lists all types defined within the assembly.
+//! * `methods` - lists all methods, with their signatures and op counts.
+//! * `method <filter>` - prints the locals and ops of all methods with names containing `filter`.
+//! * `calls <filter>` - lists all call sites calling methods with names containing `filter`.
+//! * `patterns [max_len]` - lists the most common ops and op sequences of up to `max_len`(default 3) ops.
+use rustc_codegen_clr::{assembly::Assembly, op_stats, summary::AssemblySummary};
+/// Number of entries shown in the "largest" and "most common" lists.
+const TOP_COUNT: usize = 10;
+fn print_stats(asm: &Assembly, file_size: u64) {
+    let summary = AssemblySummary::new(asm);
+    println!("file size: {file_size} bytes");
+    println!("types:     {}", summary.types.len());
+    println!("methods:   {}", summary.methods.len());
+    println!("statics:   {}", summary.statics.len());
+    println!("ops:       {}", asm.op_count());
+    println!("calls:     {}", summary.call_edges.len());
+    let mut methods: Vec<_> = summary.methods.iter().collect();
+    methods.sort_by(|a, b| b.op_count.cmp(&a.op_count));
+    println!("largest methods:");
+    for method in methods.iter().take(TOP_COUNT) {
+        println!(
+            "{ops:>10} {name}",
+            ops = method.op_count,
+            name = method.name
+        );
+    }
+    let mut histogram = std::collections::BTreeMap::new();
+    for method in &summary.methods {
+        for (op, count) in &method.op_histogram {
+            *histogram.entry(op.as_str()).or_insert(0) += count;
+        }
+    }
+    let mut histogram: Vec<_> = histogram.into_iter().collect();
+    histogram.sort_by(|a, b| b.1.cmp(&a.1));
+    println!("most common ops:");
+    for (op, count) in histogram.iter().take(TOP_COUNT) {
+        println!("{count:>10} {op}");
+    }
+}
+fn print_types(asm: &Assembly) {
+    let mut types: Vec<_> = asm.types().collect();
+    types.sort_by(|a, b| a.name().cmp(b.name()));
+    for tpe in types {
+        println!("{name}", name = tpe.name());
+        for (field_name, field_tpe) in tpe.fields() {
+            println!("\t{field_name}: {field_tpe:?}");
+        }
+    }
+}
+fn print_methods(asm: &Assembly) {
+    let mut methods: Vec<_> = asm.methods().collect();
+    methods.sort_by(|a, b| a.name().cmp(b.name()));
+    for method in methods {
+        println!(
+            "{name}{sig:?} ops:{ops}",
+            name = method.name(),
+            sig = method.sig(),
+            ops = method.get_ops().len()
+        );
+    }
+}
+fn print_method(asm: &Assembly, filter: &str) {
+    let mut found = false;
+    for method in asm
+        .methods()
+        .filter(|method| method.name().contains(filter))
+    {
+        found = true;
+        println!("{name}{sig:?}", name = method.name(), sig = method.sig());
+        println!("locals:");
+        for (idx, (name, tpe)) in method.locals().iter().enumerate() {
+            match name {
+                Some(name) => println!("\t{idx}({name}): {tpe:?}"),
+                None => println!("\t{idx}: {tpe:?}"),
+            }
+        }
+        println!("ops:");
+        for op in method.get_ops() {
+            println!("\t{op:?}");
+        }
+    }
+    if !found {
+        eprintln!("No method with name containing {filter:?} found.");
+        std::process::exit(1);
+    }
+}
+fn print_calls(asm: &Assembly, filter: &str) {
+    for method in asm.methods() {
+        for op in method.get_ops() {
+            let Some(call) = op.call() else {
+                continue;
+            };
+            if call.name().contains(filter) {
+                println!("{caller} -> {op:?}", caller = method.name());
+            }
+        }
+    }
+}
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        eprintln!("Usage: clr-inspect <file> [stats|types|methods|method <filter>|calls <filter>|patterns [max_len]]");
+        std::process::exit(1);
+    };
+    let asm = Assembly::load(path.as_ref()).expect("ERROR: Could not load the assembly file!");
+    let filter = || {
+        args.get(2)
+            .map(String::as_str)
+            .expect("ERROR: this command requires a filter!")
+    };
+    match args.get(1).map(String::as_str) {
+        None | Some("stats") => {
+            let file_size = std::fs::metadata(path)
+                .map(|meta| meta.len())
+                .unwrap_or_default();
+            print_stats(&asm, file_size);
+        }
+        Some("types") => print_types(&asm),
+        Some("methods") => print_methods(&asm),
+        Some("method") => print_method(&asm, filter()),
+        Some("calls") => print_calls(&asm, filter()),
+        Some("patterns") => {
+            let max_len = args
+                .get(2)
+                .map_or(op_stats::DEFAULT_MAX_PATTERN_LEN, |len| {
+                    len.parse().expect("ERROR: max_len must be a number!")
+                });
+            let stats = op_stats::OpStats::new(&asm, max_len, op_stats::DEFAULT_TOP);
+            print!("{}", stats.report());
+        }
+        Some(other) => {
+            eprintln!("Unknown command {other:?}.");
+            std::process::exit(1);
+        }
+    }
+}
@@ -3,81 +3,23 @@
 use rustc_codegen_clr::{assembly::Assembly, r#type::Type, *};
 use std::env;
 
-fn load_ar(r: &mut impl std::io::Read) -> std::io::Result<assembly::Assembly> {
-    use ar::Archive;
-    use std::io::Read;
-    let mut final_assembly = assembly::Assembly::empty();
-    let mut archive = Archive::new(r);
-    // Iterate over all entries in the archive:
-    while let Some(entry_result) = archive.next_entry() {
-        let mut entry = entry_result.unwrap();
-        let name = String::from_utf8_lossy(entry.header().identifier());
-        if name.contains(".bc") {
-            let mut asm_bytes = Vec::with_capacity(0x100);
-            entry
-                .read_to_end(&mut asm_bytes)
-                .expect("ERROR: Could not load the assembly file!");
-            let assembly = postcard::from_bytes(&asm_bytes)
-                .expect("ERROR:Could not decode the assembly file!");
-            final_assembly = final_assembly.join(assembly);
+/// Returns the runtime selected by the `--aot_mode` flag, if it is present.
+fn aot_compile_mode(args: &[String]) -> Option<config::Runtime> {
+    let aot_idx = args.iter().position(|arg| arg == "--aot_mode")? + 1;
+    let aot = args
+        .get(aot_idx)
+        .expect("ERROR: \"--aot_mode\" provided, but no AOT mode set!");
+    Some(match aot.as_str() {
+        "no" | "none" | "no_aot" | "no-aot" => config::Runtime::CoreCLR,
+        "mono" | "mono_aot" | "mono-aot" => config::Runtime::MonoAOT,
+        "mono_full" | "mono-full" | "mono_full_aot" | "mono-full-aot" => {
+            config::Runtime::MonoFullAOT
         }
-    }
-    Ok(final_assembly)
-}
-enum AOTCompileMode {
-    NoAOT,
-    MonoAOT,
-    FullMonoAOT,
-}
-impl AOTCompileMode {
-    pub fn compile(self, path: &str) {
-        match self {
-            Self::NoAOT => (),
-            Self::MonoAOT => {
-                let out = std::process::Command::new("mono")
-                    .arg("--aot")
-                    .arg("-O=all")
-                    .arg(path)
-                    .output()
-                    .expect("failed run mono AOT process");
-                if !out.stderr.is_empty() {
-                    panic!("Could not run AOT!");
-                }
-            }
-            Self::FullMonoAOT => {
-                let out = std::process::Command::new("mono")
-                    .arg("--aot=full")
-                    .arg("-O=all")
-                    .arg(path)
-                    .output()
-                    .expect("failed run mono AOT process");
-                if !out.stderr.is_empty() {
-                    panic!("Could not run AOT!");
-                }
-            }
-        }
-    }
-}
-fn aot_compile_mode(args: &[String]) -> AOTCompileMode {
-    if let Some(aot_idx) = args.iter().position(|arg| arg == "--aot_mode") {
-        let aot_idx = aot_idx + 1;
-        let aot = args
-            .get(aot_idx)
-            .expect("ERROR: \"--aot_mode\" provided, but no AOT mode set!");
-        match aot.as_str() {
-            "no" | "none" | "no_aot" | "no-aot" => AOTCompileMode::NoAOT,
-            "mono" | "mono_aot" | "mono-aot" => AOTCompileMode::MonoAOT,
-            "mono_full" | "mono-full" | "mono_full_aot" | "mono-full-aot" => {
-                AOTCompileMode::FullMonoAOT
-            }
-            _ => panic!("Unknown AOT mode:{aot:?}"),
-        }
-    } else {
-        AOTCompileMode::NoAOT
-    }
+        _ => panic!("Unknown AOT mode:{aot:?}"),
+    })
 }
 fn patch_missing_method(call_site: &cil::CallSite) -> method::Method {
-    let sig = call_site.signature().clone();
+    let sig = call_site.shared_signature().clone();
     let mut method = method::Method::new(
         access_modifier::AccessModifer::Private,
         true,
@@ -92,6 +34,18 @@ fn patch_missing_method(call_site: &cil::CallSite) -> method::Method {
     method.set_ops(ops.into());
     method
 }
+/// Adds the default implementations of weak lang items(see [`ffi::weak_default`]) called, but not defined, by any crate.
+fn add_weak_defaults(asm: &mut Assembly) {
+    let defaults: std::collections::HashMap<_, _> = asm
+        .call_sites()
+        .filter(|call| call.is_static() && call.class().is_none())
+        .filter(|call| !asm.contains_fn_named(call.name()))
+        .filter_map(|call| Some((call.clone(), ffi::weak_default(call)?)))
+        .collect();
+    defaults
+        .into_values()
+        .for_each(|method| asm.add_method(method));
+}
 fn autopatch(asm: &mut Assembly) {
     let call_sites = asm
         .call_sites()
@@ -107,13 +61,36 @@ fn autopatch(asm: &mut Assembly) {
         .values()
         .for_each(|method| asm.add_method(method.clone()));
 }
+/// Loads and joins the assemblies at `paths`. Each crate is split into one file per codegen unit, so there are usually many of them.
+/// They are loaded by multiple threads, each joining a contiguous chunk of the files, so the result does not depend on the number of threads.
+fn load_and_join(paths: &[&String]) -> Assembly {
+    let threads = std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+    let chunk_size = paths.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        let chunks: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk.iter().fold(Assembly::empty(), |asm, path| {
+                        let loaded = Assembly::load(path.as_ref())
+                            .expect("ERROR: Could not load the assembly file!");
+                        asm.join(loaded)
+                    })
+                })
+            })
+            .collect();
+        chunks
+            .into_iter()
+            .map(|chunk| chunk.join().expect("ERROR: Could not load the assemblies!"))
+            .fold(Assembly::empty(), Assembly::join)
+    })
+}
 fn add_mandatory_statics(asm: &mut Assembly) {
     asm.add_static(Type::U8, "__rust_alloc_error_handler_should_panic");
     asm.add_static(Type::U8, "__rust_no_alloc_shim_is_unstable");
     asm.add_static(Type::Ptr(Type::Ptr(Type::U8.into()).into()), "environ");
 }
 fn main() {
-    use std::io::Read;
     let args: Vec<String> = env::args().collect();
     let args = &args[1..];
     let to_link: Vec<_> = args.iter().filter(|arg| arg.contains(".bc")).collect();
@@ -122,43 +99,117 @@ fn main() {
         .iter()
         .position(|arg| arg == "-o")
         .expect("No output file!")];
-    let mut final_assembly = assembly::Assembly::empty();
-    for asm_path in &to_link {
-        let mut asm_file =
-            std::fs::File::open(asm_path).expect("ERROR:Could not open the assembly file!");
-        let mut asm_bytes = Vec::with_capacity(0x100);
-        asm_file
-            .read_to_end(&mut asm_bytes)
-            .expect("ERROR: Could not load the assembly file!");
-        let assembly =
-            postcard::from_bytes(&asm_bytes).expect("ERROR:Could not decode the assembly file!");
-        final_assembly = final_assembly.join(assembly);
-    }
-    for asm_path in &ar_to_link {
-        let mut asm_file =
-            std::fs::File::open(asm_path).expect("ERROR: Could not open the assembly file!");
-        let assembly = load_ar(&mut asm_file).expect("Could not open archive");
-        final_assembly = final_assembly.join(assembly);
-    }
+    let mut timings = timings::Timings::with_progress();
+    let paths: Vec<&String> = to_link.iter().chain(&ar_to_link).copied().collect();
+    let mut final_assembly = timings.time("loading", 0, || load_and_join(&paths));
+    final_assembly.call_init_array();
+    let ops = final_assembly.op_count();
     //final_assembly.add_array_types();
     //
-    if !rustc_codegen_clr::ABORT_ON_ERROR {
-        autopatch(&mut final_assembly);
+    add_weak_defaults(&mut final_assembly);
+    if rustc_codegen_clr::config::config().patch_missing_methods {
+        timings.time("autopatch", ops, || autopatch(&mut final_assembly));
+    }
+    // Every crate carries its own copy of the runtime shims, so only the ones the program uses are kept.
+    let removed = timings.time("shim elimination", ops, || {
+        final_assembly.remove_unreferenced_private_methods()
+    });
+    if rustc_codegen_clr::config::config().timings {
+        eprintln!("Removed {removed} unreferenced shims.");
+    }
+    if rustc_codegen_clr::config::config().fold_identical_methods {
+        let folded = timings.time("icf", ops, || {
+            icf::fold_identical_methods(&mut final_assembly)
+        });
+        if rustc_codegen_clr::config::config().timings {
+            eprintln!("Folded {folded} identical methods.");
+        }
+    }
+    if rustc_codegen_clr::config::config().tail_calls {
+        let marked = timings.time("tail calls", ops, || {
+            tail_call::mark_all_tail_calls(&mut final_assembly)
+        });
+        if rustc_codegen_clr::config::config().timings {
+            eprintln!("Marked {marked} tail calls.");
+        }
     }
 
-    use rustc_codegen_clr::assembly_exporter::AssemblyExporter;
+    if let Some(summary_idx) = args.iter().position(|arg| arg == "--json-summary") {
+        let summary_path = args
+            .get(summary_idx + 1)
+            .expect("ERROR: \"--json-summary\" provided, but no summary path set!");
+        std::fs::write(summary_path, final_assembly.to_json_summary())
+            .expect("ERROR: Could not write the assembly summary!");
+    }
+    if let Some(stats_idx) = args.iter().position(|arg| arg == "--op-stats") {
+        let stats_path = args
+            .get(stats_idx + 1)
+            .expect("ERROR: \"--op-stats\" provided, but no statistics path set!");
+        let stats = op_stats::OpStats::new(
+            &final_assembly,
+            op_stats::DEFAULT_MAX_PATTERN_LEN,
+            op_stats::DEFAULT_TOP,
+        );
+        std::fs::write(stats_path, stats.to_json())
+            .expect("ERROR: Could not write the op statistics!");
+    }
+    use rustc_codegen_clr::assembly_exporter::{ilasm_exporter::ILASMExporter, AssemblyExporter};
     let path = output;
     let is_lib = output.contains(".dll") || output.contains(".so") || output.contains(".o");
     add_mandatory_statics(&mut final_assembly);
+    if config::config().verify_il {
+        let errors = timings.time("verify", ops, || verify::verify_assembly(&final_assembly));
+        for error in &errors {
+            eprintln!("{error}");
+        }
+        assert!(
+            errors.is_empty() || config::config().allow_miscompilations,
+            "ERROR: {count} methods contain invalid IL!",
+            count = errors.len()
+        );
+    }
+    timings.time("max stack", ops, || {
+        for method in final_assembly.methods_mut() {
+            method.update_max_stack();
+        }
+    });
+    let exporter = timings.time("export", ops, || {
+        let mut exporter = ILASMExporter::init("asm");
+        for (asm_name, asm_ref) in final_assembly.extern_refs() {
+            exporter.add_extern_ref(asm_name, asm_ref);
+        }
+        for tpe in final_assembly.types() {
+            exporter.add_type(tpe);
+        }
+        for method in final_assembly.methods() {
+            exporter.add_method(method);
+        }
+        for (name, tpe) in final_assembly.globals() {
+            exporter.add_global(tpe, name);
+        }
+        exporter
+    });
     // Run ILASM
-    rustc_codegen_clr::assembly_exporter::ilasm_exporter::ILASMExporter::export_assembly(
-        &final_assembly,
-        path.as_ref(),
-        is_lib,
-    )
-    .expect("Assembly export faliure!");
+    timings
+        .time("ilasm", ops, || exporter.finalize(path.as_ref(), is_lib))
+        .expect("Assembly export faliure!");
     // Run AOT compiler
-    let aot_compile_mode = aot_compile_mode(args);
-    aot_compile_mode.compile(path.as_ref());
+    let runtime = aot_compile_mode(args).unwrap_or(config::config().runtime);
+    timings
+        .time("aot", ops, || runtime.profile().aot_compile(path.as_ref()))
+        .expect("Could not run AOT!");
+    if args.iter().any(|arg| arg == "--scaffold") {
+        let assembly_path = assembly_exporter::ilasm_exporter::output_path(path.as_ref())
+            .expect("ERROR: Could not get the path of the assembly!");
+        scaffold::scaffold(&assembly_path, is_lib)
+            .expect("ERROR: Could not scaffold a .NET project around the assembly!");
+    }
+    if let Some(timings_idx) = args.iter().position(|arg| arg == "--timings") {
+        let timings_path = args
+            .get(timings_idx + 1)
+            .expect("ERROR: \"--timings\" provided, but no timings path set!");
+        std::fs::write(timings_path, timings.to_json())
+            .expect("ERROR: Could not write the linker timings!");
+    }
     //todo!()
 }
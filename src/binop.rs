@@ -18,6 +18,9 @@ pub(crate) fn binop_unchecked<'tyctx>(
     let ops_b = crate::operand::handle_operand(operand_b, tyctx, method, method_instance, tycache);
     let ty_a = operand_a.ty(&method.local_decls, tyctx);
     let ty_b = operand_b.ty(&method.local_decls, tyctx);
+    if let Some(op) = int128_binop(binop, ty_a, ty_b, tyctx, &method_instance, tycache) {
+        return [ops_a, ops_b, op].into_iter().flatten().collect();
+    }
     match binop {
         BinOp::Add | BinOp::AddUnchecked => [
             ops_a,
@@ -83,24 +86,14 @@ pub(crate) fn binop_unchecked<'tyctx>(
             .into_iter()
             .flatten()
             .collect(),
-        BinOp::Ge => [
-            ops_a,
-            ops_b,
-            lt_unchecked(ty_a, ty_b),
-            vec![CILOp::LdcI32(0), CILOp::Eq],
-        ]
-        .into_iter()
-        .flatten()
-        .collect(),
-        BinOp::Le => [
-            ops_a,
-            ops_b,
-            gt_unchecked(ty_a, ty_b),
-            vec![CILOp::LdcI32(0), CILOp::Eq],
-        ]
-        .into_iter()
-        .flatten()
-        .collect(),
+        BinOp::Ge => [ops_a, ops_b, ge_unchecked(ty_a, ty_b)]
+            .into_iter()
+            .flatten()
+            .collect(),
+        BinOp::Le => [ops_a, ops_b, le_unchecked(ty_a, ty_b)]
+            .into_iter()
+            .flatten()
+            .collect(),
         BinOp::Offset => {
             let pointed_ty = if let TyKind::RawPtr(inner_and_mut) = ty_a.kind() {
                 inner_and_mut.ty
@@ -121,6 +114,84 @@ pub(crate) fn binop_unchecked<'tyctx>(
         } //_ => todo!("Unsupported bionp {binop:?}"),
     }
 }
+/// Returns the `System.Int128` or `System.UInt128` type matching `ty`, if it is a 128 bit integer.
+pub(crate) fn int128_class(ty: Ty) -> Option<DotnetTypeRef> {
+    match ty.kind() {
+        TyKind::Int(IntTy::I128) => Some(DotnetTypeRef::int_128()),
+        TyKind::Uint(UintTy::U128) => Some(DotnetTypeRef::uint_128()),
+        _ => None,
+    }
+}
+/// Preforms an unchecked binary operation on 128 bit integers, by calling the operator method of `System.Int128` or `System.UInt128`.
+/// Returns `None` if `ty_a` is not a 128 bit integer. Addition and subtraction are handled by [`add_unchecked`] and [`sub_unchecked`].
+fn int128_binop<'tyctx>(
+    binop: BinOp,
+    ty_a: Ty<'tyctx>,
+    ty_b: Ty<'tyctx>,
+    tyctx: TyCtxt<'tyctx>,
+    method_instance: &Instance<'tyctx>,
+    tycache: &mut TyCache,
+) -> Option<Vec<CILOp>> {
+    let class = int128_class(ty_a)?;
+    let (name, returns_bool) = match binop {
+        BinOp::Eq => ("op_Equality", true),
+        BinOp::Ne => ("op_Inequality", true),
+        BinOp::Lt => ("op_LessThan", true),
+        BinOp::Le => ("op_LessThanOrEqual", true),
+        BinOp::Gt => ("op_GreaterThan", true),
+        BinOp::Ge => ("op_GreaterThanOrEqual", true),
+        BinOp::BitAnd => ("op_BitwiseAnd", false),
+        BinOp::BitOr => ("op_BitwiseOr", false),
+        BinOp::BitXor => ("op_ExclusiveOr", false),
+        BinOp::Mul | BinOp::MulUnchecked => ("op_Multiply", false),
+        BinOp::Div => ("op_Division", false),
+        BinOp::Rem => ("op_Modulus", false),
+        BinOp::Shl | BinOp::ShlUnchecked => ("op_LeftShift", false),
+        BinOp::Shr | BinOp::ShrUnchecked => ("op_RightShift", false),
+        _ => return None,
+    };
+    let tpe = tycache.type_from_cache(ty_a, tyctx, Some(*method_instance));
+    let mut ops = Vec::new();
+    let rhs = if matches!(
+        binop,
+        BinOp::Shl | BinOp::ShlUnchecked | BinOp::Shr | BinOp::ShrUnchecked
+    ) {
+        // The shift amount is always an `int`.
+        match int128_class(ty_b) {
+            Some(shift_class) => {
+                let shift_tpe = tycache.type_from_cache(ty_b, tyctx, Some(*method_instance));
+                ops.push(CILOp::Call(
+                    CallSite::new(
+                        Some(shift_class),
+                        "op_Explicit".into(),
+                        FnSig::new(&[shift_tpe], &crate::r#type::Type::I32),
+                        true,
+                    )
+                    .into(),
+                ));
+            }
+            None => ops.push(CILOp::ConvI32(false)),
+        }
+        crate::r#type::Type::I32
+    } else {
+        tpe.clone()
+    };
+    let output = if returns_bool {
+        crate::r#type::Type::Bool
+    } else {
+        tpe.clone()
+    };
+    ops.push(CILOp::Call(
+        CallSite::new(
+            Some(class),
+            name.into(),
+            FnSig::new(&[tpe, rhs], &output),
+            true,
+        )
+        .into(),
+    ));
+    Some(ops)
+}
 /// Preforms unchecked addition
 fn add_unchecked<'tyctx>(
     ty_a: Ty<'tyctx>,
@@ -227,11 +298,49 @@ fn ne_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
 fn eq_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
     vec![CILOp::Eq]
 }
-fn lt_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Lt]
+/// Checks if values of type `ty` are compared as unsigned integers.
+fn is_unsigned(ty: Ty) -> bool {
+    matches!(
+        ty.kind(),
+        TyKind::Uint(_)
+            | TyKind::Char
+            | TyKind::Bool
+            | TyKind::RawPtr(_)
+            | TyKind::Ref(..)
+            | TyKind::FnPtr(_)
+    )
+}
+fn lt_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    if is_unsigned(ty_a) {
+        vec![CILOp::LtUn]
+    } else {
+        vec![CILOp::Lt]
+    }
+}
+fn gt_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    if is_unsigned(ty_a) {
+        vec![CILOp::GtUn]
+    } else {
+        vec![CILOp::Gt]
+    }
+}
+/// `a >= b` is `!(a < b)`. Floats are compared as unordered, so that the result is false if either value is NaN.
+fn ge_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    let lt = if is_unsigned(ty_a) || ty_a.is_floating_point() {
+        CILOp::LtUn
+    } else {
+        CILOp::Lt
+    };
+    vec![lt, CILOp::LdcI32(0), CILOp::Eq]
 }
-fn gt_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
-    vec![CILOp::Gt]
+/// `a <= b` is `!(a > b)`. Floats are compared as unordered, so that the result is false if either value is NaN.
+fn le_unchecked<'tyctx>(ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
+    let gt = if is_unsigned(ty_a) || ty_a.is_floating_point() {
+        CILOp::GtUn
+    } else {
+        CILOp::Gt
+    };
+    vec![gt, CILOp::LdcI32(0), CILOp::Eq]
 }
 fn bit_and_unchecked<'tyctx>(_ty_a: Ty<'tyctx>, _ty_b: Ty<'tyctx>) -> Vec<CILOp> {
     vec![CILOp::And]
@@ -0,0 +1,186 @@
+//! Bit manipulation intrinsics, used by integer and float formatting(eg. `flt2dec` normalizes numbers using `leading_zeros`).
+//!
+//! | Rust                            | .NET                                                           |
+//! |---------------------------------|----------------------------------------------------------------|
+//! | `ctlz`, `ctlz_nonzero`          | `System.Numerics.BitOperations.LeadingZeroCount`               |
+//! | `cttz`, `cttz_nonzero`          | `System.Numerics.BitOperations.TrailingZeroCount`              |
+//! | `ctpop`                         | `System.Numerics.BitOperations.PopCount`                       |
+//! | `rotate_left`,`rotate_right`    | `System.Numerics.BitOperations.RotateLeft`/`RotateRight`       |
+//! | `bswap`                         | `System.Buffers.Binary.BinaryPrimitives.ReverseEndianness`     |
+//!
+//! `BitOperations` has no overloads for 8 and 16 bit integers, so those are zero-extended to 32 bits, and the result is adjusted.
+//! 128 bit integers use the methods of `System.Int128` and `System.UInt128` with the same names.
+use crate::{
+    cil::{CILOp, CallSite},
+    function_sig::FnSig,
+    r#type::{DotnetTypeRef, Type},
+};
+/// Returns the size of the integer type `tpe` in bits(`None` for pointer-sized integers), and the unsigned type of the same size.
+/// Returns `None` if `tpe` is not an integer supported by this module.
+fn int_info(tpe: &Type) -> Option<(Option<i32>, Type)> {
+    match tpe {
+        Type::U8 | Type::I8 => Some((Some(8), Type::U32)),
+        Type::U16 | Type::I16 => Some((Some(16), Type::U32)),
+        Type::U32 | Type::I32 => Some((Some(32), Type::U32)),
+        Type::U64 | Type::I64 => Some((Some(64), Type::U64)),
+        Type::USize | Type::ISize => Some((None, Type::USize)),
+        _ => None,
+    }
+}
+/// Calls the static method `name` of `System.Numerics.BitOperations`, with signature `inputs -> output`.
+fn bit_operations(name: &str, inputs: &[Type], output: &Type) -> CILOp {
+    CILOp::Call(CallSite::boxed(
+        Some(
+            DotnetTypeRef::new(Some("System.Runtime"), "System.Numerics.BitOperations")
+                .with_valuetype(false),
+        ),
+        name.into(),
+        FnSig::new(inputs, output),
+        true,
+    ))
+}
+/// Returns the op converting the value on top of the stack to `tpe`.
+fn conv_to(tpe: &Type) -> CILOp {
+    match tpe {
+        Type::U8 => CILOp::ConvU8(false),
+        Type::I8 => CILOp::ConvI8(false),
+        Type::U16 => CILOp::ConvU16(false),
+        Type::I16 => CILOp::ConvI16(false),
+        Type::U32 => CILOp::ConvU32(false),
+        Type::I32 => CILOp::ConvI32(false),
+        Type::U64 => CILOp::ConvU64(false),
+        Type::I64 => CILOp::ConvI64(false),
+        Type::USize => CILOp::ConvUSize(false),
+        Type::ISize => CILOp::ConvISize(false),
+        _ => panic!("{tpe:?} is not an integer!"),
+    }
+}
+/// Returns the ops loading the value `ops` load, zero-extended to 32 bits if it is smaller.
+fn zero_extended(mut ops: Vec<CILOp>, tpe: &Type) -> Vec<CILOp> {
+    match tpe {
+        Type::U8 | Type::I8 => ops.push(CILOp::ConvU8(false)),
+        Type::U16 | Type::I16 => ops.push(CILOp::ConvU16(false)),
+        _ => (),
+    }
+    ops
+}
+/// Returns the `System.Buffers.Binary.BinaryPrimitives` class.
+fn binary_primitives() -> DotnetTypeRef {
+    DotnetTypeRef::new(
+        Some("System.Memory"),
+        "System.Buffers.Binary.BinaryPrimitives",
+    )
+    .with_valuetype(false)
+}
+/// Like [`bit_intrinsic`], but for 128 bit integers. `System.Int128` and `System.UInt128` implement all the intrinsics as static methods, returning
+/// a value of the same type.
+fn int128_bit_intrinsic(name: &str, args: Vec<Vec<CILOp>>, tpe: &Type) -> Option<Vec<CILOp>> {
+    let class = match tpe {
+        Type::I128 => DotnetTypeRef::int_128(),
+        _ => DotnetTypeRef::uint_128(),
+    };
+    let (class, method, inputs) = match name {
+        "ctlz" | "ctlz_nonzero" => (class, "LeadingZeroCount", vec![tpe.clone()]),
+        "cttz" | "cttz_nonzero" => (class, "TrailingZeroCount", vec![tpe.clone()]),
+        "ctpop" => (class, "PopCount", vec![tpe.clone()]),
+        "rotate_left" => (class, "RotateLeft", vec![tpe.clone(), Type::I32]),
+        "rotate_right" => (class, "RotateRight", vec![tpe.clone(), Type::I32]),
+        "bswap" => (binary_primitives(), "ReverseEndianness", vec![tpe.clone()]),
+        _ => return None,
+    };
+    let mut ops: Vec<CILOp> = args.into_iter().flatten().collect();
+    ops.push(CILOp::Call(CallSite::boxed(
+        Some(class),
+        method.into(),
+        FnSig::new(&inputs, tpe),
+        true,
+    )));
+    Some(ops)
+}
+/// Returns the ops calculating the result of the bit manipulation intrinsic `name`, with arguments loaded by `args`, operating on integers of type `tpe`.
+/// Returns `None` if `name` is not a bit manipulation intrinsic, or if it is not supported for `tpe`.
+pub(crate) fn bit_intrinsic(
+    name: &str,
+    mut args: Vec<Vec<CILOp>>,
+    tpe: &Type,
+) -> Option<Vec<CILOp>> {
+    if let Type::I128 | Type::U128 = tpe {
+        return int128_bit_intrinsic(name, args, tpe);
+    }
+    let (bits, unsigned) = int_info(tpe)?;
+    let small_bits = bits.filter(|bits| *bits < 32);
+    let value = zero_extended(args.remove(0), tpe);
+    let mut ops = Vec::new();
+    match name {
+        "ctlz" | "ctlz_nonzero" => {
+            ops.extend(value);
+            ops.push(bit_operations("LeadingZeroCount", &[unsigned], &Type::I32));
+            // The value was zero-extended, so it has `32 - bits` more leading zeroes.
+            if let Some(bits) = small_bits {
+                ops.extend([CILOp::LdcI32(32 - bits), CILOp::Sub]);
+            }
+        }
+        "cttz" | "cttz_nonzero" => {
+            ops.extend(value);
+            // Setting the bit right above the value makes the count of a zero equal to `bits`, not 32.
+            if let Some(bits) = small_bits {
+                ops.extend([CILOp::LdcI32(1 << bits), CILOp::Or]);
+            }
+            ops.push(bit_operations("TrailingZeroCount", &[unsigned], &Type::I32));
+        }
+        "ctpop" => {
+            ops.extend(value);
+            ops.push(bit_operations("PopCount", &[unsigned], &Type::I32));
+        }
+        "rotate_left" | "rotate_right" => {
+            let shift = args.remove(0);
+            if let Some(bits) = small_bits {
+                // x << n | x >> (bits - n), with n reduced modulo `bits`. The value is zero-extended, so `x >> bits` is 0.
+                let (first, second) = if name == "rotate_left" {
+                    (CILOp::Shl, CILOp::Shr)
+                } else {
+                    (CILOp::Shr, CILOp::Shl)
+                };
+                let reduced = [shift.as_slice(), &[CILOp::LdcI32(bits - 1), CILOp::And]].concat();
+                ops.extend(value.iter().cloned());
+                ops.extend(reduced.iter().cloned());
+                ops.push(first);
+                ops.extend(value);
+                ops.push(CILOp::LdcI32(bits));
+                ops.extend(reduced);
+                ops.extend([CILOp::Sub, second, CILOp::Or]);
+            } else {
+                let method = if name == "rotate_left" {
+                    "RotateLeft"
+                } else {
+                    "RotateRight"
+                };
+                ops.extend(value);
+                ops.extend(shift);
+                ops.push(bit_operations(
+                    method,
+                    &[unsigned.clone(), Type::I32],
+                    &unsigned,
+                ));
+            }
+        }
+        "bswap" => {
+            ops.extend(value);
+            let unsigned = match bits {
+                Some(8) => return Some([ops, vec![conv_to(tpe)]].concat()),
+                Some(16) => Type::U16,
+                _ => unsigned,
+            };
+            ops.push(CILOp::Call(CallSite::boxed(
+                Some(binary_primitives()),
+                "ReverseEndianness".into(),
+                FnSig::new(&[unsigned.clone()], &unsigned),
+                true,
+            )));
+        }
+        _ => return None,
+    }
+    // The intrinsics return a value of the same type as their argument.
+    ops.push(conv_to(tpe));
+    Some(ops)
+}
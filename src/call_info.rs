@@ -32,7 +32,6 @@ impl CallInfo {
             Conv::C => (),
             _ => panic!("ERROR:calling using convention {conv:?} is not supported!"),
         }
-        assert!(!fn_abi.c_variadic);
         let ret = tycache.type_from_cache(fn_abi.ret.layout.ty, tcx, Some(function));
         let mut args = Vec::with_capacity(fn_abi.args.len());
         for arg in fn_abi.args.iter() {
@@ -58,7 +57,11 @@ impl CallInfo {
             ))?,*/
             _ => todo!("Unsuported ABI:{internal_abi:?}"),
         };
-        let sig = FnSig::new(&args, &ret);
+        let sig = if fn_abi.c_variadic {
+            FnSig::new_vararg(&args, &ret)
+        } else {
+            FnSig::new(&args, &ret)
+        };
         let has_track_caller = false;
         Ok(Self {
             sig,
@@ -54,6 +54,57 @@ pub fn int_to_int(src: Type, target: Type) -> Vec<CILOp> {
         _ => to_int(target),
     }
 }
+/// Returns the op validating that an u32 on top of the stack is a valid `char`(not a surrogate and at most `char::MAX`).
+/// Throws an exception if it is not.
+pub fn check_char() -> CILOp {
+    CILOp::Call(CallSite::boxed(
+        None,
+        "check_char".into(),
+        FnSig::new(&[Type::U32], &Type::U32),
+        true,
+    ))
+}
+/// Returns the ops converting the `char` on top of the stack to a `System.Char`(an UTF-16 code unit). Throws an `OverflowException` if the `char`
+/// does not fit in a single code unit.
+pub fn char_to_dotnet_char() -> Vec<CILOp> {
+    vec![CILOp::ConvU16(true)]
+}
+/// Returns the ops converting the `System.Char` on top of the stack to a `char`. Throws an exception if it is a surrogate, which is not a
+/// valid `char` on its own.
+pub fn dotnet_char_to_char() -> Vec<CILOp> {
+    vec![CILOp::ConvU32(false), check_char()]
+}
+/// Returns the ops converting the `char` on top of the stack to a `System.Text.Rune`. Both represent a Unicode scalar value, so the
+/// conversion always succeeds.
+pub fn char_to_rune() -> Vec<CILOp> {
+    let rune = DotnetTypeRef::rune();
+    vec![CILOp::Call(CallSite::boxed(
+        Some(rune.clone()),
+        "op_Explicit".into(),
+        FnSig::new(&[Type::U32], &rune.into()),
+        true,
+    ))]
+}
+/// Returns the ops converting the `System.Text.Rune` on top of the stack to a `char`.
+pub fn rune_to_char() -> Vec<CILOp> {
+    let rune = DotnetTypeRef::rune();
+    vec![
+        CILOp::NewTMPLocal(Type::DotnetType(rune.clone().into()).into()),
+        CILOp::SetTMPLocal,
+        CILOp::LoadAddresOfTMPLocal,
+        CILOp::Call(CallSite::boxed(
+            Some(rune.clone()),
+            "get_Value".into(),
+            FnSig::new(
+                &[Type::Ptr(Type::DotnetType(rune.into()).into())],
+                &Type::I32,
+            ),
+            false,
+        )),
+        CILOp::FreeTMPLocal,
+        CILOp::ConvU32(false),
+    ]
+}
 /// Returns CIL ops required to convert type src to target
 pub fn float_to_int(src: Type, target: Type) -> Vec<CILOp> {
     match target {
@@ -93,6 +144,8 @@ pub fn to_int(target: Type) -> Vec<CILOp> {
         Type::I16 => vec![CILOp::ConvI16(false)],
         Type::U16 => vec![CILOp::ConvU16(false)],
         Type::U32 => vec![CILOp::ConvU32(false)],
+        // Only `u8` can be cast to `char`, so the value is always valid.
+        Type::Char => vec![CILOp::ConvU32(false)],
         Type::I32 => vec![CILOp::ConvI32(false)],
         Type::I64 => vec![CILOp::ConvI64(false)],
         Type::U64 => vec![CILOp::ConvU64(false)],
@@ -109,10 +162,20 @@ pub fn int_to_float(src: Type, target: Type) -> Vec<CILOp> {
     if matches!(target, Type::I128 | Type::U128) {
         todo!("Casting to 128 bit intiegers is not supported!")
     } else {
-        match target {
-            Type::F32 => vec![CILOp::ConvF32(false)],
-            Type::F64 => vec![CILOp::ConvF64(false)],
+        // `conv.r4` and `conv.r8` treat the integer as signed, so unsigned integers with the highest bit set(eg. `u64::MAX`) would become negative.
+        let unsigned = matches!(
+            src,
+            Type::U8 | Type::U16 | Type::U32 | Type::U64 | Type::USize
+        );
+        let conv = match target {
+            Type::F32 => CILOp::ConvF32(false),
+            Type::F64 => CILOp::ConvF64(false),
             _ => todo!("Can't cast to {target:?} yet!"),
+        };
+        if unsigned {
+            vec![CILOp::ConvRUn, conv]
+        } else {
+            vec![conv]
         }
     }
 }
@@ -0,0 +1,228 @@
+use super::{CILOp, CallSite, FieldDescriptor};
+use crate::r#type::Type;
+use std::collections::HashMap;
+/// Builds a sequence of ops, using chainable methods. Keeps track of the depth of the evaluation stack, and of labels, and panics when an op would
+/// pop from an empty stack, when a label is reached with different stack depths, or when a branch targets a label which is never placed.
+/// ```ignore
+/// let mut builder = CilBuilder::new();
+/// let is_zero = builder.new_label();
+/// let ops = builder
+///     .ldarg(0)
+///     .branch_if_zero(is_zero)
+///     .ldc_i32(1)
+///     .ret()
+///     .label(is_zero)
+///     .ldc_i32(0)
+///     .ret()
+///     .build();
+/// ```
+#[derive(Debug, Default)]
+pub struct CilBuilder {
+    ops: Vec<CILOp>,
+    /// Current depth of the evaluation stack.
+    depth: isize,
+    /// The biggest depth the evaluation stack reached.
+    max_depth: isize,
+    /// The id the next label created by [`Self::new_label`] will have.
+    next_label: u32,
+    /// The depth of the stack at each label some branch targets, or which was placed.
+    label_depths: HashMap<u32, isize>,
+    /// Labels already placed using [`Self::label`].
+    placed: Vec<u32>,
+}
+impl CilBuilder {
+    /// Creates a new builder, with no ops.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns a new label, which can be targeted by branches, and placed using [`Self::label`].
+    pub fn new_label(&mut self) -> u32 {
+        let label = self.next_label;
+        self.next_label += 1;
+        label
+    }
+    /// Returns the current depth of the evaluation stack.
+    #[must_use]
+    pub fn depth(&self) -> isize {
+        self.depth
+    }
+    /// Returns the biggest depth the evaluation stack reached.
+    #[must_use]
+    pub fn max_depth(&self) -> isize {
+        self.max_depth
+    }
+    /// Records that the stack has `depth` values when `label` is reached.
+    fn record_depth(&mut self, label: u32, depth: isize) {
+        let recorded = *self.label_depths.entry(label).or_insert(depth);
+        assert_eq!(
+            recorded, depth,
+            "Label {label} reached with stack depths {recorded} and {depth}!"
+        );
+    }
+    /// Appends `op`.
+    #[must_use]
+    pub fn op(mut self, op: CILOp) -> Self {
+        // The stack is empty after diverging ops, unless a label says otherwise.
+        let diverges = op.is_diverging();
+        if let CILOp::Label(label) = op {
+            assert!(
+                !self.placed.contains(&label),
+                "Label {label} placed more than once!"
+            );
+            self.placed.push(label);
+            let reachable = self.ops.last().map_or(true, |last| !last.is_diverging());
+            if reachable {
+                self.record_depth(label, self.depth);
+            } else {
+                self.depth = self.label_depths.get(&label).copied().unwrap_or(0);
+            }
+        }
+        self.depth += op.stack_diff();
+        // `ret` only pops a value in methods returning one.
+        if op == CILOp::Ret {
+            self.depth = self.depth.max(0);
+        }
+        assert!(self.depth >= 0, "Stack underflow after op {op:?}!");
+        self.max_depth = self.max_depth.max(self.depth);
+        for target in op.branch_targets() {
+            self.record_depth(*target, self.depth);
+        }
+        self.ops.push(op);
+        if diverges {
+            self.depth = 0;
+        }
+        self
+    }
+    /// Appends `ops`, in order.
+    #[must_use]
+    pub fn ops(self, ops: impl IntoIterator<Item = CILOp>) -> Self {
+        ops.into_iter().fold(self, Self::op)
+    }
+    /// Loads argument `arg`.
+    #[must_use]
+    pub fn ldarg(self, arg: u32) -> Self {
+        self.op(CILOp::LDArg(arg))
+    }
+    /// Loads local `local`.
+    #[must_use]
+    pub fn ldloc(self, local: u32) -> Self {
+        self.op(CILOp::LDLoc(local))
+    }
+    /// Stores the value on top of the stack in local `local`.
+    #[must_use]
+    pub fn stloc(self, local: u32) -> Self {
+        self.op(CILOp::STLoc(local))
+    }
+    /// Loads the `int32` constant `value`.
+    #[must_use]
+    pub fn ldc_i32(self, value: i32) -> Self {
+        self.op(CILOp::LdcI32(value))
+    }
+    /// Adds the 2 values on top of the stack.
+    #[must_use]
+    pub fn add(self) -> Self {
+        self.op(CILOp::Add)
+    }
+    /// Calls the method `site`.
+    #[must_use]
+    pub fn call(self, site: CallSite) -> Self {
+        self.op(CILOp::Call(site.into()))
+    }
+    /// Loads the value of `field`, of the object or pointer on top of the stack.
+    #[must_use]
+    pub fn ldfld(self, field: FieldDescriptor) -> Self {
+        self.op(CILOp::LDField(field.into()))
+    }
+    /// Loads the address of `field`, of the object or pointer on top of the stack.
+    #[must_use]
+    pub fn ldflda(self, field: FieldDescriptor) -> Self {
+        self.op(CILOp::LDFieldAdress(field.into()))
+    }
+    /// Sets `field` of the object or pointer under the value on top of the stack to that value.
+    #[must_use]
+    pub fn stfld(self, field: FieldDescriptor) -> Self {
+        self.op(CILOp::STField(field.into()))
+    }
+    /// Loads a value of type `tpe` from the pointer on top of the stack.
+    #[must_use]
+    pub fn ldobj(self, tpe: Type) -> Self {
+        self.op(CILOp::LdObj(tpe.into()))
+    }
+    /// Stores the value on top of the stack, of type `tpe`, at the pointer under it.
+    #[must_use]
+    pub fn stobj(self, tpe: Type) -> Self {
+        self.op(CILOp::STObj(tpe.into()))
+    }
+    /// Places `label`, created by [`Self::new_label`].
+    #[must_use]
+    pub fn label(self, label: u32) -> Self {
+        self.op(CILOp::Label(label))
+    }
+    /// Jumps to `label`.
+    #[must_use]
+    pub fn branch_to(self, label: u32) -> Self {
+        self.op(CILOp::GoTo(label))
+    }
+    /// Jumps to `label` if the value on top of the stack is zero.
+    #[must_use]
+    pub fn branch_if_zero(self, label: u32) -> Self {
+        self.op(CILOp::BZero(label))
+    }
+    /// Jumps to `label` if the value on top of the stack is not zero.
+    #[must_use]
+    pub fn branch_if_true(self, label: u32) -> Self {
+        self.op(CILOp::BTrue(label))
+    }
+    /// Returns from the method.
+    #[must_use]
+    pub fn ret(self) -> Self {
+        self.op(CILOp::Ret)
+    }
+    /// Returns the built ops. Panics if any branch targets a label which was never placed.
+    #[must_use]
+    pub fn build(self) -> Vec<CILOp> {
+        for label in self.label_depths.keys() {
+            assert!(
+                self.placed.contains(label),
+                "Label {label} is targeted, but never placed!"
+            );
+        }
+        self.ops
+    }
+}
+#[test]
+fn builds_branches() {
+    let mut builder = CilBuilder::new();
+    let is_zero = builder.new_label();
+    let builder = builder
+        .ldarg(0)
+        .branch_if_zero(is_zero)
+        .ldc_i32(1)
+        .ldc_i32(2)
+        .add()
+        .ret()
+        .label(is_zero)
+        .ldc_i32(0)
+        .ret();
+    assert_eq!(builder.max_depth(), 2);
+    assert_eq!(
+        builder.build(),
+        [
+            CILOp::LDArg(0),
+            CILOp::BZero(0),
+            CILOp::LdcI32(1),
+            CILOp::LdcI32(2),
+            CILOp::Add,
+            CILOp::Ret,
+            CILOp::Label(0),
+            CILOp::LdcI32(0),
+            CILOp::Ret,
+        ]
+    );
+}
+#[test]
+#[should_panic(expected = "Stack underflow")]
+fn detects_underflow() {
+    let _ = CilBuilder::new().ldarg(0).add();
+}
@@ -1,40 +1,74 @@
 use serde::{Deserialize, Serialize};
 
+use std::sync::Arc;
+
 use crate::{
     function_sig::FnSig,
-    r#type::{DotnetTypeRef, Type},
+    r#type::{DotnetTypeRef, Type, TypeIdx},
     IString,
 };
 use rustc_middle::ty::TyCtxt;
 /// Represenation of a target of a call.
 #[derive(Clone, PartialEq, Serialize, Deserialize, Eq, Hash, Debug)]
 pub struct CallSite {
-    class: Option<DotnetTypeRef>,
+    class: Option<TypeIdx>,
     name: IString,
-    signature: FnSig,
+    #[serde(with = "crate::function_sig::shared")]
+    signature: Arc<FnSig>,
     is_static: bool,
+    /// Generic arguments of the method, if it is a generic .NET method.
+    #[serde(default)]
+    generics: Vec<Type>,
 }
 impl CallSite {
     /// Constructs a new call site targeting method `name`, with signature `signature` and bleonging to class `class`. If `class` is [`None`], then the `<Module>` class
     /// is assumed.
+    /// `signature` may be either a [`FnSig`], or a signature shared with other call sites.
     pub fn new(
         class: Option<DotnetTypeRef>,
         name: IString,
-        signature: FnSig,
+        signature: impl Into<Arc<FnSig>>,
         is_static: bool,
     ) -> Self {
         Self {
-            class,
+            class: class.map(TypeIdx::from),
             name,
-            signature,
+            signature: signature.into(),
             is_static,
+            generics: Vec::new(),
+        }
+    }
+    /// Makes this call site call the instantiation of a generic .NET method with `generics`. The signature of such a call site refers to the
+    /// generic arguments using [`Type::MethodGenericArg`], like the definition of the method does(eg. `Unsafe.As<T>` has signature `!!0(object)`).
+    #[must_use]
+    pub fn with_generics(mut self, generics: Vec<Type>) -> Self {
+        self.generics = generics;
+        self
+    }
+    /// Returns the generic arguments of the called method. Empty if the method is not generic.
+    pub fn generics(&self) -> &[Type] {
+        &self.generics
+    }
+    /// Returns the signature of the called method, with its generic arguments replaced by the types this call site instantiates it with.
+    pub fn instantiated_signature(&self) -> FnSig {
+        if self.generics.is_empty() {
+            return self.signature().clone();
         }
+        let inputs: Vec<Type> = self
+            .inputs()
+            .iter()
+            .map(|input| input.map_method_generic(&self.generics))
+            .collect();
+        FnSig::new(
+            &inputs,
+            &self.signature.output().map_method_generic(&self.generics),
+        )
     }
     /// The same as [`Self::new`], but boxes the result.
     pub fn boxed(
         class: Option<DotnetTypeRef>,
         name: IString,
-        signature: FnSig,
+        signature: impl Into<Arc<FnSig>>,
         is_static: bool,
     ) -> Box<Self> {
         Box::new(Self::new(class, name, signature, is_static))
@@ -43,6 +77,10 @@ impl CallSite {
     pub fn signature(&self) -> &FnSig {
         &self.signature
     }
+    /// Returns the signature of the function this call site targets, so that it can be shared with other call sites.
+    pub fn shared_signature(&self) -> &Arc<FnSig> {
+        &self.signature
+    }
     /// Returns the call site refering to the function malloc.
     pub fn malloc(ctx: TyCtxt) -> Self {
         Self::new(
@@ -54,7 +92,7 @@ impl CallSite {
     }
     /// Returns the class the targeted method belongs to.
     pub fn class(&self) -> Option<&DotnetTypeRef> {
-        self.class.as_ref()
+        self.class.map(TypeIdx::get)
     }
     /// Returns `true` if the method in question is static.
     pub fn is_static(&self) -> bool {
@@ -96,3 +134,33 @@ impl CallSite {
         }
     }
 }
+#[test]
+fn generic_call_site() {
+    // `ref TTo Unsafe.As<TFrom, TTo>(ref TFrom source)`
+    let site = CallSite::new(
+        Some(
+            DotnetTypeRef::new(
+                Some("System.Runtime"),
+                "System.Runtime.CompilerServices.Unsafe",
+            )
+            .with_valuetype(false),
+        ),
+        "As".into(),
+        FnSig::new(
+            &[Type::ManagedReference(Type::MethodGenericArg(0).into())],
+            &Type::ManagedReference(Type::MethodGenericArg(1).into()),
+        ),
+        true,
+    )
+    .with_generics(vec![Type::I32, Type::F32]);
+    assert_eq!(
+        site.instantiated_signature(),
+        FnSig::new(
+            &[Type::ManagedReference(Type::I32.into())],
+            &Type::ManagedReference(Type::F32.into())
+        )
+    );
+    let cil = crate::assembly_exporter::ilasm_op::call_site_cil(&site);
+    assert!(cil.starts_with(" !!1& "));
+    assert!(cil.ends_with("As<int32,float32>(!!0&)"));
+}
@@ -1,14 +1,14 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    r#type::{DotnetTypeRef, Type},
+    r#type::{DotnetTypeRef, Type, TypeIdx},
     IString,
 };
 
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 /// This struct descibes a .NET field. It contains information about the type this field belongs to, the name of the field, and the fields type.
 pub struct FieldDescriptor {
-    owner: DotnetTypeRef,
+    owner: TypeIdx,
     tpe: Type,
     name: IString,
 }
@@ -23,16 +23,20 @@ impl FieldDescriptor {
     }
     /// Returns the the type this field belongs to. For getting the type of this field, see [self.tpe]
     pub fn owner(&self) -> &DotnetTypeRef {
-        &self.owner
+        self.owner.get()
     }
     /// Constructs a new fieldref, reffering to field of type `tpe`, belonging to `owner`, and named `name`
     #[must_use]
     pub fn new(owner: DotnetTypeRef, tpe: Type, name: IString) -> Self {
-        Self { owner, tpe, name }
+        Self {
+            owner: owner.into(),
+            tpe,
+            name,
+        }
     }
     /// The same as [`Self::new`], but also boxes the field descriptor.
     #[must_use]
     pub fn boxed(owner: DotnetTypeRef, tpe: Type, name: IString) -> Box<Self> {
-        Box::new(Self { owner, tpe, name })
+        Box::new(Self::new(owner, tpe, name))
     }
 }
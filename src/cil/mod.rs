@@ -1,11 +1,38 @@
 use crate::{function_sig::FnSig, r#type::DotnetTypeRef, IString};
+mod builder;
+pub use builder::*;
 mod call_site;
 pub use call_site::*;
 mod field_desc;
 pub use field_desc::*;
+mod source_span;
+pub use source_span::*;
 mod static_field_desc;
 use serde::{Deserialize, Serialize};
 pub use static_field_desc::*;
+/// Call sites of the private helper methods emitted into every assembly by [`crate::ffi::insert_ffi_functions`]. Common op sequences call
+/// those helpers, instead of repeating their bodies at every use. Built only once, since they are needed very often.
+struct Helpers {
+    /// `rust_exception(string) -> System.Exception`.
+    exception: Box<CallSite>,
+    /// `rust_print(string)`, writing a line to STDOUT.
+    print: Box<CallSite>,
+    /// `rust_print_no_nl(string)`, writing to STDOUT without a new line.
+    print_no_nl: Box<CallSite>,
+}
+lazy_static::lazy_static! {
+    static ref HELPERS: Helpers = {
+        let string: crate::r#type::Type = crate::utilis::string_class().into();
+        let helper = |name: &str, output: crate::r#type::Type| {
+            CallSite::boxed(None, name.into(), FnSig::new(&[string.clone()], &output), true)
+        };
+        Helpers {
+            exception: helper("rust_exception", crate::utilis::exception_class().into()),
+            print: helper("rust_print", crate::r#type::Type::Void),
+            print_no_nl: helper("rust_print_no_nl", crate::r#type::Type::Void),
+        }
+    };
+}
 /// Represenation of a CIL opcode.
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub enum CILOp {
@@ -29,10 +56,14 @@ pub enum CILOp {
     BZero(u32),
     /// Jump to target if the top value on the stack is zero, continue otherwise. WARING: make sure the compared values have the same type, othewise IL is invalid.
     BTrue(u32),
+    /// Jump to the target at index equal to the (unsigned) top value on the stack. Continue if the index is outside the jump table.
+    Switch(Box<[u32]>),
     /// Call the metod behind `call_site`.`
     Call(Box<CallSite>),
     /// Call the virtual method behind `call_site`.`
     CallVirt(Box<CallSite>),
+    /// Loads a native pointer to the method behind `call_site`.
+    LDFtn(Box<CallSite>),
     /// Throw the top value on the stack as an exception
     Throw,
     /// Rethrow the current exception
@@ -232,6 +263,64 @@ pub enum CILOp {
     STStaticField(Box<StaticFieldDescriptor>),
     /// Copies to *dst* from *src* *count* bytes.  
     CpBlk,
+    // Prefixes
+    /// Prefix: the memory access right after it may use an address aligned to only `alignment` bytes.
+    Unaligned(u8),
+    /// Prefix: the memory access right after it is volatile, and may not be cached or reordered by the JIT.
+    Volatile,
+    /// IL text inserted verbatim, written in the support library(see [`crate::inline_il`]). Inline IL blocks must leave the evaluation stack as they found it.
+    /// Inline IL may refer to the arguments of the method, but not to its locals, which may be renumbered by optimizations. The IL can't be analyzed, so
+    /// it declares how many values it pushes onto the evaluation stack at most(above the depth it started at), which is used for computing `.maxstack`.
+    InlineIL(IString, u32),
+    /// Exits a protected region or a `catch` handler, emptying the evaluation stack, and jumps to a label with the specified id. Runs the `finally` handlers
+    /// of the exited regions.
+    Leave(u32),
+    /// Ends a `finally` or `fault` handler.
+    EndFinally,
+    /// Calls the function behind the native function pointer on top of the stack, with signature `sig`. The arguments are under the pointer.
+    CallI(Box<FnSig>),
+    /// Prefix: the call right after it is a tail call, replacing the frame of the current method. It must be followed by [`CILOp::Ret`].
+    Tail,
+    /// Boxes the value of `type` on top of the stack, turning it into a `System.Object`.
+    Box(Box<crate::r#type::Type>),
+    /// Unboxes the `System.Object` on top of the stack, loading the value of `type` it holds. Throws an exception if it does not hold a `type`.
+    UnboxAny(Box<crate::r#type::Type>),
+    /// Checks if the object reference on top of the stack is an instance of `class`(or a class derived from it). Leaves the reference on the stack
+    /// if it is, and replaces it with null otherwise.
+    IsInst(Box<DotnetTypeRef>),
+    /// Casts the object reference on top of the stack to `class`. Throws `System.InvalidCastException` if it is not an instance of `class`.
+    CastClass(Box<DotnetTypeRef>),
+    /// Zero-initializes the value of `type` behind the pointer on top of the stack.
+    InitObj(Box<crate::r#type::Type>),
+    /// Sets *count* bytes at *dst* to *value*.
+    InitBlk,
+    /// Checks if the upper value on the stack is less than the lower one, comparing integers as unsigned, and floats as unordered(true if either
+    /// one is NaN). Pushes 0 if not, and 1 if it is.
+    LtUn,
+    /// Checks if the upper value on the stack is greater than the lower one, comparing integers as unsigned, and floats as unordered(true if either
+    /// one is NaN). Pushes 0 if not, and 1 if it is.
+    GtUn,
+    /// Jump to target if the top value is less than the bottom one, comparing integers as unsigned, and floats as unordered. Continue otherwise.
+    BLtUn(u32),
+    /// Jump to target if the top value is greater than or equal to the bottom one, comparing integers as unsigned, and floats as unordered.
+    /// Continue otherwise.
+    BGeUn(u32),
+    /// Jump to target if the top value is less than or equal to the bottom one, comparing integers as unsigned, and floats as unordered.
+    /// Continue otherwise.
+    BLeUn(u32),
+    /// Convert the unsigned integer on top of the stack to a native-size float. Must be followed by `ConvF32` or `ConvF64`, since the
+    /// signed conversions treat integers with the highest bit set as negative.
+    ConvRUn,
+    /// Load the runtime handle(a `System.RuntimeTypeHandle`) of a type.
+    LDTypeToken(Box<crate::r#type::Type>),
+    /// Loads a native pointer to the implementation of virtual method `call_site` for the object on top of the stack. Used to create delegates
+    /// bound to virtual methods.
+    LDVirtFtn(Box<CallSite>),
+    /// Prefix: the `callvirt` right after it is called on a managed pointer to a value of `type`, instead of an object. If `type` is a value type, it
+    /// is not boxed, unless it does not implement the method itself(eg. it uses the `ToString` of `System.Object`).
+    Constrained(Box<crate::r#type::Type>),
+    /// Marks the following ops(up to the next `SourceSpan`) as generated from the Rust code at the given location. Does nothing.
+    SourceSpan(Box<SourceSpan>),
 }
 impl CILOp {
     /// If this op is a branch operation, and its target is `original`, replaces the target with `replacement`
@@ -244,53 +333,162 @@ impl CILOp {
             | CILOp::BGe(target)
             | CILOp::BLe(target)
             | CILOp::BZero(target)
-            | CILOp::BTrue(target) => {
+            | CILOp::BTrue(target)
+            | CILOp::BLtUn(target)
+            | CILOp::BGeUn(target)
+            | CILOp::BLeUn(target)
+            | CILOp::Leave(target) => {
                 if orignal == *target {
                     *target = replacement
                 }
             }
+            CILOp::Switch(targets) => targets
+                .iter_mut()
+                .filter(|target| **target == orignal)
+                .for_each(|target| *target = replacement),
             _ => (),
         }
     }
-    /// If the cil op is a call, virtual call or new object cosntructor, returns the [`CallSite`] representing the called function.
+    /// Returns the labels this op may jump to. Empty if the op is not a branch.
+    pub fn branch_targets(&self) -> &[u32] {
+        match self {
+            CILOp::GoTo(target)
+            | CILOp::BEq(target)
+            | CILOp::BNe(target)
+            | CILOp::BLt(target)
+            | CILOp::BGe(target)
+            | CILOp::BLe(target)
+            | CILOp::BZero(target)
+            | CILOp::BTrue(target)
+            | CILOp::BLtUn(target)
+            | CILOp::BGeUn(target)
+            | CILOp::BLeUn(target)
+            | CILOp::Leave(target) => std::slice::from_ref(target),
+            CILOp::Switch(targets) => targets,
+            _ => &[],
+        }
+    }
+    /// Returns true if execution never continues to the op after this one.
+    pub fn is_diverging(&self) -> bool {
+        matches!(
+            self,
+            CILOp::GoTo(_)
+                | CILOp::Ret
+                | CILOp::Throw
+                | CILOp::Rethrow
+                | CILOp::Leave(_)
+                | CILOp::EndFinally
+        )
+    }
+    /// If the cil op is a call, virtual call, new object cosntructor or a function pointer load, returns the [`CallSite`] representing the referenced function.
     pub fn call(&self) -> Option<&CallSite> {
         match self {
             Self::Call(site) => Some(site),
             Self::CallVirt(site) => Some(site),
+            Self::LDFtn(site) => Some(site),
+            Self::LDVirtFtn(site) => Some(site),
             Self::NewObj(site) => Some(site),
             _ => None,
         }
     }
+    /// Returns the ops necesary to terminate the process immediately, using `System.Environment.FailFast`, reporting message `msg`.
+    /// No cleanup(`finally` blocks, finalizers) runs, like with a native `abort`.
+    #[must_use]
+    pub fn fail_fast(msg: &str) -> [CILOp; 2] {
+        [CILOp::LdStr(msg.into()), Self::fail_fast_with_msg()]
+    }
+    /// Like [`Self::fail_fast`], but the message is the string on top of the stack.
+    #[must_use]
+    pub fn fail_fast_with_msg() -> CILOp {
+        CILOp::Call(CallSite::boxed(
+            Some(crate::utilis::environment_class()),
+            "FailFast".into(),
+            FnSig::new(
+                &[crate::utilis::string_class().into()],
+                &crate::r#type::Type::Void,
+            ),
+            true,
+        ))
+    }
     /// Returns the ops necesary to construct and throw a new `System.Exception` with message `msg`.
+    /// The exception is constructed by the shared `rust_exception` helper, so that each throw site stays short.
     pub fn throw_msg(msg: &str) -> [CILOp; 3] {
-        let mut class = DotnetTypeRef::new(Some("System.Runtime"), "System.Exception");
-        class.set_valuetype(false);
-        let name = ".ctor".into();
-        let signature = FnSig::new(
-            &[class.clone().into(), crate::utilis::string_class().into()],
-            &crate::r#type::Type::Void,
-        );
         [
             CILOp::LdStr(msg.into()),
-            CILOp::NewObj(CallSite::boxed(Some(class), name, signature, false)),
+            CILOp::Call(HELPERS.exception.clone()),
             CILOp::Throw,
         ]
     }
-    /// Returns the ops necesary to  write message `msg` to STDOUT. Ends with new line.
+    /// Like [`Self::throw_msg`], but the message is the string on top of the stack.
     #[must_use]
-    pub fn debug_msg(msg: &str) -> [CILOp; 2] {
+    pub fn throw_with_msg() -> [CILOp; 2] {
+        [CILOp::Call(HELPERS.exception.clone()), CILOp::Throw]
+    }
+    /// Returns the ops converting the value of type `tpe` on top of the stack to a string, using its `ToString` method.
+    #[must_use]
+    pub fn value_to_string(tpe: &crate::r#type::Type) -> [CILOp; 2] {
+        let string: crate::r#type::Type = crate::utilis::string_class().into();
+        [
+            CILOp::Box(tpe.clone().into()),
+            CILOp::CallVirt(
+                CallSite::new(
+                    Some(crate::utilis::object_class()),
+                    "ToString".into(),
+                    FnSig::new(&[crate::utilis::object_class().into()], &string),
+                    false,
+                )
+                .into(),
+            ),
+        ]
+    }
+    /// Returns the ops necesary to write the value of type `tpe` from stack to stdout, formatted by its `ToString` method. Ends without a new line.
+    #[must_use]
+    pub fn debug_value(tpe: &crate::r#type::Type) -> [CILOp; 2] {
         let mut class = DotnetTypeRef::new(Some("System.Console"), "System.Console");
         class.set_valuetype(false);
-        let name = "WriteLine".into();
         let signature = FnSig::new(
-            &[crate::utilis::string_class().into()],
+            &[crate::utilis::object_class().into()],
             &crate::r#type::Type::Void,
         );
         [
-            CILOp::LdStr(msg.into()),
-            CILOp::Call(CallSite::new(Some(class), name, signature, true).into()),
+            CILOp::Box(tpe.clone().into()),
+            CILOp::Call(CallSite::new(Some(class), "Write".into(), signature, true).into()),
+        ]
+    }
+    /// Returns the ops passing the value of type `tpe` on top of the stack trough a `System.Object`: boxing it, and unboxing it back.
+    /// Managed APIs taking and returning objects(eg. collections) are called between the two ops.
+    #[must_use]
+    pub fn object_round_trip(tpe: &crate::r#type::Type) -> [CILOp; 2] {
+        [
+            CILOp::Box(tpe.clone().into()),
+            CILOp::UnboxAny(tpe.clone().into()),
         ]
     }
+    /// Returns the ops loading the `System.Type` representing `tpe`(like `typeof(T)` in C#). Used to pass types to reflection-based managed APIs.
+    #[must_use]
+    pub fn system_type(tpe: &crate::r#type::Type) -> [CILOp; 2] {
+        let signature = FnSig::new(
+            &[crate::utilis::runtime_type_handle().into()],
+            &crate::utilis::type_class().into(),
+        );
+        [
+            CILOp::LDTypeToken(tpe.clone().into()),
+            CILOp::Call(
+                CallSite::new(
+                    Some(crate::utilis::type_class()),
+                    "GetTypeFromHandle".into(),
+                    signature,
+                    true,
+                )
+                .into(),
+            ),
+        ]
+    }
+    /// Returns the ops necesary to  write message `msg` to STDOUT. Ends with new line.
+    #[must_use]
+    pub fn debug_msg(msg: &str) -> [CILOp; 2] {
+        [CILOp::LdStr(msg.into()), CILOp::Call(HELPERS.print.clone())]
+    }
     /// Returns the ops necesary to  write message `msg` to STDOUT. Ends with new line.
     #[must_use]
     pub fn new_line() -> Self {
@@ -304,16 +502,9 @@ impl CILOp {
     /// Returns the ops necesary to  write message `msg` to STDOUT. Does not end with new line.
     #[must_use]
     pub fn debug_msg_no_nl(msg: &str) -> [CILOp; 2] {
-        let mut class = DotnetTypeRef::new(Some("System.Console"), "System.Console");
-        class.set_valuetype(false);
-        let name = "Write".into();
-        let signature = FnSig::new(
-            &[crate::utilis::string_class().into()],
-            &crate::r#type::Type::Void,
-        );
         [
             CILOp::LdStr(msg.into()),
-            CILOp::Call(CallSite::new(Some(class), name, signature, true).into()),
+            CILOp::Call(HELPERS.print_no_nl.clone()),
         ]
     }
     /// Returns the ops necesary to  write message bool from stack to stdout. Ends without a new line.
@@ -359,8 +550,9 @@ impl CILOp {
             CILOp::Nop => 0,
             CILOp::Comment(_) => 0,
             CILOp::Label(_) | CILOp::GoTo(_) => 0,
-            CILOp::BZero(_) | CILOp::BTrue(_) => -1,
+            CILOp::BZero(_) | CILOp::BTrue(_) | CILOp::Switch(_) => -1,
             CILOp::BEq(_) | CILOp::BNe(_) | CILOp::BLt(_) | CILOp::BGe(_) | CILOp::BLe(_) => -2,
+            CILOp::BLtUn(_) | CILOp::BGeUn(_) | CILOp::BLeUn(_) => -2,
             CILOp::LDArg(_) | CILOp::LDArgA(_) | CILOp::LDLoc(_) | CILOp::LDLocA(_) => 1,
             CILOp::LdcI32(_)
             | CILOp::LdcI64(_)
@@ -380,7 +572,8 @@ impl CILOp {
             | CILOp::ConvU64(_)
             | CILOp::ConvUSize(_)
             | CILOp::ConvF32(_)
-            | CILOp::ConvF64(_) => 0,
+            | CILOp::ConvF64(_)
+            | CILOp::ConvRUn => 0,
             CILOp::LDIndI8
             | CILOp::LDIndI16
             | CILOp::LDIndI32
@@ -402,6 +595,8 @@ impl CILOp {
             CILOp::LocAlloc => 0,
             CILOp::NewObj(site) => 1 - (site.explicit_inputs().len() as isize),
             CILOp::LdObj(_) => 0,
+            CILOp::Box(_) | CILOp::UnboxAny(_) => 0,
+            CILOp::IsInst(_) | CILOp::CastClass(_) => 0,
             CILOp::LDStaticField(_) => 1,
             CILOp::STStaticField(_) => -1,
             CILOp::STObj(_) => -2,
@@ -423,20 +618,38 @@ impl CILOp {
             | CILOp::XOr
             | CILOp::Eq
             | CILOp::Lt
-            | CILOp::Gt => -1,
+            | CILOp::Gt
+            | CILOp::LtUn
+            | CILOp::GtUn => -1,
             CILOp::Not | CILOp::Neg => 0,
             CILOp::STLoc(_) | CILOp::STArg(_) => -1,
             CILOp::Call(site) | CILOp::CallVirt(site) => {
                 if *site.signature().output() == crate::r#type::Type::Void {
-                    -(site.signature().inputs().len() as isize)
+                    -(site.signature().arg_count() as isize)
                 } else {
-                    1 - (site.signature().inputs().len() as isize)
+                    1 - (site.signature().arg_count() as isize)
+                }
+            }
+            CILOp::LDFtn(_) | CILOp::LDTypeToken(_) => 1,
+            CILOp::LDVirtFtn(_) => 0,
+            CILOp::CallI(sig) => {
+                let inputs = sig.inputs().len() as isize + 1;
+                if *sig.output() == crate::r#type::Type::Void {
+                    -inputs
+                } else {
+                    1 - inputs
                 }
             }
             CILOp::Throw => -1,
             CILOp::Rethrow => -1,
             CILOp::Ret => -1,
-            CILOp::CpBlk => -3,
+            CILOp::CpBlk | CILOp::InitBlk => -3,
+            CILOp::InitObj(_) => -1,
+            CILOp::Unaligned(_) | CILOp::Volatile | CILOp::Tail | CILOp::Constrained(_) => 0,
+            CILOp::SourceSpan(_) => 0,
+            CILOp::InlineIL(..) => 0,
+            // `leave` empties the whole stack, but nothing may be left on it when a block ends anyway.
+            CILOp::Leave(_) | CILOp::EndFinally => 0,
             // Syntetic instructions
             CILOp::NewTMPLocal(_) | CILOp::FreeTMPLocal => 0,
             CILOp::LoadAddresOfTMPLocal
@@ -447,6 +660,34 @@ impl CILOp {
             CILOp::LoadGlobalAllocPtr { alloc_id: _ } => 1,
         }
     }
+    /// Checks if this op reads or writes memory, and may be prefixed with [`CILOp::Unaligned`] or [`CILOp::Volatile`].
+    pub fn is_memory_access(&self) -> bool {
+        matches!(
+            self,
+            CILOp::LDIndI8
+                | CILOp::LDIndI16
+                | CILOp::LDIndI32
+                | CILOp::LDIndI64
+                | CILOp::LDIndISize
+                | CILOp::LDIndF32
+                | CILOp::LDIndF64
+                | CILOp::LDIndRef
+                | CILOp::STIndI8
+                | CILOp::STIndI16
+                | CILOp::STIndI32
+                | CILOp::STIndI64
+                | CILOp::STIndISize
+                | CILOp::STIndF32
+                | CILOp::STIndF64
+                | CILOp::LDField(_)
+                | CILOp::STField(_)
+                | CILOp::LdObj(_)
+                | CILOp::STObj(_)
+                | CILOp::CpBlk
+                | CILOp::InitBlk
+                | CILOp::InitObj(_)
+        )
+    }
     /// Flips a conditional, changing the order of its arguments. Eg. BLt(a,b) [a < b] becomes BGt(b,a) [b > a].
     // There may be a bug there.
     pub fn flip_cond(&self) -> Self {
@@ -455,6 +696,10 @@ impl CILOp {
                     CILOp::BLe(*target),
                 CILOp::BLe(target) =>
                     CILOp::BGe(*target),
+                CILOp::BGeUn(target) =>
+                    CILOp::BLeUn(*target),
+                CILOp::BLeUn(target) =>
+                    CILOp::BGeUn(*target),
                 CILOp::BEq(target)=>CILOp::BEq(*target),
                 CILOp::Eq=>CILOp::Eq,
                 CILOp::BNe(target)=>CILOp::BNe(*target),
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+use crate::IString;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+/// The location in the Rust source code some ops were generated from. Lines and columns are counted from 1, and the span ends right before `col_end`.
+/// Spans covering multiple lines are shortened to their first column.
+pub struct SourceSpan {
+    file: IString,
+    line: u32,
+    col_start: u32,
+    col_end: u32,
+}
+impl SourceSpan {
+    /// Creates a span covering columns `col_start..col_end` of line `line` of `file`.
+    #[must_use]
+    pub fn new(file: IString, line: u32, col_start: u32, col_end: u32) -> Self {
+        Self {
+            file,
+            line,
+            col_start,
+            col_end,
+        }
+    }
+    /// Returns the location of the rustc span `span`. Spans within macro expansions point to the outermost macro invocation.
+    #[must_use]
+    pub fn from_span(span: Span, tyctx: TyCtxt) -> Self {
+        let span = span.ctxt().outer_expn().expansion_cause().unwrap_or(span);
+        let source_map = tyctx.sess.source_map();
+        let start = source_map.lookup_char_pos(span.lo());
+        let end = source_map.lookup_char_pos(span.hi());
+        let col_start = start.col_display as u32 + 1;
+        let col_end = if end.line == start.line {
+            end.col_display as u32 + 1
+        } else {
+            col_start
+        };
+        Self {
+            file: start.file.name.prefer_remapped().to_string_lossy().into(),
+            line: start.line as u32,
+            col_start,
+            col_end,
+        }
+    }
+    /// Returns the path to the source file.
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+    /// Returns the line the span is on.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+    /// Returns the column the span starts at.
+    pub fn col_start(&self) -> u32 {
+        self.col_start
+    }
+    /// Returns the column right after the end of the span.
+    pub fn col_end(&self) -> u32 {
+        self.col_end
+    }
+}
+impl std::fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{file}:{line}:{col_start}-{col_end}",
+            file = self.file,
+            line = self.line,
+            col_start = self.col_start,
+            col_end = self.col_end
+        )
+    }
+}
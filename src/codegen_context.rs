@@ -0,0 +1,128 @@
+//! Tracks what the codegen is currently working on, so that errors raised deep inside of it can name the function, block and statement that caused them.
+//!
+//! The context is thread-local. [`enter_function`] sets the function being compiled, and [`set_block`] and [`set_statement`] update the location within it.
+//! [`install_panic_hook`] makes every panic message end with a description of the current context.
+use std::{cell::RefCell, sync::Once};
+/// The function, block and statement the codegen is currently compiling.
+#[derive(Debug, Clone, Default)]
+struct CodegenContext {
+    /// Symbol name of the function.
+    function: Option<String>,
+    /// The instance of the function, as printed by rustc.
+    instance: Option<String>,
+    /// Index of the current basic block.
+    block: Option<u32>,
+    /// Index of the current statement within the block, or `None` if the terminator is being compiled.
+    statement: Option<usize>,
+    /// The next label of the function not used yet, handed out by [`fresh_label`].
+    next_label: u32,
+}
+thread_local! {
+    static CONTEXT: RefCell<CodegenContext> = RefCell::new(CodegenContext::default());
+}
+/// Restores the previous context when dropped.
+#[must_use]
+pub struct FunctionGuard {
+    previous: CodegenContext,
+}
+impl Drop for FunctionGuard {
+    fn drop(&mut self) {
+        let previous = std::mem::take(&mut self.previous);
+        CONTEXT.with(|ctx| *ctx.borrow_mut() = previous);
+    }
+}
+/// Marks function `name`(instance `instance`) as the one being compiled, until the returned guard is dropped.
+pub fn enter_function(name: &str, instance: String) -> FunctionGuard {
+    let previous = CONTEXT.with(|ctx| {
+        ctx.replace(CodegenContext {
+            function: Some(name.into()),
+            instance: Some(instance),
+            block: None,
+            statement: None,
+            next_label: 0,
+        })
+    });
+    FunctionGuard { previous }
+}
+/// Marks basic block `block` of the current function as the one being compiled.
+pub fn set_block(block: u32) {
+    CONTEXT.with(|ctx| {
+        let mut ctx = ctx.borrow_mut();
+        ctx.block = Some(block);
+        ctx.statement = None;
+    });
+}
+/// Reserves labels `0..count` of the current function, which are used by its basic blocks. [`fresh_label`] hands out the labels after them.
+pub fn reserve_labels(count: u32) {
+    CONTEXT.with(|ctx| ctx.borrow_mut().next_label = count);
+}
+/// Returns a label not used anywhere else in the function being compiled. Used by lowerings which need control flow within a single terminator
+/// or statement(eg. binary searches of switches, or `CompareExchange` loops).
+pub fn fresh_label() -> u32 {
+    CONTEXT.with(|ctx| {
+        let mut ctx = ctx.borrow_mut();
+        assert!(
+            ctx.function.is_some(),
+            "Labels can only be allocated while compiling a function!"
+        );
+        let label = ctx.next_label;
+        ctx.next_label = label
+            .checked_add(1)
+            .expect("The function uses too many labels!");
+        label
+    })
+}
+/// Marks statement `statement` of the current block as the one being compiled. `None` marks the terminator of the block.
+pub fn set_statement(statement: Option<usize>) {
+    CONTEXT.with(|ctx| ctx.borrow_mut().statement = statement);
+}
+/// Returns a description of the current context(eg. `in `foo`(Instance { .. }), bb3, statement 2`), or `None` if no function is being compiled.
+pub fn describe() -> Option<String> {
+    CONTEXT.with(|ctx| {
+        let ctx = ctx.borrow();
+        let function = ctx.function.as_ref()?;
+        let mut desc = format!("in `{function}`");
+        if let Some(instance) = &ctx.instance {
+            desc.push_str(&format!("({instance})"));
+        }
+        if let Some(block) = ctx.block {
+            desc.push_str(&format!(", bb{block}"));
+            match ctx.statement {
+                Some(statement) => desc.push_str(&format!(", statement {statement}")),
+                None => desc.push_str(", terminator"),
+            }
+        }
+        Some(desc)
+    })
+}
+/// Should the statements of the current function be traced? Checks both [`trace_statements`](crate::config::BackendConfig::trace_statements)
+/// and the [`trace_filter`](crate::config::BackendConfig::trace_filter).
+pub fn trace_statements() -> bool {
+    let config = crate::config::config();
+    config.trace_statements
+        && CONTEXT.with(|ctx| {
+            ctx.borrow()
+                .function
+                .as_ref()
+                .map_or(true, |function| config.traced(function))
+        })
+}
+/// Installs a panic hook, which prints the current codegen context after the usual panic message. Can be called multiple times, but installs the hook only once.
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            default_hook(info);
+            // The context may already be borrowed, if the panic happened while it was being updated.
+            let desc = CONTEXT
+                .try_with(|ctx| ctx.try_borrow().is_ok())
+                .unwrap_or(false)
+                .then(describe)
+                .flatten();
+            if let Some(desc) = desc {
+                eprintln!("note: the codegen panicked {desc}");
+            }
+        }));
+    });
+}
@@ -1,4 +1,10 @@
+use rustc_middle::{
+    mir::{Terminator, TerminatorKind},
+    ty::TyCtxt,
+};
+use rustc_span::Span;
 use std::fmt::Debug;
+use std::sync::Mutex;
 
 #[derive(Debug)]
 /// Repersentation of an error which occured while converting MIR to CIL assembly.
@@ -19,6 +25,14 @@ impl From<MethodCodegenError> for CodegenError {
         Self::Method(value)
     }
 }
+impl std::fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error(msg) => write!(f, "{msg}"),
+            _ => write!(f, "{self:?}"),
+        }
+    }
+}
 impl CodegenError {
     fn report_error(&self) {
         eprintln!("Codegen faliled with error:{self:?}")
@@ -67,3 +81,133 @@ impl Debug for MethodCodegenError {
         )
     }
 }
+/// Category of a construct the codegen does not support yet. Each category has its own [`ErrorPolicy`](crate::config::ErrorPolicy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnsupportedKind {
+    Statement,
+    Terminator,
+    Intrinsic,
+    Type,
+    Static,
+}
+impl UnsupportedKind {
+    /// Returns the kind of `term`. Calls to intrinsics are classified separately, since they are the most common source of unsupported terminators.
+    pub fn of_terminator(term: &Terminator, tcx: TyCtxt) -> Self {
+        match &term.kind {
+            TerminatorKind::Call { func, .. }
+                if func
+                    .const_fn_def()
+                    .is_some_and(|(def_id, _)| tcx.is_intrinsic(def_id)) =>
+            {
+                Self::Intrinsic
+            }
+            _ => Self::Terminator,
+        }
+    }
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Statement => "statement",
+            Self::Terminator => "terminator",
+            Self::Intrinsic => "intrinsic",
+            Self::Type => "type",
+            Self::Static => "static",
+        }
+    }
+}
+/// A single occurrence of an unsupported feature, reported while compiling `function`.
+struct Unsupported {
+    kind: UnsupportedKind,
+    function: String,
+    location: String,
+    message: String,
+}
+/// All unsupported features encountered during codegen, summarized by [`report_unsupported_summary`].
+static UNSUPPORTED: Mutex<Vec<Unsupported>> = Mutex::new(Vec::new());
+/// Extracts the message from a panic payload, if it is a string.
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> Option<&str> {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        Some(msg)
+    } else {
+        payload.downcast_ref::<String>().map(|msg| msg.as_str())
+    }
+}
+/// Handles an unsupported construct at `span`, according to the [`ErrorPolicy`](crate::config::ErrorPolicy) of its `kind`.
+/// If the policy is `Abort`, compilation stops with an error. Otherwise, a warning is emitted, the construct is recorded for the final summary,
+/// and the caller is expected to replace it with code throwing an exception.
+pub fn report_unsupported(
+    tcx: TyCtxt,
+    span: Span,
+    kind: UnsupportedKind,
+    function: &str,
+    message: &str,
+) {
+    if crate::config::config().error_policy(kind) == crate::config::ErrorPolicy::Abort {
+        tcx.sess.span_fatal(
+            span,
+            format!(
+                "unsupported {kind} in `{function}`: {message}.",
+                kind = kind.as_str()
+            ),
+        );
+    }
+    tcx.sess.span_warn(
+        span,
+        format!(
+            "unsupported {kind} in `{function}`: {message}. It will throw an exception when executed.",
+            kind = kind.as_str()
+        ),
+    );
+    let location = tcx.sess.source_map().span_to_diagnostic_string(span);
+    UNSUPPORTED
+        .lock()
+        .expect("Unsupported feature list poisoned!")
+        .push(Unsupported {
+            kind,
+            function: function.into(),
+            location,
+            message: message.into(),
+        });
+}
+/// Returns the number of unsupported features encountered so far.
+pub fn unsupported_count() -> usize {
+    UNSUPPORTED
+        .lock()
+        .expect("Unsupported feature list poisoned!")
+        .len()
+}
+/// Prints a summary of all unsupported features encountered during codegen, grouped by their kind and message.
+pub fn report_unsupported_summary() {
+    let unsupported = UNSUPPORTED
+        .lock()
+        .expect("Unsupported feature list poisoned!");
+    if unsupported.is_empty() {
+        return;
+    }
+    let mut grouped: std::collections::BTreeMap<(UnsupportedKind, &str), Vec<&Unsupported>> =
+        std::collections::BTreeMap::new();
+    for occurrence in unsupported.iter() {
+        grouped
+            .entry((occurrence.kind, &occurrence.message))
+            .or_default()
+            .push(occurrence);
+    }
+    eprintln!(
+        "WARNING: {count} unsupported features were replaced with throwing placeholders:",
+        count = unsupported.len()
+    );
+    for ((kind, message), occurrences) in grouped {
+        eprintln!(
+            "  {count}x unsupported {kind}: {message}",
+            count = occurrences.len(),
+            kind = kind.as_str()
+        );
+        for occurrence in occurrences {
+            eprintln!(
+                "    in `{function}` at {location}",
+                function = occurrence.function,
+                location = occurrence.location
+            );
+        }
+    }
+}
@@ -59,6 +59,13 @@ fn test_dotnet_executable(file_path: &str, test_dir: &str) {
         "You must have either mono or dotnet runtime installed to run tests."
     );
 }
+/// Runs `rustc` with `args` inside `test_dir`, panicking if it reports any errors.
+#[cfg(test)]
+pub(crate) fn compile_checked(test_dir: &str, args: &[&str]) {
+    if let Err(err) = crate::differential::compile(std::path::Path::new(test_dir), args) {
+        panic!("{err}");
+    }
+}
 macro_rules! test_lib {
     ($test_name:ident) => {
         mod $test_name {
@@ -193,8 +200,9 @@ macro_rules! test_lib {
         }
     };
 }
+/// Compiles and runs `test/$prefix/$test_name.rs`. Any further arguments are passed to the backend as `-C llvm-args`(eg. `"--generic-types"`).
 macro_rules! run_test {
-    ($prefix:ident,$test_name:ident) => {
+    ($prefix:ident,$test_name:ident $(,$backend_arg:literal)*) => {
         mod $test_name {
             #[cfg(test)]
             use ntest::timeout;
@@ -227,6 +235,7 @@ macro_rules! run_test {
                         //"--target",
                         //"clr64-unknown-clr"
                     ])
+                    .args(&[$(concat!("-Cllvm-args=", $backend_arg)),*] as &[&str])
                     .output()
                     .expect("failed to execute process");
                 // If stderr is not empty, then something went wrong, so print the stdout and stderr for debuging.
@@ -270,6 +279,7 @@ macro_rules! run_test {
                         //"--target",
                         //"clr64-unknown-clr"
                     ])
+                    .args(&[$(concat!("-Cllvm-args=", $backend_arg)),*] as &[&str])
                     .output()
                     .expect("failed to execute process");
                 // If stderr is not empty, then something went wrong, so print the stdout and stderr for debuging.
@@ -528,7 +538,7 @@ fn absolute_backend_path() -> PathBuf {
     }
 }
 #[cfg(test)]
-fn backend_path() -> &'static str {
+pub(crate) fn backend_path() -> &'static str {
     if cfg!(debug_assertions) {
         backend_path_debug()
     } else {
@@ -579,7 +589,11 @@ run_test! {arthm,add}
 run_test! {types,tuple_structs}
 run_test! {arthm,mul}
 run_test! {arthm,sub}
+run_test! {arthm,cmp}
+run_test! {arthm,uint_to_float}
 run_test! {types,enums}
+run_test! {types,niches}
+run_test! {types,dyn_trait}
 run_test! {types,nbody}
 run_test! {types,structs}
 run_test! {types,interop}
@@ -590,9 +604,28 @@ run_test! {types,slice_ptr_cast}
 run_test! {types,slice_index_ref}
 run_test! {types,slice}
 run_test! {types,statics}
+run_test! {types,char}
+run_test! {types,packed}
+run_test! {types,unions}
+run_test! {types,volatile}
+run_test! {types,type_id}
+run_test! {types,atomics}
+run_test! {types,const_bytes}
+run_test! {types,const_refs}
+run_test! {types,fn_ptr_statics}
+run_test! {types,zst}
+run_test! {types,generic_zst,"--generic-types"}
 run_test! {std,main}
+run_test! {std,collections}
+run_test! {std,float_fmt}
+run_test! {std,int128_fmt}
+run_test! {std,unicode}
 run_test! {control_flow,cf_for}
 run_test! {control_flow,drop}
+run_test! {control_flow,uninhabited}
+run_test! {control_flow,caller_location}
+run_test! {control_flow,switch}
+run_test! {control_flow,fn_ptr_calls}
 cargo_test! {hello_world}
 cargo_test! {std_hello_world}
 cargo_test_ignored! {build_core}
@@ -603,7 +636,7 @@ cargo_test! {glam_test}
 cargo_test! {fastrand_test}
 use lazy_static::*;
 lazy_static! {
-    static ref RUNTIME_CONFIG: String = {
+    pub(crate) static ref RUNTIME_CONFIG: String = {
         let info = std::process::Command::new("dotnet")
             .arg("--info")
             .output()
@@ -634,11 +667,11 @@ lazy_static! {
           }}"
         )
     };
-    static ref IS_MONO_PRESENT: bool = std::process::Command::new("mono").output().is_ok();
+    pub(crate) static ref IS_MONO_PRESENT: bool = std::process::Command::new("mono").output().is_ok();
     static ref IS_PEVERIFY_PRESENT: bool = std::process::Command::new("peverify").output().is_ok();
-    static ref IS_DOTNET_PRESENT: bool = std::process::Command::new("dotnet").output().is_ok();
-    static ref RUSTC_BUILD_STATUS: Result<(), String> = build_backend();
-    static ref RUSTC_CODEGEN_CLR_LINKER:PathBuf = {
+    pub(crate) static ref IS_DOTNET_PRESENT: bool = std::process::Command::new("dotnet").output().is_ok();
+    pub(crate) static ref RUSTC_BUILD_STATUS: Result<(), String> = build_backend();
+    pub(crate) static ref RUSTC_CODEGEN_CLR_LINKER:PathBuf = {
         if cfg!(debug_assertions) {
             std::process::Command::new("cargo").args(["build","--bin","linker"]).output().unwrap();
             //TODO: Fix this for other platforms
@@ -0,0 +1,577 @@
+//! Configuration of the backend and linker.
+//!
+//! The config is built once, by applying, in order:
+//! 1. The defaults.
+//! 2. The TOML file at the path in `RCCLR_CONFIG`, or `rustc_codegen_clr.toml` in the current directory, if it exists.
+//! 3. Environment variables named `RCCLR_` followed by the uppercase option name, eg. `RCCLR_TRACE_CALLS=false`.
+//! 4. Options passed to the backend using `-C llvm-args=<option>=<value>`(backend only).
+//!
+//! All options are set using the same names, listed in [`BackendConfig::OPTIONS`].
+//! Options passed to the backend are more lenient, to fit in with other rustc flags: they may start with `-` or `--`, use `-` instead of `_`,
+//! and boolean options may be passed without a value to turn them on, or prefixed with `no-` to turn them off(eg. `-C llvm-args=--no-trace-calls`).
+//! Unknown options are reported as errors, with a suggestion of the closest known option.
+use crate::codegen_error::UnsupportedKind;
+use std::{path::PathBuf, sync::OnceLock};
+/// Prefix of the environment variables setting config options.
+pub const ENV_PREFIX: &str = "RCCLR_";
+/// Environment variable holding the path to the config file.
+pub const CONFIG_PATH_ENV: &str = "RCCLR_CONFIG";
+/// Name of the config file looked up in the current directory.
+pub const CONFIG_FILE_NAME: &str = "rustc_codegen_clr.toml";
+/// The .NET runtime the produced assemblies target. The differences between runtimes are described by their [`RuntimeProfile`](crate::runtime::RuntimeProfile).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    /// The .NET(Core) runtime.
+    CoreCLR,
+    /// The Mono runtime.
+    Mono,
+    /// The Mono runtime, with assemblies compiled ahead of time by the linker.
+    MonoAOT,
+    /// The Mono runtime, with assemblies fully compiled ahead of time by the linker, so that no JIT is needed.
+    MonoFullAOT,
+    /// The .NET WebAssembly runtime(`browser-wasm`).
+    Wasm,
+}
+impl Runtime {
+    /// Parses the name of a runtime.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "coreclr" | "dotnet" => Ok(Self::CoreCLR),
+            "mono" => Ok(Self::Mono),
+            "mono_aot" | "mono-aot" => Ok(Self::MonoAOT),
+            "mono_full_aot" | "mono-full-aot" => Ok(Self::MonoFullAOT),
+            "wasm" => Ok(Self::Wasm),
+            _ => Err(format!(
+                "Unknown runtime {value:?}. Expected `coreclr`, `mono`, `mono_aot`, `mono_full_aot` or `wasm`"
+            )),
+        }
+    }
+    /// Returns the profile describing the quirks of this runtime.
+    pub fn profile(self) -> &'static dyn crate::runtime::RuntimeProfile {
+        use crate::runtime::{CoreClrProfile, MonoProfile, WasmProfile};
+        match self {
+            Self::CoreCLR => &CoreClrProfile,
+            Self::Mono => &MonoProfile { aot: None },
+            Self::MonoAOT => &MonoProfile { aot: Some("--aot") },
+            Self::MonoFullAOT => &MonoProfile {
+                aot: Some("--aot=full"),
+            },
+            Self::Wasm => &WasmProfile,
+        }
+    }
+}
+/// The .NET target framework(TFM) of the produced assemblies. It decides which .NET APIs the codegen may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TargetFramework {
+    /// .NET Standard 2.1
+    NetStandard21,
+    /// .NET 7
+    Net7,
+    /// .NET 8
+    Net8,
+}
+impl TargetFramework {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "netstandard2.1" => Ok(Self::NetStandard21),
+            "net7.0" => Ok(Self::Net7),
+            "net8.0" => Ok(Self::Net8),
+            _ => Err(format!(
+                "Unknown target framework {value:?}. Expected `netstandard2.1`, `net7.0` or `net8.0`"
+            )),
+        }
+    }
+    /// Returns the TFM of this target framework, eg. `net8.0`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::NetStandard21 => "netstandard2.1",
+            Self::Net7 => "net7.0",
+            Self::Net8 => "net8.0",
+        }
+    }
+    /// Returns true if executables may target this framework. .NET Standard is only an API surface for libraries, so `dotnet` can't run
+    /// assemblies targeting it.
+    pub fn is_runnable(self) -> bool {
+        !matches!(self, Self::NetStandard21)
+    }
+}
+/// .NET APIs used by the codegen, which are not available on all target frameworks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DotnetApi {
+    /// `System.Int128` and `System.UInt128`, used to represent 128 bit integers.
+    Int128,
+    /// `System.Runtime.InteropServices.NativeMemory`, used for aligned allocations.
+    NativeMemory,
+    /// `System.Text.Rune`, the .NET equivalent of `char`.
+    Rune,
+}
+impl DotnetApi {
+    /// Returns the oldest target framework providing this API.
+    pub fn min_framework(self) -> TargetFramework {
+        match self {
+            Self::Int128 | Self::NativeMemory | Self::Rune => TargetFramework::Net7,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Self::Int128 => "System.Int128",
+            Self::NativeMemory => "System.Runtime.InteropServices.NativeMemory",
+            Self::Rune => "System.Text.Rune",
+        }
+    }
+}
+/// What the codegen does when it encounters something it does not support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop the compilation with an error.
+    Abort,
+    /// Emit a warning, and replace the unsupported code with a stub throwing an exception.
+    Stub,
+}
+impl ErrorPolicy {
+    fn parse(key: &str, value: &str) -> Result<Self, String> {
+        match value {
+            "abort" => Ok(Self::Abort),
+            "stub" => Ok(Self::Stub),
+            _ => Err(format!(
+                "Option {key} expects `abort` or `stub`, not {value:?}"
+            )),
+        }
+    }
+}
+/// All the settings of the backend and linker.
+#[derive(Debug, Clone)]
+pub struct BackendConfig {
+    // Error handling
+    /// Policy for unsupported intrinsics.
+    pub intrinsic_errors: ErrorPolicy,
+    /// Policy for unsupported terminators, other than intrinsic calls.
+    pub terminator_errors: ErrorPolicy,
+    /// Policy for unsupported statements.
+    pub statement_errors: ErrorPolicy,
+    /// Policy for types which can't be converted, eg. in function signatures. Functions with such types are skipped.
+    pub type_errors: ErrorPolicy,
+    /// Policy for statics whose initializers can't be evaluated. Such statics are skipped.
+    pub static_errors: ErrorPolicy,
+    /// Should the codegen continue working after it encoutnered a miscompilation?
+    pub allow_miscompilations: bool,
+    // Tracing
+    /// Preapends each function call with a debug message
+    pub trace_calls: bool,
+    /// Preapends each statement with a debug message
+    pub trace_statements: bool,
+    /// Name filter. If set, calls and statements are only traced in functions with names containing the filter.
+    pub trace_filter: Option<String>,
+    /// Tells the codegen to insert comments containing the MIR statemtens after each one of them.
+    pub insert_mir_debug_comments: bool,
+    /// Tells the codegen to mark the ops of each MIR statement and terminator with the location of the Rust code they come from.
+    pub source_spans: bool,
+    /// Prints local types of all compiled MIR functions.
+    pub print_local_types: bool,
+    /// Prints debug info during type handling.
+    pub print_ty_convertion: bool,
+    /// Name filter. CIL of all functions with names containing the filter is dumped during codegen.
+    pub dump_il: Option<String>,
+    /// Name filter. The control flow graphs of all functions with names containing the filter are dumped as Graphviz DOT files during codegen.
+    pub dump_cfg: Option<String>,
+    /// Directory CIL and control flow graphs are dumped to.
+    pub dump_il_dir: PathBuf,
+    /// If set, no code is emitted, and a JSON feature-coverage report of each crate is written to this directory instead.
+    pub coverage_dir: Option<PathBuf>,
+    /// Print the time spent in each phase of the codegen, and its peak memory usage.
+    pub timings: bool,
+    /// If set, the serialized assembly of each crate is copied to this directory, to be used as a benchmark corpus.
+    pub capture_dir: Option<PathBuf>,
+    // Optimizations
+    /// Tells the codegen to optmize the emiited CIL. Optimizations are always disabled when tracing statements or inserting MIR comments.
+    pub optimize: bool,
+    /// Turns on the struct spliting optimzation.
+    pub split_local_structs: bool,
+    /// Turns on the local removal optimization.
+    pub remove_unused_locals: bool,
+    /// Allows the optimizer to inline very simple functions. It is buggy.
+    pub inline_simple_functions: bool,
+    /// Reuse methods lowered by previous builds, when rustc runs in incremental mode. Described in [`crate::incremental`].
+    pub incremental_cache: bool,
+    /// Write each method to the output file as soon as it is lowered, instead of keeping all of them in memory until the end of codegen.
+    /// Bounds the memory usage when compiling large crates, but methods can then only be inlined within their codegen unit. Described in [`crate::spill`].
+    pub spill_methods: bool,
+    /// Lowers generic structs to CLR generic types, instead of emitting a separate type for each of their instances. Only used for structs whose
+    /// fields can be expressed using CLR generics, and for instances whose generic arguments are valid CLR generic arguments(eg. not pointers).
+    pub generic_types: bool,
+    // Runtime target
+    /// The runtime the produced assemblies target.
+    pub runtime: Runtime,
+    /// The target framework of the produced assemblies.
+    pub tfm: TargetFramework,
+    /// Changes `.locals` into `.locals init`. Causes the runtime to always initialize local variables.
+    /// Try turining on in cause of issues. If it fixes them, then their root cause is UB(eg. use of uninitailized memory).
+    pub always_init_locals: bool,
+    // Shim policies
+    /// Turns `assume` intrinsics into checks, which throw an exception if the assumption does not hold. Try turning on if UB is suspected.
+    pub check_assumptions: bool,
+    /// Makes the linker replace calls to missing methods with stubs throwing an exception, instead of failing.
+    pub patch_missing_methods: bool,
+    /// Makes the linker fold methods with identical bodies into one. Described in [`crate::icf`].
+    pub fold_identical_methods: bool,
+    /// Makes the linker turn self-recursive calls in return position into tail calls, so that deep recursion does not overflow the stack.
+    /// Described in [`crate::tail_call`].
+    pub tail_calls: bool,
+    /// Makes the linker verify the evaluation stack of all methods before export, reporting invalid IL together with the method and op causing it.
+    /// Described in [`crate::verify`].
+    pub verify_il: bool,
+}
+impl Default for BackendConfig {
+    fn default() -> Self {
+        Self {
+            intrinsic_errors: ErrorPolicy::Stub,
+            terminator_errors: ErrorPolicy::Stub,
+            statement_errors: ErrorPolicy::Stub,
+            type_errors: ErrorPolicy::Stub,
+            static_errors: ErrorPolicy::Stub,
+            allow_miscompilations: true,
+            trace_calls: true,
+            trace_statements: false,
+            trace_filter: None,
+            insert_mir_debug_comments: false,
+            source_spans: false,
+            print_local_types: false,
+            print_ty_convertion: false,
+            dump_il: None,
+            dump_cfg: None,
+            dump_il_dir: "il_dump".into(),
+            coverage_dir: None,
+            timings: false,
+            capture_dir: None,
+            optimize: true,
+            split_local_structs: false,
+            remove_unused_locals: false,
+            inline_simple_functions: false,
+            incremental_cache: true,
+            spill_methods: false,
+            generic_types: false,
+            runtime: Runtime::CoreCLR,
+            tfm: TargetFramework::Net7,
+            always_init_locals: false,
+            check_assumptions: false,
+            patch_missing_methods: true,
+            fold_identical_methods: true,
+            tail_calls: false,
+            verify_il: false,
+        }
+    }
+}
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        // Flags passed without a value(eg. `RCCLR_TIMINGS=`) are treated as turned on.
+        "" | "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("Option {key} expects a boolean, not {value:?}")),
+    }
+}
+fn parse_filter(value: &str) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.into())
+    }
+}
+/// Returns the known option closest to the unknown option `key`, if any is close enough to be a likely typo.
+fn suggest_option(key: &str) -> Option<&'static str> {
+    BackendConfig::OPTIONS
+        .iter()
+        .map(|option| (edit_distance(key, option), *option))
+        .filter(|(distance, _)| *distance <= (key.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, option)| option)
+}
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(a_char != *b_char);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+fn parse_path(value: &str) -> Option<PathBuf> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.into())
+    }
+}
+impl BackendConfig {
+    /// Names of all the options which can be set.
+    pub const OPTIONS: &'static [&'static str] = &[
+        // Sets the policy of all error categories at once, so it must be applied before the per-category options.
+        "abort_on_error",
+        "intrinsic_errors",
+        "terminator_errors",
+        "statement_errors",
+        "type_errors",
+        "static_errors",
+        "allow_miscompilations",
+        "trace_calls",
+        "trace_statements",
+        "trace_filter",
+        // Shorthand for enabling the dumps and debug comments, applied before the options it sets.
+        "emit",
+        "insert_mir_debug_comments",
+        "source_spans",
+        "print_local_types",
+        "print_ty_convertion",
+        "dump_il",
+        "dump_cfg",
+        "dump_il_dir",
+        "coverage_dir",
+        "timings",
+        "capture_dir",
+        "optimize",
+        "split_local_structs",
+        "remove_unused_locals",
+        "inline_simple_functions",
+        "incremental_cache",
+        "spill_methods",
+        "generic_types",
+        "runtime",
+        "tfm",
+        "always_init_locals",
+        "check_assumptions",
+        "patch_missing_methods",
+        "fold_identical_methods",
+        "tail_calls",
+        "verify_il",
+    ];
+    /// Sets option `key` to `value`. Returns an error if the option does not exist, or the value is invalid.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "abort_on_error" => {
+                let policy = if parse_bool(key, value)? {
+                    ErrorPolicy::Abort
+                } else {
+                    ErrorPolicy::Stub
+                };
+                self.intrinsic_errors = policy;
+                self.terminator_errors = policy;
+                self.statement_errors = policy;
+                self.type_errors = policy;
+                self.static_errors = policy;
+            }
+            "intrinsic_errors" => self.intrinsic_errors = ErrorPolicy::parse(key, value)?,
+            "terminator_errors" => self.terminator_errors = ErrorPolicy::parse(key, value)?,
+            "statement_errors" => self.statement_errors = ErrorPolicy::parse(key, value)?,
+            "type_errors" => self.type_errors = ErrorPolicy::parse(key, value)?,
+            "static_errors" => self.static_errors = ErrorPolicy::parse(key, value)?,
+            "allow_miscompilations" => self.allow_miscompilations = parse_bool(key, value)?,
+            "trace_calls" => self.trace_calls = parse_bool(key, value)?,
+            "trace_statements" => self.trace_statements = parse_bool(key, value)?,
+            "trace_filter" => self.trace_filter = parse_filter(value),
+            "emit" => {
+                for kind in value.split(',') {
+                    match kind {
+                        "il" => self.dump_il = Some(String::new()),
+                        "cfg" => self.dump_cfg = Some(String::new()),
+                        "mir_comments" | "mir-comments" => self.insert_mir_debug_comments = true,
+                        "spans" => self.source_spans = true,
+                        _ => {
+                            return Err(format!(
+                                "Option {key} expects a comma-separated list of `il`, `cfg`, `mir-comments` and `spans`, not {kind:?}"
+                            ))
+                        }
+                    }
+                }
+            }
+            "insert_mir_debug_comments" => {
+                self.insert_mir_debug_comments = parse_bool(key, value)?;
+            }
+            "source_spans" => self.source_spans = parse_bool(key, value)?,
+            "print_local_types" => self.print_local_types = parse_bool(key, value)?,
+            "print_ty_convertion" => self.print_ty_convertion = parse_bool(key, value)?,
+            "dump_il" => self.dump_il = Some(value.into()),
+            "dump_cfg" => self.dump_cfg = Some(value.into()),
+            "dump_il_dir" => self.dump_il_dir = value.into(),
+            "coverage_dir" => self.coverage_dir = parse_path(value),
+            "timings" => self.timings = parse_bool(key, value)?,
+            "capture_dir" => self.capture_dir = parse_path(value),
+            "optimize" => self.optimize = parse_bool(key, value)?,
+            "split_local_structs" => self.split_local_structs = parse_bool(key, value)?,
+            "remove_unused_locals" => self.remove_unused_locals = parse_bool(key, value)?,
+            "inline_simple_functions" => self.inline_simple_functions = parse_bool(key, value)?,
+            "incremental_cache" => self.incremental_cache = parse_bool(key, value)?,
+            "spill_methods" => self.spill_methods = parse_bool(key, value)?,
+            "generic_types" => self.generic_types = parse_bool(key, value)?,
+            "runtime" => self.runtime = Runtime::parse(value)?,
+            "tfm" => self.tfm = TargetFramework::parse(value)?,
+            "always_init_locals" => self.always_init_locals = parse_bool(key, value)?,
+            "check_assumptions" => self.check_assumptions = parse_bool(key, value)?,
+            "patch_missing_methods" => self.patch_missing_methods = parse_bool(key, value)?,
+            "fold_identical_methods" => self.fold_identical_methods = parse_bool(key, value)?,
+            "tail_calls" => self.tail_calls = parse_bool(key, value)?,
+            "verify_il" => self.verify_il = parse_bool(key, value)?,
+            _ => {
+                return Err(match suggest_option(key) {
+                    Some(suggestion) => {
+                        format!("Unknown option {key:?}. Did you mean {suggestion:?}?")
+                    }
+                    None => format!("Unknown option {key:?}"),
+                })
+            }
+        }
+        Ok(())
+    }
+    /// Returns the policy for errors of `kind`.
+    pub fn error_policy(&self, kind: UnsupportedKind) -> ErrorPolicy {
+        match kind {
+            UnsupportedKind::Intrinsic => self.intrinsic_errors,
+            UnsupportedKind::Terminator => self.terminator_errors,
+            UnsupportedKind::Statement => self.statement_errors,
+            UnsupportedKind::Type => self.type_errors,
+            UnsupportedKind::Static => self.static_errors,
+        }
+    }
+    /// Returns true if `api` is available on the target framework and runtime.
+    pub fn supports(&self, api: DotnetApi) -> bool {
+        self.tfm >= api.min_framework() && self.runtime.profile().supports(api)
+    }
+    /// Panics with an explanation if `api` is not available on the target framework or runtime.
+    /// Used by code which has no fallback for `api`.
+    pub fn require(&self, api: DotnetApi) {
+        assert!(
+            self.tfm >= api.min_framework(),
+            "{api} is not available on target framework {tfm}. It requires {min} or newer. Select a newer framework using the `tfm` option.",
+            api = api.name(),
+            tfm = self.tfm.name(),
+            min = api.min_framework().name()
+        );
+        assert!(
+            self.runtime.profile().supports(api),
+            "{api} is not supported by the {runtime} runtime.",
+            api = api.name(),
+            runtime = self.runtime.profile().name()
+        );
+    }
+    /// Should the emitted CIL be optimized?
+    pub fn optimize_cil(&self) -> bool {
+        self.optimize && !self.trace_statements && !self.insert_mir_debug_comments
+    }
+    /// Applies all options set in the TOML config `file`.
+    pub fn apply_toml(&mut self, file: &str) -> Result<(), String> {
+        let table: toml::Table = file.parse().map_err(|err| format!("{err}"))?;
+        for (key, value) in table {
+            let value = match value {
+                toml::Value::String(string) => string,
+                toml::Value::Boolean(boolean) => boolean.to_string(),
+                other => return Err(format!("Option {key} has unsupported value {other}")),
+            };
+            self.set(&key, &value)?;
+        }
+        Ok(())
+    }
+    /// Applies all options set using environment variables.
+    pub fn apply_env(&mut self) -> Result<(), String> {
+        for key in Self::OPTIONS {
+            let var = format!("{ENV_PREFIX}{}", key.to_uppercase());
+            if let Ok(value) = std::env::var(&var) {
+                self.set(key, &value)
+                    .map_err(|err| format!("{var}: {err}"))?;
+            }
+        }
+        Ok(())
+    }
+    /// Builds the config out of the defaults, the config file and environment variables.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        let path = std::env::var(CONFIG_PATH_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| CONFIG_FILE_NAME.into());
+        if let Ok(file) = std::fs::read_to_string(&path) {
+            if let Err(err) = config.apply_toml(&file) {
+                eprintln!(
+                    "WARNING: invalid config file {path}: {err}",
+                    path = path.display()
+                );
+            }
+        }
+        if let Err(err) = config.apply_env() {
+            eprintln!("WARNING: invalid config option {err}");
+        }
+        config
+    }
+    /// Applies a single option passed to the backend(eg. `--trace-filter=main`, `--no-optimize` or `dump_il=foo`).
+    pub fn apply_arg(&mut self, arg: &str) -> Result<(), String> {
+        let (key, value) = arg.split_once('=').unwrap_or((arg, ""));
+        let key = key.trim_start_matches('-').replace('-', "_");
+        if !Self::OPTIONS.contains(&key.as_str()) {
+            if let Some(negated) = key.strip_prefix("no_") {
+                if Self::OPTIONS.contains(&negated) && value.is_empty() {
+                    return self.set(negated, "false");
+                }
+            }
+        }
+        self.set(&key, value)
+    }
+    /// Builds the config out of the defaults, the config file, environment variables and options passed to the backend using `-C llvm-args`.
+    /// Returns the config, and a description of each invalid option passed to the backend. Invalid options are ignored.
+    pub fn from_args(args: &[String]) -> (Self, Vec<String>) {
+        let mut config = Self::from_env();
+        let errors = args
+            .iter()
+            .filter_map(|arg| {
+                config
+                    .apply_arg(arg)
+                    .err()
+                    .map(|err| format!("invalid backend option {arg:?}: {err}"))
+            })
+            .collect();
+        (config, errors)
+    }
+    /// Should calls and statements within function `name` be traced(if tracing is enabled)?
+    pub fn traced(&self, name: &str) -> bool {
+        self.trace_filter
+            .as_ref()
+            .map_or(true, |filter| name.contains(filter.as_str()))
+    }
+}
+static CONFIG: OnceLock<BackendConfig> = OnceLock::new();
+/// Sets the config used by the backend. Must be called before the config is first used, and can only be called once.
+pub fn init(config: BackendConfig) {
+    CONFIG
+        .set(config)
+        .expect("The backend config was already initialized!");
+}
+/// Returns the config of the backend. If it was not initialized, it is built out of the config file and environment variables.
+pub fn config() -> &'static BackendConfig {
+    CONFIG.get_or_init(BackendConfig::from_env)
+}
+#[test]
+fn backend_args() {
+    let mut config = BackendConfig::default();
+    config.apply_arg("--trace-filter=main").unwrap();
+    assert_eq!(config.trace_filter.as_deref(), Some("main"));
+    config.apply_arg("--no-trace-calls").unwrap();
+    assert!(!config.trace_calls);
+    config.apply_arg("-trace-statements").unwrap();
+    assert!(config.trace_statements);
+    config.apply_arg("runtime=mono").unwrap();
+    assert_eq!(config.runtime, Runtime::Mono);
+    config.apply_arg("--emit=il,cfg").unwrap();
+    assert_eq!(config.dump_il.as_deref(), Some(""));
+    assert_eq!(config.dump_cfg.as_deref(), Some(""));
+    assert!(config.apply_arg("--emit=llvm-ir").is_err());
+    // Negation only applies to options passed without a value.
+    assert!(config.apply_arg("--no-trace-calls=true").is_err());
+}
+#[test]
+fn unknown_option_suggestions() {
+    let mut config = BackendConfig::default();
+    let err = config.apply_arg("--trace-cals").unwrap_err();
+    assert!(err.contains("Did you mean \"trace_calls\"?"), "{err}");
+    let err = config.apply_arg("--frobnicate").unwrap_err();
+    assert!(!err.contains("Did you mean"), "{err}");
+}
@@ -4,7 +4,7 @@ use crate::{
 };
 use rustc_abi::Size;
 use rustc_middle::mir::{
-    interpret::{AllocId, AllocRange, GlobalAlloc, Scalar},
+    interpret::{read_target_uint, write_target_uint, AllocId, AllocRange, GlobalAlloc, Scalar},
     ConstOperand, ConstValue,
 };
 use rustc_middle::ty::{
@@ -43,15 +43,23 @@ fn create_const_adt_from_bytes<'ctx>(
 ) -> Vec<CILOp> {
     match adt_def.adt_kind() {
         AdtKind::Struct => {
-            let mut curr_offset = 0;
-            let cil_ty = crate::utilis::monomorphize(&method_instance, ty, tyctx);
-            let cil_ty = tycache.type_from_cache(cil_ty, tyctx, Some(method_instance));
+            let ty = crate::utilis::monomorphize(&method_instance, ty, tyctx);
+            // Fields may be reordered and padded, so their offsets must come from the layout.
+            let layout = tyctx
+                .layout_of(ParamEnv::reveal_all().and(ty))
+                .expect("Could not get the layout of a const struct!");
+            let cil_ty = tycache.type_from_cache(ty, tyctx, Some(method_instance));
             let dotnet_ty = cil_ty.as_dotnet().expect("ADT must be a value type!");
             let mut creator_ops = vec![CILOp::NewTMPLocal(cil_ty.clone().into())];
-            for field in adt_def.all_fields() {
+            for (field_idx, field) in adt_def.all_fields().enumerate() {
                 let ftype = field.ty(tyctx, subst);
-                let sizeof = crate::utilis::compiletime_sizeof(ftype, tyctx);
-                let field_bytes = &bytes[curr_offset..(curr_offset + sizeof)];
+                // ZST fields are not stored.
+                if crate::utilis::is_zst(ftype, tyctx) {
+                    continue;
+                }
+                let offset = layout.fields.offset(field_idx).bytes() as usize;
+                let sizeof = const_sizeof(ftype, tyctx);
+                let field_bytes = &bytes[offset..(offset + sizeof)];
                 let field_ops =
                     create_const_from_slice(ftype, tyctx, field_bytes, method_instance, tycache);
                 creator_ops.push(CILOp::LoadAddresOfTMPLocal);
@@ -60,6 +68,7 @@ fn create_const_adt_from_bytes<'ctx>(
                     tycache.type_from_cache(field.ty(tyctx, subst), tyctx, Some(method_instance));
                 let name = field.name.to_string();
                 let name = crate::r#type::escape_field_name(&name);
+                let cil_ftype = tycache.declared_field_type(&dotnet_ty, &name, cil_ftype);
                 creator_ops.push(CILOp::STField(crate::cil::FieldDescriptor::boxed(
                     dotnet_ty.clone(),
                     cil_ftype,
@@ -69,58 +78,108 @@ fn create_const_adt_from_bytes<'ctx>(
                     "Const field {name} of type {ftype} with bytes {field_bytes:?}",
                     name = field.name
                 )};
-                // Increment the offset.
-                curr_offset += sizeof;
             }
             creator_ops.push(CILOp::LoadTMPLocal);
             creator_ops.push(CILOp::FreeTMPLocal);
             creator_ops
         }
         AdtKind::Enum => {
-            let variant_size = crate::utilis::enum_tag_size(adt_def.variants().len() as u64);
-            // This will need to be mutable in order to handle enum fields.
-            #[allow(unused_mut)]
-            let mut curr_offset = variant_size;
-            let enum_ty = crate::utilis::monomorphize(&method_instance, ty, tyctx);
-            let enum_ty = tycache.type_from_cache(enum_ty, tyctx, Some(method_instance));
-            let enum_dotnet: DotnetTypeRef = if let Type::DotnetType(ty_ref) = &enum_ty {
-                ty_ref.as_ref().clone()
-            } else {
+            let ty = crate::utilis::monomorphize(&method_instance, ty, tyctx);
+            // The tag and fields are placed where rustc placed them, so the layout tells where to read them from.
+            let layout = crate::discriminant::enum_layout(ty, tyctx);
+            let variant_idx =
+                crate::discriminant::variant_from_bytes(*adt_def, &layout, bytes, tyctx);
+            let offsets = crate::discriminant::variant_field_offsets(&layout, variant_idx)
+                .expect("The active variant of a const enum is not a part of its layout!");
+            let enum_ty = tycache.type_from_cache(ty, tyctx, Some(method_instance));
+            let Some(enum_dotnet) = enum_ty.as_dotnet() else {
                 panic!("Invalid enum type {enum_ty:?}");
             };
+            let variant = &adt_def.variants()[variant_idx];
+            let mut variant_dotnet = enum_dotnet.clone();
+            variant_dotnet.append_path(&format!("/{}", variant.name));
+            let variant_field = FieldDescriptor::boxed(
+                enum_dotnet,
+                variant_dotnet.clone().into(),
+                format!("v_{}", variant.name).into(),
+            );
             let mut ops = vec![CILOp::NewTMPLocal(enum_ty.into())];
-            let curr_variant = match variant_size {
-                0 => todo!("Can't yet handle constant enums with 0-sized tags."),
-                1 => {
-                    //curr_offset = 1;
-                    let variant = bytes[0] as u32;
-                    ops.extend([
-                        CILOp::LoadAddresOfTMPLocal,
-                        CILOp::LdcI32(variant as i32),
-                        CILOp::STField(FieldDescriptor::boxed(
-                            enum_dotnet.clone(),
-                            Type::U8,
-                            "_tag".into(),
-                        )),
-                    ]);
-                    variant
+            for (field, offset) in variant.fields.iter().zip(offsets) {
+                let ftype = field.ty(tyctx, subst);
+                if crate::utilis::is_zst(ftype, tyctx) {
+                    continue;
                 }
-                _ => todo!("Can't yet support enums with {variant_size} wide tags."),
-            };
-            assert!(curr_variant < adt_def.variants().len() as u32);
-            let active_variant = &adt_def.variants()[curr_variant.into()];
-            for _field in &active_variant.fields {
-                // Use offset to get the right bytes
-                let _ = curr_offset;
-                todo!("Can't yet create const enum fields.");
+                let offset = offset as usize;
+                let sizeof = const_sizeof(ftype, tyctx);
+                let field_ops = create_const_from_slice(
+                    ftype,
+                    tyctx,
+                    &bytes[offset..(offset + sizeof)],
+                    method_instance,
+                    tycache,
+                );
+                ops.extend([
+                    CILOp::LoadAddresOfTMPLocal,
+                    CILOp::LDFieldAdress(variant_field.clone()),
+                ]);
+                ops.extend(field_ops);
+                let cil_ftype = tycache.type_from_cache(ftype, tyctx, Some(method_instance));
+                let name = crate::r#type::escape_field_name(&field.name.to_string());
+                ops.push(CILOp::STField(FieldDescriptor::boxed(
+                    variant_dotnet.clone(),
+                    cil_ftype,
+                    name,
+                )));
             }
-            ops.push(CILOp::FreeTMPLocal);
-            //todo!("Can't load const enum from bytes {bytes:?}!");
+            ops.extend(crate::discriminant::set_discr(
+                vec![CILOp::LoadAddresOfTMPLocal],
+                ty,
+                variant_idx,
+                tyctx,
+                method_instance,
+                tycache,
+            ));
+            ops.extend([CILOp::LoadTMPLocal, CILOp::FreeTMPLocal]);
+            ops
+        }
+        // Which field of a union is active is not known, so its bytes are copied one by one.
+        AdtKind::Union => {
+            let ty = crate::utilis::monomorphize(&method_instance, ty, tyctx);
+            let layout = tyctx
+                .layout_of(ParamEnv::reveal_all().and(ty))
+                .expect("Could not get the layout of a const union!");
+            let cil_ty = tycache.type_from_cache(ty, tyctx, Some(method_instance));
+            let size = layout.size.bytes() as usize;
+            let mut ops = vec![CILOp::NewTMPLocal(cil_ty.into())];
+            for (offset, byte) in bytes[..size].iter().enumerate() {
+                ops.extend([
+                    CILOp::LoadAddresOfTMPLocal,
+                    CILOp::LdcI32(offset as i32),
+                    CILOp::Add,
+                    CILOp::LdcI32(i32::from(*byte)),
+                    CILOp::STIndI8,
+                ]);
+            }
+            ops.extend([CILOp::LoadTMPLocal, CILOp::FreeTMPLocal]);
             ops
         }
-        AdtKind::Union => todo!("Can't load const union from bytes {bytes:?}!"),
     }
 }
+/// Returns the size of `ty` inside a const allocation, including any padding(so nested ADTs are sliced with their real size).
+fn const_sizeof<'ctx>(ty: Ty<'ctx>, tyctx: TyCtxt<'ctx>) -> usize {
+    tyctx
+        .layout_of(ParamEnv::reveal_all().and(ty))
+        .expect("Could not get the layout of a const field!")
+        .size
+        .bytes() as usize
+}
+/// Reads the value of the scalar of type `ty` from the start of `bytes`, using the byte order of the target.
+/// All multi-byte scalars inside const allocations should be read using this function, and never with host-endian assumptions.
+fn read_const_scalar<'ctx>(ty: Ty<'ctx>, tyctx: TyCtxt<'ctx>, bytes: &[u8]) -> u128 {
+    let size = crate::utilis::compiletime_sizeof(ty, tyctx);
+    read_target_uint(tyctx.data_layout.endian, &bytes[..size])
+        .expect("Could not read a scalar from a const allocation!")
+}
 /// Returns the ops neceasry to create constant value of type `ty` with byte values matching the ones in the slice bytes
 fn create_const_from_slice<'ctx>(
     ty: Ty<'ctx>,
@@ -130,70 +189,13 @@ fn create_const_from_slice<'ctx>(
     tycache: &mut TyCache,
 ) -> Vec<CILOp> {
     let ty = crate::utilis::monomorphize(&method_instance, ty, tyctx);
-    // TODO: Read up on the order of bytes inside a const allocation and ensure it is correct. All .NET target will be Little Enidian, but if we want to support
-    // big enidian targets in the future, this will need to be revised.
     match ty.kind() {
         TyKind::Adt(adt_def, subst) => {
             create_const_adt_from_bytes(ty, *adt_def, subst, tyctx, bytes, method_instance, tycache)
         }
-        TyKind::Int(int) => match int {
-            IntTy::I8 => vec![
-                CILOp::LdcI32(i8::from_le_bytes(
-                    bytes[..std::mem::size_of::<i8>()].try_into().unwrap(),
-                ) as i32),
-                CILOp::ConvI8(false),
-            ],
-            IntTy::I16 => vec![
-                CILOp::LdcI32(i16::from_le_bytes(
-                    bytes[..std::mem::size_of::<i16>()].try_into().unwrap(),
-                ) as i32),
-                CILOp::ConvI16(false),
-            ],
-            IntTy::I32 => vec![CILOp::LdcI32(i32::from_le_bytes(
-                bytes[..std::mem::size_of::<i32>()].try_into().unwrap(),
-            ))],
-            IntTy::I64 => vec![CILOp::LdcI64(i64::from_le_bytes(
-                bytes[..std::mem::size_of::<i64>()].try_into().unwrap(),
-            ))],
-            IntTy::Isize => vec![
-                CILOp::LdcI64(i64::from_le_bytes(
-                    bytes[..crate::utilis::compiletime_sizeof(ty, tyctx)]
-                        .try_into()
-                        .unwrap(),
-                )),
-                CILOp::ConvUSize(false),
-            ],
-            _ => todo!("Can't yet load const value of type {int:?} with bytes:{bytes:?}"),
-        },
-        TyKind::Uint(int) => match int {
-            UintTy::U8 => vec![
-                CILOp::LdcI32(i8::from_le_bytes(
-                    bytes[..std::mem::size_of::<i8>()].try_into().unwrap(),
-                ) as i32),
-                CILOp::ConvU8(false),
-            ],
-            UintTy::U16 => vec![
-                CILOp::LdcI32(i16::from_le_bytes(
-                    bytes[..std::mem::size_of::<i16>()].try_into().unwrap(),
-                ) as i32),
-                CILOp::ConvU16(false),
-            ],
-            UintTy::U32 => vec![CILOp::LdcI32(i32::from_le_bytes(
-                bytes[..std::mem::size_of::<i32>()].try_into().unwrap(),
-            ))],
-            UintTy::U64 => vec![CILOp::LdcI64(i64::from_le_bytes(
-                bytes[..std::mem::size_of::<i64>()].try_into().unwrap(),
-            ))],
-            UintTy::Usize => vec![
-                CILOp::LdcI64(i64::from_le_bytes(
-                    bytes[..crate::utilis::compiletime_sizeof(ty, tyctx)]
-                        .try_into()
-                        .unwrap(),
-                )),
-                CILOp::ConvUSize(false),
-            ],
-            _ => todo!("Can't yet load const value of type {int:?} with bytes:{bytes:?}"),
-        },
+        TyKind::Int(int) => load_const_int(read_const_scalar(ty, tyctx, bytes), int),
+        TyKind::Uint(uint) => load_const_uint(read_const_scalar(ty, tyctx, bytes), uint),
+        TyKind::Float(float) => load_const_float(read_const_scalar(ty, tyctx, bytes), float, tyctx),
         TyKind::RawPtr(type_and_mut) => match type_and_mut.ty.kind() {
             TyKind::Slice(_) => {
                 todo!("Can't load const slices.")
@@ -201,14 +203,12 @@ fn create_const_from_slice<'ctx>(
             TyKind::Str => {
                 todo!("Can't load const string slices.")
             }
-            _ => {
-                eprintln!("WARNING: assuming sizeof<*T>() == 8!");
-                vec![CILOp::LdcI64(i64::from_le_bytes(
-                    bytes[..std::mem::size_of::<i64>()].try_into().unwrap(),
-                ))]
-            }
+            _ => load_const_uint(read_const_scalar(ty, tyctx, bytes), &UintTy::Usize),
         },
         TyKind::Bool => vec![CILOp::LdcI32(bytes[0] as i32)],
+        TyKind::Char => vec![CILOp::LdcI32(
+            read_const_scalar(ty, tyctx, bytes) as u32 as i32
+        )],
         TyKind::Tuple(elements) => {
             assert!(
                 elements.len() < 8,
@@ -224,12 +224,16 @@ fn create_const_from_slice<'ctx>(
             let tuple_dotnet = crate::r#type::simple_tuple(&element_types);
             let tuple_type: Type = tuple_dotnet.clone().into();
             let mut ops = vec![CILOp::NewTMPLocal(tuple_type.clone().into())];
-            let mut curr_offset = 0;
+            // Tuple elements may be reordered and padded, so their offsets must come from the layout, and not from summing the element sizes.
+            let layout = tyctx
+                .layout_of(ParamEnv::reveal_all().and(ty))
+                .expect("Could not get the layout of a const tuple!");
             for (idx, (element_type, element_ty)) in
                 element_types.iter().zip(elements.iter()).enumerate()
             {
-                let sizeof = crate::utilis::compiletime_sizeof(element_ty, tyctx);
-                let field_bytes = &bytes[curr_offset..(curr_offset + sizeof)];
+                let offset = layout.fields.offset(idx).bytes() as usize;
+                let sizeof = const_sizeof(element_ty, tyctx);
+                let field_bytes = &bytes[offset..(offset + sizeof)];
                 let field_ops = create_const_from_slice(
                     element_ty,
                     tyctx,
@@ -244,7 +248,6 @@ fn create_const_from_slice<'ctx>(
                     element_type.clone(),
                     format!("Item{num}", num = idx + 1).into(),
                 )));
-                curr_offset += sizeof;
             }
             ops.push(CILOp::LoadTMPLocal);
             ops.push(CILOp::FreeTMPLocal);
@@ -256,7 +259,7 @@ fn create_const_from_slice<'ctx>(
             let length = crate::utilis::monomorphize(&method_instance, *length, tyctx);
             let element_ty = crate::utilis::monomorphize(&method_instance, *element_ty, tyctx);
 
-            let element_sizeof = crate::utilis::compiletime_sizeof(element_ty, tyctx);
+            let element_sizeof = const_sizeof(element_ty, tyctx);
             let length = crate::utilis::try_resolve_const_size(length).unwrap();
             let mut curr_offset = 0;
             let mut res = vec![CILOp::NewTMPLocal(array_type.clone().into())];
@@ -311,7 +314,7 @@ fn create_const_from_data<'ctx>(
     create_const_from_slice(ty, tyctx, bytes, method_instance, tycache)
 }
 
-fn load_const_value<'ctx>(
+pub(crate) fn load_const_value<'ctx>(
     const_val: ConstValue<'ctx>,
     const_ty: Ty<'ctx>,
     tyctx: TyCtxt<'ctx>,
@@ -326,37 +329,16 @@ fn load_const_value<'ctx>(
         ConstValue::ZeroSized => {
             let tpe = crate::utilis::monomorphize(&method_instance, const_ty, tyctx);
             let tpe = tycache.type_from_cache(tpe, tyctx, Some(method_instance));
-            vec![
-                CILOp::NewTMPLocal(tpe.into()),
-                CILOp::LoadTMPLocal,
-                CILOp::FreeTMPLocal,
-            ]
+            crate::utilis::zst_value(tpe)
         }
         ConstValue::Slice { data, meta } => {
-            let slice_type = tycache.type_from_cache(const_ty, tyctx, Some(method_instance));
-            let slice_dotnet = slice_type.as_dotnet().expect("Slice type invalid!");
-            let metadata_field =
-                FieldDescriptor::new(slice_dotnet.clone(), Type::USize, "metadata".into());
-            let ptr_field = FieldDescriptor::new(
-                slice_dotnet,
-                Type::Ptr(Type::Void.into()),
-                "data_address".into(),
-            );
             // TODO: find a better way to get an alloc_id. This is likely to be incoreect.
             let alloc_id = tyctx.reserve_and_set_memory_alloc(data);
             let alloc_id: u64 = crate::utilis::alloc_id_to_u64(alloc_id);
-
-            vec![
-                CILOp::NewTMPLocal(slice_type.into()),
-                CILOp::LoadAddresOfTMPLocal,
-                CILOp::LdcI64(meta as i64),
-                CILOp::STField(metadata_field.into()),
-                CILOp::LoadAddresOfTMPLocal,
-                CILOp::LoadGlobalAllocPtr { alloc_id },
-                CILOp::STField(ptr_field.into()),
-                CILOp::LoadTMPLocal,
-                CILOp::FreeTMPLocal,
-            ]
+            crate::fat_ptr::make_fat_ptr(
+                vec![CILOp::LoadGlobalAllocPtr { alloc_id }],
+                vec![CILOp::LdcI64(meta as i64), CILOp::ConvUSize(false)],
+            )
         }
         ConstValue::Indirect { alloc_id, offset } => {
             create_const_from_data(
@@ -459,8 +441,6 @@ fn load_const_scalar<'ctx>(
             //panic!("alloc_id:{alloc_id:?}")
         }
     };
-    let tpe = crate::utilis::monomorphize(&method_instance, scalar_type, tyctx);
-    let tpe = tycache.type_from_cache(tpe, tyctx, Some(method_instance));
     match scalar_type.kind() {
         TyKind::Int(int_type) => load_const_int(scalar_u128, int_type),
         TyKind::Uint(uint_type) => load_const_uint(scalar_u128, uint_type),
@@ -470,29 +450,27 @@ fn load_const_scalar<'ctx>(
             let value = i64::from_ne_bytes((scalar_u128 as u64).to_ne_bytes());
             vec![CILOp::LdcI64(value)]
         }
-        TyKind::Adt(adt_def, _subst) => match adt_def.adt_kind() {
+        TyKind::Adt(adt_def, subst) => match adt_def.adt_kind() {
+            // The scalar holds all the bytes of the enum.
             AdtKind::Enum => {
-                let field_type = Type::U8;
-                let enum_dotnet = tpe.as_dotnet().expect("Enum scalar not an ADT!");
-                vec![
-                    CILOp::NewTMPLocal(tpe.into()),
-                    CILOp::LoadAddresOfTMPLocal,
-                    CILOp::LdcI64(scalar_u128 as i64),
-                    CILOp::STField(Box::new(crate::cil::FieldDescriptor::new(
-                        enum_dotnet.clone(),
-                        field_type,
-                        "_tag".into(),
-                    ))),
-                    CILOp::LoadTMPLocal,
-                    CILOp::FreeTMPLocal,
-                ]
+                let size = crate::utilis::compiletime_sizeof(scalar_type, tyctx);
+                let mut bytes = vec![0; size];
+                write_target_uint(tyctx.data_layout.endian, &mut bytes, scalar_u128)
+                    .expect("Could not write the bytes of a const enum!");
+                create_const_adt_from_bytes(
+                    scalar_type,
+                    *adt_def,
+                    subst,
+                    tyctx,
+                    &bytes,
+                    method_instance,
+                    tycache,
+                )
             }
             _ => todo!("Can't load const ADT scalars of type {scalar_type:?}"),
         },
-        TyKind::Char => {
-            let value = i64::from_ne_bytes((scalar_u128 as u64).to_ne_bytes());
-            vec![CILOp::LdcI64(value), CILOp::ConvU64(false)]
-        }
+        // Rust `char` is represented as an u32, and not as `System.Char`, which is only 2 bytes wide.
+        TyKind::Char => vec![CILOp::LdcI32(scalar_u128 as u32 as i32)],
         _ => todo!("Can't load scalar constants of type {scalar_type:?}!"),
     }
 }
@@ -532,19 +510,24 @@ pub fn load_const_int(value: u128, int_type: &IntTy) -> Vec<CILOp> {
         }
         IntTy::I128 => {
             let low = (value & u128::from(u64::MAX)) as u64;
-            let high = (value << 64) as u64;
+            let high = (value >> 64) as u64;
             let low = i64::from_ne_bytes(low.to_ne_bytes());
             let high = i64::from_ne_bytes(high.to_ne_bytes());
-            let i128_class = DotnetTypeRef::new(Some("System.Runtime"), "System.Int128");
-            let ctor_sig = crate::function_sig::FnSig::new(&[Type::U64, Type::U64], &Type::I128);
+            let i128_class = DotnetTypeRef::int_128();
+            let ctor_sig = crate::function_sig::FnSig::new(
+                &[Type::I128, Type::U64, Type::U64],
+                &Type::Void,
+            );
             vec![
                 CILOp::LdcI64(high),
+                CILOp::ConvU64(false),
                 CILOp::LdcI64(low),
-                CILOp::Call(CallSite::boxed(
+                CILOp::ConvU64(false),
+                CILOp::NewObj(CallSite::boxed(
                     Some(i128_class),
                     ".ctor".into(),
                     ctor_sig,
-                    true,
+                    false,
                 )),
             ]
         }
@@ -574,19 +557,24 @@ pub fn load_const_uint(value: u128, int_type: &UintTy) -> Vec<CILOp> {
         }
         UintTy::U128 => {
             let low = (value & u128::from(u64::MAX)) as u64;
-            let high = (value << 64) as u64;
+            let high = (value >> 64) as u64;
             let low = i64::from_ne_bytes(low.to_ne_bytes());
             let high = i64::from_ne_bytes(high.to_ne_bytes());
-            let i128_class = DotnetTypeRef::new(Some("System.Runtime"), "System.UInt128");
-            let ctor_sig = crate::function_sig::FnSig::new(&[Type::U64, Type::U64], &Type::U128);
+            let i128_class = DotnetTypeRef::uint_128();
+            let ctor_sig = crate::function_sig::FnSig::new(
+                &[Type::U128, Type::U64, Type::U64],
+                &Type::Void,
+            );
             vec![
                 CILOp::LdcI64(high),
+                CILOp::ConvU64(false),
                 CILOp::LdcI64(low),
-                CILOp::Call(CallSite::boxed(
+                CILOp::ConvU64(false),
+                CILOp::NewObj(CallSite::boxed(
                     Some(i128_class),
                     ".ctor".into(),
                     ctor_sig,
-                    true,
+                    false,
                 )),
             ]
         }
@@ -0,0 +1,144 @@
+//! Feature-coverage reporting. When [`coverage_dir`](crate::config::BackendConfig::coverage_dir) is set, the codegen does not emit any code. Instead, it tries to lower every
+//! statement and terminator of every function in a crate, and writes a JSON report describing which of them are supported to `{crate_name}.coverage.json`.
+use crate::{codegen_error::UnsupportedKind, r#type::TyCache};
+use rustc_middle::{
+    mir::mono::MonoItem,
+    ty::{Instance, TyCtxt},
+};
+use rustc_span::Span;
+use serde::Serialize;
+/// Number of supported and unsupported occurrences of a kind of MIR construct.
+#[derive(Serialize, Default, Debug)]
+struct Counts {
+    supported: usize,
+    unsupported: usize,
+}
+impl Counts {
+    fn add(&mut self, supported: bool) {
+        if supported {
+            self.supported += 1;
+        } else {
+            self.unsupported += 1;
+        }
+    }
+}
+/// A single MIR construct the backend could not lower.
+#[derive(Serialize, Debug)]
+struct UnsupportedItem {
+    kind: UnsupportedKind,
+    function: String,
+    location: String,
+    message: String,
+}
+/// Coverage report of a single crate.
+#[derive(Serialize, Default, Debug)]
+pub(crate) struct CoverageReport {
+    crate_name: String,
+    /// Number of functions with MIR.
+    functions: usize,
+    /// Number of functions whose every statement and terminator could be lowered.
+    fully_supported_functions: usize,
+    statements: Counts,
+    terminators: Counts,
+    intrinsics: Counts,
+    unsupported: Vec<UnsupportedItem>,
+}
+impl CoverageReport {
+    /// Walks all mono items of the crate, and classifies their statements and terminators.
+    pub(crate) fn for_crate(tcx: TyCtxt) -> Self {
+        let (_defid_set, cgus) = tcx.collect_and_partition_mono_items(());
+        let mut report = Self {
+            crate_name: tcx.crate_name(rustc_span::def_id::LOCAL_CRATE).to_string(),
+            ..Default::default()
+        };
+        let mut cache = TyCache::empty();
+        for cgu in cgus {
+            for item in cgu.items().keys() {
+                if let MonoItem::Fn(instance) = item {
+                    report.add_fn(*instance, tcx, &mut cache);
+                }
+            }
+        }
+        report
+    }
+    fn add_fn<'tcx>(&mut self, instance: Instance<'tcx>, tcx: TyCtxt<'tcx>, cache: &mut TyCache) {
+        let name = crate::utilis::function_name(tcx.symbol_name(instance));
+        if crate::utilis::is_function_magic(&name) || !tcx.is_mir_available(instance.def_id()) {
+            return;
+        }
+        let mir = tcx.optimized_mir(instance.def_id());
+        self.functions += 1;
+        let unsupported_before = self.unsupported.len();
+        let reachable = rustc_middle::mir::traversal::reachable_as_bitset(mir);
+        for (block_id, block_data) in mir.basic_blocks.iter_enumerated() {
+            if !reachable.contains(block_id) {
+                continue;
+            }
+            for statement in &block_data.statements {
+                let result = lower(cache, |cache| {
+                    crate::statement::handle_statement(statement, tcx, mir, instance, cache)
+                });
+                self.statements.add(result.is_ok());
+                if let Err(msg) = result {
+                    self.add_unsupported(
+                        tcx,
+                        UnsupportedKind::Statement,
+                        &name,
+                        statement.source_info.span,
+                        msg,
+                    );
+                }
+            }
+            if let Some(term) = &block_data.terminator {
+                let kind = UnsupportedKind::of_terminator(term, tcx);
+                let result = lower(cache, |cache| {
+                    crate::terminator::handle_terminator(term, mir, tcx, mir, instance, cache)
+                });
+                match kind {
+                    UnsupportedKind::Intrinsic => self.intrinsics.add(result.is_ok()),
+                    _ => self.terminators.add(result.is_ok()),
+                }
+                if let Err(msg) = result {
+                    self.add_unsupported(tcx, kind, &name, term.source_info.span, msg);
+                }
+            }
+        }
+        if self.unsupported.len() == unsupported_before {
+            self.fully_supported_functions += 1;
+        }
+    }
+    fn add_unsupported(
+        &mut self,
+        tcx: TyCtxt,
+        kind: UnsupportedKind,
+        function: &str,
+        span: Span,
+        message: String,
+    ) {
+        self.unsupported.push(UnsupportedItem {
+            kind,
+            function: function.into(),
+            location: tcx.sess.source_map().span_to_diagnostic_string(span),
+            message,
+        });
+    }
+    /// Writes the report to `{crate_name}.coverage.json` in `dir`.
+    pub(crate) fn write_to(&self, dir: &std::path::Path) {
+        std::fs::create_dir_all(dir).expect("Could not create the coverage report directory!");
+        let file = std::fs::File::create(dir.join(format!("{}.coverage.json", self.crate_name)))
+            .expect("Could not create the coverage report file!");
+        serde_json::to_writer_pretty(file, self).expect("Could not write the coverage report!");
+    }
+}
+/// Runs `lowering`, discarding the produced ops. Returns the panic message if lowering failed.
+fn lower<T>(cache: &mut TyCache, lowering: impl FnOnce(&mut TyCache) -> T) -> Result<(), String> {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| lowering(cache))) {
+        Ok(_) => Ok(()),
+        Err(payload) => {
+            cache.recover_from_panic();
+            Err(crate::codegen_error::panic_message(&*payload)
+                .unwrap_or("lowering panicked with a non-string message")
+                .to_owned())
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Differential testing: compiles a program twice, with this backend and with the default(LLVM) backend, runs both builds, and checks that their
+//! stdout and exit codes are identical. This catches miscompilations, even in programs which do not check their own results.
+//!
+//! The test programs are driven by the integration tests in `tests/differential.rs`, which run every program in `test/diff`.
+use crate::runtime::RuntimeProfile;
+use std::path::{Path, PathBuf};
+/// Observable behaviour of a test program: what it wrote to stdout and how it exited.
+#[derive(Debug, PartialEq)]
+pub struct ProgramOutput {
+    pub stdout: String,
+    /// `None` if the program was terminated by a signal.
+    pub exit_code: Option<i32>,
+}
+impl From<std::process::Output> for ProgramOutput {
+    fn from(out: std::process::Output) -> Self {
+        Self {
+            stdout: String::from_utf8(out.stdout).expect("Stdout is not UTF8 String!"),
+            exit_code: out.status.code(),
+        }
+    }
+}
+/// The backend and linker used to build programs for the CLR.
+pub struct Toolchain {
+    /// Path of the backend library.
+    pub backend: PathBuf,
+    /// Path of the linker executable.
+    pub linker: PathBuf,
+}
+impl Toolchain {
+    /// Returns the toolchain built into `dir`(eg. `target/debug`), which contains both the linker and the backend library.
+    #[must_use]
+    pub fn in_dir(dir: &Path) -> Self {
+        let backend = if cfg!(target_os = "linux") {
+            "librustc_codegen_clr.so"
+        } else if cfg!(target_os = "windows") {
+            "rustc_codegen_clr.dll"
+        } else if cfg!(target_os = "macos") {
+            "librustc_codegen_clr.dylib"
+        } else {
+            panic!("Unsupported target OS");
+        };
+        let linker = if cfg!(target_os = "windows") {
+            "linker.exe"
+        } else {
+            "linker"
+        };
+        Self {
+            backend: dir.join(backend),
+            linker: dir.join(linker),
+        }
+    }
+}
+/// Runs `rustc` with `args` inside `dir`, returning its errors, if it reports any.
+pub fn compile(dir: &Path, args: &[&str]) -> Result<(), String> {
+    let out = std::process::Command::new("rustc")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .map_err(|err| format!("Could not run rustc: {err}"))?;
+    // If stderr is not empty, then something went wrong, so print the stdout and stderr for debuging.
+    if out.stderr.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "args:{args:?}\nstdout:\n{stdout}\nstderr:\n{stderr}",
+        stdout = String::from_utf8_lossy(&out.stdout),
+        stderr = String::from_utf8_lossy(&out.stderr)
+    ))
+}
+/// Runs the assembly `{exec_path}.exe` on `runtime`, with `dir` as the working directory.
+pub fn run_assembly(
+    exec_path: &str,
+    dir: &Path,
+    runtime: &dyn RuntimeProfile,
+) -> Result<std::process::Output, String> {
+    let host = runtime.host().ok_or_else(|| {
+        format!(
+            "Assemblies targeting {runtime} can't be run directly.",
+            runtime = runtime.name()
+        )
+    })?;
+    if runtime.needs_runtime_config() {
+        std::fs::write(
+            dir.join(format!("{exec_path}.runtimeconfig.json")),
+            crate::runtime::runtime_config(),
+        )
+        .map_err(|err| format!("Could not write the runtime config: {err}"))?;
+    }
+    std::process::Command::new(host)
+        .current_dir(dir)
+        .arg(format!("{exec_path}.exe"))
+        .output()
+        .map_err(|err| format!("Could not run {host}: {err}"))
+}
+/// Compiles the program `dir/name.rs` with `toolchain` and with the default backend, runs both builds(the CLR one on `runtime`), and compares
+/// their outputs. Returns a description of the difference, if there is one.
+pub fn compare_with_native(
+    toolchain: &Toolchain,
+    runtime: &dyn RuntimeProfile,
+    dir: &Path,
+    name: &str,
+    optimize: bool,
+) -> Result<(), String> {
+    let opt: &[&str] = if optimize { &["-O"] } else { &[] };
+    let mode = if optimize { "release" } else { "debug" };
+    let source = format!("./{name}.rs");
+    // Build and run using this backend.
+    let clr_name = format!("clr_{mode}_{name}");
+    let backend = format!("codegen-backend={}", toolchain.backend.display());
+    let linker = format!("linker={}", toolchain.linker.display());
+    let clr_out = format!("./{clr_name}.exe");
+    let mut clr_args = opt.to_vec();
+    clr_args.extend([
+        "-Z",
+        backend.as_str(),
+        "-C",
+        linker.as_str(),
+        source.as_str(),
+        "-o",
+        clr_out.as_str(),
+    ]);
+    compile(dir, &clr_args)?;
+    let clr: ProgramOutput = run_assembly(&format!("../{clr_name}"), dir, runtime)?.into();
+    // Build and run using the default backend.
+    let native_name = format!("./native_{mode}_{name}");
+    let mut native_args = opt.to_vec();
+    native_args.extend([source.as_str(), "-o", native_name.as_str()]);
+    compile(dir, &native_args)?;
+    let native: ProgramOutput = std::process::Command::new(&native_name)
+        .current_dir(dir)
+        .output()
+        .map_err(|err| format!("Could not run the native build of {name}: {err}"))?
+        .into();
+    if clr == native {
+        return Ok(());
+    }
+    Err(format!(
+        "Output of {name}({mode}) compiled with rustc_codegen_clr differs from the output of the native build.\nclr:{clr:?}\nnative:{native:?}"
+    ))
+}
@@ -0,0 +1,265 @@
+//! Getting and setting the discriminants of enums.
+//!
+//! Enums are laid out just like rustc lays them out. The tag of an enum(if it has one) is a field named `_tag`, of the size and at the offset
+//! calculated by rustc. The fields of each variant are stored in a nested type, placed at offset 0 of the enum, whose fields have the offsets
+//! rustc gave them within the whole enum.
+//!
+//! The tag may hold the discriminant directly(`TagEncoding::Direct`), or be stored in a niche: a range of invalid values of a field of one of the
+//! variants(eg. the null value of the reference in `Option<&T>`). In the second case, the variant owning the niche(`untagged_variant`) is active
+//! if the tag holds a valid value, so setting it as active is a no-op. The other variants are encoded by their index, relative to the first one
+//! encoded in the niche, and offset by `niche_start`.
+use crate::{
+    cil::{CILOp, FieldDescriptor},
+    r#type::{TyCache, Type},
+};
+use rustc_middle::{
+    mir::interpret::read_target_uint,
+    ty::{layout::TyAndLayout, AdtDef, Instance, ParamEnv, Ty, TyCtxt},
+};
+use rustc_target::abi::{Integer, Primitive, TagEncoding, VariantIdx, Variants};
+/// The name of the field holding the tag of an enum.
+pub(crate) const TAG_FIELD: &str = "_tag";
+/// Returns the layout of the enum `ty`.
+pub(crate) fn enum_layout<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> TyAndLayout<'tcx> {
+    tcx.layout_of(ParamEnv::reveal_all().and(ty))
+        .expect("Could not get the layout of an enum!")
+}
+/// Returns the type of a tag stored as `primitive`, and whether it is signed.
+fn tag_type(primitive: Primitive) -> (Type, bool) {
+    match primitive {
+        Primitive::Int(int, signed) => {
+            let tpe = match (int, signed) {
+                (Integer::I8, false) => Type::U8,
+                (Integer::I8, true) => Type::I8,
+                (Integer::I16, false) => Type::U16,
+                (Integer::I16, true) => Type::I16,
+                (Integer::I32, false) => Type::U32,
+                (Integer::I32, true) => Type::I32,
+                (Integer::I64, false) => Type::U64,
+                (Integer::I64, true) => Type::I64,
+                (Integer::I128, _) => todo!("Can't yet have 16 byte wide enum tags!"),
+            };
+            (tpe, signed)
+        }
+        // Niches in pointers(eg. references or `NonNull`).
+        Primitive::Pointer(_) => (Type::USize, false),
+        _ => todo!("Unsuported enum tag primitive {primitive:?}!"),
+    }
+}
+/// Returns the offset and type of the tag of an enum with layout `layout`. Returns `None` if the enum has no tag, because only one of its variants
+/// can ever be active.
+pub(crate) fn tag_field(layout: &TyAndLayout) -> Option<(u32, Type)> {
+    let Variants::Multiple { tag, tag_field, .. } = &layout.variants else {
+        return None;
+    };
+    let offset = u32::try_from(layout.fields.offset(*tag_field).bytes())
+        .expect("Enum tag offset over 2^32.");
+    Some((offset, tag_type(tag.primitive()).0))
+}
+/// Returns the offsets of the fields of variant `variant`, relative to the start of the enum with layout `layout`. Returns `None` if the variant is
+/// not a part of the layout, because it can never be active.
+pub(crate) fn variant_field_offsets(layout: &TyAndLayout, variant: VariantIdx) -> Option<Vec<u32>> {
+    let fields = match &layout.variants {
+        Variants::Single { index } if *index == variant => &layout.fields,
+        Variants::Single { .. } => return None,
+        Variants::Multiple { variants, .. } => &variants[variant].fields,
+    };
+    Some(
+        (0..fields.count())
+            .map(|idx| {
+                u32::try_from(fields.offset(idx).bytes()).expect("Enum field offset over 2^32.")
+            })
+            .collect(),
+    )
+}
+/// Returns the ops loading the discriminant of the enum `enum_ty`, whose address is calculated by `addr`. The discriminant has the type
+/// `enum_ty.discriminant_ty()`.
+pub(crate) fn get_discr<'tcx>(
+    addr: Vec<CILOp>,
+    enum_ty: Ty<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    method_instance: Instance<'tcx>,
+    tycache: &mut TyCache,
+) -> Vec<CILOp> {
+    let layout = enum_layout(enum_ty, tcx);
+    let discr_ty =
+        tycache.type_from_cache(enum_ty.discriminant_ty(tcx), tcx, Some(method_instance));
+    let (tag, tag_encoding) = match &layout.variants {
+        Variants::Single { index } => {
+            let discr = enum_ty
+                .discriminant_for_variant(tcx, *index)
+                .map_or(0, |discr| discr.val);
+            let mut ops = vec![CILOp::LdcI64(discr as u64 as i64)];
+            ops.extend(crate::casts::to_int(discr_ty));
+            return ops;
+        }
+        Variants::Multiple {
+            tag, tag_encoding, ..
+        } => (tag, tag_encoding),
+    };
+    let (tag_ty, signed) = tag_type(tag.primitive());
+    let enum_dotnet = tycache
+        .type_from_cache(enum_ty, tcx, Some(method_instance))
+        .as_dotnet()
+        .expect("Enum is not a .NET type!");
+    let load_tag = CILOp::LDField(FieldDescriptor::boxed(
+        enum_dotnet,
+        tag_ty,
+        TAG_FIELD.into(),
+    ));
+    match tag_encoding {
+        TagEncoding::Direct => {
+            let mut ops = addr;
+            ops.push(load_tag);
+            // The tag holds the discriminant, truncated to its size.
+            ops.push(if signed {
+                CILOp::ConvI64(false)
+            } else {
+                CILOp::ConvU64(false)
+            });
+            ops.extend(crate::casts::to_int(discr_ty));
+            ops
+        }
+        TagEncoding::Niche {
+            untagged_variant,
+            niche_variants,
+            niche_start,
+        } => {
+            let first = u64::from(niche_variants.start().as_u32());
+            let range = u64::from(niche_variants.end().as_u32()) - first;
+            let untagged = u64::from(untagged_variant.as_u32());
+            let mut ops = vec![CILOp::NewTMPLocal(Type::U64.into())];
+            ops.extend(addr);
+            ops.extend([load_tag, CILOp::ConvU64(false)]);
+            // The index of the variant, relative to the first one encoded in the niche. Wraps around, like the tag.
+            ops.extend([CILOp::LdcI64(*niche_start as u64 as i64), CILOp::Sub]);
+            match tag.size(&tcx).bytes() {
+                1 => ops.extend([CILOp::ConvU8(false), CILOp::ConvU64(false)]),
+                2 => ops.extend([CILOp::ConvU16(false), CILOp::ConvU64(false)]),
+                4 => ops.extend([CILOp::ConvU32(false), CILOp::ConvU64(false)]),
+                _ => (),
+            }
+            ops.push(CILOp::SetTMPLocal);
+            // `untagged + is_niche * (relative + first - untagged)`, which is `relative + first` if the relative index is in the range
+            // of variants encoded in the niche, and `untagged` otherwise.
+            ops.extend([
+                CILOp::LdcI64(untagged as i64),
+                CILOp::LoadTMPLocal,
+                CILOp::LdcI64(range as i64),
+                CILOp::GtUn,
+                CILOp::LdcI32(0),
+                CILOp::Eq,
+                CILOp::ConvU64(false),
+                CILOp::LoadTMPLocal,
+                CILOp::LdcI64(first.wrapping_sub(untagged) as i64),
+                CILOp::Add,
+                CILOp::Mul,
+                CILOp::Add,
+                CILOp::FreeTMPLocal,
+            ]);
+            ops.extend(crate::casts::to_int(discr_ty));
+            ops
+        }
+    }
+}
+/// Returns the ops setting the discriminant of the enum `enum_ty`, whose address is calculated by `addr`, to the one of variant `variant`. The
+/// fields of the variant must be set before its discriminant, since they may overlap with the tag.
+pub(crate) fn set_discr<'tcx>(
+    addr: Vec<CILOp>,
+    enum_ty: Ty<'tcx>,
+    variant: VariantIdx,
+    tcx: TyCtxt<'tcx>,
+    method_instance: Instance<'tcx>,
+    tycache: &mut TyCache,
+) -> Vec<CILOp> {
+    let layout = enum_layout(enum_ty, tcx);
+    // Only one variant can be active, so there is nothing to set.
+    let Variants::Multiple {
+        tag, tag_encoding, ..
+    } = &layout.variants
+    else {
+        return vec![];
+    };
+    let value = match tag_encoding {
+        TagEncoding::Direct => {
+            enum_ty
+                .discriminant_for_variant(tcx, variant)
+                .expect("Enum variant has no discriminant!")
+                .val
+        }
+        TagEncoding::Niche {
+            untagged_variant,
+            niche_variants,
+            niche_start,
+        } => {
+            if variant == *untagged_variant {
+                return vec![];
+            }
+            let relative = variant.as_u32() - niche_variants.start().as_u32();
+            u128::from(relative).wrapping_add(*niche_start)
+        }
+    };
+    let (tag_ty, _) = tag_type(tag.primitive());
+    let enum_dotnet = tycache
+        .type_from_cache(enum_ty, tcx, Some(method_instance))
+        .as_dotnet()
+        .expect("Enum is not a .NET type!");
+    let mut ops = addr;
+    // Tags narrower than 8 bytes are truncated when stored.
+    ops.push(if tag.size(&tcx).bytes() == 8 {
+        CILOp::LdcI64(value as u64 as i64)
+    } else {
+        CILOp::LdcI32(value as u32 as i32)
+    });
+    ops.push(CILOp::STField(FieldDescriptor::boxed(
+        enum_dotnet,
+        tag_ty,
+        TAG_FIELD.into(),
+    )));
+    ops
+}
+/// Returns the active variant of a constant enum of type `adt`, with layout `layout`, whose value is stored in `bytes`.
+pub(crate) fn variant_from_bytes<'tcx>(
+    adt: AdtDef<'tcx>,
+    layout: &TyAndLayout<'tcx>,
+    bytes: &[u8],
+    tcx: TyCtxt<'tcx>,
+) -> VariantIdx {
+    let (tag, tag_encoding, tag_field) = match &layout.variants {
+        Variants::Single { index } => return *index,
+        Variants::Multiple {
+            tag,
+            tag_encoding,
+            tag_field,
+            ..
+        } => (tag, tag_encoding, tag_field),
+    };
+    let offset = layout.fields.offset(*tag_field).bytes() as usize;
+    let size = tag.size(&tcx);
+    let tag_value = read_target_uint(
+        tcx.data_layout.endian,
+        &bytes[offset..(offset + size.bytes() as usize)],
+    )
+    .expect("Could not read the tag of a const enum!");
+    match tag_encoding {
+        TagEncoding::Direct => {
+            adt.discriminants(tcx)
+                .find(|(_, discr)| size.truncate(discr.val) == tag_value)
+                .expect("No enum variant with the discriminant of a const enum!")
+                .0
+        }
+        TagEncoding::Niche {
+            untagged_variant,
+            niche_variants,
+            niche_start,
+        } => {
+            let first = niche_variants.start().as_u32();
+            let relative = size.truncate(tag_value.wrapping_sub(*niche_start));
+            if relative <= u128::from(niche_variants.end().as_u32() - first) {
+                VariantIdx::from_u32(first + relative as u32)
+            } else {
+                *untagged_variant
+            }
+        }
+    }
+}
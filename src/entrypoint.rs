@@ -2,51 +2,216 @@ use crate::{
     cil::{CILOp, CallSite},
     function_sig::FnSig,
     method::Method,
-    r#type::Type,
+    r#type::{DotnetTypeRef, TyCache, Type},
 };
-/// Creates a wrapper method around entypoint represented by `CallSite`
-pub fn wrapper(entrypoint: &CallSite) -> Method {
-    if entrypoint.signature().inputs()
+use rustc_middle::ty::{Instance, List, ParamEnv, TyCtxt};
+/// Describes how the exit code of a program is obtained from the value returned by its entrypoint.
+#[derive(Debug, Clone)]
+pub enum ExitCodeSource {
+    /// The entrypoint returns nothing, so the program exits with 0.
+    Zero,
+    /// The entrypoint(like a `#[start]` function) takes `argc` and `argv`, and returns the exit code as an `isize`.
+    Returned,
+    /// The entrypoint returns a value implementing `Termination`. `report` turns it into a `std::process::ExitCode`, which is `exit_code_size` bytes big,
+    /// and holds nothing but the exit code.
+    Report {
+        report: CallSite,
+        exit_code_size: u64,
+    },
+}
+impl ExitCodeSource {
+    /// Returns the source of the exit code of entrypoint `main`, with signature `sig`.
+    pub fn for_entrypoint<'tcx>(
+        tcx: TyCtxt<'tcx>,
+        main: Instance<'tcx>,
+        sig: &FnSig,
+        cache: &mut TyCache,
+    ) -> Self {
+        if sig.output() == &Type::Void {
+            return Self::Zero;
+        }
+        if is_start_sig(sig) {
+            return Self::Returned;
+        }
+        let abi_of = |instance| {
+            tcx.fn_abi_of_instance(ParamEnv::reveal_all().and((instance, List::empty())))
+                .expect("Could not get the ABI of a function!")
+        };
+        let output = abi_of(main).ret.layout.ty;
+        let termination = tcx
+            .lang_items()
+            .termination()
+            .expect("The `Termination` trait is missing!");
+        let report = tcx
+            .associated_item_def_ids(termination)
+            .iter()
+            .find(|item| tcx.item_name(**item).as_str() == "report")
+            .expect("`Termination::report` is missing!");
+        let report = Instance::resolve(
+            tcx,
+            ParamEnv::reveal_all(),
+            *report,
+            tcx.mk_args(&[output.into()]),
+        )
+        .expect("Could not resolve `Termination::report`!")
+        .expect("Could not resolve `Termination::report`!");
+        let report_sig = FnSig::sig_from_instance_(report, tcx, cache)
+            .expect("Could not get the signature of `Termination::report`!");
+        Self::Report {
+            report: CallSite::new(
+                None,
+                crate::utilis::function_name(tcx.symbol_name(report)),
+                report_sig,
+                true,
+            ),
+            exit_code_size: abi_of(report).ret.layout.size.bytes(),
+        }
+    }
+}
+/// Checks if `sig` is the signature of a `#[start]` function: `fn(isize, *const *const u8) -> isize`.
+fn is_start_sig(sig: &FnSig) -> bool {
+    sig.inputs()
         == [
             Type::ISize,
             Type::Ptr(Box::new(Type::Ptr(Box::new(Type::U8)))),
         ]
-        && entrypoint.signature().output() == &Type::ISize
-    {
-        let sig = FnSig::new(&[], &Type::Void);
-        let ops = vec![
-            CILOp::LdcI32(0),
+        && sig.output() == &Type::ISize
+}
+/// Creates a wrapper method around entypoint represented by `CallSite`. The wrapper calls the entrypoint, computes the exit code as described by `exit_code`,
+/// flushes stdout, and exits with that code. The linker inserts calls to `.init_array` functions at its start(see [`crate::assembly::Assembly::call_init_array`]).
+pub fn wrapper(entrypoint: &CallSite, exit_code: &ExitCodeSource) -> Method {
+    let mut ops = Vec::new();
+    let mut locals = vec![(Some("exit_code".into()), Type::I32)];
+    match exit_code {
+        ExitCodeSource::Zero => {
+            assert!(
+                entrypoint.signature().inputs().is_empty(),
+                "Unsuported entrypoint wrapper signature! entrypoint:{entrypoint:?}"
+            );
+            ops.extend([CILOp::Call(Box::new(entrypoint.clone())), CILOp::LdcI32(0)]);
+        }
+        ExitCodeSource::Returned => {
+            assert!(
+                is_start_sig(entrypoint.signature()),
+                "Unsuported entrypoint wrapper signature! entrypoint:{entrypoint:?}"
+            );
+            ops.extend([
+                crate::libc::argc(),
+                CILOp::ConvISize(false),
+                crate::libc::argv(),
+                CILOp::Call(Box::new(entrypoint.clone())),
+                CILOp::ConvI32(false),
+            ]);
+        }
+        ExitCodeSource::Report {
+            report,
+            exit_code_size,
+        } => {
+            // `ExitCode` holds only the exit code, so it is read directly from the returned value.
+            locals.push((Some("report".into()), report.signature().output().clone()));
+            ops.extend([
+                CILOp::Call(Box::new(entrypoint.clone())),
+                CILOp::Call(Box::new(report.clone())),
+                CILOp::STLoc(1),
+                CILOp::LDLocA(1),
+            ]);
+            match exit_code_size {
+                1 => ops.extend([CILOp::LDIndI8, CILOp::ConvU8(false)]),
+                4 => ops.push(CILOp::LDIndI32),
+                _ => panic!("Unsuported size of `ExitCode`: {exit_code_size}"),
+            }
+        }
+    }
+    ops.push(CILOp::STLoc(0));
+    ops.extend(flush_stdout());
+    ops.extend([CILOp::LDLoc(0), CILOp::Call(exit()), CILOp::Ret]);
+    let mut method = Method::new(
+        crate::access_modifier::AccessModifer::Public,
+        true,
+        FnSig::new(&[], &Type::Void),
+        "entrypoint",
+        locals,
+    );
+    method.set_ops(ops);
+    method.add_attribute(crate::method::Attribute::EntryPoint);
+    method
+}
+/// Prefix of the names of methods calling functions placed in `.init_array`. The linker makes the entrypoint call them, sorted by name.
+pub const INIT_ARRAY_PREFIX: &str = "rust_init_array_";
+/// Returns the priority of the `.init_array` section `section`(lower priorities run first), or `None` if `section` is not an `.init_array` section.
+/// Functions placed in `.init_array` without a priority run last.
+pub fn init_array_priority(section: &str) -> Option<u16> {
+    let priority = section.strip_prefix(".init_array")?;
+    if priority.is_empty() {
+        return Some(u16::MAX);
+    }
+    priority.strip_prefix('.')?.parse().ok()
+}
+/// Creates a method calling `init`, a function placed in an `.init_array` section with priority `priority` by static `name`. Like on Linux, `init` gets
+/// `argc`, `argv` and `envp`(always null), if it takes any arguments. `std` uses this to get the command line arguments.
+pub fn init_array_caller(name: &str, priority: u16, init: CallSite) -> Method {
+    let mut ops = Vec::new();
+    match init.signature().inputs().len() {
+        0 => (),
+        3 => ops.extend([
+            crate::libc::argc(),
+            crate::libc::argv(),
             CILOp::LdcI32(0),
-            CILOp::Call(Box::new(entrypoint.clone())),
-            CILOp::Pop,
-            CILOp::Ret,
-        ];
-        let mut method = Method::new(
-            crate::access_modifier::AccessModifer::Public,
-            true,
-            sig,
-            "entrypoint",
-            vec![],
-        );
-        method.set_ops(ops);
-        method.add_attribute(crate::method::Attribute::EntryPoint);
-        method
-    } else if entrypoint.signature().inputs().is_empty()
-        && entrypoint.signature().output() == &Type::Void
-    {
-        let sig = FnSig::new(&[], &Type::Void);
-        let ops = vec![CILOp::Call(Box::new(entrypoint.clone())), CILOp::Ret];
-        let mut method = Method::new(
-            crate::access_modifier::AccessModifer::Public,
-            true,
-            sig,
-            "entrypoint",
-            vec![],
-        );
-        method.set_ops(ops);
-        method.add_attribute(crate::method::Attribute::EntryPoint);
-        method
-    } else {
-        panic!("Unsuported entrypoint wrapper signature! entrypoint:{entrypoint:?}");
+            CILOp::ConvUSize(false),
+        ]),
+        _ => panic!("Unsuported signature of an `.init_array` function {init:?}!"),
+    }
+    let returns_value = init.signature().output() != &Type::Void;
+    ops.push(CILOp::Call(Box::new(init)));
+    if returns_value {
+        ops.push(CILOp::Pop);
     }
+    ops.push(CILOp::Ret);
+    let mut method = Method::new(
+        crate::access_modifier::AccessModifer::Public,
+        true,
+        FnSig::new(&[], &Type::Void),
+        // Zero-padded, so that sorting by name sorts by priority.
+        &format!("{INIT_ARRAY_PREFIX}{priority:05}_{name}"),
+        vec![],
+    );
+    method.set_ops(ops);
+    method
+}
+/// Returns the call site of `System.Environment.Exit(int32)`.
+pub fn exit() -> Box<CallSite> {
+    CallSite::boxed(
+        Some(crate::utilis::environment_class()),
+        "Exit".into(),
+        FnSig::new(&[Type::I32], &Type::Void),
+        true,
+    )
+}
+/// Returns the ops flushing `System.Console.Out`, so that no output is lost when the program exits.
+pub fn flush_stdout() -> [CILOp; 2] {
+    let text_writer =
+        DotnetTypeRef::new(Some("System.Runtime"), "System.IO.TextWriter").with_valuetype(false);
+    [
+        CILOp::Call(CallSite::boxed(
+            Some(
+                DotnetTypeRef::new(Some("System.Console"), "System.Console").with_valuetype(false),
+            ),
+            "get_Out".into(),
+            FnSig::new(&[], &Type::DotnetType(Box::new(text_writer.clone()))),
+            true,
+        )),
+        CILOp::CallVirt(CallSite::boxed(
+            Some(text_writer.clone()),
+            "Flush".into(),
+            FnSig::new(&[Type::DotnetType(Box::new(text_writer))], &Type::Void),
+            false,
+        )),
+    ]
+}
+#[test]
+fn init_array_priorities() {
+    assert_eq!(init_array_priority(".init_array.00099"), Some(99));
+    assert_eq!(init_array_priority(".init_array"), Some(u16::MAX));
+    assert_eq!(init_array_priority(".init_arrayx"), None);
+    assert_eq!(init_array_priority(".data"), None);
 }
@@ -0,0 +1,154 @@
+//! Wide pointers: pointers to dynamically sized types(slices, `str` and trait objects).
+//!
+//! All wide pointers share one representation: a [`FAT_PTR_NAME`] struct, holding the address of the data(`data_address`), followed by the
+//! pointer metadata(`metadata`). The metadata is the length of a slice or `str`, or the address of the vtable of a trait object. Both are
+//! pointer-sized, so one type is enough for all wide pointers, and converting between them(eg. `*const [u8]` to `*const str`) does not change
+//! their representation.
+use crate::{
+    cil::{CILOp, FieldDescriptor},
+    r#type::{DotnetTypeRef, Type, TypeDef},
+};
+use rustc_middle::ty::{ParamEnv, Ty, TyCtxt, TyKind};
+/// The name of the type of wide pointers.
+pub const FAT_PTR_NAME: &str = "PtrComponents";
+/// Returns the definition of the type of wide pointers.
+#[must_use]
+pub fn fat_ptr_def() -> TypeDef {
+    TypeDef::ptr_components(FAT_PTR_NAME, Type::USize)
+}
+/// Returns a reference to the type of wide pointers.
+#[must_use]
+pub fn fat_ptr_type() -> DotnetTypeRef {
+    DotnetTypeRef::new(None, FAT_PTR_NAME)
+}
+/// Returns the descriptor of the field holding the address of the data behind a wide pointer.
+#[must_use]
+pub fn data_address_field() -> FieldDescriptor {
+    FieldDescriptor::new(
+        fat_ptr_type(),
+        Type::Ptr(Type::Void.into()),
+        "data_address".into(),
+    )
+}
+/// Returns the descriptor of the field holding the metadata of a wide pointer.
+#[must_use]
+pub fn metadata_field() -> FieldDescriptor {
+    FieldDescriptor::new(fat_ptr_type(), Type::USize, "metadata".into())
+}
+/// Returns true if pointers to `pointee` are wide pointers.
+pub fn is_wide_pointee(pointee: Ty) -> bool {
+    matches!(
+        pointee.kind(),
+        TyKind::Slice(_) | TyKind::Str | TyKind::Dynamic(..)
+    )
+}
+/// Returns the ops creating a wide pointer out of the thin pointer loaded by `data_address`, and the metadata loaded by `metadata`.
+#[must_use]
+pub fn make_fat_ptr(data_address: Vec<CILOp>, metadata: Vec<CILOp>) -> Vec<CILOp> {
+    let mut ops = vec![
+        CILOp::NewTMPLocal(Type::DotnetType(fat_ptr_type().into()).into()),
+        CILOp::LoadAddresOfTMPLocal,
+    ];
+    ops.extend(data_address);
+    ops.extend([
+        CILOp::STField(data_address_field().into()),
+        CILOp::LoadAddresOfTMPLocal,
+    ]);
+    ops.extend(metadata);
+    ops.extend([
+        CILOp::STField(metadata_field().into()),
+        CILOp::LoadTMPLocal,
+        CILOp::FreeTMPLocal,
+    ]);
+    ops
+}
+/// Returns the op replacing the wide pointer(or its address) on top of the stack with its data address.
+#[must_use]
+pub fn load_data_address() -> CILOp {
+    CILOp::LDField(data_address_field().into())
+}
+/// Returns the op replacing the wide pointer(or its address) on top of the stack with its metadata.
+#[must_use]
+pub fn load_metadata() -> CILOp {
+    CILOp::LDField(metadata_field().into())
+}
+/// Returns the ops calculating the size(or, if `align` is true, the alignment) of the value of type `pointee` behind the pointer loaded by
+/// `ptr`. The size of sized types is known at compile time. The size of slices is calculated from their length, and the size and alignment
+/// of trait objects are read from their vtables.
+pub(crate) fn size_or_align_of_val<'tcx>(
+    ptr: Vec<CILOp>,
+    pointee: Ty<'tcx>,
+    align: bool,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<CILOp> {
+    let layout_value = |ty: Ty<'tcx>| {
+        let layout = tcx
+            .layout_of(ParamEnv::reveal_all().and(ty))
+            .expect("Could not get the layout of a type!");
+        if align {
+            layout.align.abi.bytes()
+        } else {
+            layout.size.bytes()
+        }
+    };
+    match pointee.kind() {
+        TyKind::Slice(element) => {
+            if align {
+                return vec![
+                    CILOp::LdcI64(layout_value(*element) as i64),
+                    CILOp::ConvUSize(false),
+                ];
+            }
+            let mut ops = ptr;
+            ops.extend([
+                load_metadata(),
+                CILOp::LdcI64(layout_value(*element) as i64),
+                CILOp::ConvUSize(false),
+                CILOp::Mul,
+            ]);
+            ops
+        }
+        TyKind::Str => {
+            if align {
+                return vec![CILOp::LdcI32(1), CILOp::ConvUSize(false)];
+            }
+            let mut ops = ptr;
+            ops.push(load_metadata());
+            ops
+        }
+        // The size and alignment are the 2nd and 3rd entry of a vtable, right after the drop glue.
+        TyKind::Dynamic(..) => {
+            let mut ops = vec![CILOp::NewTMPLocal(
+                Type::DotnetType(fat_ptr_type().into()).into(),
+            )];
+            ops.extend(ptr);
+            ops.push(CILOp::SetTMPLocal);
+            ops.extend(crate::vtable::vtable_slot(
+                &[CILOp::LoadAddresOfTMPLocal],
+                if align { 2 } else { 1 },
+                tcx,
+            ));
+            ops.push(CILOp::FreeTMPLocal);
+            ops
+        }
+        _ if pointee.is_sized(tcx, ParamEnv::reveal_all()) => {
+            vec![
+                CILOp::LdcI64(layout_value(pointee) as i64),
+                CILOp::ConvUSize(false),
+            ]
+        }
+        _ => todo!("Can't yet get the size or alignment of a value of type {pointee:?}!"),
+    }
+}
+#[test]
+fn fat_ptr_fields() {
+    let ops = make_fat_ptr(vec![CILOp::LDArg(0)], vec![CILOp::LDArg(1)]);
+    assert_eq!(ops.len(), 9);
+    assert_eq!(ops[3], CILOp::STField(data_address_field().into()));
+    assert_eq!(ops[6], CILOp::STField(metadata_field().into()));
+    assert_eq!(fat_ptr_def().fields().len(), 2);
+    assert_eq!(
+        Type::DotnetType(fat_ptr_type().into()).metadata(),
+        Type::USize
+    );
+}
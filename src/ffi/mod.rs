@@ -1,3 +1,4 @@
+use crate::config::DotnetApi;
 use crate::r#type::DotnetTypeRef;
 use crate::{
     access_modifier::AccessModifer,
@@ -7,6 +8,7 @@ use crate::{
     method::Method,
     r#type::Type,
 };
+use rustc_ast::expand::allocator::AllocatorKind;
 use rustc_middle::ty::TyCtxt;
 macro_rules! add_method {
     ($name:ident,$input:expr,$output:expr,$ops:expr) => {
@@ -55,9 +57,14 @@ pub fn insert_ffi_functions(asm: &mut Assembly, tyctx: TyCtxt) {
     asm.add_typedef(crate::r#type::TypeDef::nameonly("Foreign"));
     asm.add_typedef(crate::r#type::TypeDef::nameonly("RustStr"));
     //rust_slice(asm);
+    helpers(asm);
+    libc_helpers(asm);
     math(asm);
-    io(asm);
+    io(asm, &c_void);
+    mem(asm, &c_void);
     unlikely(asm);
+    check_char(asm);
+    check_assumption(asm);
     //malloc(asm);
     let mut marshal = DotnetTypeRef::new(
         Some("System.Runtime.InteropServices"),
@@ -105,6 +112,112 @@ pub fn insert_ffi_functions(asm: &mut Assembly, tyctx: TyCtxt) {
         CILOp::Ret,
     ]);
     asm.add_method(realloc);
+    // Only the crate linking the final artifact knows which allocator is used, so only it defines the allocator shims, like with other backends.
+    match tyctx.allocator_kind(()) {
+        Some(AllocatorKind::Global) => global_alloc_forwarders(asm),
+        Some(AllocatorKind::Default) => {
+            if crate::config::config().supports(DotnetApi::NativeMemory) {
+                native_mem_alloc(asm);
+            } else {
+                marshal_aligned_alloc(asm, marshal.clone());
+                realloc_and_zeroed_from_alloc(asm);
+            }
+        }
+        None => (),
+    }
+    let mut free = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(&[Type::Ptr(c_void.clone().into())], &Type::Void),
+        "free",
+        vec![],
+    );
+    free.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::Call(CallSite::boxed(
+            marshal.clone(),
+            "FreeHGlobal".into(),
+            FnSig::new(&[Type::ISize], &Type::Void),
+            true,
+        )),
+        CILOp::Ret,
+    ]);
+    asm.add_method(free);
+    abort(asm);
+    exit(asm);
+}
+
+/// Returns the default implementation of the weak lang item(or another symbol the platform normally provides) called by `site`, or `None` if `site`
+/// has none. The linker adds those defaults to programs which call, but do not define, such symbols.
+pub fn weak_default(site: &CallSite) -> Option<Method> {
+    let ops = match site.name() {
+        // Nothing is unwound using the personality routine, since panics are .NET exceptions. Returns `_URC_NO_REASON`.
+        "rust_eh_personality" => match site.signature().output() {
+            Type::Void => vec![CILOp::Ret],
+            Type::I32 | Type::U32 => vec![CILOp::LdcI32(0), CILOp::Ret],
+            _ => return None,
+        },
+        // Like the default `alloc_error_handler`, aborts the process.
+        "__rust_alloc_error_handler" | "__rg_oom" | "__rdl_oom" | "rust_oom" => [
+            CILOp::fail_fast("Memory allocation failed: out of memory.").as_slice(),
+            &[CILOp::LdNull, CILOp::Throw],
+        ]
+        .concat(),
+        // The .NET runtime checks for stack overflows on its own.
+        "__rust_probestack" => vec![CILOp::Ret],
+        _ => return None,
+    };
+    let mut method = Method::new(
+        AccessModifer::Private,
+        true,
+        site.signature().clone(),
+        site.name(),
+        vec![],
+    );
+    method.set_ops(ops);
+    Some(method)
+}
+/// Implements the allocator shims by forwarding them to the `#[global_allocator]`. The `#[global_allocator]` macro exposes the methods
+/// of its `GlobalAlloc` impl as functions named `__rg_*`.
+fn global_alloc_forwarders(asm: &mut Assembly) {
+    let ptr = || Type::Ptr(Type::U8.into());
+    let shims = [
+        ("alloc", vec![Type::USize, Type::USize], ptr()),
+        ("dealloc", vec![ptr(), Type::USize, Type::USize], Type::Void),
+        (
+            "realloc",
+            vec![ptr(), Type::USize, Type::USize, Type::USize],
+            ptr(),
+        ),
+        ("alloc_zeroed", vec![Type::USize, Type::USize], ptr()),
+    ];
+    for (name, inputs, output) in shims {
+        let sig = FnSig::new(&inputs, &output);
+        let mut ops: Vec<_> = (0..inputs.len())
+            .map(|arg| CILOp::LDArg(arg as u32))
+            .collect();
+        ops.extend([
+            CILOp::Call(CallSite::boxed(
+                None,
+                format!("__rg_{name}").into(),
+                sig.clone(),
+                true,
+            )),
+            CILOp::Ret,
+        ]);
+        let mut method = Method::new(
+            AccessModifer::Private,
+            true,
+            sig,
+            &format!("__rust_{name}"),
+            vec![],
+        );
+        method.set_ops(ops);
+        asm.add_method(method);
+    }
+}
+/// Implements `__rust_alloc`, `__rust_dealloc`, `__rust_realloc` and `__rust_alloc_zeroed` using `NativeMemory`.
+fn native_mem_alloc(asm: &mut Assembly) {
     let mut native_mem = DotnetTypeRef::new(
         Some("System.Runtime.InteropServices"),
         "System.Runtime.InteropServices.NativeMemory",
@@ -151,52 +264,634 @@ pub fn insert_ffi_functions(asm: &mut Assembly, tyctx: TyCtxt) {
         CILOp::Ret,
     ]);
     asm.add_method(__rust_dealloc);
-    let mut free = Method::new(
+    let mut __rust_realloc = Method::new(
         AccessModifer::Private,
         true,
-        FnSig::new(&[Type::Ptr(c_void.clone().into())], &Type::Void),
-        "free",
+        FnSig::new(
+            &[
+                Type::Ptr(Type::U8.into()),
+                Type::USize,
+                Type::USize,
+                Type::USize,
+            ],
+            &Type::Ptr(Type::U8.into()),
+        ),
+        "__rust_realloc",
         vec![],
     );
-    free.set_ops(vec![
+    // AlignedRealloc(ptr, new_size, align)
+    __rust_realloc.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LDArg(3),
+        CILOp::LDArg(2),
+        CILOp::Call(CallSite::boxed(
+            native_mem.clone(),
+            "AlignedRealloc".into(),
+            FnSig::new(
+                &[Type::Ptr(Type::Void.into()), Type::USize, Type::USize],
+                &Type::Ptr(Type::Void.into()),
+            ),
+            true,
+        )),
+        CILOp::Ret,
+    ]);
+    asm.add_method(__rust_realloc);
+    let mut __rust_alloc_zeroed = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(&[Type::USize, Type::USize], &Type::Ptr(Type::U8.into())),
+        "__rust_alloc_zeroed",
+        vec![],
+    );
+    __rust_alloc_zeroed.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::Call(CallSite::boxed(
+            native_mem.clone(),
+            "AlignedAlloc".into(),
+            FnSig::new(&[Type::USize, Type::USize], &Type::Ptr(Type::Void.into())),
+            true,
+        )),
+        CILOp::Dup,
+        CILOp::LDArg(0),
+        CILOp::Call(CallSite::boxed(
+            native_mem,
+            "Clear".into(),
+            FnSig::new(&[Type::Ptr(Type::Void.into()), Type::USize], &Type::Void),
+            true,
+        )),
+        CILOp::Ret,
+    ]);
+    asm.add_method(__rust_alloc_zeroed);
+}
+/// Implements `__rust_realloc` and `__rust_alloc_zeroed` using `__rust_alloc` and `__rust_dealloc`, for allocators which can't do that directly.
+/// Reallocating allocates a new buffer with the same alignment, and copies the contents of the old one into it.
+fn realloc_and_zeroed_from_alloc(asm: &mut Assembly) {
+    let ptr = || Type::Ptr(Type::U8.into());
+    let rust_alloc = || {
+        CILOp::Call(CallSite::boxed(
+            None,
+            "__rust_alloc".into(),
+            FnSig::new(&[Type::USize, Type::USize], &ptr()),
+            true,
+        ))
+    };
+    let mut __rust_realloc = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(&[ptr(), Type::USize, Type::USize, Type::USize], &ptr()),
+        "__rust_realloc",
+        vec![(None, ptr()), (None, Type::USize)],
+    );
+    __rust_realloc.set_ops(vec![
+        // new = __rust_alloc(new_size, align)
+        CILOp::LDArg(3),
+        CILOp::LDArg(2),
+        rust_alloc(),
+        CILOp::STLoc(0),
+        // copied = min(old_size, new_size)
+        CILOp::LDArg(1),
+        CILOp::STLoc(1),
+        CILOp::LDArg(3),
+        CILOp::LDArg(1),
+        CILOp::BGe(0),
+        CILOp::LDArg(3),
+        CILOp::STLoc(1),
+        CILOp::Label(0),
+        CILOp::LDLoc(0),
         CILOp::LDArg(0),
+        CILOp::LDLoc(1),
+        CILOp::CpBlk,
+        // __rust_dealloc(ptr, old_size, align)
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::LDArg(2),
+        CILOp::Call(CallSite::boxed(
+            None,
+            "__rust_dealloc".into(),
+            FnSig::new(&[ptr(), Type::USize, Type::USize], &Type::Void),
+            true,
+        )),
+        CILOp::LDLoc(0),
+        CILOp::Ret,
+    ]);
+    asm.add_method(__rust_realloc);
+    let mut __rust_alloc_zeroed = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(&[Type::USize, Type::USize], &ptr()),
+        "__rust_alloc_zeroed",
+        vec![(None, ptr()), (None, Type::USize)],
+    );
+    __rust_alloc_zeroed.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        rust_alloc(),
+        CILOp::STLoc(0),
+        // for idx in 0..size { *(ptr + idx) = 0 }
+        CILOp::LdcI32(0),
+        CILOp::ConvUSize(false),
+        CILOp::STLoc(1),
+        CILOp::Label(0),
+        CILOp::LDLoc(1),
+        CILOp::LDArg(0),
+        CILOp::BGe(1),
+        CILOp::LDLoc(0),
+        CILOp::LDLoc(1),
+        CILOp::Add,
+        CILOp::LdcI32(0),
+        CILOp::STIndI8,
+        CILOp::LDLoc(1),
+        CILOp::LdcI32(1),
+        CILOp::ConvUSize(false),
+        CILOp::Add,
+        CILOp::STLoc(1),
+        CILOp::GoTo(0),
+        CILOp::Label(1),
+        CILOp::LDLoc(0),
+        CILOp::Ret,
+    ]);
+    asm.add_method(__rust_alloc_zeroed);
+}
+/// Implements `__rust_alloc` and `__rust_dealloc` using `Marshal`, for target frameworks without `NativeMemory`.
+/// `Marshal` can't allocate aligned memory, so a bigger buffer is allocated, and the pointer returned to the caller is aligned manually.
+/// The pointer to the whole buffer is stored right before the aligned pointer, so that it can be freed later.
+fn marshal_aligned_alloc(asm: &mut Assembly, marshal: Option<DotnetTypeRef>) {
+    let header = || CILOp::SizeOf(Type::ISize.into());
+    let align_mask = || {
+        [
+            CILOp::LDArg(1),
+            CILOp::LdcI32(1),
+            CILOp::ConvUSize(false),
+            CILOp::Sub,
+        ]
+    };
+    let mut __rust_alloc = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(&[Type::USize, Type::USize], &Type::Ptr(Type::U8.into())),
+        "__rust_alloc",
+        vec![(None, Type::ISize), (None, Type::ISize)],
+    );
+    let mut ops = vec![
+        // buffer = AllocHGlobal(size + align + header)
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::Add,
+        header(),
+        CILOp::Add,
         CILOp::Call(CallSite::boxed(
             marshal.clone(),
+            "AllocHGlobal".into(),
+            FnSig::new(&[Type::ISize], &Type::ISize),
+            true,
+        )),
+        CILOp::STLoc(0),
+        // aligned = (buffer + header + align - 1) & !(align - 1)
+        CILOp::LDLoc(0),
+        header(),
+        CILOp::Add,
+    ];
+    ops.extend(align_mask());
+    ops.push(CILOp::Add);
+    ops.extend(align_mask());
+    ops.extend([
+        CILOp::Not,
+        CILOp::And,
+        CILOp::STLoc(1),
+        // *(aligned - header) = buffer
+        CILOp::LDLoc(1),
+        header(),
+        CILOp::Sub,
+        CILOp::LDLoc(0),
+        CILOp::STIndISize,
+        CILOp::LDLoc(1),
+        CILOp::Ret,
+    ]);
+    __rust_alloc.set_ops(ops);
+    asm.add_method(__rust_alloc);
+    let mut __rust_dealloc = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(
+            &[Type::Ptr(Type::U8.into()), Type::USize, Type::USize],
+            &Type::Void,
+        ),
+        "__rust_dealloc",
+        vec![],
+    );
+    __rust_dealloc.set_ops(vec![
+        CILOp::LDArg(0),
+        header(),
+        CILOp::Sub,
+        CILOp::LDIndISize,
+        CILOp::Call(CallSite::boxed(
+            marshal,
             "FreeHGlobal".into(),
             FnSig::new(&[Type::ISize], &Type::Void),
             true,
         )),
         CILOp::Ret,
     ]);
-    asm.add_method(free);
-    let mut volatile_load = Method::new(
+    asm.add_method(__rust_dealloc);
+}
+/// Helpers called by the op sequences created by `CILOp::throw_msg`, `CILOp::debug_msg` and `CILOp::debug_msg_no_nl`, and by panics.
+fn helpers(asm: &mut Assembly) {
+    rust_exception(asm);
+    rust_print(asm);
+    rust_print_no_nl(asm);
+    asm.add_static(crate::utilis::string_class().into(), "rust_panic_message");
+    rust_panic_message(asm);
+}
+/// Returns the call site of `rust_panic_message() -> string`, which returns the message of the current panic.
+pub(crate) fn panic_message() -> Box<CallSite> {
+    CallSite::boxed(
+        None,
+        "rust_panic_message".into(),
+        FnSig::new(&[], &crate::utilis::string_class().into()),
+        true,
+    )
+}
+/// Returns the descriptor of the static field holding the message of the current panic.
+pub(crate) fn panic_message_static() -> Box<crate::cil::StaticFieldDescriptor> {
+    crate::cil::StaticFieldDescriptor::boxed(
+        None,
+        crate::utilis::string_class().into(),
+        "rust_panic_message".into(),
+    )
+}
+/// Helpers called by the libc shims(see [`crate::libc`]) and the entrypoint.
+fn libc_helpers(asm: &mut Assembly) {
+    rust_argc(asm);
+    rust_argv(asm);
+    rust_clock_gettime(asm);
+    rust_getenv(asm);
+    signal_helpers(asm);
+}
+/// Signal number of `SIGINT`.
+const SIGINT: i32 = 2;
+/// `atexit` must support at least this many handlers.
+const ATEXIT_MAX: i32 = 32;
+/// Signal handling: `SIGINT` handlers are called on `System.Console.CancelKeyPress`, and `atexit` handlers on `System.AppDomain.ProcessExit`.
+/// Handlers for other signals can't be registered: `signal` returns `SIG_ERR`, and `sigaction` returns -1.
+fn signal_helpers(asm: &mut Assembly) {
+    asm.add_static(Type::USize, "rust_sigint_handler");
+    asm.add_static(Type::U8, "rust_sigint_subscribed");
+    asm.add_static(Type::Ptr(Type::USize.into()), "rust_atexit_handlers");
+    asm.add_static(Type::I32, "rust_atexit_count");
+    signal(asm);
+    rust_sigaction(asm);
+    rust_on_sigint(asm);
+    atexit(asm);
+    rust_on_process_exit(asm);
+}
+/// Returns the descriptor of the static field `name`, with type `tpe`, inserted by [`signal_helpers`].
+fn signal_static(name: &str, tpe: Type) -> Box<crate::cil::StaticFieldDescriptor> {
+    crate::cil::StaticFieldDescriptor::boxed(None, tpe, name.into())
+}
+/// Returns the type of `System.Object`.
+fn object() -> Type {
+    Type::DotnetType(crate::utilis::object_class().into())
+}
+/// Returns the class `name`, defined in assembly `assembly`.
+fn class(assembly: &str, name: &str) -> DotnetTypeRef {
+    DotnetTypeRef::new(Some(assembly), name).with_valuetype(false)
+}
+/// Returns the ops creating a delegate of type `tpe`, calling the static method `target`.
+fn delegate(tpe: DotnetTypeRef, target: Box<CallSite>) -> [CILOp; 3] {
+    [
+        CILOp::LdNull,
+        CILOp::LDFtn(target),
+        CILOp::NewObj(CallSite::boxed(
+            Some(tpe.clone()),
+            ".ctor".into(),
+            FnSig::new(
+                &[Type::DotnetType(tpe.into()), object(), Type::ISize],
+                &Type::Void,
+            ),
+            false,
+        )),
+    ]
+}
+/// Returns the type of `string[]`.
+fn string_array() -> Type {
+    Type::DotnetArray(Box::new(crate::r#type::DotnetArray {
+        element: crate::utilis::string_class().into(),
+        dimensions: 1,
+    }))
+}
+/// Returns the call site of `System.Environment::GetCommandLineArgs()`.
+fn command_line_args() -> Box<CallSite> {
+    CallSite::boxed(
+        Some(crate::utilis::environment_class()),
+        "GetCommandLineArgs".into(),
+        FnSig::new(&[], &string_array()),
+        true,
+    )
+}
+/// Returns the call site of `System.Runtime.InteropServices.Marshal::{name}`, with signature `sig`.
+fn marshal(name: &str, sig: FnSig) -> Box<CallSite> {
+    CallSite::boxed(
+        Some(
+            DotnetTypeRef::new(
+                Some("System.Runtime.InteropServices"),
+                "System.Runtime.InteropServices.Marshal",
+            )
+            .with_valuetype(false),
+        ),
+        name.into(),
+        sig,
+        true,
+    )
+}
+/// Returns the call site of `System.String::Concat(string, string)`.
+pub(crate) fn string_concat() -> Box<CallSite> {
+    let string: Type = crate::utilis::string_class().into();
+    CallSite::boxed(
+        Some(crate::utilis::string_class()),
+        "Concat".into(),
+        FnSig::new(&[string.clone(), string.clone()], &string),
+        true,
+    )
+}
+/// Returns the call site of `System.Diagnostics.Stopwatch::{name}()`.
+fn stopwatch(name: &str) -> Box<CallSite> {
+    CallSite::boxed(
+        Some(
+            DotnetTypeRef::new(Some("System.Runtime"), "System.Diagnostics.Stopwatch")
+                .with_valuetype(false),
+        ),
+        name.into(),
+        FnSig::new(&[], &Type::I64),
+        true,
+    )
+}
+/// Returns the call site of `System.Console::{name}(string)`.
+fn console_write(name: &str) -> Box<CallSite> {
+    CallSite::boxed(
+        Some(DotnetTypeRef::new(Some("System.Console"), "System.Console").with_valuetype(false)),
+        name.into(),
+        FnSig::new(&[crate::utilis::string_class().into()], &Type::Void),
+        true,
+    )
+}
+/// The libc memory and string functions `core` and `std` call: `memcmp`, `memset`, `memmove` and `strlen`. Their signatures match
+/// the declarations in `core` and `libc`, so `memset` and `memmove` take `c_void` pointers, and `strlen` takes a `c_char`(`i8`) pointer.
+fn mem(asm: &mut Assembly, c_void: &Type) {
+    let void_ptr = Type::Ptr(c_void.clone().into());
+    let idx = || vec![(Some("idx".into()), Type::ISize)];
+    let mut memset = Method::new(
         AccessModifer::Private,
         true,
-        FnSig::new(&[Type::Ptr(Type::U8.into())], &Type::U8),
-        "volatile_load",
-        vec![],
+        FnSig::new(&[void_ptr.clone(), Type::I32, Type::USize], &void_ptr),
+        "memset",
+        idx(),
     );
-    volatile_load.set_ops(vec![CILOp::LDArg(0), CILOp::LDIndI8, CILOp::Ret]);
-    asm.add_method(volatile_load);
-    let mut volatile_load = Method::new(
+    memset.set_ops(vec![
+        CILOp::LdcI32(0),
+        CILOp::ConvISize(false),
+        CILOp::STLoc(0),
+        // while idx < n { dst[idx] = c as u8; idx += 1 }
+        CILOp::Label(0),
+        CILOp::LDLoc(0),
+        CILOp::LDArg(2),
+        CILOp::BGe(1),
+        CILOp::LDArg(0),
+        CILOp::LDLoc(0),
+        CILOp::Add,
+        CILOp::LDArg(1),
+        CILOp::STIndI8,
+        CILOp::LDLoc(0),
+        CILOp::LdcI32(1),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        CILOp::STLoc(0),
+        CILOp::GoTo(0),
+        CILOp::Label(1),
+        CILOp::LDArg(0),
+        CILOp::Ret,
+    ]);
+    asm.add_method(memset);
+    let mut memmove = Method::new(
         AccessModifer::Private,
         true,
-        FnSig::new(&[Type::Ptr(Type::USize.into())], &Type::USize),
-        "volatile_load",
-        vec![],
+        FnSig::new(
+            &[void_ptr.clone(), void_ptr.clone(), Type::USize],
+            &void_ptr,
+        ),
+        "memmove",
+        idx(),
     );
-    volatile_load.set_ops(vec![CILOp::LDArg(0), CILOp::LDIndISize, CILOp::Ret]);
-    asm.add_method(volatile_load);
-    abort(asm);
+    memmove.set_ops(vec![
+        // `cpblk` does not allow overlapping buffers, so it is only used if `dst + n <= src` or `src + n <= dst`.
+        CILOp::LDArg(0),
+        CILOp::LDArg(2),
+        CILOp::Add,
+        CILOp::LDArg(1),
+        CILOp::BLe(0),
+        CILOp::LDArg(1),
+        CILOp::LDArg(2),
+        CILOp::Add,
+        CILOp::LDArg(0),
+        CILOp::BLe(0),
+        // Overlapping buffers are copied byte by byte, starting from the side which will not be overwritten before it is read.
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::BLt(1),
+        // idx = n; while idx != 0 { idx -= 1; dst[idx] = src[idx] }
+        CILOp::LDArg(2),
+        CILOp::STLoc(0),
+        CILOp::Label(2),
+        CILOp::LDLoc(0),
+        CILOp::BZero(3),
+        CILOp::LDLoc(0),
+        CILOp::LdcI32(1),
+        CILOp::ConvISize(false),
+        CILOp::Sub,
+        CILOp::STLoc(0),
+        CILOp::LDArg(0),
+        CILOp::LDLoc(0),
+        CILOp::Add,
+        CILOp::LDArg(1),
+        CILOp::LDLoc(0),
+        CILOp::Add,
+        CILOp::LDIndI8,
+        CILOp::STIndI8,
+        CILOp::GoTo(2),
+        CILOp::Label(0),
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::LDArg(2),
+        CILOp::CpBlk,
+        CILOp::GoTo(3),
+        // idx = 0; while idx < n { dst[idx] = src[idx]; idx += 1 }
+        CILOp::Label(1),
+        CILOp::LdcI32(0),
+        CILOp::ConvISize(false),
+        CILOp::STLoc(0),
+        CILOp::Label(4),
+        CILOp::LDLoc(0),
+        CILOp::LDArg(2),
+        CILOp::BGe(3),
+        CILOp::LDArg(0),
+        CILOp::LDLoc(0),
+        CILOp::Add,
+        CILOp::LDArg(1),
+        CILOp::LDLoc(0),
+        CILOp::Add,
+        CILOp::LDIndI8,
+        CILOp::STIndI8,
+        CILOp::LDLoc(0),
+        CILOp::LdcI32(1),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        CILOp::STLoc(0),
+        CILOp::GoTo(4),
+        CILOp::Label(3),
+        CILOp::LDArg(0),
+        CILOp::Ret,
+    ]);
+    asm.add_method(memmove);
+    memcmp(asm);
+    strlen(asm);
 }
-
 fn math(asm: &mut Assembly) {
     sqrtf32(asm);
 }
-fn io(asm: &mut Assembly) {
+fn io(asm: &mut Assembly, c_void: &Type) {
     puts(asm);
+    read(asm, c_void);
+}
+/// libc `read(fd, buf, count) -> ssize_t`. Only stdin(fd 0) can be read, using the stream returned by `System.Console.OpenStandardInput`.
+/// The stream is not buffered, and returns the bytes as they arrive(not whole lines), so `std::io::stdin` buffers them, like on other platforms.
+fn read(asm: &mut Assembly, c_void: &Type) {
+    let stream = class("System.Runtime", "System.IO.Stream");
+    let mut span = DotnetTypeRef::new(Some("System.Runtime"), "System.Span`1");
+    span.set_generics([Type::U8]);
+    let mut read = Method::new(
+        AccessModifer::Private,
+        true,
+        FnSig::new(
+            &[Type::I32, Type::Ptr(c_void.clone().into()), Type::USize],
+            &Type::ISize,
+        ),
+        "read",
+        vec![(Some("len".into()), Type::I32)],
+    );
+    read.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::BZero(0),
+        CILOp::LdcI32(-1),
+        CILOp::ConvISize(false),
+        CILOp::Ret,
+        // len = min(count, i32::MAX), since spans are indexed using `int`.
+        CILOp::Label(0),
+        CILOp::LdcI32(i32::MAX),
+        CILOp::STLoc(0),
+        CILOp::LDArg(2),
+        CILOp::LdcI32(i32::MAX),
+        CILOp::ConvUSize(false),
+        CILOp::BGe(1),
+        CILOp::LDArg(2),
+        CILOp::ConvI32(false),
+        CILOp::STLoc(0),
+        // Console.OpenStandardInput().Read(new Span<byte>(buf, len))
+        CILOp::Label(1),
+        CILOp::Call(CallSite::boxed(
+            Some(class("System.Console", "System.Console")),
+            "OpenStandardInput".into(),
+            FnSig::new(&[], &Type::DotnetType(stream.clone().into())),
+            true,
+        )),
+        CILOp::LDArg(1),
+        CILOp::LDLoc(0),
+        CILOp::NewObj(CallSite::boxed(
+            Some(span.clone()),
+            ".ctor".into(),
+            FnSig::new(
+                &[
+                    Type::DotnetType(span.clone().into()),
+                    Type::Ptr(Type::Void.into()),
+                    Type::I32,
+                ],
+                &Type::Void,
+            ),
+            false,
+        )),
+        CILOp::CallVirt(CallSite::boxed(
+            Some(stream.clone()),
+            "Read".into(),
+            FnSig::new(
+                &[
+                    Type::DotnetType(stream.into()),
+                    Type::DotnetType(span.into()),
+                ],
+                &Type::I32,
+            ),
+            false,
+        )),
+        CILOp::ConvISize(false),
+        CILOp::Ret,
+    ]);
+    asm.add_method(read);
 }
 
+add_method!(
+    rust_exception,
+    &[crate::utilis::string_class().into()],
+    &crate::utilis::exception_class().into(),
+    [
+        CILOp::LDArg(0),
+        CILOp::NewObj(CallSite::boxed(
+            Some(crate::utilis::exception_class()),
+            ".ctor".into(),
+            FnSig::new(
+                &[
+                    crate::utilis::exception_class().into(),
+                    crate::utilis::string_class().into()
+                ],
+                &Type::Void
+            ),
+            false,
+        )),
+        CILOp::Ret
+    ]
+);
+// Panics started without a message(eg. by a `#[panic_handler]` calling `__rust_start_panic` directly) use a generic one.
+add_method!(
+    rust_panic_message,
+    &[],
+    &crate::utilis::string_class().into(),
+    [
+        CILOp::LDStaticField(panic_message_static()),
+        CILOp::Dup,
+        CILOp::BTrue(0),
+        CILOp::Pop,
+        CILOp::LdStr("Rust panic".into()),
+        CILOp::Label(0),
+        CILOp::Ret,
+    ]
+);
+add_method!(
+    rust_print,
+    &[crate::utilis::string_class().into()],
+    &Type::Void,
+    [
+        CILOp::LDArg(0),
+        CILOp::Call(console_write("WriteLine")),
+        CILOp::Ret
+    ]
+);
+add_method!(
+    rust_print_no_nl,
+    &[crate::utilis::string_class().into()],
+    &Type::Void,
+    [
+        CILOp::LDArg(0),
+        CILOp::Call(console_write("Write")),
+        CILOp::Ret
+    ]
+);
 add_method!(
     sqrtf32,
     &[Type::F32],
@@ -235,11 +930,658 @@ add_method!(
     ],
     [(None, Type::U8)]
 );
+// Compares the bytes as unsigned, and returns the difference of the first pair of different bytes.
+add_method!(
+    memcmp,
+    &[
+        Type::Ptr(Type::U8.into()),
+        Type::Ptr(Type::U8.into()),
+        Type::USize
+    ],
+    &Type::I32,
+    [
+        CILOp::LdcI32(0),
+        CILOp::ConvISize(false),
+        CILOp::STLoc(0),
+        // while idx < n
+        CILOp::Label(0),
+        CILOp::LDLoc(0),
+        CILOp::LDArg(2),
+        CILOp::BGe(1),
+        // diff = a[idx] - b[idx]
+        CILOp::LDArg(0),
+        CILOp::LDLoc(0),
+        CILOp::Add,
+        CILOp::LDIndI8,
+        CILOp::ConvU8(false),
+        CILOp::LDArg(1),
+        CILOp::LDLoc(0),
+        CILOp::Add,
+        CILOp::LDIndI8,
+        CILOp::ConvU8(false),
+        CILOp::Sub,
+        CILOp::STLoc(1),
+        CILOp::LDLoc(1),
+        CILOp::BTrue(2),
+        CILOp::LDLoc(0),
+        CILOp::LdcI32(1),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        CILOp::STLoc(0),
+        CILOp::GoTo(0),
+        CILOp::Label(1),
+        CILOp::LdcI32(0),
+        CILOp::Ret,
+        CILOp::Label(2),
+        CILOp::LDLoc(1),
+        CILOp::Ret,
+    ],
+    vec![
+        (Some("idx".into()), Type::ISize),
+        (Some("diff".into()), Type::I32),
+    ]
+);
+add_method!(
+    strlen,
+    &[Type::Ptr(Type::I8.into())],
+    &Type::USize,
+    [
+        CILOp::LdcI32(0),
+        CILOp::ConvISize(false),
+        CILOp::STLoc(0),
+        // while s[len] != 0 { len += 1 }
+        CILOp::Label(0),
+        CILOp::LDArg(0),
+        CILOp::LDLoc(0),
+        CILOp::Add,
+        CILOp::LDIndI8,
+        CILOp::BZero(1),
+        CILOp::LDLoc(0),
+        CILOp::LdcI32(1),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        CILOp::STLoc(0),
+        CILOp::GoTo(0),
+        CILOp::Label(1),
+        CILOp::LDLoc(0),
+        CILOp::Ret,
+    ],
+    vec![(Some("len".into()), Type::ISize)]
+);
 add_method!(
     unlikely,
     &[Type::Bool],
     &Type::Bool,
     [CILOp::LDArg(0), CILOp::Ret,]
 );
-//System.Environment.Exit(a_ExitCode)
-add_method!(abort, &[], &Type::Void, CILOp::throw_msg("Called abort!"));
+add_method!(
+    check_char,
+    &[Type::U32],
+    &Type::U32,
+    [
+        [
+            CILOp::LDArg(0),
+            CILOp::ConvU64(false),
+            CILOp::LdcI64(char::MAX as i64),
+            CILOp::Gt,
+            CILOp::BTrue(1),
+            CILOp::LDArg(0),
+            CILOp::LdcI32(0xD800),
+            CILOp::Lt,
+            CILOp::BTrue(0),
+            CILOp::LDArg(0),
+            CILOp::LdcI32(0xDFFF),
+            CILOp::Gt,
+            CILOp::BTrue(0),
+            CILOp::Label(1),
+        ]
+        .as_slice(),
+        &CILOp::throw_msg("Invalid char value!"),
+        &[CILOp::Label(0), CILOp::LDArg(0), CILOp::Ret],
+    ]
+    .concat()
+);
+add_method!(
+    check_assumption,
+    &[Type::Bool],
+    &Type::Void,
+    [
+        [CILOp::LDArg(0), CILOp::BTrue(0)].as_slice(),
+        &CILOp::throw_msg("Undefined behaviour! An `assume` intrinsic was called with `false`."),
+        &[CILOp::Label(0), CILOp::Ret],
+    ]
+    .concat()
+);
+add_method!(
+    rust_argc,
+    &[],
+    &Type::I32,
+    [
+        CILOp::Call(command_line_args()),
+        CILOp::CallVirt(CallSite::boxed(
+            Some(DotnetTypeRef::new(Some("System.Runtime"), "System.Array").with_valuetype(false)),
+            "get_Length".into(),
+            FnSig::new(&[string_array()], &Type::I32),
+            false,
+        )),
+        CILOp::Ret,
+    ]
+);
+// The arguments are joined into one UTF-8 buffer, separated by nulls. `argv` points to the start of each argument within it.
+add_method!(
+    rust_argv,
+    &[],
+    &Type::Ptr(Type::Ptr(Type::U8.into()).into()),
+    [
+        // buffer = StringToCoTaskMemUTF8(string.Join('\0', GetCommandLineArgs()))
+        CILOp::LdcI32(0),
+        CILOp::Call(command_line_args()),
+        CILOp::Call(CallSite::boxed(
+            Some(crate::utilis::string_class()),
+            "Join".into(),
+            FnSig::new(
+                &[Type::DotnetChar, string_array()],
+                &crate::utilis::string_class().into()
+            ),
+            true,
+        )),
+        CILOp::Call(marshal(
+            "StringToCoTaskMemUTF8",
+            FnSig::new(&[crate::utilis::string_class().into()], &Type::ISize)
+        )),
+        CILOp::STLoc(0),
+        // argv = AllocHGlobal((argc + 1) * sizeof(nint)); argv[0] = buffer
+        crate::libc::argc(),
+        CILOp::LdcI32(1),
+        CILOp::Add,
+        CILOp::ConvISize(false),
+        CILOp::SizeOf(Type::ISize.into()),
+        CILOp::Mul,
+        CILOp::Call(marshal(
+            "AllocHGlobal",
+            FnSig::new(&[Type::ISize], &Type::ISize)
+        )),
+        CILOp::STLoc(1),
+        CILOp::LDLoc(1),
+        CILOp::LDLoc(0),
+        CILOp::STIndISize,
+        CILOp::LdcI32(1),
+        CILOp::STLoc(2),
+        CILOp::LdcI32(0),
+        CILOp::ConvISize(false),
+        CILOp::STLoc(3),
+        // while arg < argc
+        CILOp::Label(0),
+        CILOp::LDLoc(2),
+        crate::libc::argc(),
+        CILOp::BGe(1),
+        // if buffer[idx] == 0 { argv[arg] = buffer + idx + 1; arg += 1 }
+        CILOp::LDLoc(0),
+        CILOp::LDLoc(3),
+        CILOp::Add,
+        CILOp::LDIndI8,
+        CILOp::BTrue(2),
+        CILOp::LDLoc(1),
+        CILOp::LDLoc(2),
+        CILOp::ConvISize(false),
+        CILOp::SizeOf(Type::ISize.into()),
+        CILOp::Mul,
+        CILOp::Add,
+        CILOp::LDLoc(0),
+        CILOp::LDLoc(3),
+        CILOp::Add,
+        CILOp::LdcI32(1),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        CILOp::STIndISize,
+        CILOp::LDLoc(2),
+        CILOp::LdcI32(1),
+        CILOp::Add,
+        CILOp::STLoc(2),
+        // idx += 1
+        CILOp::Label(2),
+        CILOp::LDLoc(3),
+        CILOp::LdcI32(1),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        CILOp::STLoc(3),
+        CILOp::GoTo(0),
+        // argv[argc] = null
+        CILOp::Label(1),
+        CILOp::LDLoc(1),
+        CILOp::LDLoc(2),
+        CILOp::ConvISize(false),
+        CILOp::SizeOf(Type::ISize.into()),
+        CILOp::Mul,
+        CILOp::Add,
+        CILOp::LdcI32(0),
+        CILOp::ConvISize(false),
+        CILOp::STIndISize,
+        CILOp::LDLoc(1),
+        CILOp::Ret,
+    ],
+    vec![
+        (Some("buffer".into()), Type::Ptr(Type::U8.into())),
+        (
+            Some("argv".into()),
+            Type::Ptr(Type::Ptr(Type::U8.into()).into())
+        ),
+        (Some("arg".into()), Type::I32),
+        (Some("idx".into()), Type::ISize),
+    ]
+);
+// Fills in a `timespec`(two 64 bit integers: seconds and nanoseconds). Clock 0(`CLOCK_REALTIME`) measures the time since the Unix epoch,
+// all other clocks are treated as monotonic.
+add_method!(
+    rust_clock_gettime,
+    &[Type::I32, Type::Ptr(Type::I64.into())],
+    &Type::I32,
+    [
+        CILOp::LDArg(0),
+        CILOp::BZero(0),
+        // ticks = Stopwatch.GetTimestamp(), in units of 1 / Stopwatch.Frequency seconds
+        CILOp::Call(stopwatch("GetTimestamp")),
+        CILOp::STLoc(0),
+        CILOp::LDArg(1),
+        CILOp::LDLoc(0),
+        CILOp::Call(stopwatch("get_Frequency")),
+        CILOp::Div,
+        CILOp::STIndI64,
+        CILOp::LDArg(1),
+        CILOp::LdcI32(8),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        CILOp::LDLoc(0),
+        CILOp::Call(stopwatch("get_Frequency")),
+        CILOp::Rem,
+        CILOp::LdcI64(1_000_000_000),
+        CILOp::Mul,
+        CILOp::Call(stopwatch("get_Frequency")),
+        CILOp::Div,
+        CILOp::STIndI64,
+        CILOp::LdcI32(0),
+        CILOp::Ret,
+        // ticks = DateTime.UtcNow.Ticks - UnixEpoch.Ticks, in units of 100 ns
+        CILOp::Label(0),
+        CILOp::Call(CallSite::boxed(
+            Some(date_time()),
+            "get_UtcNow".into(),
+            FnSig::new(&[], &Type::DotnetType(date_time().into())),
+            true,
+        )),
+        CILOp::STLoc(1),
+        CILOp::LDLocA(1),
+        CILOp::Call(CallSite::boxed(
+            Some(date_time()),
+            "get_Ticks".into(),
+            FnSig::new(
+                &[Type::ManagedReference(
+                    Type::DotnetType(date_time().into()).into()
+                )],
+                &Type::I64
+            ),
+            false,
+        )),
+        CILOp::LdcI64(621_355_968_000_000_000),
+        CILOp::Sub,
+        CILOp::STLoc(0),
+        CILOp::LDArg(1),
+        CILOp::LDLoc(0),
+        CILOp::LdcI64(10_000_000),
+        CILOp::Div,
+        CILOp::STIndI64,
+        CILOp::LDArg(1),
+        CILOp::LdcI32(8),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        CILOp::LDLoc(0),
+        CILOp::LdcI64(10_000_000),
+        CILOp::Rem,
+        CILOp::LdcI64(100),
+        CILOp::Mul,
+        CILOp::STIndI64,
+        CILOp::LdcI32(0),
+        CILOp::Ret,
+    ],
+    vec![
+        (Some("ticks".into()), Type::I64),
+        (Some("now".into()), Type::DotnetType(date_time().into())),
+    ]
+);
+// The returned string is never freed, but `std` copies it right away, and environment variables are rarely read.
+add_method!(
+    rust_getenv,
+    &[Type::Ptr(Type::U8.into())],
+    &Type::Ptr(Type::U8.into()),
+    [
+        CILOp::LDArg(0),
+        CILOp::Call(marshal(
+            "PtrToStringUTF8",
+            FnSig::new(&[Type::ISize], &crate::utilis::string_class().into())
+        )),
+        CILOp::Call(CallSite::boxed(
+            Some(crate::utilis::environment_class()),
+            "GetEnvironmentVariable".into(),
+            FnSig::new(
+                &[crate::utilis::string_class().into()],
+                &crate::utilis::string_class().into()
+            ),
+            true,
+        )),
+        CILOp::STLoc(0),
+        CILOp::LDLoc(0),
+        CILOp::BTrue(0),
+        CILOp::LdcI32(0),
+        CILOp::ConvUSize(false),
+        CILOp::Ret,
+        CILOp::Label(0),
+        CILOp::LDLoc(0),
+        CILOp::Call(marshal(
+            "StringToCoTaskMemUTF8",
+            FnSig::new(&[crate::utilis::string_class().into()], &Type::ISize)
+        )),
+        CILOp::Ret,
+    ],
+    vec![(Some("value".into()), crate::utilis::string_class().into())]
+);
+// libc `signal(signum, handler) -> sighandler_t`. `SIG_DFL`(0) and `SIG_IGN`(1) are accepted for all signals, since the runtime already
+// handles them(eg. `std` ignores `SIGPIPE`, which .NET never raises).
+add_method!(
+    signal,
+    &[Type::I32, Type::USize],
+    &Type::USize,
+    [
+        [
+            CILOp::LDArg(0),
+            CILOp::LdcI32(SIGINT),
+            CILOp::BEq(0),
+            CILOp::LDArg(1),
+            CILOp::LdcI32(1),
+            CILOp::ConvUSize(false),
+            CILOp::BLe(1),
+            // SIG_ERR
+            CILOp::LdcI32(-1),
+            CILOp::ConvISize(false),
+            CILOp::Ret,
+            CILOp::Label(1),
+            CILOp::LdcI32(0),
+            CILOp::ConvUSize(false),
+            CILOp::Ret,
+            // old = rust_sigint_handler; rust_sigint_handler = handler
+            CILOp::Label(0),
+            CILOp::LDStaticField(signal_static("rust_sigint_handler", Type::USize)),
+            CILOp::STLoc(0),
+            CILOp::LDArg(1),
+            CILOp::STStaticField(signal_static("rust_sigint_handler", Type::USize)),
+            // The event is subscribed to only once, and calls whatever handler is registered when it is raised.
+            CILOp::LDStaticField(signal_static("rust_sigint_subscribed", Type::U8)),
+            CILOp::BTrue(2),
+            CILOp::LdcI32(1),
+            CILOp::STStaticField(signal_static("rust_sigint_subscribed", Type::U8)),
+        ]
+        .as_slice(),
+        &delegate(cancel_handler(), on_sigint()),
+        &[
+            CILOp::Call(CallSite::boxed(
+                Some(class("System.Console", "System.Console")),
+                "add_CancelKeyPress".into(),
+                FnSig::new(&[Type::DotnetType(cancel_handler().into())], &Type::Void),
+                true,
+            )),
+            CILOp::Label(2),
+            CILOp::LDLoc(0),
+            CILOp::Ret,
+        ],
+    ]
+    .concat(),
+    vec![(Some("old".into()), Type::USize)]
+);
+// Called by the `sigaction` libc shim. Only the handler(the first field of `struct sigaction`) is read, and `oldact` is left as-is.
+add_method!(
+    rust_sigaction,
+    &[
+        Type::I32,
+        Type::Ptr(Type::USize.into()),
+        Type::Ptr(Type::USize.into())
+    ],
+    &Type::I32,
+    [
+        // A null `act` only queries the current handler.
+        CILOp::LDArg(1),
+        CILOp::BZero(0),
+        CILOp::LDArg(0),
+        CILOp::LDArg(1),
+        CILOp::LDIndISize,
+        CILOp::Call(CallSite::boxed(
+            None,
+            "signal".into(),
+            FnSig::new(&[Type::I32, Type::USize], &Type::USize),
+            true,
+        )),
+        CILOp::LdcI32(-1),
+        CILOp::ConvISize(false),
+        CILOp::BNe(0),
+        CILOp::LdcI32(-1),
+        CILOp::Ret,
+        CILOp::Label(0),
+        CILOp::LdcI32(0),
+        CILOp::Ret,
+    ]
+);
+/// Returns the `System.ConsoleCancelEventHandler` delegate type.
+fn cancel_handler() -> DotnetTypeRef {
+    class("System.Console", "System.ConsoleCancelEventHandler")
+}
+/// Returns the call site of `rust_on_sigint`.
+fn on_sigint() -> Box<CallSite> {
+    CallSite::boxed(
+        None,
+        "rust_on_sigint".into(),
+        FnSig::new(
+            &[
+                object(),
+                Type::DotnetType(class("System.Console", "System.ConsoleCancelEventArgs").into()),
+            ],
+            &Type::Void,
+        ),
+        true,
+    )
+}
+// Handles `System.Console.CancelKeyPress`(raised on ctrl-C) by calling the `SIGINT` handler. Unless the handler is `SIG_DFL`, the process keeps running.
+add_method!(
+    rust_on_sigint,
+    &[
+        object(),
+        Type::DotnetType(class("System.Console", "System.ConsoleCancelEventArgs").into())
+    ],
+    &Type::Void,
+    [
+        CILOp::LDStaticField(signal_static("rust_sigint_handler", Type::USize)),
+        CILOp::BZero(0),
+        CILOp::LDArg(1),
+        CILOp::LdcI32(1),
+        CILOp::Call(CallSite::boxed(
+            Some(class("System.Console", "System.ConsoleCancelEventArgs")),
+            "set_Cancel".into(),
+            FnSig::new(
+                &[
+                    Type::DotnetType(
+                        class("System.Console", "System.ConsoleCancelEventArgs").into()
+                    ),
+                    Type::Bool
+                ],
+                &Type::Void
+            ),
+            false,
+        )),
+        CILOp::LDStaticField(signal_static("rust_sigint_handler", Type::USize)),
+        CILOp::LdcI32(1),
+        CILOp::ConvUSize(false),
+        CILOp::BEq(0),
+        CILOp::LdcI32(SIGINT),
+        CILOp::LDStaticField(signal_static("rust_sigint_handler", Type::USize)),
+        CILOp::CallI(FnSig::new(&[Type::I32], &Type::Void).into()),
+        CILOp::Label(0),
+        CILOp::Ret,
+    ]
+);
+// libc `atexit(function) -> c_int`. The handlers are kept in an unmanaged array, allocated when the first one is registered.
+add_method!(
+    atexit,
+    &[Type::USize],
+    &Type::I32,
+    [
+        [
+            CILOp::LDStaticField(signal_static("rust_atexit_count", Type::I32)),
+            CILOp::LdcI32(ATEXIT_MAX),
+            CILOp::BLt(0),
+            CILOp::LdcI32(-1),
+            CILOp::Ret,
+            CILOp::Label(0),
+            CILOp::LDStaticField(signal_static(
+                "rust_atexit_handlers",
+                Type::Ptr(Type::USize.into())
+            )),
+            CILOp::BTrue(1),
+            CILOp::LdcI32(ATEXIT_MAX),
+            CILOp::ConvISize(false),
+            CILOp::SizeOf(Type::USize.into()),
+            CILOp::Mul,
+            CILOp::Call(marshal(
+                "AllocHGlobal",
+                FnSig::new(&[Type::ISize], &Type::ISize)
+            )),
+            CILOp::STStaticField(signal_static(
+                "rust_atexit_handlers",
+                Type::Ptr(Type::USize.into())
+            )),
+            CILOp::Call(CallSite::boxed(
+                Some(class("System.Runtime", "System.AppDomain")),
+                "get_CurrentDomain".into(),
+                FnSig::new(
+                    &[],
+                    &Type::DotnetType(class("System.Runtime", "System.AppDomain").into())
+                ),
+                true,
+            )),
+        ]
+        .as_slice(),
+        &delegate(
+            class("System.Runtime", "System.EventHandler"),
+            on_process_exit()
+        ),
+        &[
+            CILOp::CallVirt(CallSite::boxed(
+                Some(class("System.Runtime", "System.AppDomain")),
+                "add_ProcessExit".into(),
+                FnSig::new(
+                    &[
+                        Type::DotnetType(class("System.Runtime", "System.AppDomain").into()),
+                        Type::DotnetType(class("System.Runtime", "System.EventHandler").into())
+                    ],
+                    &Type::Void
+                ),
+                false,
+            )),
+            // handlers[count] = function; count += 1
+            CILOp::Label(1),
+            CILOp::LDStaticField(signal_static(
+                "rust_atexit_handlers",
+                Type::Ptr(Type::USize.into())
+            )),
+            CILOp::LDStaticField(signal_static("rust_atexit_count", Type::I32)),
+            CILOp::ConvISize(false),
+            CILOp::SizeOf(Type::USize.into()),
+            CILOp::Mul,
+            CILOp::Add,
+            CILOp::LDArg(0),
+            CILOp::STIndISize,
+            CILOp::LDStaticField(signal_static("rust_atexit_count", Type::I32)),
+            CILOp::LdcI32(1),
+            CILOp::Add,
+            CILOp::STStaticField(signal_static("rust_atexit_count", Type::I32)),
+            CILOp::LdcI32(0),
+            CILOp::Ret,
+        ],
+    ]
+    .concat()
+);
+/// Returns the call site of `rust_on_process_exit`.
+fn on_process_exit() -> Box<CallSite> {
+    CallSite::boxed(
+        None,
+        "rust_on_process_exit".into(),
+        FnSig::new(
+            &[
+                object(),
+                Type::DotnetType(class("System.Runtime", "System.EventArgs").into()),
+            ],
+            &Type::Void,
+        ),
+        true,
+    )
+}
+// Handles `System.AppDomain.ProcessExit` by calling the `atexit` handlers, in reverse order of registration. Not raised by `abort`(`FailFast`).
+add_method!(
+    rust_on_process_exit,
+    &[
+        object(),
+        Type::DotnetType(class("System.Runtime", "System.EventArgs").into())
+    ],
+    &Type::Void,
+    [
+        // while count > 0 { count -= 1; handlers[count]() }
+        CILOp::Label(0),
+        CILOp::LDStaticField(signal_static("rust_atexit_count", Type::I32)),
+        CILOp::LdcI32(0),
+        CILOp::BLe(1),
+        CILOp::LDStaticField(signal_static("rust_atexit_count", Type::I32)),
+        CILOp::LdcI32(1),
+        CILOp::Sub,
+        CILOp::STStaticField(signal_static("rust_atexit_count", Type::I32)),
+        CILOp::LDStaticField(signal_static(
+            "rust_atexit_handlers",
+            Type::Ptr(Type::USize.into())
+        )),
+        CILOp::LDStaticField(signal_static("rust_atexit_count", Type::I32)),
+        CILOp::ConvISize(false),
+        CILOp::SizeOf(Type::USize.into()),
+        CILOp::Mul,
+        CILOp::Add,
+        CILOp::LDIndISize,
+        CILOp::CallI(FnSig::new(&[], &Type::Void).into()),
+        CILOp::GoTo(0),
+        CILOp::Label(1),
+        CILOp::Ret,
+    ]
+);
+/// Returns the `System.DateTime` value type.
+fn date_time() -> DotnetTypeRef {
+    DotnetTypeRef::new(Some("System.Runtime"), "System.DateTime")
+}
+// libc `abort`, used by `std::process::abort` and the `panic=abort` strategy. Terminates the process without unwinding, like a native abort.
+add_method!(
+    abort,
+    &[],
+    &Type::Void,
+    [
+        CILOp::fail_fast("The Rust program aborted.").as_slice(),
+        &[CILOp::Ret]
+    ]
+    .concat()
+);
+// libc `exit`, used by `std::process::exit`. Flushes stdout, since `System.Environment.Exit` does not.
+add_method!(
+    exit,
+    &[Type::I32],
+    &Type::Void,
+    [
+        crate::entrypoint::flush_stdout().as_slice(),
+        &[
+            CILOp::LDArg(0),
+            CILOp::Call(crate::entrypoint::exit()),
+            CILOp::Ret
+        ]
+    ]
+    .concat()
+);
@@ -2,15 +2,27 @@ use crate::{
     codegen_error::CodegenError,
     r#type::{TyCache, Type},
 };
-use rustc_middle::ty::{Instance, List, ParamEnv, ParamEnvAnd, TyCtxt, TyKind};
+use rustc_middle::ty::{Instance, List, ParamEnv, ParamEnvAnd, Ty, TyCtxt, TyKind};
 use rustc_target::abi::call::Conv;
 use rustc_target::spec::abi::Abi as TargetAbi;
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, OnceLock},
+};
 /// Function signature.
+/// Call sites and methods share signatures using [`Arc`]s, since the same signature is often used by a huge number of call sites.
 #[derive(Clone, PartialEq, Serialize, Deserialize, Eq, Hash, Debug)]
 pub struct FnSig {
     inputs: Vec<Type>,
     output: Type,
+    /// Set if the function is C-variadic, and accepts any number of arguments after `inputs`.
+    #[serde(default)]
+    vararg: bool,
+    /// The types of the arguments passed after `inputs`(after the sentinel) by a call to a variadic function. Always empty in the signatures of
+    /// methods, since each call may pass different arguments.
+    #[serde(default)]
+    varargs: Vec<Type>,
 }
 impl FnSig {
     /// Returns the signature of function behind `function`.
@@ -33,7 +45,6 @@ impl FnSig {
             Conv::C => (),
             _ => panic!("ERROR:calling using convention {conv:?} is not supported!"),
         }
-        assert!(!fn_abi.c_variadic);
         let ret = tycache.type_from_cache(fn_abi.ret.layout.ty, tcx, Some(function));
         let mut args = Vec::with_capacity(fn_abi.args.len());
         for arg in fn_abi.args.iter() {
@@ -62,8 +73,44 @@ impl FnSig {
         Ok(Self {
             inputs: args,
             output: ret,
+            vararg: fn_abi.c_variadic,
+            varargs: vec![],
         })
     }
+    /// Returns the signature of functions behind pointers of type `fn_ptr`, used within `method_instance`.
+    pub fn sig_from_fn_ptr<'tcx>(
+        fn_ptr: Ty<'tcx>,
+        tcx: TyCtxt<'tcx>,
+        tycache: &mut TyCache,
+        method_instance: Instance<'tcx>,
+    ) -> Self {
+        let TyKind::FnPtr(sig) = fn_ptr.kind() else {
+            panic!("{fn_ptr:?} is not a function pointer!");
+        };
+        let fn_abi = tcx
+            .fn_abi_of_fn_ptr(ParamEnv::reveal_all().and((*sig, List::empty())))
+            .expect("Could not get the ABI of a function pointer!");
+        match fn_abi.conv {
+            Conv::Rust | Conv::C => (),
+            conv => panic!("ERROR:calling using convention {conv:?} is not supported!"),
+        }
+        assert!(
+            !fn_abi.c_variadic,
+            "Calling variadic functions trough pointers is not supported!"
+        );
+        let inputs = fn_abi
+            .args
+            .iter()
+            .map(|arg| tycache.type_from_cache(arg.layout.ty, tcx, Some(method_instance)))
+            .collect();
+        let output = tycache.type_from_cache(fn_abi.ret.layout.ty, tcx, Some(method_instance));
+        Self {
+            inputs,
+            output,
+            vararg: false,
+            varargs: vec![],
+        }
+    }
     /// Returns the list of function inputs.
     pub fn inputs(&self) -> &[Type] {
         &self.inputs
@@ -77,8 +124,68 @@ impl FnSig {
         Self {
             inputs: inputs.into(),
             output: output.clone(),
+            vararg: false,
+            varargs: vec![],
+        }
+    }
+    /// Creates a new signature of a C-variadic function, accepting any number of arguments after `inputs`.
+    pub fn new_vararg(inputs: &[Type], output: &Type) -> Self {
+        Self {
+            vararg: true,
+            ..Self::new(inputs, output)
         }
     }
+    /// Returns true if the function is C-variadic.
+    pub fn is_vararg(&self) -> bool {
+        self.vararg
+    }
+    /// Returns the types of the arguments passed after the fixed inputs, by a call to a variadic function.
+    pub fn varargs(&self) -> &[Type] {
+        &self.varargs
+    }
+    /// Returns the signature used to call this variadic function, passing arguments of types `varargs` after the fixed inputs.
+    #[must_use]
+    pub fn with_varargs(&self, varargs: Vec<Type>) -> Self {
+        assert!(
+            self.vararg,
+            "Only variadic functions may be called with additional arguments!"
+        );
+        Self {
+            varargs,
+            ..self.clone()
+        }
+    }
+    /// Returns the number of arguments a call using this signature pops, including the variadic ones.
+    pub fn arg_count(&self) -> usize {
+        self.inputs.len() + self.varargs.len()
+    }
+    /// Returns a shared instance of `self`. Equal signatures interned this way are stored only once.
+    #[must_use]
+    pub fn intern(self) -> Arc<Self> {
+        static INTERNED: OnceLock<Mutex<HashSet<Arc<FnSig>>>> = OnceLock::new();
+        let mut interned = INTERNED
+            .get_or_init(Mutex::default)
+            .lock()
+            .expect("Signature interner poisoned!");
+        if let Some(sig) = interned.get(&self) {
+            return sig.clone();
+        }
+        let sig = Arc::new(self);
+        interned.insert(sig.clone());
+        sig
+    }
+}
+/// Serializes shared signatures as plain signatures. Deserialized signatures are interned, so that each distinct signature is stored only once.
+pub(crate) mod shared {
+    use super::FnSig;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::sync::Arc;
+    pub fn serialize<S: Serializer>(sig: &Arc<FnSig>, serializer: S) -> Result<S::Ok, S::Error> {
+        sig.as_ref().serialize(serializer)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<FnSig>, D::Error> {
+        Ok(FnSig::deserialize(deserializer)?.intern())
+    }
 }
 #[derive(Clone, PartialEq, Serialize, Deserialize, Eq, Hash, Debug)]
 pub struct FunctionCallInfo {
@@ -0,0 +1,329 @@
+//! Utilities for fuzzing the CILOp -> ILASM -> ilasm pipeline.
+//!
+//! [`MethodGenerator`] generates arbitrary, but stack-valid methods. Their bodies are made out of [`Fragment`]s, each of which leaves the evaluation stack
+//! unchanged. This allows [`GeneratedMethod::shrink`] to remove fragments from a failing method, without making it invalid.
+//! [`check_method`] exports a method using ILASM, and verifies the result with ILVerify, if it is available.
+use crate::{
+    access_modifier::AccessModifer,
+    assembly::Assembly,
+    assembly_exporter::{ilasm_exporter::ILASMExporter, AssemblyExportError, AssemblyExporter},
+    cil::CILOp,
+    function_sig::FnSig,
+    method::Method,
+    r#type::Type,
+};
+use std::path::Path;
+/// Environment variable holding the reference assemblies passed to ILVerify (`-r`), eg. `/usr/share/dotnet/shared/Microsoft.NETCore.App/8.0.0/*.dll`.
+pub const ILVERIFY_REFS_ENV: &str = "RCCLR_ILVERIFY_REFS";
+/// Source of randomness for [`MethodGenerator`]. Consumes the bytes provided by a fuzzer first, and then falls back to a xorshift PRNG.
+pub struct Entropy<'a> {
+    bytes: &'a [u8],
+    state: u64,
+}
+impl<'a> Entropy<'a> {
+    /// Creates a source of entropy out of fuzzer-provided `bytes`.
+    #[must_use]
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            state: 0x2545_F491_4F6C_DD1D ^ bytes.len() as u64,
+        }
+    }
+    /// Creates a source of entropy out of `seed`. Useful for deterministic tests.
+    #[must_use]
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            bytes: &[],
+            state: seed | 1,
+        }
+    }
+    fn next_u64(&mut self) -> u64 {
+        if let Some((first, rest)) = self.bytes.split_first() {
+            self.bytes = rest;
+            self.state = self.state.rotate_left(8) ^ u64::from(*first);
+            return u64::from(*first);
+        }
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+    /// Returns a number in range `0..bound`.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+    fn byte(&mut self) -> u8 {
+        self.next_u64() as u8
+    }
+}
+/// A sequence of ops, which leaves the evaluation stack unchanged.
+pub type Fragment = Vec<CILOp>;
+/// A method created by [`MethodGenerator`].
+#[derive(Debug, Clone)]
+pub struct GeneratedMethod {
+    locals: Vec<Type>,
+    fragments: Vec<Fragment>,
+}
+impl GeneratedMethod {
+    /// Turns the generated method into a static method named `name`, taking no arguments and returning nothing.
+    #[must_use]
+    pub fn to_method(&self, name: &str) -> Method {
+        let locals = self.locals.iter().map(|tpe| (None, tpe.clone())).collect();
+        let mut method = Method::new(
+            AccessModifer::Public,
+            true,
+            FnSig::new(&[], &Type::Void),
+            name,
+            locals,
+        );
+        let mut ops: Vec<CILOp> = self.fragments.iter().flatten().cloned().collect();
+        ops.push(CILOp::Ret);
+        method.set_ops(ops);
+        method
+    }
+    /// Returns the fragments making up the body of this method.
+    #[must_use]
+    pub fn fragments(&self) -> &[Fragment] {
+        &self.fragments
+    }
+    /// Removes fragments, as long as `fails` still returns true for the resulting method. Returns the smallest failing method found.
+    #[must_use]
+    pub fn shrink(mut self, fails: impl Fn(&Method) -> bool) -> Self {
+        // Try removing large chunks first, and then gradually smaller ones.
+        let mut chunk = self.fragments.len().max(1);
+        while chunk > 0 {
+            let mut start = 0;
+            while start < self.fragments.len() {
+                let mut candidate = self.clone();
+                let end = (start + chunk).min(candidate.fragments.len());
+                candidate.fragments.drain(start..end);
+                if fails(&candidate.to_method("shrunk")) {
+                    self = candidate;
+                } else {
+                    start += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+        self
+    }
+}
+/// Type of a value on the evaluation stack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StackType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+impl StackType {
+    const ALL: [Self; 4] = [Self::I32, Self::I64, Self::F32, Self::F64];
+    fn tpe(self) -> Type {
+        match self {
+            Self::I32 => Type::I32,
+            Self::I64 => Type::I64,
+            Self::F32 => Type::F32,
+            Self::F64 => Type::F64,
+        }
+    }
+    fn is_int(self) -> bool {
+        matches!(self, Self::I32 | Self::I64)
+    }
+}
+/// Generates arbitrary, stack-valid methods.
+pub struct MethodGenerator<'a> {
+    entropy: Entropy<'a>,
+    locals: Vec<StackType>,
+    max_depth: usize,
+    max_fragments: usize,
+}
+impl<'a> MethodGenerator<'a> {
+    /// Creates a new generator, using `entropy` as the source of its decisions.
+    #[must_use]
+    pub fn new(entropy: Entropy<'a>) -> Self {
+        Self {
+            entropy,
+            locals: Vec::new(),
+            max_depth: 4,
+            max_fragments: 64,
+        }
+    }
+    /// Generates a new method.
+    pub fn generate(&mut self) -> GeneratedMethod {
+        self.locals = (0..self.entropy.below(8))
+            .map(|_| StackType::ALL[self.entropy.below(StackType::ALL.len())])
+            .collect();
+        // Locals must be initialized before they are read.
+        let mut fragments: Vec<Fragment> = (0..self.locals.len())
+            .map(|local| {
+                let mut ops = self.leaf(self.locals[local]);
+                ops.push(CILOp::STLoc(local as u32));
+                ops
+            })
+            .collect();
+        for _ in 0..self.entropy.below(self.max_fragments) {
+            let fragment = self.fragment();
+            fragments.push(fragment);
+        }
+        GeneratedMethod {
+            locals: self.locals.iter().map(|local| local.tpe()).collect(),
+            fragments,
+        }
+    }
+    fn fragment(&mut self) -> Fragment {
+        match self.entropy.below(4) {
+            0 => vec![CILOp::Comment(self.text().into())],
+            1 => vec![CILOp::LdStr(self.text().into()), CILOp::Pop],
+            2 if !self.locals.is_empty() => {
+                let local = self.entropy.below(self.locals.len());
+                let mut ops = self.expr(self.locals[local], 0);
+                ops.push(CILOp::STLoc(local as u32));
+                ops
+            }
+            _ => {
+                let tpe = StackType::ALL[self.entropy.below(StackType::ALL.len())];
+                let mut ops = self.expr(tpe, 0);
+                ops.push(CILOp::Pop);
+                ops
+            }
+        }
+    }
+    /// Generates a string, likely to contain characters which need escaping.
+    fn text(&mut self) -> String {
+        const SPECIAL: &[char] = &[
+            '"', '\'', '\\', '\n', '\r', '\t', '\0', '/', '*', 'ł', 'ß', '\u{200B}', '🦀',
+        ];
+        (0..self.entropy.below(16))
+            .map(|_| {
+                if self.entropy.below(2) == 0 {
+                    SPECIAL[self.entropy.below(SPECIAL.len())]
+                } else {
+                    char::from(b' ' + self.entropy.byte() % 95)
+                }
+            })
+            .collect()
+    }
+    /// Generates ops pushing a value of type `tpe`.
+    fn leaf(&mut self, tpe: StackType) -> Vec<CILOp> {
+        let bits = self.entropy.next_u64();
+        vec![match tpe {
+            StackType::I32 => CILOp::LdcI32(bits as i32),
+            StackType::I64 => CILOp::LdcI64(bits as i64),
+            StackType::F32 => CILOp::LdcF32(f32::from_bits(bits as u32)),
+            StackType::F64 => CILOp::LdcF64(f64::from_bits(bits)),
+        }]
+    }
+    /// Generates an expression evaluating to a value of type `tpe`.
+    fn expr(&mut self, tpe: StackType, depth: usize) -> Vec<CILOp> {
+        if depth >= self.max_depth {
+            return self.leaf(tpe);
+        }
+        match self.entropy.below(6) {
+            0 => {
+                let locals: Vec<_> = (0..self.locals.len())
+                    .filter(|local| self.locals[*local] == tpe)
+                    .collect();
+                if locals.is_empty() {
+                    self.leaf(tpe)
+                } else {
+                    vec![CILOp::LDLoc(
+                        locals[self.entropy.below(locals.len())] as u32,
+                    )]
+                }
+            }
+            1 => {
+                let mut ops = self.expr(tpe, depth + 1);
+                ops.push(CILOp::Neg);
+                ops
+            }
+            2 if tpe.is_int() => {
+                let mut ops = self.expr(tpe, depth + 1);
+                ops.push(CILOp::Not);
+                ops
+            }
+            3 => {
+                // Conversions
+                let src = StackType::ALL[self.entropy.below(StackType::ALL.len())];
+                let mut ops = self.expr(src, depth + 1);
+                ops.push(match tpe {
+                    StackType::I32 => CILOp::ConvI32(false),
+                    StackType::I64 => CILOp::ConvI64(false),
+                    StackType::F32 => CILOp::ConvF32(false),
+                    StackType::F64 => CILOp::ConvF64(false),
+                });
+                ops
+            }
+            _ => {
+                // Binary operations. Division is avoided, since it may throw.
+                let mut ops = self.expr(tpe, depth + 1);
+                ops.extend(self.expr(tpe, depth + 1));
+                let binops: &[CILOp] = if tpe.is_int() {
+                    &[
+                        CILOp::Add,
+                        CILOp::Sub,
+                        CILOp::Mul,
+                        CILOp::And,
+                        CILOp::Or,
+                        CILOp::XOr,
+                    ]
+                } else {
+                    &[CILOp::Add, CILOp::Sub, CILOp::Mul]
+                };
+                ops.push(binops[self.entropy.below(binops.len())].clone());
+                ops
+            }
+        }
+    }
+}
+/// Exports `method` using ILASM into an assembly in `dir`, and verifies it using ILVerify, if it is available.
+/// Returns an error message describing the failure, if there was one.
+pub fn check_method(method: &Method, dir: &Path) -> Result<(), String> {
+    let asm = Assembly::empty();
+    let mut exporter = ILASMExporter::init("fuzz");
+    for (asm_name, asm_ref) in asm.extern_refs() {
+        exporter.add_extern_ref(asm_name, asm_ref);
+    }
+    exporter.add_method(method);
+    std::fs::create_dir_all(dir.join("out")).map_err(|err| err.to_string())?;
+    // The exporter places the assembly in the parent directory of the target path.
+    exporter
+        .finalize(&dir.join("out").join("fuzz.dll"), true)
+        .map_err(|err| match err {
+            AssemblyExportError::ExporterError(msg) => msg.to_string(),
+            err => format!("{err:?}"),
+        })?;
+    let Ok(refs) = std::env::var(ILVERIFY_REFS_ENV) else {
+        return Ok(());
+    };
+    let out = std::process::Command::new("ilverify")
+        .arg(dir.join("fuzz.dll"))
+        .args(["-r", &refs])
+        .output()
+        .map_err(|err| format!("Could not run ilverify: {err}"))?;
+    if out.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "ILVerify failed:\n{stdout}",
+            stdout = String::from_utf8_lossy(&out.stdout)
+        ))
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::{Entropy, MethodGenerator};
+    #[test]
+    fn generated_methods_are_stack_valid() {
+        for seed in 0..256 {
+            let generated = MethodGenerator::new(Entropy::from_seed(seed)).generate();
+            for fragment in generated.fragments() {
+                let mut depth = 0;
+                for op in fragment {
+                    depth += op.stack_diff();
+                    assert!(depth >= 0, "Stack underflow in {fragment:?}");
+                }
+                assert_eq!(depth, 0, "Unbalanced fragment {fragment:?}");
+            }
+        }
+    }
+}
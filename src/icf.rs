@@ -0,0 +1,172 @@
+//! Identical code folding: removing methods whose bodies are identical to the body of another method.
+//!
+//! Monomorphization produces many methods which differ only by name(eg. the same routine instantiated for `Vec<u8>` and `Vec<i8>`).
+//! Two methods are identical if they have the same signature, the same types of locals, the same ops(ignoring comments) and the same exception
+//! handlers. Of each group of identical methods, one is kept, and calls to the others are redirected to it. Redirecting calls can make more methods
+//! identical, so this is repeated until no more methods can be folded.
+//!
+//! Exported methods(the entrypoint, static initializers, and all methods not named using Rust's mangling scheme, like `#[no_mangle]` functions)
+//! are never removed, since code outside the assembly may refer to them by name. They are kept as the folded-into method when possible.
+use crate::{
+    assembly::Assembly,
+    cil::{CILOp, CallSite},
+    method::Method,
+    r#type::TypeDef,
+};
+use std::collections::{hash_map::Entry, HashMap};
+/// Checks if `method` may be referred to from outside the assembly, and so must not be removed.
+fn is_exported(method: &Method, asm: &Assembly) -> bool {
+    let name = method.name();
+    method.is_entrypoint()
+        || name == ".cctor"
+        || asm
+            .entrypoint()
+            .is_some_and(|entrypoint| entrypoint.name() == name)
+        || !(name.starts_with("_ZN") || name.starts_with("_R"))
+}
+/// Returns the normalized body of `method`: everything two identical methods must share. Names are ignored.
+fn normalized_body(method: &Method) -> Vec<u8> {
+    let locals: Vec<_> = method.locals().iter().map(|(_, tpe)| tpe).collect();
+    let ops: Vec<_> = method
+        .get_ops()
+        .iter()
+        .filter(|op| !matches!(op, CILOp::Comment(_)))
+        .collect();
+    // P/Invoke methods have no ops, so they differ only by the native function they call.
+    crate::serialization::encode(&(
+        method.sig(),
+        locals,
+        ops,
+        method.handlers(),
+        method.pinvoke(),
+    ))
+}
+/// Folds identical methods of `asm`. Returns the number of removed methods.
+pub fn fold_identical_methods(asm: &mut Assembly) -> usize {
+    let mut folded = 0;
+    loop {
+        // Normalized body -> the method kept for it.
+        let mut kept: HashMap<Vec<u8>, (CallSite, bool)> = HashMap::new();
+        let mut redirects: HashMap<CallSite, CallSite> = HashMap::new();
+        for method in asm.methods().filter(|method| method.is_static()) {
+            let exported = is_exported(method, asm);
+            let site = method.call_site();
+            let (kept_site, kept_exported) = match kept.entry(normalized_body(method)) {
+                Entry::Vacant(entry) => {
+                    entry.insert((site, exported));
+                    continue;
+                }
+                Entry::Occupied(entry) => entry.into_mut(),
+            };
+            if !exported {
+                redirects.insert(site, kept_site.clone());
+            } else if !*kept_exported {
+                // An exported duplicate becomes the method others are folded into, unless another exported method already is.
+                redirects.insert(kept_site.clone(), site.clone());
+                *kept_site = site;
+                *kept_exported = true;
+            }
+        }
+        // Redirects may form chains, if a kept method was later replaced by an exported one.
+        let targets: HashMap<CallSite, CallSite> = redirects
+            .keys()
+            .map(|site| {
+                let mut target = &redirects[site];
+                while let Some(next) = redirects.get(target) {
+                    target = next;
+                }
+                (site.clone(), target.clone())
+            })
+            .collect();
+        if targets.is_empty() {
+            return folded;
+        }
+        for site in targets.keys() {
+            asm.remove_method(site);
+        }
+        folded += targets.len();
+        for method in asm.methods_mut() {
+            redirect_calls(method, &targets);
+        }
+        // Methods of types(eg. static constructors) may call the folded methods too.
+        for method in asm.types_mut().flat_map(TypeDef::methods_mut) {
+            redirect_calls(method, &targets);
+        }
+    }
+}
+/// Redirects all calls and function pointers in `method` to the methods given by `targets`.
+fn redirect_calls(method: &mut Method, targets: &HashMap<CallSite, CallSite>) {
+    for op in method.ops_mut() {
+        if let CILOp::Call(site) | CILOp::LDFtn(site) = op {
+            if let Some(target) = targets.get(&**site) {
+                *site = target.clone().into();
+            }
+        }
+    }
+}
+#[test]
+fn folds_duplicates() {
+    use crate::{access_modifier::AccessModifer, function_sig::FnSig, r#type::Type};
+    let method = |name: &str, ops: Vec<CILOp>| {
+        let mut method = Method::new(
+            AccessModifer::Public,
+            true,
+            FnSig::new(&[Type::I32], &Type::I32),
+            name,
+            vec![],
+        );
+        method.set_ops(ops);
+        method
+    };
+    let body = vec![CILOp::LDArg(0), CILOp::LdcI32(1), CILOp::Add, CILOp::Ret];
+    let call = |name: &str| {
+        vec![
+            CILOp::LDArg(0),
+            CILOp::Call(method(name, vec![]).call_site().into()),
+            CILOp::Ret,
+        ]
+    };
+    let mut asm = Assembly::empty();
+    asm.add_method(method("_ZN3inc2u817h0E", body.clone()));
+    asm.add_method(method("_ZN3inc2i817h1E", body.clone()));
+    // Identical only after the calls to the methods above are redirected.
+    asm.add_method(method("_ZN4wrap2u817h2E", call("_ZN3inc2u817h0E")));
+    asm.add_method(method("_ZN4wrap2i817h3E", call("_ZN3inc2i817h1E")));
+    // Exported, so kept, and calls to its duplicates are redirected to it.
+    asm.add_method(method("inc_exported", body));
+    assert_eq!(fold_identical_methods(&mut asm), 3);
+    let names: Vec<_> = asm.methods().map(Method::name).collect();
+    assert_eq!(names, ["_ZN4wrap2u817h2E", "inc_exported"]);
+    assert_eq!(
+        asm.methods().next().unwrap().get_ops()[1],
+        CILOp::Call(method("inc_exported", vec![]).call_site().into())
+    );
+}
+#[test]
+fn redirects_calls_from_type_methods() {
+    use crate::{access_modifier::AccessModifer, function_sig::FnSig, r#type::Type};
+    let method = |name: &str, ops: Vec<CILOp>| {
+        let mut method = Method::new(
+            AccessModifer::Public,
+            true,
+            FnSig::new(&[], &Type::I32),
+            name,
+            vec![],
+        );
+        method.set_ops(ops);
+        method
+    };
+    let body = vec![CILOp::LdcI32(7), CILOp::Ret];
+    let mut asm = Assembly::empty();
+    asm.add_method(method("_ZN5seven2u817h0E", body.clone()));
+    asm.add_method(method("_ZN5seven2i817h1E", body));
+    let folded_site = method("_ZN5seven2i817h1E", vec![]).call_site();
+    let mut tpe = TypeDef::nameonly("Statics");
+    tpe.extend_cctor([CILOp::Call(folded_site.clone().into()), CILOp::Pop]);
+    asm.add_typedef(tpe);
+    assert_eq!(fold_identical_methods(&mut asm), 1);
+    let kept = asm.methods().next().unwrap().call_site();
+    assert_ne!(kept, folded_site);
+    let cctor = asm.types().next().unwrap().cctor().unwrap();
+    assert_eq!(cctor.get_ops()[0], CILOp::Call(kept.into()));
+}
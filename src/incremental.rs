@@ -0,0 +1,284 @@
+//! Incremental codegen: reusing methods lowered by previous builds, instead of lowering them again.
+//!
+//! Each function is keyed by a stable hash of its `Instance` and symbol name, combined with fingerprints of everything else which could change
+//! the result of lowering it:
+//! * the backend itself(its version, serialization format, and the size and modification time of the backend library),
+//! * the config of the backend,
+//! * the target, and the hashes of all crates the current crate depends on,
+//! * the hash of the current crate, if the function mentions any item defined within it. Functions instantiated only from upstream items(eg.
+//!   `Vec<u8>::push`) survive edits of the current crate.
+//!
+//! Lowered methods are stored in a directory next to rustc's incremental data, so the cache is only used when rustc runs in incremental mode,
+//! and [`incremental_cache`](crate::config::BackendConfig::incremental_cache) is set. Each entry holds the method, and all the type definitions it needs.
+//! Methods which refer to global allocations, or which contain unsupported constructs, are never cached: the former depend on the allocation ids of the
+//! current session, and the latter should keep being reported.
+//! After codegen, entries which were not used by the current build are removed, so the cache never grows past the size of a single build.
+use crate::{method::Method, r#type::TyCache, r#type::TypeDef};
+use rustc_data_structures::{
+    fingerprint::Fingerprint,
+    stable_hasher::{HashStable, StableHasher},
+};
+use rustc_middle::ty::{Instance, InstanceDef, Ty, TyCtxt, TyKind};
+use rustc_span::def_id::{DefId, LOCAL_CRATE};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    hash::Hash,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+};
+/// A method lowered by a previous build, together with the type definitions it needs.
+#[derive(Serialize, Deserialize)]
+pub struct CachedMethod {
+    pub method: Method,
+    pub types: Vec<TypeDef>,
+}
+/// The cache of the crate being compiled.
+struct IncrementalCache {
+    /// Directory the entries are stored in.
+    dir: PathBuf,
+    /// Fingerprint of the backend, its config and the dependencies of the crate.
+    base: Fingerprint,
+    /// Fingerprint of the current crate. Part of the keys of functions mentioning local items.
+    local: Fingerprint,
+    /// Keys of the entries used or created by this build.
+    used: Mutex<HashSet<String>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+static CACHE: OnceLock<IncrementalCache> = OnceLock::new();
+/// Enables the cache for the crate being compiled, if rustc runs in incremental mode and the cache is not disabled.
+pub fn init(tcx: TyCtxt) {
+    if !crate::config::config().incremental_cache {
+        return;
+    }
+    let Some(incremental_dir) = &tcx.sess.opts.incremental else {
+        return;
+    };
+    let dir = incremental_dir.join(format!(
+        "rcclr-{name}-{id:x}",
+        name = tcx.crate_name(LOCAL_CRATE),
+        id = tcx.stable_crate_id(LOCAL_CRATE).as_u64()
+    ));
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        eprintln!("WARNING: could not create the incremental cache directory, so it will not be used: {err}");
+        return;
+    }
+    let mut hasher = StableHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    crate::serialization::FORMAT_VERSION.hash(&mut hasher);
+    // Rebuilding the backend changes its library, which invalidates the cache.
+    if let Some(backend) = &tcx.sess.opts.unstable_opts.codegen_backend {
+        if let Ok(meta) = std::fs::metadata(backend) {
+            meta.len().hash(&mut hasher);
+            meta.modified().ok().hash(&mut hasher);
+        }
+    }
+    format!("{:?}", crate::config::config()).hash(&mut hasher);
+    format!("{:?}", tcx.sess.opts.target_triple).hash(&mut hasher);
+    // Panics and cleanup blocks are lowered differently with `-C panic=abort`.
+    tcx.sess.panic_strategy().hash(&mut hasher);
+    for cnum in tcx.crates(()) {
+        tcx.crate_hash(*cnum).hash(&mut hasher);
+    }
+    let base = hasher.finish::<Fingerprint>();
+    let mut hasher = StableHasher::new();
+    tcx.crate_hash(LOCAL_CRATE).hash(&mut hasher);
+    let local = hasher.finish::<Fingerprint>();
+    let _ = CACHE.set(IncrementalCache {
+        dir,
+        base,
+        local,
+        used: Mutex::new(HashSet::new()),
+        hits: AtomicUsize::new(0),
+        misses: AtomicUsize::new(0),
+    });
+}
+/// Returns the key of function `instance`, with symbol name `name`, or `None` if the cache is disabled.
+fn key<'tcx>(tcx: TyCtxt<'tcx>, instance: Instance<'tcx>, name: &str) -> Option<String> {
+    let cache = CACHE.get()?;
+    let fingerprint = tcx.with_stable_hashing_context(|mut hcx| {
+        let mut hasher = StableHasher::new();
+        cache.base.hash(&mut hasher);
+        if mentions_local(instance) {
+            cache.local.hash(&mut hasher);
+        }
+        instance.hash_stable(&mut hcx, &mut hasher);
+        name.hash(&mut hasher);
+        hasher.finish::<Fingerprint>()
+    });
+    let (high, low) = fingerprint.as_value();
+    Some(format!("{high:016x}{low:016x}"))
+}
+/// Checks if `instance` mentions any item defined within the current crate. Shims are conservatively assumed to always do so.
+fn mentions_local(instance: Instance) -> bool {
+    if !matches!(instance.def, InstanceDef::Item(_)) || instance.def_id().is_local() {
+        return true;
+    }
+    instance
+        .args
+        .iter()
+        .flat_map(|arg| arg.walk())
+        .filter_map(|arg| arg.as_type())
+        .any(|ty| ty_def_id(ty).is_some_and(DefId::is_local))
+}
+/// Returns the id of the item defining `ty`, if it is defined by one.
+fn ty_def_id(ty: Ty) -> Option<DefId> {
+    match ty.kind() {
+        TyKind::Adt(def, _) => Some(def.did()),
+        TyKind::Foreign(def_id)
+        | TyKind::FnDef(def_id, _)
+        | TyKind::Closure(def_id, _)
+        | TyKind::Generator(def_id, _, _) => Some(*def_id),
+        TyKind::Alias(_, alias) => Some(alias.def_id),
+        TyKind::Dynamic(predicates, _, _) => predicates.principal_def_id(),
+        _ => None,
+    }
+}
+/// Returns the method lowered from `instance` by a previous build, if it was cached.
+pub fn load<'tcx>(tcx: TyCtxt<'tcx>, instance: Instance<'tcx>, name: &str) -> Option<CachedMethod> {
+    let key = key(tcx, instance, name)?;
+    let cache = CACHE.get()?;
+    let cached = std::fs::read(cache.dir.join(&key))
+        .ok()
+        .and_then(|bytes| crate::serialization::decode(&bytes).ok());
+    if cached.is_some() {
+        cache.hits.fetch_add(1, Ordering::Relaxed);
+        cache
+            .used
+            .lock()
+            .expect("Incremental cache poisoned!")
+            .insert(key);
+    } else {
+        cache.misses.fetch_add(1, Ordering::Relaxed);
+    }
+    cached
+}
+/// Stores `method`, lowered from `instance`, together with the types from `types` it needs.
+pub fn store<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    instance: Instance<'tcx>,
+    name: &str,
+    method: &Method,
+    types: &TyCache,
+) {
+    let Some(key) = key(tcx, instance, name) else {
+        return;
+    };
+    let Some(cache) = CACHE.get() else {
+        return;
+    };
+    let entry = CachedMethod {
+        method: method.clone(),
+        types: needed_types(method, types),
+    };
+    // Written to a temporary file first, so that an interrupted build never leaves a partial entry behind.
+    let tmp = cache.dir.join(format!("{key}.tmp"));
+    let stored = std::fs::write(&tmp, crate::serialization::encode(&entry))
+        .and_then(|()| std::fs::rename(&tmp, cache.dir.join(&key)));
+    if let Err(err) = stored {
+        eprintln!("WARNING: could not store {name} in the incremental cache: {err}");
+        return;
+    }
+    cache
+        .used
+        .lock()
+        .expect("Incremental cache poisoned!")
+        .insert(key);
+}
+/// Removes all entries not used by the current build, and prints the cache statistics if [`timings`](crate::config::BackendConfig::timings) is set.
+pub fn finish() {
+    let Some(cache) = CACHE.get() else {
+        return;
+    };
+    let used = cache.used.lock().expect("Incremental cache poisoned!");
+    if let Ok(entries) = std::fs::read_dir(&cache.dir) {
+        for entry in entries.flatten() {
+            if !used.contains(entry.file_name().to_string_lossy().as_ref()) {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+    if crate::config::config().timings {
+        eprintln!(
+            "Incremental cache: {hits} methods reused, {misses} lowered.",
+            hits = cache.hits.load(Ordering::Relaxed),
+            misses = cache.misses.load(Ordering::Relaxed)
+        );
+    }
+}
+/// Returns the definitions of all types from `types` which `method` refers to, directly or through other types.
+fn needed_types(method: &Method, types: &TyCache) -> Vec<TypeDef> {
+    let mut names = Vec::new();
+    collect_type_names(
+        &serde_json::to_value(method).expect("Could not serialize a method!"),
+        &mut names,
+    );
+    let mut visited = HashSet::new();
+    let mut needed = Vec::new();
+    while let Some(name) = names.pop() {
+        if !visited.insert(name.clone()) {
+            continue;
+        }
+        if let Some(def) = types.get(&name) {
+            collect_type_names(
+                &serde_json::to_value(def).expect("Could not serialize a type!"),
+                &mut names,
+            );
+            needed.push(def.clone());
+        }
+    }
+    needed
+}
+/// Collects the names of all types defined within the assembly(type references without an assembly) mentioned in `value`.
+/// Works on the serialized form of methods and types, so that it does not need to know where each kind of op stores its types.
+fn collect_type_names(value: &serde_json::Value, names: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            // A `DotnetTypeRef` referring to a type from this assembly.
+            if fields.get("assembly") == Some(&serde_json::Value::Null) {
+                if let Some(serde_json::Value::String(name)) = fields.get("name_path") {
+                    names.push(name.clone());
+                }
+            }
+            fields
+                .values()
+                .for_each(|value| collect_type_names(value, names));
+        }
+        serde_json::Value::Array(values) => values
+            .iter()
+            .for_each(|value| collect_type_names(value, names)),
+        _ => (),
+    }
+}
+#[test]
+fn type_names() {
+    use crate::{
+        access_modifier::AccessModifer,
+        cil::CILOp,
+        function_sig::FnSig,
+        r#type::{DotnetTypeRef, Type},
+    };
+    let local = Type::DotnetType(DotnetTypeRef::new(None, "Wrapper").into());
+    let external =
+        Type::DotnetType(DotnetTypeRef::new(Some("System.Runtime"), "System.Int128").into());
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[local.clone()], &external),
+        "unwrap",
+        vec![(
+            None,
+            Type::Ptr(Type::DotnetType(DotnetTypeRef::new(None, "Inner").into()).into()),
+        )],
+    );
+    method.set_ops(vec![CILOp::SizeOf(local.into()), CILOp::Ret]);
+    let mut names = Vec::new();
+    collect_type_names(&serde_json::to_value(&method).unwrap(), &mut names);
+    names.sort();
+    names.dedup();
+    assert_eq!(names, ["Inner", "Wrapper"]);
+}
@@ -0,0 +1,74 @@
+//! Raw IL written in the support library(`mycorrhiza`), for code the lowering can't produce, or can't produce efficiently.
+//!
+//! There are two ways to write raw IL:
+//! 1. Inline IL blocks: calls to `rustc_clr_inline_il::<"IL", MAX_STACK>()`(made by the `clr_inline_il!` macro) are replaced with the IL.
+//! 2. IL method bodies: a function with `#[link_section = "clr_il_body:MAX_STACK:IL"]` gets the IL as its whole body, and its MIR is ignored.
+//! This allows writing shims as ordinary Rust functions, with signatures checked by rustc.
+//!
+//! `MAX_STACK` is the maximum number of values the IL pushes onto the evaluation stack, which is needed for computing the `.maxstack` of the method.
+//!
+//! Raw IL is not checked in any way, so only the crates in [`INLINE_IL_CRATES`] may use it.
+use crate::cil::CILOp;
+use rustc_middle::ty::{GenericArg, TyCtxt};
+use rustc_span::{def_id::DefId, Span};
+/// Name of the function whose calls are replaced with the IL passed to it as a const generic argument.
+pub const INLINE_IL_FN_NAME: &str = "rustc_clr_inline_il";
+/// Prefix of the link section of functions whose body is replaced with the IL following it.
+pub const IL_BODY_SECTION_PREFIX: &str = "clr_il_body:";
+/// Crates allowed to use raw IL.
+pub const INLINE_IL_CRATES: &[&str] = &["mycorrhiza"];
+fn assert_allowed(krate: &str, what: &str) {
+    assert!(
+        INLINE_IL_CRATES.contains(&krate),
+        "{what} may only be used by {INLINE_IL_CRATES:?}, but it was used in {krate}!"
+    );
+}
+/// Returns the inline IL(and its stack depth) passed to `rustc_clr_inline_il`, called at `span`.
+pub fn inline_il<'ctx>(
+    subst_ref: &[GenericArg<'ctx>],
+    tyctx: TyCtxt<'ctx>,
+    span: Span,
+) -> Vec<CILOp> {
+    // The call may have been inlined into a method of another crate, so the crate is the one the call is written in.
+    let krate = tyctx.crate_name(tyctx.sess.source_map().lookup_source_file(span.lo()).cnum);
+    assert_allowed(krate.as_str(), "Inline IL");
+    let il = crate::utilis::garg_to_string(subst_ref[0], tyctx);
+    let max_stack = subst_ref[1]
+        .as_const()
+        .expect("The stack depth of inline IL is not a constant!");
+    let max_stack = crate::utilis::try_resolve_const_size(max_stack).unwrap();
+    vec![CILOp::InlineIL(
+        il.into(),
+        u32::try_from(max_stack).expect("Inline IL stack depth over 2^32!"),
+    )]
+}
+/// Returns the IL body of function `def_id`, if it has one.
+pub fn il_body(tyctx: TyCtxt, def_id: DefId) -> Option<Vec<CILOp>> {
+    let section = tyctx.codegen_fn_attrs(def_id).link_section?;
+    let body = section.as_str().strip_prefix(IL_BODY_SECTION_PREFIX)?;
+    assert_allowed(tyctx.crate_name(def_id.krate).as_str(), "IL method bodies");
+    Some(vec![parse_il_body(body)])
+}
+/// Parses the `MAX_STACK:IL` following the prefix of the link section of a function with an IL body.
+fn parse_il_body(body: &str) -> CILOp {
+    let (max_stack, il) = body
+        .split_once(':')
+        .expect("IL method bodies must start with their stack depth(`clr_il_body:MAX_STACK:IL`)!");
+    let max_stack = max_stack
+        .trim()
+        .parse()
+        .expect("The stack depth of an IL method body is not a number!");
+    CILOp::InlineIL(il.into(), max_stack)
+}
+#[test]
+fn il_body_stack_depth() {
+    assert_eq!(
+        parse_il_body("2:ldarg.0 ldarg.1 add ret"),
+        CILOp::InlineIL("ldarg.0 ldarg.1 add ret".into(), 2)
+    );
+    // IL may contain colons itself(eg. in method references).
+    assert_eq!(
+        parse_il_body("0:call void Foo::Bar() ret"),
+        CILOp::InlineIL("call void Foo::Bar() ret".into(), 0)
+    );
+}
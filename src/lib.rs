@@ -45,6 +45,8 @@
 
 // References to internal rustc crates.
 extern crate rustc_abi;
+extern crate rustc_ast;
+extern crate rustc_attr;
 extern crate rustc_codegen_ssa;
 extern crate rustc_data_structures;
 extern crate rustc_driver;
@@ -59,34 +61,6 @@ extern crate rustc_symbol_mangling;
 extern crate rustc_target;
 extern crate rustc_ty_utils;
 extern crate stable_mir;
-// Debug config
-
-/// Tells the codegen to insert comments containing the MIR statemtens after each one of them.
-const INSERT_MIR_DEBUG_COMMENTS: bool = false;
-/// Prints local types of all compiled MIR functions.
-const PRINT_LOCAL_TYPES: bool = false;
-/// Tells the codegen to optmize the emiited CIL.
-const OPTIMIZE_CIL: bool = (!TRACE_STATEMENTS) && (!INSERT_MIR_DEBUG_COMMENTS) && (true);
-/// Turns on the struct spliting optimzation.
-const SPLIT_LOCAL_STRUCTS: bool = false;
-/// Turns on the local removal optimization.
-const REMOVE_UNSUED_LOCALS: bool = false;
-/// Prints debug info during type handling.
-const PRINT_TY_CONVERTION: bool = false;
-/// Changes `.locals` into `.locals init`. Causes the runtime to always initialize local variables.
-/// Try turining on in cause of issues. If it fixes them, then their root cause is UB(eg. use of uninitailized memory).
-pub const ALWAYS_INIT_LOCALS: bool = false;
-/// Should the codegen stop working when ecountering an error, or try to press on, replacing unusuported code with exceptions throws?
-pub const ABORT_ON_ERROR: bool = false;
-/// Should the codegen continue working after it encoutnered a miscompilation?
-pub const ALLOW_MISCOMPILATIONS: bool = true;
-/// Preapends each function call with a debug message
-pub const TRACE_CALLS: bool = true;
-/// Preapends each statement with a debug message
-pub const TRACE_STATEMENTS: bool = false;
-/// Allows the optimizer to inline very simple functions. It is buggy.
-pub const INLINE_SIMPLE_FUNCTIONS: bool = false;
-
 // Modules
 
 /// Specifies if a method/type is private or public.
@@ -98,45 +72,98 @@ mod aggregate;
 pub mod assembly;
 /// Module containg ILASM-based exporter and code shared between all IL exporter.
 pub mod assembly_exporter;
-mod basic_block;
+/// Code handling atomic intrinsics.
+mod atomic;
+/// Basic-block representation of method bodies, used by passes reasoning about control flow.
+pub mod basic_block;
 /// Code handling binary operations
 mod binop;
+/// Lowering of bit manipulation intrinsics(`ctlz`, `cttz`, `ctpop`, `bswap`, rotations).
+mod bit_ops;
 mod call_info;
 /// Code hansling rust `as` casts.
 mod casts;
 mod checked_binop;
 /// A representation of C# IL op.
 pub mod cil;
+/// Tracks the function, block and statement being compiled, for use in error messages.
+mod codegen_context;
 /// Runtime errors and utlity functions/macros related to them
 mod codegen_error;
 /// Test harnesses.
 mod compile_test;
+/// Configuration of the backend and linker.
+pub mod config;
 /// Code handling loading constant values in CIL.
 mod constant;
+/// Feature-coverage reports, describing which parts of a crate can be compiled.
+mod coverage;
+/// Differential testing, comparing programs built with this backend with their native builds.
+pub mod differential;
+/// Code getting and setting the discriminants of enums, laid out like rustc lays them out.
+mod discriminant;
 /// Code detecting and inserting wrappers around entrypoints.
 mod entrypoint;
+/// Wide pointers: their representation, and helpers for constructing and splitting them.
+mod fat_ptr;
 /// Implementation of key external functions(eg. libc) necesary for propely running a Rust executable
 pub mod ffi;
 /// Signature of a function (inputs)->output
 pub mod function_sig;
+/// Generator of arbitrary methods, used for fuzzing the ILASM exporter.
+pub mod fuzz;
+/// Identical code folding: removing duplicate method bodies at link time.
+pub mod icf;
+/// Reusing methods lowered by previous builds.
+mod incremental;
+/// Raw IL written in the support library.
+mod inline_il;
 /// Interop type handling.
 mod interop;
+/// Shims for the libc functions `std` relies on.
+mod libc;
 //
 
 /// A representation of a .NET method
 pub mod method;
+/// Op-frequency and pattern statistics, used to guide optimization work.
+pub mod op_stats;
 /// Handles a MIR operand.
 mod operand;
 /// Method-level CIL opitimizations
 mod opt;
+/// Formatting of panic messages, trough a managed `fmt::Write` implementation.
+mod panic_fmt;
+/// Calling functions from native libraries using P/Invoke.
+pub mod pinvoke;
 /// Code handling getting/setting/adressing memory locations.
 mod place;
+/// Differences between the supported .NET runtimes.
+pub mod runtime;
 /// Converts righthandside of a MIR statement into CIL ops.
 mod rvalue;
+/// Generation of .NET projects around the produced assemblies.
+pub mod scaffold;
+/// Snapshot testing of the IL generated for small functions.
+pub mod snapshot;
+/// The on-disk format of serialized assemblies.
+pub mod serialization;
+/// SIMD vector intrinsics.
+mod simd;
+/// Spill mode: writing lowered methods to disk during codegen, bounding memory usage.
+pub mod spill;
 /// Code dealing with truning an individual MIR statement into CIL ops.
 mod statement;
+/// Machine-readable summaries of the structure of assemblies.
+pub mod summary;
+/// Marking self-recursive calls in return position as tail calls, at link time.
+pub mod tail_call;
 /// Converts a terminator of a basic block into CIL ops.
 mod terminator;
+/// Directory-driven test runner, comparing the output of test programs with their expected output.
+mod test_support;
+/// Measuring the throughput of the codegen.
+pub mod timings;
 /// Code handling transmutes.
 mod transmute;
 /// Code related to types.
@@ -146,6 +173,10 @@ pub mod r#type;
 mod unop;
 /// Contains small helper functions(debug assertions, functions used to get field names, etc), which are frequently used, but are not specific to a part of the coodegen.
 mod utilis;
+/// Verification of the evaluation stack of methods before export.
+pub mod verify;
+/// Trait objects: unsizing pointers to them, their vtables, and calls to their methods.
+mod vtable;
 // rustc functions used here.
 use rustc_codegen_ssa::{
     back::archive::{
@@ -161,7 +192,7 @@ use rustc_middle::{
     ty::TyCtxt,
 };
 use rustc_session::{
-    config::{OutputFilenames, OutputType},
+    config::{CrateType, OutputFilenames, OutputType},
     Session,
 };
 use rustc_span::ErrorGuaranteed;
@@ -174,12 +205,44 @@ use std::{
 pub type IString = Box<str>;
 
 use assembly::Assembly;
+/// The lowered code of a single codegen unit, saved to its own `.bc` file.
+struct CguFragment {
+    /// Name of the codegen unit, as assigned by rustc.
+    name: IString,
+    asm: Assembly,
+    /// The file the methods of the fragment were spilled to, in spill mode.
+    spill: Option<spill::MethodSpill>,
+}
 /// An instance of the codegen.
 struct MyBackend;
 impl CodegenBackend for MyBackend {
     fn locale_resource(&self) -> &'static str {
         ""
     }
+    /// Initializes the backend config, using the options passed with `-C llvm-args`.
+    fn init(&self, sess: &Session) {
+        crate::codegen_context::install_panic_hook();
+        // Proc-macros and build scripts are loaded and run by the compiler and cargo, so they must be native code for the host.
+        let host_only = if sess.opts.crate_types.contains(&CrateType::ProcMacro) {
+            Some("proc-macro")
+        } else if sess.opts.crate_name.as_deref() == Some("build_script_build") {
+            Some("build script")
+        } else {
+            None
+        };
+        if let Some(kind) = host_only {
+            sess.fatal(format!(
+                "The {kind} crate {name} must be compiled for the host, which the .NET backend can't do. Build with `cargo dotnet`, or pass \
+                `--target` to cargo, so that it compiles host crates without `RUSTFLAGS` and with the default backend.",
+                name = sess.opts.crate_name.as_deref().unwrap_or("?")
+            ));
+        }
+        let (config, errors) = crate::config::BackendConfig::from_args(&sess.opts.cg.llvm_args);
+        for err in errors {
+            sess.err(err);
+        }
+        crate::config::init(config);
+    }
     /// Compiles a crate, and returns its in-memory representaion as a .NET assembly.
     fn codegen_crate<'a>(
         &self,
@@ -189,19 +252,51 @@ impl CodegenBackend for MyBackend {
     ) -> Box<dyn Any> {
         {
             let (_defid_set, cgus) = tcx.collect_and_partition_mono_items(());
-
-            let mut codegen = Assembly::empty();
-            let mut cache = crate::r#type::TyCache::empty();
-            for cgu in cgus {
-                //println!("codegen {} has {} items.", cgu.name(), cgu.items().len());
-                for (item, data) in cgu.items() {
-                    // Data will be needed in the future.
-                    let _data = data;
-                    codegen
-                        .add_item(*item, tcx, &mut cache)
-                        .expect("Could not add function");
-                }
+            if let Some(dir) = &crate::config::config().coverage_dir {
+                coverage::CoverageReport::for_crate(tcx).write_to(dir);
+                let fragment = CguFragment {
+                    name: cgus.iter().next().unwrap().name().to_string().into(),
+                    asm: Assembly::empty(),
+                    spill: None,
+                };
+                return Box::new((
+                    vec![fragment],
+                    metadata,
+                    CrateInfo::new(tcx, "clr".to_string()),
+                ));
             }
+            incremental::init(tcx);
+            let outputs = tcx.output_filenames(());
+            let mut timings = timings::Timings::new();
+            let lowering_start = std::time::Instant::now();
+            // Each codegen unit is lowered into a separate fragment, with its own type cache, and saved to a separate `.bc` file. When rustc runs with
+            // multiple threads(`-Z threads`), the fragments are lowered in parallel. Otherwise, `par_map` lowers them one after another.
+            let mut fragments: Vec<CguFragment> =
+                rustc_data_structures::sync::par_map(cgus, |cgu| {
+                    let name: IString = cgu.name().to_string().into();
+                    // In spill mode, methods are written straight to the `.bc` file `join_codegen` would write the fragment to.
+                    let spill = crate::config::config().spill_methods.then(|| {
+                        spill::MethodSpill::create(
+                            &outputs.temp_path(OutputType::Bitcode, Some(&name)),
+                        )
+                        .expect("Could not create the file methods are spilled to!")
+                    });
+                    let mut asm = Assembly::empty();
+                    let mut cache = crate::r#type::TyCache::empty();
+                    for (item, data) in cgu.items() {
+                        // Data will be needed in the future.
+                        let _data = data;
+                        asm.add_item(*item, tcx, &mut cache)
+                            .expect("Could not add function");
+                        if let Some(spill) = &spill {
+                            asm.spill_methods(spill);
+                        }
+                    }
+                    CguFragment { name, asm, spill }
+                });
+            // The entrypoint and the FFI functions are only added to the first fragment, since the fragments get joined by the linker.
+            let first = &mut fragments[0].asm;
+            let mut cache = crate::r#type::TyCache::empty();
 
             if let Some((entrypoint, _kind)) = tcx.entry_fn(()) {
                 let penv = rustc_middle::ty::ParamEnv::reveal_all();
@@ -215,26 +310,52 @@ impl CodegenBackend for MyBackend {
                 .expect("Could not resolve entrypoint!");
                 let sig = function_sig::FnSig::sig_from_instance_(entrypoint, tcx, &mut cache)
                     .expect("Could not get the signature of the entrypoint.");
+                let exit_code =
+                    entrypoint::ExitCodeSource::for_entrypoint(tcx, entrypoint, &sig, &mut cache);
                 let symbol = tcx.symbol_name(entrypoint);
                 let symbol = format!("{symbol:?}");
                 let cs = cil::CallSite::new(None, symbol.into(), sig, true);
-                codegen.set_entrypoint(cs);
+                first.set_entrypoint(cs, &exit_code);
+                // Types needed by the signatures of the entrypoint and `Termination::report`.
+                for tpe in cache.take_new_defs() {
+                    first.add_typedef(tpe.clone());
+                }
             }
-            codegen.opt();
-            // Done twice for inlining!
-            codegen.opt();
-            ffi::insert_ffi_functions(&mut codegen, tcx);
-            let name: IString = cgus.iter().next().unwrap().name().to_string().into();
-
-            Box::new((
-                name,
-                codegen,
-                metadata,
-                CrateInfo::new(tcx, "clr".to_string()),
-            ))
+            pinvoke::add_pinvoke_decls(first, tcx, &mut cache);
+            panic_fmt::add_panic_writer(first, tcx, &mut cache);
+            for tpe in cache.take_new_defs() {
+                first.add_typedef(tpe.clone());
+            }
+            codegen_error::report_unsupported_summary();
+            incremental::finish();
+            let op_count = |fragments: &[CguFragment]| -> usize {
+                fragments
+                    .iter()
+                    .map(|fragment| fragment.asm.op_count())
+                    .sum()
+            };
+            timings.record("lowering", lowering_start.elapsed(), op_count(&fragments));
+            let ops = op_count(&fragments);
+            let mut fragments: Vec<CguFragment> = timings.time("opt", ops, || {
+                rustc_data_structures::sync::par_map(fragments, |mut fragment| {
+                    fragment.asm.opt();
+                    // Done twice for inlining!
+                    fragment.asm.opt();
+                    fragment
+                })
+            });
+            ffi::insert_ffi_functions(&mut fragments[0].asm, tcx);
+            if crate::config::config().timings {
+                eprint!(
+                    "Codegen timings of {crate_name}:\n{report}",
+                    crate_name = tcx.crate_name(rustc_span::def_id::LOCAL_CRATE),
+                    report = timings.report()
+                );
+            }
+            Box::new((fragments, metadata, CrateInfo::new(tcx, "clr".to_string())))
         }
     }
-    /// Saves an in-memory assemably to codegen specific IR in a .bc file.
+    /// Saves the in-memory fragments of an assembly to codegen specific IR, in one .bc file per codegen unit.
     fn join_codegen(
         &self,
         ongoing_codegen: Box<dyn Any>,
@@ -243,27 +364,48 @@ impl CodegenBackend for MyBackend {
     ) -> Result<(CodegenResults, FxIndexMap<WorkProductId, WorkProduct>), ErrorGuaranteed> {
         std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
             use std::io::Write;
-            let (_asm_name, asm, metadata, crate_info) = *ongoing_codegen
-                .downcast::<(IString, Assembly, EncodedMetadata, CrateInfo)>()
+            let (fragments, metadata, crate_info) = *ongoing_codegen
+                .downcast::<(Vec<CguFragment>, EncodedMetadata, CrateInfo)>()
                 .expect("in join_codegen: ongoing_codegen is not an Assembly");
-            let asm_name = "";
-            let serialized_asm_path = outputs.temp_path(OutputType::Bitcode, Some(asm_name));
-            //std::fs::create_dir_all(&serialized_asm_path).expect("Could not create the directory temporary files are supposed to be in.");
-            let mut asm_out = std::fs::File::create(&serialized_asm_path).expect(
-                "Could not create the temporary files necessary for building the assembly!",
-            );
-            asm_out
-                .write_all(
-                    &postcard::to_stdvec(&asm).expect("Could not serialize the tmp assembly file!"),
+            let mut modules = Vec::with_capacity(fragments.len());
+            for CguFragment { name, asm, spill } in fragments {
+                let serialized_asm_path = outputs.temp_path(OutputType::Bitcode, Some(&name));
+                if let Some(spill) = spill {
+                    // The spilled methods are already in the file, only the rest of the fragment is missing.
+                    assert_eq!(spill.path(), serialized_asm_path);
+                    spill
+                        .finish(&asm)
+                        .expect("Could not save the tmp assembly file!");
+                } else {
+                    let mut asm_out = std::fs::File::create(&serialized_asm_path).expect(
+                        "Could not create the temporary files necessary for building the assembly!",
+                    );
+                    asm_out
+                        .write_all(&asm.to_bytes())
+                        .expect("Could not save the tmp assembly file!");
+                }
+                modules.push(CompiledModule {
+                    name: name.into(),
+                    kind: ModuleKind::Regular,
+                    object: Some(serialized_asm_path),
+                    bytecode: None,
+                    dwarf_object: None,
+                });
+            }
+            if let Some(dir) = &crate::config::config().capture_dir {
+                // The fragments are captured as a single assembly, so that each file in the corpus holds a whole crate.
+                let asm = modules
+                    .iter()
+                    .filter_map(|module| module.object.as_ref())
+                    .map(|path| Assembly::load(path).expect("Could not load a fragment!"))
+                    .fold(Assembly::empty(), Assembly::join);
+                std::fs::create_dir_all(dir).expect("Could not create the capture directory!");
+                std::fs::write(
+                    dir.join(format!("{}.bc", crate_info.local_crate_name)),
+                    asm.to_bytes(),
                 )
-                .expect("Could not save the tmp assembly file!");
-            let modules = vec![CompiledModule {
-                name: asm_name.into(),
-                kind: ModuleKind::Regular,
-                object: Some(serialized_asm_path),
-                bytecode: None,
-                dwarf_object: None,
-            }];
+                .expect("Could not capture the assembly!");
+            }
             let codegen_results = CodegenResults {
                 modules,
                 allocator_module: None,
@@ -0,0 +1,103 @@
+//! Shims for the libc functions `std` calls in ways which can't be supported by simply implementing them.
+//!
+//! The signatures of libc functions use types(like `timespec` or `pthread_attr_t`) which are only known while lowering the call, so
+//! calls to them are replaced with inline ops, instead of calls to methods implementing them. Those ops may call helpers with simpler
+//! signatures, inserted into every assembly by [`crate::ffi`].
+use crate::{
+    cil::{CILOp, CallSite},
+    function_sig::FnSig,
+    r#type::Type,
+};
+/// `ENOSYS`: function not implemented.
+const ENOSYS: i32 = 38;
+/// Checks if calls to the foreign function `name` are replaced by [`call_shim`].
+pub(crate) fn has_shim(name: &str) -> bool {
+    matches!(
+        name,
+        "pthread_attr_init"
+            | "pthread_attr_destroy"
+            | "pthread_attr_setstacksize"
+            | "pthread_create"
+            | "clock_gettime"
+            | "getenv"
+            | "isatty"
+            | "dlsym"
+            | "sigaction"
+    )
+}
+/// Returns the ops calculating the return value of libc function `name`, called with the arguments loaded by `args`.
+pub(crate) fn call_shim(name: &str, args: Vec<Vec<CILOp>>) -> Vec<CILOp> {
+    match name {
+        "pthread_attr_init" | "pthread_attr_destroy" | "pthread_attr_setstacksize" => {
+            vec![CILOp::LdcI32(0)]
+        }
+        // Threads are not supported yet. `std` reports `ENOSYS` as `ErrorKind::Unsupported`, which makes libtest run tests on the main thread.
+        "pthread_create" => vec![CILOp::LdcI32(ENOSYS)],
+        "clock_gettime" => with_args(args, clock_gettime()),
+        "getenv" => with_args(args, getenv()),
+        // Output is never a terminal, so no escape sequences(eg. colors) are written.
+        "isatty" => vec![CILOp::LdcI32(0)],
+        // No symbols can be looked up dynamically, so optional(`weak!`) functions are always reported as missing.
+        "dlsym" => vec![CILOp::LdcI32(0), CILOp::ConvUSize(false)],
+        // `struct sigaction` differs between targets, so only a pointer to it is passed to the helper implementing `sigaction`.
+        "sigaction" => with_args(args, sigaction()),
+        _ => panic!("{name} has no libc shim!"),
+    }
+}
+fn with_args(args: Vec<Vec<CILOp>>, call: CILOp) -> Vec<CILOp> {
+    let mut ops: Vec<CILOp> = args.into_iter().flatten().collect();
+    ops.push(call);
+    ops
+}
+/// Returns a call to `rust_clock_gettime(clock: i32, timespec: *mut i64) -> i32`.
+fn clock_gettime() -> CILOp {
+    CILOp::Call(CallSite::boxed(
+        None,
+        "rust_clock_gettime".into(),
+        FnSig::new(&[Type::I32, Type::Ptr(Type::I64.into())], &Type::I32),
+        true,
+    ))
+}
+/// Returns a call to `rust_getenv(name: *const u8) -> *const u8`.
+fn getenv() -> CILOp {
+    CILOp::Call(CallSite::boxed(
+        None,
+        "rust_getenv".into(),
+        FnSig::new(&[Type::Ptr(Type::U8.into())], &Type::Ptr(Type::U8.into())),
+        true,
+    ))
+}
+/// Returns a call to `rust_sigaction(signum: i32, act: *const usize, oldact: *mut usize) -> i32`.
+fn sigaction() -> CILOp {
+    CILOp::Call(CallSite::boxed(
+        None,
+        "rust_sigaction".into(),
+        FnSig::new(
+            &[
+                Type::I32,
+                Type::Ptr(Type::USize.into()),
+                Type::Ptr(Type::USize.into()),
+            ],
+            &Type::I32,
+        ),
+        true,
+    ))
+}
+/// Returns the ops loading `argc`: the number of command line arguments, including the path of the program.
+pub(crate) fn argc() -> CILOp {
+    CILOp::Call(CallSite::boxed(
+        None,
+        "rust_argc".into(),
+        FnSig::new(&[], &Type::I32),
+        true,
+    ))
+}
+/// Returns the ops loading `argv`: a null-terminated array of null-terminated UTF-8 command line arguments.
+pub(crate) fn argv() -> CILOp {
+    CILOp::Call(CallSite::boxed(
+        None,
+        "rust_argv".into(),
+        FnSig::new(&[], &Type::Ptr(Type::Ptr(Type::U8.into()).into())),
+        true,
+    ))
+}
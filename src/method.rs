@@ -1,37 +1,181 @@
 use crate::{
     access_modifier::AccessModifer,
+    basic_block::MethodBody,
     cil::{CILOp, CallSite},
     function_sig::FnSig,
-    r#type::Type,
+    pinvoke::{CallConv, PInvoke},
+    r#type::{DotnetTypeRef, Type},
     IString,
 };
 use serde::{Deserialize, Serialize};
-use std::hash::{Hash, Hasher};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
 /// Represenation of a CIL method.
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct Method {
     access: AccessModifer,
     is_static: bool,
-    sig: FnSig,
+    #[serde(with = "crate::function_sig::shared")]
+    sig: Arc<FnSig>,
     name: IString,
     locals: Vec<LocalDef>,
     ops: Vec<CILOp>,
     attributes: Vec<Attribute>,
+    #[serde(default)]
+    handlers: Vec<ExceptionHandler>,
+    /// The maximum depth of the evaluation stack, if it was computed since the ops last changed. See [`Method::update_max_stack`].
+    #[serde(default)]
+    max_stack: Option<u32>,
+    #[serde(default)]
+    impl_flags: ImplFlags,
+}
+/// Maximum number of ops shown in a single block of a CFG dump.
+pub const CFG_MAX_OPS: usize = 16;
+/// Escapes `text`, so that it can be placed within a quoted DOT string.
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
 }
 /// Local varaible. Consists of an optional name and type.
 pub type LocalDef = (Option<IString>, Type);
 impl Eq for Method {}
+/// Returns the maximum depth of the evaluation stack reached by `ops`, along all paths trough them. Each path is followed from the start of the method,
+/// a handler or a branch target, until a diverging op or an already visited label. Labels reached by different paths have the same stack depth in valid IL,
+/// so each label needs to be visited only once.
+fn max_stack_depth(ops: &[CILOp], handlers: &[ExceptionHandler]) -> u32 {
+    let labels: HashMap<u32, usize> = ops
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, op)| match op {
+            CILOp::Label(id) => Some((*id, idx)),
+            _ => None,
+        })
+        .collect();
+    // The stack at the start of a `catch` handler holds the exception.
+    let mut pending: Vec<(usize, isize)> = vec![(0, 0)];
+    pending.extend(handlers.iter().filter_map(|handler| {
+        let depth = isize::from(matches!(handler.kind(), HandlerKind::Catch(_)));
+        labels
+            .get(&handler.handler_range().0)
+            .map(|idx| (*idx, depth))
+    }));
+    let mut visited = HashSet::new();
+    let mut max = 0;
+    while let Some((start, mut depth)) = pending.pop() {
+        max = max.max(depth);
+        for op in ops.iter().skip(start) {
+            if let CILOp::Label(id) = op {
+                if !visited.insert(*id) {
+                    break;
+                }
+            }
+            // Inline IL leaves the stack as it found it, but may use it in the meantime.
+            if let CILOp::InlineIL(_, il_stack) = op {
+                max = max.max(depth + *il_stack as isize);
+            }
+            // Pops happen before pushes, so the depth after an op is its highest one.
+            depth = (depth + op.stack_diff()).max(0);
+            // `leave` empties the stack.
+            if let CILOp::Leave(_) = op {
+                depth = 0;
+            }
+            max = max.max(depth);
+            for target in op.branch_targets() {
+                if let Some(idx) = labels.get(target).filter(|_| !visited.contains(target)) {
+                    pending.push((*idx, depth));
+                }
+            }
+            if op.is_diverging() {
+                break;
+            }
+        }
+    }
+    u32::try_from(max).expect("Evaluation stack deeper than 2^32 values!")
+}
 impl Hash for Method {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.sig.hash(state);
         self.name.hash(state);
     }
 }
+/// Kind of an exception handler.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum HandlerKind {
+    /// Runs if an exception of the given type(or a type derived from it) is thrown within the protected region. Ends with [`CILOp::Leave`].
+    Catch(Box<DotnetTypeRef>),
+    /// Runs whenever control leaves the protected region, be it normally or by an exception. Ends with [`CILOp::EndFinally`].
+    Finally,
+    /// Runs only if an exception is thrown within the protected region, and is not caught. Ends with [`CILOp::EndFinally`].
+    Fault,
+}
+/// A protected(`try`) region and its handler. Both are described by labels: a region starts at its start label, and ends right before its end label.
+/// Control may only leave the protected region and `catch` handlers using [`CILOp::Leave`].
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct ExceptionHandler {
+    try_start: u32,
+    try_end: u32,
+    kind: HandlerKind,
+    handler_start: u32,
+    handler_end: u32,
+}
+impl ExceptionHandler {
+    /// Creates a handler of kind `kind`, spanning labels `handler_start..handler_end`, protecting the region spanning labels `try_start..try_end`.
+    #[must_use]
+    pub fn new(
+        try_start: u32,
+        try_end: u32,
+        kind: HandlerKind,
+        handler_start: u32,
+        handler_end: u32,
+    ) -> Self {
+        Self {
+            try_start,
+            try_end,
+            kind,
+            handler_start,
+            handler_end,
+        }
+    }
+    /// Returns the labels the protected region starts and ends at.
+    pub fn try_range(&self) -> (u32, u32) {
+        (self.try_start, self.try_end)
+    }
+    /// Returns the labels the handler starts and ends at.
+    pub fn handler_range(&self) -> (u32, u32) {
+        (self.handler_start, self.handler_end)
+    }
+    /// Returns the kind of this handler.
+    pub fn kind(&self) -> &HandlerKind {
+        &self.kind
+    }
+}
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
 /// Method attribute.
 pub enum Attribute {
     /// Set if the function is the assemblys entrypoint.
     EntryPoint,
+    /// Set if the function is implemented by a native library. Such functions have no body.
+    PInvoke(Box<PInvoke>),
+    /// Set if native code may call the function directly, using the name `entry_point` and the calling convention `call_conv`.
+    UnmanagedCallersOnly {
+        entry_point: IString,
+        call_conv: CallConv,
+    },
+}
+/// Implementation flags of a method, telling the JIT how to treat it.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum ImplFlags {
+    /// The JIT decides whether the method is inlined.
+    #[default]
+    None,
+    /// The method should be inlined whenever possible(`aggressiveinlining`). Used for small shims and `#[inline(always)]` functions.
+    AggressiveInlining,
+    /// The method must never be inlined(`noinlining`). Used for `#[inline(never)]` functions.
+    NoInlining,
 }
 impl Method {
     /// Creates new method with `access` access modifier, signature `sig`, name `name`, locals `locals`, and `is_static` if method is static.
@@ -39,18 +183,21 @@ impl Method {
     pub fn new(
         access: AccessModifer,
         is_static: bool,
-        sig: FnSig,
+        sig: impl Into<Arc<FnSig>>,
         name: &str,
         locals: Vec<LocalDef>,
     ) -> Self {
         Self {
             access,
             is_static,
-            sig,
+            sig: sig.into(),
             name: name.into(),
             locals,
             ops: Vec::new(),
             attributes: Vec::new(),
+            handlers: Vec::new(),
+            max_stack: None,
+            impl_flags: ImplFlags::default(),
         }
     }
     pub(crate) fn ensure_valid(&mut self) {
@@ -84,6 +231,7 @@ impl Method {
     }
     /// Returns a mutable reference to this functions ops.
     pub fn ops_mut(&mut self) -> &mut Vec<CILOp> {
+        self.max_stack = None;
         &mut self.ops
     }
     /// Returns the access modifier of this function.
@@ -108,8 +256,21 @@ impl Method {
     }
     /// Sets this methods CIL ops to `ops`.
     pub fn set_ops(&mut self, ops: Vec<CILOp>) {
+        self.max_stack = None;
         self.ops = ops;
     }
+    /// Computes the maximum depth of the evaluation stack of this method, and stores it, so that the exporter does not need to compute it again.
+    pub fn update_max_stack(&mut self) -> u32 {
+        let max_stack = max_stack_depth(&self.ops, &self.handlers);
+        self.max_stack = Some(max_stack);
+        max_stack
+    }
+    /// Returns the maximum depth of the evaluation stack of this method(its `.maxstack`). It is computed, unless it was stored by
+    /// [`Method::update_max_stack`] since the ops last changed.
+    pub fn max_stack(&self) -> u32 {
+        self.max_stack
+            .unwrap_or_else(|| max_stack_depth(&self.ops, &self.handlers))
+    }
     /// Returns the ops of this method.
     pub fn get_ops(&self) -> &[CILOp] {
         &self.ops
@@ -119,7 +280,7 @@ impl Method {
         self.ops.iter().filter_map(|op| op.call())
     }
     pub(crate) fn call_site(&self) -> CallSite {
-        CallSite::new(None, self.name().into(), self.sig().clone(), true)
+        CallSite::new(None, self.name().into(), self.sig.clone(), true)
     }
     /*
     pub(crate) fn failed_to_compile(name:&str,reason:&str)->Self{
@@ -127,19 +288,33 @@ impl Method {
     }*/
     pub(crate) fn allocate_temporaries(&mut self) {
         let mut tmp_stack = vec![];
-        let ops = &mut self.ops;
+        // Freed temporaries, by type. A temporary is always set before it is used, so a freed one can be reused by any later temporary
+        // of the same type, instead of allocating a new local.
+        let mut free: HashMap<Type, Vec<usize>> = HashMap::new();
+        let mut body = self.body();
+        // Temporaries are allocated and freed in the order of the ops, so the blocks are visited in order.
+        let ops = body
+            .blocks_mut()
+            .iter_mut()
+            .flat_map(|block| block.ops_mut().iter_mut());
         for op in ops {
             match op {
                 CILOp::NewTMPLocal(tpe) => {
-                    let index = self.locals.len();
-                    self.locals.push((None, tpe.as_ref().clone()));
+                    let reused = free.get_mut(tpe.as_ref()).and_then(Vec::pop);
+                    let index = reused.unwrap_or_else(|| {
+                        self.locals.push((None, tpe.as_ref().clone()));
+                        self.locals.len() - 1
+                    });
                     tmp_stack.push(index);
                     *op = CILOp::Nop;
                 }
                 CILOp::FreeTMPLocal => {
-                    tmp_stack
+                    let index = tmp_stack
                         .pop()
                         .expect("Freeing TMP local when none existed");
+                    free.entry(self.locals[index].1.clone())
+                        .or_default()
+                        .push(index);
                     *op = CILOp::Nop;
                 }
                 CILOp::LoadTMPLocal => {
@@ -167,14 +342,246 @@ impl Method {
                 _ => (),
             }
         }
+        self.set_body(body);
         //todo!("Can't allocate temporaries quite yet!");
     }
+    /// Returns the body of this method, split into basic blocks.
+    pub fn body(&self) -> MethodBody {
+        MethodBody::from_ops(&self.ops, &self.handlers)
+    }
+    /// Sets the ops and exception handlers of this method to the ones of `body`.
+    pub fn set_body(&mut self, body: MethodBody) {
+        self.handlers = body.handlers().into();
+        self.set_ops(body.into_ops());
+    }
+    /// Adds exception handler `handler` to self. Handlers of nested regions must be added before the handlers of regions enclosing them.
+    pub fn add_handler(&mut self, handler: ExceptionHandler) {
+        self.handlers.push(handler);
+    }
+    /// Returns the exception handlers of this method, innermost first.
+    pub fn handlers(&self) -> &[ExceptionHandler] {
+        &self.handlers
+    }
     /// Adds method attribute `attr` to self.
+    /// Returns the name and calling convention native code uses to call this method, if it is exported to native code.
+    pub fn unmanaged_export(&self) -> Option<(&str, CallConv)> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::UnmanagedCallersOnly {
+                entry_point,
+                call_conv,
+            } => Some((entry_point.as_ref(), *call_conv)),
+            _ => None,
+        })
+    }
+    /// Returns the native function this method calls, if it is a P/Invoke method.
+    pub fn pinvoke(&self) -> Option<&PInvoke> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::PInvoke(pinvoke) => Some(pinvoke.as_ref()),
+            _ => None,
+        })
+    }
     pub fn add_attribute(&mut self, attr: Attribute) {
         self.attributes.push(attr);
     }
+    /// Returns the implementation flags of this method.
+    pub fn impl_flags(&self) -> ImplFlags {
+        self.impl_flags
+    }
+    /// Sets the implementation flags of this method.
+    pub fn set_impl_flags(&mut self, impl_flags: ImplFlags) {
+        self.impl_flags = impl_flags;
+    }
+    /// Returns a Graphviz DOT graph of the control flow of this method. Basic blocks are reconstructed from labels and branches,
+    /// and each one is annotated with (up to [`CFG_MAX_OPS`]) of its ops.
+    pub fn dump_cfg_dot(&self) -> String {
+        let body = self.body();
+        let blocks = body.blocks();
+        let mut dot = format!("digraph \"{}\" {{\n", escape_dot(&self.name));
+        dot.push_str("\tnode [shape=box, fontname=monospace];\n");
+        for (idx, block) in blocks.iter().enumerate() {
+            let ops: Vec<&CILOp> = block.ops().iter().chain(block.terminator()).collect();
+            let mut text = match block.label() {
+                Some(label) => format!("bb_{label}:\\l"),
+                None => format!("<unlabeled {idx}>:\\l"),
+            };
+            for op in ops.iter().copied().take(CFG_MAX_OPS) {
+                text.push_str(&escape_dot(&crate::assembly_exporter::ilasm_op::op_cli(op)));
+                text.push_str("\\l");
+            }
+            if ops.len() > CFG_MAX_OPS {
+                text.push_str(&format!("... {} more ops\\l", ops.len() - CFG_MAX_OPS));
+            }
+            dot.push_str(&format!("\tn{idx} [label=\"{text}\"];\n"));
+            for target in block.targets() {
+                match body.block_of_label(*target) {
+                    Some(target_idx) => dot.push_str(&format!("\tn{idx} -> n{target_idx};\n")),
+                    None => dot.push_str(&format!(
+                        "\tmissing_{target} [label=\"missing bb_{target}\", color=red];\n\tn{idx} -> missing_{target} [color=red];\n"
+                    )),
+                }
+            }
+            if idx + 1 < blocks.len() && block.falls_through() {
+                dot.push_str(&format!(
+                    "\tn{idx} -> n{next} [style=dashed];\n",
+                    next = idx + 1
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
     /// Sets the list of locals of self to `locals`.
     pub fn set_locals(&mut self, locals: impl Into<Vec<(Option<IString>, Type)>>) {
         self.locals = locals.into();
     }
+    /// Removes the locals which are never accessed, and for which `removable` returns true, and renumbers the remaining ones.
+    pub(crate) fn remove_unused_locals(&mut self, removable: impl Fn(usize) -> bool) {
+        let used: HashSet<u32> = self
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                CILOp::LDLoc(local) | CILOp::STLoc(local) | CILOp::LDLocA(local) => Some(*local),
+                _ => None,
+            })
+            .collect();
+        let mut local_map = Vec::with_capacity(self.locals.len());
+        let mut new_locals = Vec::with_capacity(self.locals.len());
+        for (local, def) in self.locals.iter().enumerate() {
+            if removable(local) && !used.contains(&(local as u32)) {
+                local_map.push(u32::MAX);
+            } else {
+                local_map.push(new_locals.len() as u32);
+                new_locals.push(def.clone());
+            }
+        }
+        // Only the indices of locals change, so the maximum stack depth stays the same.
+        for op in &mut self.ops {
+            if let CILOp::LDLoc(local) | CILOp::STLoc(local) | CILOp::LDLocA(local) = op {
+                *local = local_map[*local as usize];
+            }
+        }
+        self.locals = new_locals;
+    }
+}
+#[test]
+fn max_stack_of_all_paths() {
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[Type::I32], &Type::I32),
+        "max_stack",
+        vec![],
+    );
+    method.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::BZero(1),
+        CILOp::LdcI32(1),
+        CILOp::Ret,
+        // Only reachable by the branch: pushes 3 values.
+        CILOp::Label(1),
+        CILOp::LdcI32(1),
+        CILOp::LdcI32(2),
+        CILOp::LdcI32(3),
+        CILOp::Add,
+        CILOp::Add,
+        CILOp::Ret,
+    ]);
+    assert_eq!(method.max_stack(), 3);
+    assert_eq!(method.update_max_stack(), 3);
+    method.set_ops(vec![CILOp::LDArg(0), CILOp::Ret]);
+    assert_eq!(method.max_stack(), 1);
+}
+#[test]
+fn max_stack_of_inline_il() {
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[], &Type::I32),
+        "inline_il",
+        vec![],
+    );
+    method.set_ops(vec![
+        CILOp::LdcI32(1),
+        CILOp::InlineIL("ldc.i4.2 ldc.i4.3 add pop".into(), 2),
+        CILOp::Ret,
+    ]);
+    assert_eq!(method.max_stack(), 3);
+    // A whole body written in IL.
+    method.set_ops(vec![CILOp::InlineIL("ldc.i4.1 ret".into(), 1)]);
+    assert_eq!(method.max_stack(), 1);
+}
+#[test]
+fn temporaries_reused() {
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[], &Type::Void),
+        "temporaries",
+        vec![(None, Type::I32)],
+    );
+    method.set_ops(vec![
+        CILOp::NewTMPLocal(Type::I32.into()),
+        CILOp::NewTMPLocal(Type::F64.into()),
+        CILOp::SetTMPLocal,
+        CILOp::FreeTMPLocal,
+        CILOp::SetTMPLocal,
+        CILOp::FreeTMPLocal,
+        // Both temporaries were freed, so they are reused.
+        CILOp::NewTMPLocal(Type::F64.into()),
+        CILOp::NewTMPLocal(Type::I32.into()),
+        CILOp::LoadTMPLocal,
+        CILOp::FreeTMPLocal,
+        CILOp::LoadTMPLocal,
+        CILOp::FreeTMPLocal,
+        CILOp::Ret,
+    ]);
+    method.allocate_temporaries();
+    assert_eq!(method.locals().len(), 3);
+    let ops: Vec<_> = method
+        .get_ops()
+        .iter()
+        .filter(|op| **op != CILOp::Nop)
+        .cloned()
+        .collect();
+    assert_eq!(
+        ops,
+        [
+            CILOp::STLoc(2),
+            CILOp::STLoc(1),
+            CILOp::LDLoc(1),
+            CILOp::LDLoc(2),
+            CILOp::Ret,
+        ]
+    );
+}
+#[test]
+fn unused_locals_removed() {
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[], &Type::I32),
+        "unused_locals",
+        vec![
+            (None, Type::I32),
+            (None, Type::Void),
+            (None, Type::F64),
+            (None, Type::Void),
+        ],
+    );
+    method.set_ops(vec![
+        CILOp::LDLocA(3),
+        CILOp::Pop,
+        CILOp::LDLoc(0),
+        CILOp::Ret,
+    ]);
+    // Local 2 is unused, but not removable. Local 3 is removable, but used.
+    method.remove_unused_locals(|local| local != 2);
+    assert_eq!(
+        method.locals(),
+        [(None, Type::I32), (None, Type::F64), (None, Type::Void)]
+    );
+    assert_eq!(
+        method.get_ops(),
+        [CILOp::LDLocA(2), CILOp::Pop, CILOp::LDLoc(0), CILOp::Ret]
+    );
 }
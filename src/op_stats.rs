@@ -0,0 +1,209 @@
+//! Op-frequency and pattern telemetry, used to find out which op sequences dominate real programs, and prioritize optimizations accordingly.
+//!
+//! [`OpStats`] counts how often each kind of op appears in an assembly, and how often each short sequence of ops(a pattern, or n-gram) does.
+//! Patterns never cross labels, and end at the first jump or return, since a peephole optimization could not rewrite them anyway.
+//! Comments are ignored.
+use crate::{assembly::Assembly, cil::CILOp, method::Method, summary::op_name};
+use serde::Serialize;
+use std::collections::HashMap;
+/// Length of the longest pattern collected by default.
+pub const DEFAULT_MAX_PATTERN_LEN: usize = 3;
+/// Number of most common ops and patterns included in a report by default.
+pub const DEFAULT_TOP: usize = 50;
+/// Op-frequency and pattern statistics of an assembly.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct OpStats {
+    /// Number of ops in all methods.
+    pub total_ops: usize,
+    /// Number of methods the statistics were collected from.
+    pub methods: usize,
+    /// Most common kinds of ops, from the most common.
+    pub ops: Vec<OpCount>,
+    /// Most common patterns, from the most common.
+    pub patterns: Vec<PatternCount>,
+}
+/// Number of ops of a given kind.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct OpCount {
+    /// Name of the kind of op(eg. `LdcI32`).
+    pub op: String,
+    pub count: usize,
+}
+/// Number of occurrences of a sequence of ops.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PatternCount {
+    /// Names of the kinds of ops in the pattern, in order.
+    pub ops: Vec<String>,
+    pub count: usize,
+    /// Number of methods the pattern occurs in.
+    pub methods: usize,
+}
+impl OpStats {
+    /// Collects the statistics of all methods in `asm`(including the methods of its types), keeping the `top` most common ops
+    /// and patterns of 2 up to `max_pattern_len` ops.
+    #[must_use]
+    pub fn new(asm: &Assembly, max_pattern_len: usize, top: usize) -> Self {
+        let methods: Vec<&Method> = asm
+            .methods()
+            .chain(asm.types().flat_map(|tpe| tpe.methods()))
+            .collect();
+        // Op names are interned, so patterns can be keyed by cheap ids.
+        let mut names: Vec<String> = Vec::new();
+        let mut ids: HashMap<String, u32> = HashMap::new();
+        let mut op_counts: HashMap<u32, usize> = HashMap::new();
+        // Pattern -> (occurrences, methods it occurs in, index of the last method it was seen in).
+        let mut patterns: HashMap<Vec<u32>, (usize, usize, usize)> = HashMap::new();
+        let mut total_ops = 0;
+        for (method_idx, method) in methods.iter().enumerate() {
+            for run in runs(method.get_ops()) {
+                let run: Vec<u32> = run
+                    .iter()
+                    .map(|op| {
+                        let name = op_name(op);
+                        *ids.entry(name).or_insert_with_key(|name| {
+                            names.push(name.clone());
+                            (names.len() - 1) as u32
+                        })
+                    })
+                    .collect();
+                total_ops += run.len();
+                for id in &run {
+                    *op_counts.entry(*id).or_insert(0) += 1;
+                }
+                for len in 2..=max_pattern_len {
+                    for window in run.windows(len) {
+                        let entry = patterns
+                            .entry(window.to_vec())
+                            .or_insert((0, 0, usize::MAX));
+                        entry.0 += 1;
+                        if entry.2 != method_idx {
+                            entry.1 += 1;
+                            entry.2 = method_idx;
+                        }
+                    }
+                }
+            }
+        }
+        let mut ops: Vec<OpCount> = op_counts
+            .into_iter()
+            .map(|(id, count)| OpCount {
+                op: names[id as usize].clone(),
+                count,
+            })
+            .collect();
+        ops.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.op.cmp(&b.op)));
+        ops.truncate(top);
+        let mut patterns: Vec<PatternCount> = patterns
+            .into_iter()
+            .map(|(pattern, (count, methods, _))| PatternCount {
+                ops: pattern
+                    .iter()
+                    .map(|id| names[*id as usize].clone())
+                    .collect(),
+                count,
+                methods,
+            })
+            .collect();
+        patterns.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.ops.cmp(&b.ops)));
+        patterns.truncate(top);
+        Self {
+            total_ops,
+            methods: methods.len(),
+            ops,
+            patterns,
+        }
+    }
+    /// Returns the statistics as JSON.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Could not serialize the op statistics!")
+    }
+    /// Returns a human-readable report of the statistics.
+    #[must_use]
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "{total_ops} ops in {methods} methods\nmost common ops:\n",
+            total_ops = self.total_ops,
+            methods = self.methods
+        );
+        for op in &self.ops {
+            report.push_str(&format!("{count:>10} {op}\n", count = op.count, op = op.op));
+        }
+        report.push_str("most common patterns:\n");
+        for pattern in &self.patterns {
+            report.push_str(&format!(
+                "{count:>10} {ops}(in {methods} methods)\n",
+                count = pattern.count,
+                ops = pattern.ops.join(" "),
+                methods = pattern.methods
+            ));
+        }
+        report
+    }
+}
+/// Splits `ops` into runs of straight-line code, which patterns are collected from.
+fn runs(ops: &[CILOp]) -> Vec<Vec<&CILOp>> {
+    let mut runs = Vec::new();
+    let mut run = Vec::new();
+    for op in ops {
+        match op {
+            CILOp::Comment(_) => (),
+            CILOp::Label(_) => runs.push(std::mem::take(&mut run)),
+            _ => {
+                run.push(op);
+                if op.is_diverging() || !op.branch_targets().is_empty() {
+                    runs.push(std::mem::take(&mut run));
+                }
+            }
+        }
+    }
+    runs.push(run);
+    runs.retain(|run| !run.is_empty());
+    runs
+}
+#[test]
+fn patterns() {
+    use crate::{access_modifier::AccessModifer, function_sig::FnSig, r#type::Type};
+    let mut asm = Assembly::empty();
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[], &Type::I32),
+        "main",
+        vec![],
+    );
+    method.set_ops(vec![
+        CILOp::LdcI32(1),
+        CILOp::LdcI32(2),
+        CILOp::Add,
+        CILOp::Label(0),
+        CILOp::LdcI32(3),
+        CILOp::Comment("ignored".into()),
+        CILOp::Add,
+        CILOp::Ret,
+    ]);
+    asm.add_method(method);
+    let stats = OpStats::new(&asm, DEFAULT_MAX_PATTERN_LEN, DEFAULT_TOP);
+    assert_eq!(stats.total_ops, 6);
+    assert_eq!(
+        stats.ops[0],
+        OpCount {
+            op: "LdcI32".into(),
+            count: 3
+        }
+    );
+    // `LdcI32 Add` occurs twice, once on each side of the label.
+    assert_eq!(
+        stats.patterns[0],
+        PatternCount {
+            ops: vec!["LdcI32".into(), "Add".into()],
+            count: 2,
+            methods: 1
+        }
+    );
+    // No pattern crosses the label.
+    assert!(!stats
+        .patterns
+        .iter()
+        .any(|pattern| pattern.ops == ["Add", "LdcI32"]));
+}
@@ -0,0 +1,126 @@
+//! Branch simplification: removing jumps made redundant by the order of basic blocks.
+//!
+//! Basic blocks are emitted in MIR order, and each one ends with an explicit jump to its successor, even if the successor is right after it.
+//! Jumps to the next op are removed, and a block entered by only one jump, which can't be fallen into, is moved in place of that jump. Together,
+//! this lays straight-line code out contiguously, which also makes it more likely that the remaining branches are short enough for their short
+//! forms(eg. `br.s`), picked by ilasm.
+use crate::cil::CILOp;
+use std::collections::HashMap;
+/// Removes jumps to labels right after them(not counting other labels, comments, source spans and `nop`s). Returns the number of removed jumps.
+fn remove_jumps_to_next(ops: &mut Vec<CILOp>) -> usize {
+    let len = ops.len();
+    let mut idx = 0;
+    let jumps_to_next: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter_map(|(jump, op)| {
+            let CILOp::GoTo(target) = op else {
+                return None;
+            };
+            ops[jump + 1..]
+                .iter()
+                .take_while(|op| {
+                    matches!(
+                        op,
+                        CILOp::Label(_) | CILOp::Comment(_) | CILOp::SourceSpan(_) | CILOp::Nop
+                    )
+                })
+                .any(|op| *op == CILOp::Label(*target))
+                .then_some(jump)
+        })
+        .collect();
+    ops.retain(|_| {
+        let keep = !jumps_to_next.contains(&idx);
+        idx += 1;
+        keep
+    });
+    len - ops.len()
+}
+/// Returns the index one past the last op of the block starting with the label at `label`, if the block ends with a diverging op. Returns `None` if
+/// the block falls through into the next one.
+fn block_end(ops: &[CILOp], label: usize) -> Option<usize> {
+    for (idx, op) in ops.iter().enumerate().skip(label + 1) {
+        match op {
+            CILOp::Label(_) => return None,
+            op if op.is_diverging() => return Some(idx + 1),
+            _ => (),
+        }
+    }
+    None
+}
+/// Finds a jump to a block which can be moved in its place. Returns the index of the jump, and the range of the block, including its label.
+fn find_mergeable(ops: &[CILOp]) -> Option<(usize, usize, usize)> {
+    let mut uses: HashMap<u32, usize> = HashMap::new();
+    for target in ops.iter().flat_map(|op| op.branch_targets()) {
+        *uses.entry(*target).or_default() += 1;
+    }
+    ops.iter().enumerate().find_map(|(jump, op)| {
+        let CILOp::GoTo(target) = op else {
+            return None;
+        };
+        if uses[target] != 1 {
+            return None;
+        }
+        let label = ops.iter().position(|op| *op == CILOp::Label(*target))?;
+        // The block must not be entered by falling into it. The first block is entered when the method is called.
+        if label == 0 || !ops[label - 1].is_diverging() {
+            return None;
+        }
+        let end = block_end(ops, label)?;
+        (!(label..end).contains(&jump)).then_some((jump, label, end))
+    })
+}
+/// Moves blocks entered by only one jump in place of that jump. Returns the number of moved blocks.
+fn merge_blocks(ops: &mut Vec<CILOp>) -> usize {
+    let mut merged = 0;
+    // Every merge removes one jump, so this ends.
+    while let Some((jump, label, end)) = find_mergeable(ops) {
+        let body: Vec<CILOp> = ops[(label + 1)..end].to_vec();
+        // The op range which comes later is changed first, so that the indices of the other one stay valid.
+        if label > jump {
+            ops.drain(label..end);
+            ops.splice(jump..=jump, body);
+        } else {
+            ops.splice(jump..=jump, body);
+            ops.drain(label..end);
+        }
+        merged += 1;
+    }
+    merged
+}
+/// Removes redundant jumps from `ops`, and moves blocks in place of the only jumps to them. Returns the number of changes.
+pub(super) fn simplify_branches(ops: &mut Vec<CILOp>) -> usize {
+    remove_jumps_to_next(ops) + merge_blocks(ops)
+}
+#[test]
+fn blocks_merged() {
+    let mut ops = vec![
+        CILOp::LDArg(0),
+        CILOp::BZero(2),
+        CILOp::GoTo(1),
+        CILOp::Label(2),
+        CILOp::LdcI32(2),
+        // A jump to the next op.
+        CILOp::GoTo(3),
+        CILOp::Label(3),
+        CILOp::Ret,
+        // Entered only by the jump after the `BZero`, and ends with a `ret`.
+        CILOp::Label(1),
+        CILOp::LdcI32(1),
+        CILOp::Ret,
+    ];
+    assert_eq!(simplify_branches(&mut ops), 2);
+    assert_eq!(
+        ops,
+        [
+            CILOp::LDArg(0),
+            CILOp::BZero(2),
+            CILOp::LdcI32(1),
+            CILOp::Ret,
+            CILOp::Label(2),
+            CILOp::LdcI32(2),
+            CILOp::Label(3),
+            CILOp::Ret,
+        ]
+    );
+}
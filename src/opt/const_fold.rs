@@ -0,0 +1,226 @@
+//! Constant folding: evaluating arithmetic on constants at compile time.
+//!
+//! The ops are interpreted in order, keeping track of which values on top of the stack are known constants. An arithmetic op or conversion whose
+//! arguments are all known is replaced, together with the ops loading them, by a single op loading the result. Since the result is a known
+//! constant too, whole expressions(eg. `SizeOf` lowered to `LdcI32(4); LdcI32(8); Mul`) fold down to one constant. Labels, and any other op, end the
+//! known values, so nothing is folded across a jump target. Ops which could throw(division by zero, checked arithmetic and conversions) are never
+//! folded, and neither are conversions to native-size integers, since their size is only known at runtime.
+use crate::cil::CILOp;
+/// A constant value on the evaluation stack.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Const {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+impl Const {
+    /// Returns the constant loaded by `op`, if it loads a constant.
+    fn loaded_by(op: &CILOp) -> Option<Self> {
+        match op {
+            CILOp::LdcI32(value) => Some(Self::I32(*value)),
+            CILOp::LdcI64(value) => Some(Self::I64(*value)),
+            CILOp::LdcF32(value) => Some(Self::F32(*value)),
+            CILOp::LdcF64(value) => Some(Self::F64(*value)),
+            _ => None,
+        }
+    }
+    /// Returns the op loading this constant.
+    fn load(self) -> CILOp {
+        match self {
+            Self::I32(value) => CILOp::LdcI32(value),
+            Self::I64(value) => CILOp::LdcI64(value),
+            Self::F32(value) => CILOp::LdcF32(value),
+            Self::F64(value) => CILOp::LdcF64(value),
+        }
+    }
+    /// Returns the integer value of this constant, widened to 64 bits, and whether it is a 32 bit one.
+    fn int(self) -> Option<(i64, bool)> {
+        match self {
+            Self::I32(value) => Some((i64::from(value), true)),
+            Self::I64(value) => Some((value, false)),
+            _ => None,
+        }
+    }
+}
+/// Evaluates the unary op `op` on `value`. Returns `None` if `op` can't be folded.
+fn fold_unary(op: &CILOp, value: Const) -> Option<Const> {
+    Some(match (op, value) {
+        (CILOp::Neg, Const::I32(value)) => Const::I32(value.wrapping_neg()),
+        (CILOp::Neg, Const::I64(value)) => Const::I64(value.wrapping_neg()),
+        (CILOp::Neg, Const::F32(value)) => Const::F32(-value),
+        (CILOp::Neg, Const::F64(value)) => Const::F64(-value),
+        (CILOp::Not, Const::I32(value)) => Const::I32(!value),
+        (CILOp::Not, Const::I64(value)) => Const::I64(!value),
+        (CILOp::ConvF32(false), Const::F32(value)) => Const::F32(value),
+        (CILOp::ConvF32(false), Const::F64(value)) => Const::F32(value as f32),
+        (CILOp::ConvF64(false), Const::F32(value)) => Const::F64(f64::from(value)),
+        (CILOp::ConvF64(false), Const::F64(value)) => Const::F64(value),
+        // Integer to float conversions treat the value as signed.
+        (CILOp::ConvF32(false), _) => Const::F32(value.int()?.0 as f32),
+        (CILOp::ConvF64(false), _) => Const::F64(value.int()?.0 as f64),
+        _ => {
+            let (value, is_32) = value.int()?;
+            match op {
+                CILOp::ConvI8(false) => Const::I32(i32::from(value as i8)),
+                CILOp::ConvU8(false) => Const::I32(i32::from(value as u8)),
+                CILOp::ConvI16(false) => Const::I32(i32::from(value as i16)),
+                CILOp::ConvU16(false) => Const::I32(i32::from(value as u16)),
+                CILOp::ConvI32(false) | CILOp::ConvU32(false) => Const::I32(value as i32),
+                CILOp::ConvI64(false) => Const::I64(value),
+                // `int32` values are zero-extended by unsigned conversions.
+                CILOp::ConvU64(false) if is_32 => Const::I64(i64::from(value as u32)),
+                CILOp::ConvU64(false) => Const::I64(value),
+                _ => return None,
+            }
+        }
+    })
+}
+/// Evaluates the binary op `op` on `lhs` and `rhs`. Returns `None` if `op` can't be folded.
+fn fold_binary(op: &CILOp, lhs: Const, rhs: Const) -> Option<Const> {
+    let bool_const = |value: bool| Some(Const::I32(i32::from(value)));
+    match (lhs, rhs) {
+        (Const::I32(lhs), Const::I32(rhs)) => Some(Const::I32(match op {
+            CILOp::Add => lhs.wrapping_add(rhs),
+            CILOp::Sub => lhs.wrapping_sub(rhs),
+            CILOp::Mul => lhs.wrapping_mul(rhs),
+            // `checked_div` and `checked_rem` fail exactly when the `div` and `rem` ops would throw.
+            CILOp::Div => lhs.checked_div(rhs)?,
+            CILOp::Rem => lhs.checked_rem(rhs)?,
+            CILOp::And => lhs & rhs,
+            CILOp::Or => lhs | rhs,
+            CILOp::XOr => lhs ^ rhs,
+            // The result of shifting by more than the size of the value is unspecified.
+            CILOp::Shl => lhs.checked_shl(u32::try_from(rhs).ok()?)?,
+            CILOp::Shr => lhs.checked_shr(u32::try_from(rhs).ok()?)?,
+            CILOp::Eq => i32::from(lhs == rhs),
+            CILOp::Lt => i32::from(lhs < rhs),
+            CILOp::Gt => i32::from(lhs > rhs),
+            CILOp::LtUn => i32::from((lhs as u32) < (rhs as u32)),
+            CILOp::GtUn => i32::from((lhs as u32) > (rhs as u32)),
+            _ => return None,
+        })),
+        // 64 bit values are shifted by an `int32` amount.
+        (Const::I64(lhs), Const::I32(rhs)) => Some(Const::I64(match op {
+            CILOp::Shl => lhs.checked_shl(u32::try_from(rhs).ok()?)?,
+            CILOp::Shr => lhs.checked_shr(u32::try_from(rhs).ok()?)?,
+            _ => return None,
+        })),
+        (Const::I64(lhs), Const::I64(rhs)) => match op {
+            CILOp::Eq => bool_const(lhs == rhs),
+            CILOp::Lt => bool_const(lhs < rhs),
+            CILOp::Gt => bool_const(lhs > rhs),
+            CILOp::LtUn => bool_const((lhs as u64) < (rhs as u64)),
+            CILOp::GtUn => bool_const((lhs as u64) > (rhs as u64)),
+            _ => Some(Const::I64(match op {
+                CILOp::Add => lhs.wrapping_add(rhs),
+                CILOp::Sub => lhs.wrapping_sub(rhs),
+                CILOp::Mul => lhs.wrapping_mul(rhs),
+                CILOp::Div => lhs.checked_div(rhs)?,
+                CILOp::Rem => lhs.checked_rem(rhs)?,
+                CILOp::And => lhs & rhs,
+                CILOp::Or => lhs | rhs,
+                CILOp::XOr => lhs ^ rhs,
+                _ => return None,
+            })),
+        },
+        (Const::F32(lhs), Const::F32(rhs)) => match op {
+            CILOp::Add => Some(Const::F32(lhs + rhs)),
+            CILOp::Sub => Some(Const::F32(lhs - rhs)),
+            CILOp::Mul => Some(Const::F32(lhs * rhs)),
+            CILOp::Div => Some(Const::F32(lhs / rhs)),
+            CILOp::Rem => Some(Const::F32(lhs % rhs)),
+            CILOp::Eq => bool_const(lhs == rhs),
+            CILOp::Lt => bool_const(lhs < rhs),
+            CILOp::Gt => bool_const(lhs > rhs),
+            _ => None,
+        },
+        (Const::F64(lhs), Const::F64(rhs)) => match op {
+            CILOp::Add => Some(Const::F64(lhs + rhs)),
+            CILOp::Sub => Some(Const::F64(lhs - rhs)),
+            CILOp::Mul => Some(Const::F64(lhs * rhs)),
+            CILOp::Div => Some(Const::F64(lhs / rhs)),
+            CILOp::Rem => Some(Const::F64(lhs % rhs)),
+            CILOp::Eq => bool_const(lhs == rhs),
+            CILOp::Lt => bool_const(lhs < rhs),
+            CILOp::Gt => bool_const(lhs > rhs),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+/// Folds all the constant expressions in `ops`. Returns the number of folded ops.
+pub(super) fn fold_constants(ops: &mut Vec<CILOp>) -> usize {
+    let mut folded_ops: Vec<CILOp> = Vec::with_capacity(ops.len());
+    let mut folded = 0;
+    // Returns the constant loaded by the op `depth` places from the end of the already folded ops.
+    let known = |folded_ops: &[CILOp], depth: usize| {
+        folded_ops
+            .len()
+            .checked_sub(depth)
+            .and_then(|idx| Const::loaded_by(&folded_ops[idx]))
+    };
+    for op in ops.drain(..) {
+        let unary = known(&folded_ops, 1).and_then(|value| fold_unary(&op, value));
+        if let Some(result) = unary {
+            folded_ops.pop();
+            folded_ops.push(result.load());
+            folded += 1;
+            continue;
+        }
+        let binary = known(&folded_ops, 2)
+            .zip(known(&folded_ops, 1))
+            .and_then(|(lhs, rhs)| fold_binary(&op, lhs, rhs));
+        if let Some(result) = binary {
+            folded_ops.truncate(folded_ops.len() - 2);
+            folded_ops.push(result.load());
+            folded += 1;
+            continue;
+        }
+        folded_ops.push(op);
+    }
+    *ops = folded_ops;
+    folded
+}
+#[test]
+fn folds_constant_expressions() {
+    let mut ops = vec![
+        CILOp::LdcI32(4),
+        CILOp::LdcI32(8),
+        CILOp::Mul,
+        CILOp::LdcI32(2),
+        CILOp::Add,
+        CILOp::ConvU64(false),
+        CILOp::LdcI64(-1),
+        CILOp::ConvU8(false),
+        CILOp::LdcF32(1.5),
+        CILOp::ConvF64(false),
+        CILOp::LdcF64(0.5),
+        CILOp::Sub,
+        // Division by zero throws at runtime.
+        CILOp::LdcI32(1),
+        CILOp::LdcI32(0),
+        CILOp::Div,
+        // Nothing is folded across labels.
+        CILOp::LdcI32(1),
+        CILOp::Label(0),
+        CILOp::LdcI32(1),
+        CILOp::Add,
+    ];
+    assert_eq!(fold_constants(&mut ops), 6);
+    assert_eq!(
+        ops,
+        [
+            CILOp::LdcI64(34),
+            CILOp::LdcI32(255),
+            CILOp::LdcF64(1.0),
+            CILOp::LdcI32(1),
+            CILOp::LdcI32(0),
+            CILOp::Div,
+            CILOp::LdcI32(1),
+            CILOp::Label(0),
+            CILOp::LdcI32(1),
+            CILOp::Add,
+        ]
+    );
+}
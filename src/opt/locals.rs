@@ -1,29 +1,7 @@
 use crate::{assembly::Assembly, cil::CILOp, method::Method, r#type::Type};
 
 pub fn remove_unused_locals(method: &mut Method) {
-    let mut local_map = vec![u32::MAX; method.locals().len()];
-    let mut new_locals = Vec::with_capacity(method.locals().len());
-    for (local, tpe) in method.locals().iter().enumerate() {
-        if local_map[local] == u32::MAX && !is_local_unused(method.get_ops(), local as u32) {
-            local_map[local] = new_locals.len() as u32;
-            new_locals.push(tpe.clone());
-        }
-    }
-    method.ops_mut().iter_mut().for_each(|op| match op {
-        CILOp::LDLocA(idx) | CILOp::STLoc(idx) | CILOp::LDLoc(idx) => {
-            let new_loc = local_map[*idx as usize];
-            *op = CILOp::LDLocA(new_loc);
-        }
-        _ => (),
-    });
-    method.set_locals(new_locals);
-}
-/// A "Unused" local is one that is never written to or read from.
-fn is_local_unused(ops: &[CILOp], local: u32) -> bool {
-    !ops.iter().any(|op| match op {
-        CILOp::LDLocA(loc) | CILOp::STLoc(loc) | CILOp::LDLoc(loc) => *loc == local,
-        _ => false,
-    })
+    method.remove_unused_locals(|_| true);
 }
 pub fn try_split_locals(method: &mut Method, asm: &Assembly) {
     let splits: Vec<_> = method
@@ -1,10 +1,14 @@
 #![allow(clippy::similar_names)]
 use std::ops::Range;
+mod branch_layout;
+mod const_fold;
 mod locals;
 mod op2_combos;
 mod op3_combos;
+pub(crate) mod peephole;
 use crate::{
     assembly::Assembly,
+    basic_block::MethodBody,
     cil::{CILOp, CallSite},
     method::Method,
     r#type::Type,
@@ -120,9 +124,13 @@ fn try_inline_all(method: &mut Method, asm: &Assembly) {
 }
 //pub fn try_turn_locals_into_bools(method:&Method){}
 pub fn opt_method(method: &mut Method, asm: &Assembly) {
-    if !crate::OPTIMIZE_CIL {
+    if !crate::config::config().optimize_cil() {
         return;
     };
+    // The optimizations don't know about protected regions yet: they could remove or merge the labels delimiting them.
+    if !method.handlers().is_empty() {
+        return;
+    }
     //panic!("opt");
     method.ops_mut().retain(|op| match op {
         CILOp::Call(site) => !site.is_nop(),
@@ -130,19 +138,22 @@ pub fn opt_method(method: &mut Method, asm: &Assembly) {
     });
     repalce_const_sizes(method.ops_mut());
     for _ in 0..MAX_PASS {
+        const_fold::fold_constants(method.ops_mut());
         op2_combos::optimize_combos(method.ops_mut());
         op3_combos::optimize_combos(method.ops_mut());
         op4_combos(method.ops_mut());
         remove_zombie_sets(method.ops_mut());
+        remove_unreachable(method.ops_mut());
+        branch_layout::simplify_branches(method.ops_mut());
         method.ops_mut().retain(|op| *op != CILOp::Nop);
         try_alias_locals(method.ops_mut());
-        if crate::SPLIT_LOCAL_STRUCTS {
+        if crate::config::config().split_local_structs {
             try_split_locals(method, asm);
         }
-        if crate::REMOVE_UNSUED_LOCALS {
+        if crate::config::config().remove_unused_locals {
             remove_unused_locals(method);
         }
-        if crate::INLINE_SIMPLE_FUNCTIONS {
+        if crate::config::config().inline_simple_functions {
             try_inline_all(method, asm);
         }
         //try_inline_all(method, asm);
@@ -152,7 +163,10 @@ fn repalce_const_sizes(ops: &mut [CILOp]) {
     ops.iter_mut().for_each(|op| {
         if let CILOp::SizeOf(tpe) = op {
             match tpe.as_ref() {
-                Type::U8 | Type::I8 => *op = CILOp::LdcI32(1),
+                Type::U8 | Type::I8 | Type::Bool => *op = CILOp::LdcI32(1),
+                Type::U16 | Type::I16 => *op = CILOp::LdcI32(2),
+                Type::U32 | Type::I32 | Type::F32 | Type::Char => *op = CILOp::LdcI32(4),
+                Type::U64 | Type::I64 | Type::F64 => *op = CILOp::LdcI32(8),
                 _ => (),
             }
         }
@@ -176,7 +190,50 @@ fn remove_zombie_sets(ops: &mut Vec<CILOp>) {
         }
     }
 }
-
+/// Removes the blocks which can never be executed: the ones no path from the start of the method leads to, like the ones after a `ret`, `throw`
+/// or unconditional jump, which no op jumps to. Returns the number of removed ops.
+fn remove_unreachable(ops: &mut Vec<CILOp>) -> usize {
+    let original_len = ops.len();
+    let mut body = MethodBody::from_ops(ops, &[]);
+    if body.remove_unreachable_blocks() > 0 {
+        *ops = body.into_ops();
+    }
+    original_len - ops.len()
+}
+#[test]
+fn unreachable_removal() {
+    let mut ops = vec![
+        CILOp::LDArg(0),
+        CILOp::BTrue(1),
+        CILOp::LdcI32(0),
+        CILOp::Ret,
+        // Dead, together with the label only it jumps to.
+        CILOp::LdcI32(2),
+        CILOp::GoTo(2),
+        CILOp::Label(3),
+        CILOp::LdcI32(3),
+        CILOp::Ret,
+        CILOp::Label(1),
+        CILOp::LdcI32(1),
+        CILOp::Ret,
+        CILOp::Label(2),
+        CILOp::LdcI32(2),
+        CILOp::Ret,
+    ];
+    assert_eq!(remove_unreachable(&mut ops), 8);
+    assert_eq!(
+        ops,
+        [
+            CILOp::LDArg(0),
+            CILOp::BTrue(1),
+            CILOp::LdcI32(0),
+            CILOp::Ret,
+            CILOp::Label(1),
+            CILOp::LdcI32(1),
+            CILOp::Ret,
+        ]
+    );
+}
 fn op4_combos(ops: &mut [CILOp]) {
     if ops.len() < 4 {
         return;
@@ -259,7 +316,12 @@ fn is_label_unsused(ops: &[CILOp], label: u32) -> bool {
         | CILOp::BGe(target)
         | CILOp::BLe(target)
         | CILOp::BZero(target)
-        | CILOp::BTrue(target) => label == *target,
+        | CILOp::BTrue(target)
+        | CILOp::BLtUn(target)
+        | CILOp::BGeUn(target)
+        | CILOp::BLeUn(target)
+        | CILOp::Leave(target) => label == *target,
+        CILOp::Switch(targets) => targets.contains(&label),
         _ => false,
     })
 }
@@ -0,0 +1,206 @@
+//! Peephole optimization: replacing short sequences of ops with cheaper, equivalent ones.
+//!
+//! Lowering MIR statements one by one produces many redundant sequences, like a `dup` followed by a `pop`, a local stored and loaded right away,
+//! or a constant converted to the type it already has. Each entry of [`REWRITES`] matches a sequence of ops, and returns its replacement. Rewrites
+//! never match labels, so they can't change the control flow of a method. They are applied until no more sequences match, right after temporary
+//! locals are allocated, so that the ops using them are seen as plain loads and stores of locals.
+use crate::cil::CILOp;
+use rustc_session::config::OptLevel;
+/// A rewrite of a sequence of `len` ops.
+struct Rewrite {
+    len: usize,
+    /// The lowest optimization level (see [`level_rank`]) this rewrite is applied at.
+    min_level: u8,
+    /// Returns the ops replacing the sequence, or `None` if the sequence does not match.
+    rewrite: fn(&[CILOp]) -> Option<Vec<CILOp>>,
+}
+/// All the rewrites, tried in order.
+const REWRITES: &[Rewrite] = &[
+    // `nop`s only take up space.
+    Rewrite {
+        len: 1,
+        min_level: 1,
+        rewrite: |ops| matches!(ops, [CILOp::Nop]).then(Vec::new),
+    },
+    // A value which is pushed, and popped right away.
+    Rewrite {
+        len: 2,
+        min_level: 1,
+        rewrite: |ops| {
+            matches!(
+                ops,
+                [
+                    CILOp::Dup
+                        | CILOp::LDLoc(_)
+                        | CILOp::LDLocA(_)
+                        | CILOp::LDArg(_)
+                        | CILOp::LDArgA(_)
+                        | CILOp::LdcI32(_)
+                        | CILOp::LdcI64(_)
+                        | CILOp::LdcF32(_)
+                        | CILOp::LdcF64(_)
+                        | CILOp::LdNull
+                        | CILOp::LdStr(_)
+                        | CILOp::SizeOf(_),
+                    CILOp::Pop
+                ]
+            )
+            .then(Vec::new)
+        },
+    },
+    // A local stored into itself.
+    Rewrite {
+        len: 2,
+        min_level: 1,
+        rewrite: |ops| match ops {
+            [CILOp::LDLoc(src), CILOp::STLoc(dst)] if src == dst => Some(vec![]),
+            _ => None,
+        },
+    },
+    // A local stored and loaded right away: the value is duplicated instead.
+    Rewrite {
+        len: 2,
+        min_level: 2,
+        rewrite: |ops| match ops {
+            [CILOp::STLoc(stored), CILOp::LDLoc(loaded)] if stored == loaded => {
+                Some(vec![CILOp::Dup, CILOp::STLoc(*stored)])
+            }
+            _ => None,
+        },
+    },
+    // A constant converted to another integer type.
+    Rewrite {
+        len: 2,
+        min_level: 2,
+        rewrite: |ops| {
+            let [constant, conv] = ops else {
+                return None;
+            };
+            const_conv(constant, conv).map(|folded| vec![folded])
+        },
+    },
+    // Operations which do nothing.
+    Rewrite {
+        len: 2,
+        min_level: 2,
+        rewrite: |ops| {
+            matches!(
+                ops,
+                [CILOp::Not, CILOp::Not]
+                    | [CILOp::Neg, CILOp::Neg]
+                    | [
+                        CILOp::LdcI32(0),
+                        CILOp::Add | CILOp::Sub | CILOp::Or | CILOp::XOr
+                    ]
+                    | [CILOp::LdcI32(1), CILOp::Mul | CILOp::Div]
+            )
+            .then(Vec::new)
+        },
+    },
+];
+/// Returns the constant `conv` turns `constant` into, if `constant` is an integer constant, and `conv` an unchecked conversion.
+fn const_conv(constant: &CILOp, conv: &CILOp) -> Option<CILOp> {
+    let value = match constant {
+        // `int32` values are sign-extended by signed conversions, and zero-extended by unsigned ones.
+        CILOp::LdcI32(value) => match conv {
+            CILOp::ConvU64(false) => i64::from(*value as u32),
+            _ => i64::from(*value),
+        },
+        CILOp::LdcI64(value) => *value,
+        _ => return None,
+    };
+    // Native-size integers are not folded, since their size is only known at runtime.
+    Some(match conv {
+        CILOp::ConvI8(false) => CILOp::LdcI32(i32::from(value as i8)),
+        CILOp::ConvU8(false) => CILOp::LdcI32(i32::from(value as u8)),
+        CILOp::ConvI16(false) => CILOp::LdcI32(i32::from(value as i16)),
+        CILOp::ConvU16(false) => CILOp::LdcI32(i32::from(value as u16)),
+        CILOp::ConvI32(false) | CILOp::ConvU32(false) => CILOp::LdcI32(value as i32),
+        CILOp::ConvI64(false) | CILOp::ConvU64(false) => CILOp::LdcI64(value),
+        _ => return None,
+    })
+}
+/// Returns the rank of `level`: 0 for no optimizations, 1 for `-C opt-level=1`, 2 for `-C opt-level=2` and the size optimizations, and 3 for
+/// `-C opt-level=3`.
+pub(crate) fn level_rank(level: OptLevel) -> u8 {
+    match level {
+        OptLevel::No => 0,
+        OptLevel::Less => 1,
+        OptLevel::Default | OptLevel::Size | OptLevel::SizeMin => 2,
+        OptLevel::Aggressive => 3,
+    }
+}
+/// Applies the rewrites enabled at `level` to `ops`, until none of them matches. Returns the number of applied rewrites.
+fn peephole(ops: &mut Vec<CILOp>, level: u8) -> usize {
+    let rewrites: Vec<&Rewrite> = REWRITES
+        .iter()
+        .filter(|rewrite| rewrite.min_level <= level)
+        .collect();
+    let mut applied = 0;
+    loop {
+        let mut new_ops = Vec::with_capacity(ops.len());
+        let mut changed = false;
+        let mut idx = 0;
+        while idx < ops.len() {
+            let replacement = rewrites.iter().find_map(|rewrite| {
+                let sequence = ops.get(idx..idx + rewrite.len)?;
+                (rewrite.rewrite)(sequence).map(|replacement| (rewrite.len, replacement))
+            });
+            if let Some((len, replacement)) = replacement {
+                new_ops.extend(replacement);
+                idx += len;
+                applied += 1;
+                changed = true;
+            } else {
+                new_ops.push(ops[idx].clone());
+                idx += 1;
+            }
+        }
+        *ops = new_ops;
+        if !changed {
+            return applied;
+        }
+    }
+}
+/// Runs the peephole optimizer over the ops of `method`, with the rewrites enabled at optimization level `level`.
+pub(crate) fn optimize_method(method: &mut crate::method::Method, level: OptLevel) -> usize {
+    let level = level_rank(level);
+    if level == 0 {
+        return 0;
+    }
+    peephole(method.ops_mut(), level)
+}
+#[test]
+fn rewrites_redundant_sequences() {
+    let mut ops = vec![
+        CILOp::LDArg(0),
+        CILOp::STLoc(0),
+        CILOp::LDLoc(0),
+        CILOp::Dup,
+        CILOp::Pop,
+        CILOp::LdcI32(-1),
+        CILOp::ConvU64(false),
+        CILOp::LdcI64(300),
+        CILOp::ConvU8(false),
+        CILOp::Add,
+        CILOp::Nop,
+        CILOp::Ret,
+    ];
+    assert_eq!(peephole(&mut ops, 2), 5);
+    assert_eq!(
+        ops,
+        [
+            CILOp::LDArg(0),
+            CILOp::Dup,
+            CILOp::STLoc(0),
+            CILOp::LdcI64(i64::from(u32::MAX)),
+            CILOp::LdcI32(44),
+            CILOp::Add,
+            CILOp::Ret,
+        ]
+    );
+    // Only the simplest rewrites are applied at `-C opt-level=1`.
+    let mut ops = vec![CILOp::STLoc(0), CILOp::LDLoc(0), CILOp::Dup, CILOp::Pop];
+    assert_eq!(peephole(&mut ops, 1), 1);
+    assert_eq!(ops, [CILOp::STLoc(0), CILOp::LDLoc(0)]);
+}
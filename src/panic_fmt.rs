@@ -0,0 +1,209 @@
+//! Formatting of panic messages.
+//!
+//! `core::panicking::panic_fmt` gets the message as a `fmt::Arguments`. Before it is called, the message is formatted by `core::fmt::write`, into
+//! a managed writer: a `dyn fmt::Write` whose data pointer is null, and whose vtable points to shims appending to the string in the static
+//! `rust_panic_message`. The vtable is built by the static constructor, since it holds the addresses of the shims.
+use crate::{
+    access_modifier::AccessModifer,
+    assembly::Assembly,
+    call_info::CallInfo,
+    cil::{CILOp, CallSite, StaticFieldDescriptor},
+    function_sig::FnSig,
+    method::Method,
+    r#type::{DotnetTypeRef, TyCache, Type},
+};
+use rustc_middle::ty::{Instance, InstanceDef, List, ParamEnv, Ty, TyCtxt, TyKind};
+/// Name of the static field holding the address of the vtable of the managed writer.
+const WRITER_VTABLE_NAME: &str = "rust_panic_writer_vtable";
+/// Returns the descriptor of the static field holding the address of the vtable of the managed writer.
+pub(crate) fn writer_vtable() -> Box<StaticFieldDescriptor> {
+    StaticFieldDescriptor::boxed(None, Type::USize, WRITER_VTABLE_NAME.into())
+}
+/// Returns `core::fmt::write`, or `None` if the crate is `#![no_core]`.
+fn fmt_write(tcx: TyCtxt) -> Option<Instance> {
+    // `panic_fmt` takes a `fmt::Arguments`, which is defined in the same module as `fmt::write`.
+    let panic_fmt = tcx.lang_items().panic_fmt()?;
+    let arguments = tcx.fn_sig(panic_fmt).skip_binder().skip_binder().inputs()[0];
+    let TyKind::Adt(arguments, _) = arguments.kind() else {
+        panic!("panic_fmt must take fmt::Arguments, not {arguments:?}!");
+    };
+    let fmt = tcx.parent(arguments.did());
+    let children = match fmt.as_local() {
+        Some(fmt) => tcx.module_children_local(fmt),
+        None => tcx.module_children(fmt),
+    };
+    let write = children
+        .iter()
+        .find(|child| child.ident.as_str() == "write")
+        .and_then(|child| child.res.opt_def_id())
+        .expect("`core::fmt::write` is missing!");
+    Some(Instance::mono(tcx, write))
+}
+/// Returns the call site of `core::fmt::write(&mut dyn fmt::Write, fmt::Arguments) -> fmt::Result`.
+pub(crate) fn fmt_write_site(tcx: TyCtxt, cache: &mut TyCache) -> Box<CallSite> {
+    let write = fmt_write(tcx).expect("Panics with a message require `core`!");
+    let sig = CallInfo::sig_from_instance_(write, tcx, cache)
+        .expect("Could not get the signature of `core::fmt::write`!");
+    CallSite::boxed(
+        None,
+        crate::utilis::function_name(tcx.symbol_name(write)),
+        sig.sig().clone(),
+        true,
+    )
+}
+/// Adds the shims implementing `fmt::Write` for the managed writer, and its vtable, if the crate uses `core`.
+pub(crate) fn add_panic_writer<'tcx>(asm: &mut Assembly, tcx: TyCtxt<'tcx>, cache: &mut TyCache) {
+    let Some(write) = fmt_write(tcx) else {
+        return;
+    };
+    let fn_abi = tcx
+        .fn_abi_of_instance(ParamEnv::reveal_all().and((write, List::empty())))
+        .expect("Could not get the ABI of `core::fmt::write`!");
+    // `&mut dyn fmt::Write`
+    let TyKind::Ref(_, dyn_write, _) = fn_abi.args[0].layout.ty.kind() else {
+        panic!("`core::fmt::write` must take a `&mut dyn fmt::Write`!");
+    };
+    let dyn_write: Ty<'tcx> = *dyn_write;
+    let TyKind::Dynamic(preds, _, _) = dyn_write.kind() else {
+        panic!("`core::fmt::write` must take a `&mut dyn fmt::Write`!");
+    };
+    let write_trait = preds
+        .principal_def_id()
+        .expect("`dyn fmt::Write` has no principal trait!");
+    let ptr_size = tcx.data_layout.pointer_size.bytes() as i64;
+    // The drop glue, size and alignment come first. The writer has no data, so it needs no drop glue, and has the size 0 and alignment 1.
+    let mut slots: Vec<(usize, CILOp)> = vec![
+        (0, CILOp::LdcI32(0)),
+        (1, CILOp::LdcI32(0)),
+        (2, CILOp::LdcI32(1)),
+    ];
+    for method in tcx.own_existential_vtable_entries(write_trait) {
+        let virtual_instance = Instance::resolve(
+            tcx,
+            ParamEnv::reveal_all(),
+            *method,
+            tcx.mk_args(&[dyn_write.into()]),
+        )
+        .expect("Could not resolve a method of `fmt::Write`!")
+        .expect("Could not resolve a method of `fmt::Write`!");
+        let InstanceDef::Virtual(_, idx) = virtual_instance.def else {
+            panic!("Methods of `dyn fmt::Write` must be virtual, not {virtual_instance:?}!");
+        };
+        // The signature of a virtual call takes a thin pointer as `self`, just like the shims.
+        let sig = CallInfo::sig_from_instance_(virtual_instance, tcx, cache)
+            .expect("Could not get the signature of a method of `fmt::Write`!")
+            .sig()
+            .clone();
+        let name = tcx.item_name(*method);
+        let shim = writer_shim(name.as_str(), sig, tcx, cache);
+        slots.push((idx, CILOp::LDFtn(shim.call_site().into())));
+        asm.add_method(shim);
+    }
+    let mut init = vec![
+        CILOp::LdcI64(slots.len() as i64 * ptr_size),
+        CILOp::ConvISize(false),
+        CILOp::Call(CallSite::boxed(
+            Some(marshal()),
+            "AllocHGlobal".into(),
+            FnSig::new(&[Type::ISize], &Type::ISize),
+            true,
+        )),
+    ];
+    for (idx, value) in slots {
+        init.extend([
+            CILOp::Dup,
+            CILOp::LdcI64(idx as i64 * ptr_size),
+            CILOp::ConvISize(false),
+            CILOp::Add,
+            value,
+            CILOp::ConvUSize(false),
+            CILOp::STIndISize,
+        ]);
+    }
+    asm.add_static_with_init(Type::USize, WRITER_VTABLE_NAME, init);
+    for tpe in cache.take_new_defs() {
+        asm.add_typedef(tpe);
+    }
+}
+/// Returns the shim implementing the method `name` of `fmt::Write` for the managed writer, with the signature `sig`.
+fn writer_shim(name: &str, sig: FnSig, tcx: TyCtxt, cache: &mut TyCache) -> Method {
+    let ret = sig.output().clone();
+    let mut ops = Vec::new();
+    match name {
+        // message = message + Marshal.PtrToStringUTF8(s.data_address, s.metadata)
+        "write_str" => ops.extend([
+            CILOp::LDStaticField(crate::ffi::panic_message_static()),
+            CILOp::LDArg(1),
+            crate::fat_ptr::load_data_address(),
+            CILOp::LDArg(1),
+            crate::fat_ptr::load_metadata(),
+            CILOp::ConvI32(false),
+            CILOp::Call(CallSite::boxed(
+                Some(marshal()),
+                "PtrToStringUTF8".into(),
+                FnSig::new(
+                    &[Type::ISize, Type::I32],
+                    &crate::utilis::string_class().into(),
+                ),
+                true,
+            )),
+            CILOp::Call(crate::ffi::string_concat()),
+            CILOp::STStaticField(crate::ffi::panic_message_static()),
+        ]),
+        // message = message + Char.ConvertFromUtf32(c)
+        "write_char" => ops.extend([
+            CILOp::LDStaticField(crate::ffi::panic_message_static()),
+            CILOp::LDArg(1),
+            CILOp::Call(CallSite::boxed(
+                Some(DotnetTypeRef::new(Some("System.Runtime"), "System.Char")),
+                "ConvertFromUtf32".into(),
+                FnSig::new(&[Type::I32], &crate::utilis::string_class().into()),
+                true,
+            )),
+            CILOp::Call(crate::ffi::string_concat()),
+            CILOp::STStaticField(crate::ffi::panic_message_static()),
+        ]),
+        // fmt::write(self, args), like the default implementation.
+        "write_fmt" => {
+            ops.extend(crate::fat_ptr::make_fat_ptr(
+                vec![CILOp::LDArg(0)],
+                vec![CILOp::LDStaticField(writer_vtable())],
+            ));
+            ops.extend([
+                CILOp::LDArg(1),
+                CILOp::Call(fmt_write_site(tcx, cache)),
+                CILOp::Ret,
+            ]);
+        }
+        _ => panic!("Unknown method of `fmt::Write`: {name}!"),
+    }
+    // Appending to the message never fails, so the shims return `Ok(())`: the zeroed value of `fmt::Result`.
+    let locals = if ops.last() == Some(&CILOp::Ret) {
+        vec![]
+    } else {
+        ops.extend([
+            CILOp::LDLocA(0),
+            CILOp::InitObj(ret.clone().into()),
+            CILOp::LDLoc(0),
+            CILOp::Ret,
+        ]);
+        vec![(Some("ok".into()), ret)]
+    };
+    let mut method = Method::new(
+        AccessModifer::Private,
+        true,
+        sig,
+        &format!("rust_panic_writer_{name}"),
+        locals,
+    );
+    method.set_ops(ops);
+    method
+}
+/// Returns a reference to `System.Runtime.InteropServices.Marshal`.
+fn marshal() -> DotnetTypeRef {
+    DotnetTypeRef::new(
+        Some("System.Runtime.InteropServices"),
+        "System.Runtime.InteropServices.Marshal",
+    )
+    .with_valuetype(false)
+}
@@ -0,0 +1,221 @@
+//! P/Invoke: calling functions from native libraries, and letting native code call Rust functions.
+//!
+//! Functions declared in `extern` blocks linked to a native library(using `#[link(name = "...")]`) are not implemented in the assembly. Instead,
+//! each of them gets a method without a body, marked with `pinvokeimpl`, which makes the runtime load the library, and look up the function, when it
+//! is first called. Calls to such functions are lowered just like calls to any other method. Libraries emulated by the backend(like libc, whose
+//! functions are implemented in [`crate::ffi`]) are skipped, and their functions are linked like before.
+//!
+//! In the other direction, functions with a stable symbol name(`#[no_mangle]` or `#[export_name]`) and a non-Rust ABI are marked with
+//! `UnmanagedCallersOnlyAttribute`, which allows native code(eg. the host of a NativeAOT-compiled cdylib) to call them using function pointers, or
+//! by the name they are exported under.
+use crate::{
+    access_modifier::AccessModifer,
+    assembly::Assembly,
+    function_sig::FnSig,
+    method::{Attribute, Method},
+    r#type::TyCache,
+    IString,
+};
+use rustc_hir::def::DefKind;
+use rustc_middle::{
+    middle::codegen_fn_attrs::CodegenFnAttrFlags,
+    ty::{Instance, TyCtxt},
+};
+use rustc_span::def_id::LOCAL_CRATE;
+use rustc_target::spec::abi::Abi as TargetAbi;
+use serde::{Deserialize, Serialize};
+/// Native libraries whose functions are implemented by the backend, and so are never loaded.
+const EMULATED_LIBS: &[&str] = &["c", "m", "dl", "rt", "pthread", "util", "gcc_s", "unwind"];
+/// The calling convention of a native function.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+pub enum CallConv {
+    /// The C calling convention(`extern "C"`).
+    Cdecl,
+    /// `extern "stdcall"`.
+    Stdcall,
+    /// `extern "fastcall"`.
+    Fastcall,
+    /// `extern "thiscall"`.
+    Thiscall,
+    /// The default calling convention of the platform(`extern "system"`).
+    Winapi,
+}
+impl CallConv {
+    /// Returns the calling convention matching the Rust ABI `abi`. ABIs without a .NET equivalent are treated as the C one.
+    #[must_use]
+    pub fn from_abi(abi: TargetAbi) -> Self {
+        match abi {
+            TargetAbi::Stdcall { .. } => Self::Stdcall,
+            TargetAbi::Fastcall { .. } => Self::Fastcall,
+            TargetAbi::Thiscall { .. } => Self::Thiscall,
+            TargetAbi::System { .. } => Self::Winapi,
+            _ => Self::Cdecl,
+        }
+    }
+    /// Returns the full name of the type representing this calling convention in `UnmanagedCallersOnlyAttribute.CallConvs`. Returns `None` for the
+    /// default calling convention of the platform, which is used when none is given.
+    #[must_use]
+    pub fn modifier_type(self) -> Option<&'static str> {
+        match self {
+            Self::Cdecl => Some("System.Runtime.CompilerServices.CallConvCdecl"),
+            Self::Stdcall => Some("System.Runtime.CompilerServices.CallConvStdcall"),
+            Self::Fastcall => Some("System.Runtime.CompilerServices.CallConvFastcall"),
+            Self::Thiscall => Some("System.Runtime.CompilerServices.CallConvThiscall"),
+            Self::Winapi => None,
+        }
+    }
+    /// Returns the ILASM name of this calling convention, used within `pinvokeimpl`.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cdecl => "cdecl",
+            Self::Stdcall => "stdcall",
+            Self::Fastcall => "fastcall",
+            Self::Thiscall => "thiscall",
+            Self::Winapi => "winapi",
+        }
+    }
+}
+/// Describes where the implementation of a P/Invoke method is: the native library `module`, and the function `entry_point` within it.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+pub struct PInvoke {
+    module: IString,
+    entry_point: IString,
+    call_conv: CallConv,
+}
+impl PInvoke {
+    /// Creates a P/Invoke descriptor of the function `entry_point`, from the native library `module`, using the calling convention `call_conv`.
+    #[must_use]
+    pub fn new(module: IString, entry_point: IString, call_conv: CallConv) -> Self {
+        Self {
+            module,
+            entry_point,
+            call_conv,
+        }
+    }
+    /// Returns the name of the native library.
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+    /// Returns the name of the function within the native library.
+    pub fn entry_point(&self) -> &str {
+        &self.entry_point
+    }
+    /// Returns the calling convention of the function.
+    pub fn call_conv(&self) -> CallConv {
+        self.call_conv
+    }
+}
+/// Returns a method without a body, which calls the function described by `pinvoke` when invoked.
+#[must_use]
+pub fn pinvoke_method(name: &str, sig: FnSig, pinvoke: PInvoke) -> Method {
+    let mut method = Method::new(AccessModifer::Public, true, sig, name, vec![]);
+    method.add_attribute(Attribute::PInvoke(Box::new(pinvoke)));
+    method
+}
+/// Adds P/Invoke methods for all the functions from native libraries the local crate links to.
+pub(crate) fn add_pinvoke_decls<'tcx>(asm: &mut Assembly, tcx: TyCtxt<'tcx>, cache: &mut TyCache) {
+    let modules = tcx.foreign_modules(LOCAL_CRATE);
+    for lib in tcx.native_libraries(LOCAL_CRATE) {
+        let lib_name = lib.name.to_string();
+        if EMULATED_LIBS.contains(&lib_name.as_str()) {
+            continue;
+        }
+        let Some(module) = lib.foreign_module.and_then(|module| modules.get(&module)) else {
+            continue;
+        };
+        for item in &module.foreign_items {
+            // Foreign statics are not supported.
+            if tcx.def_kind(*item) != DefKind::Fn {
+                continue;
+            }
+            let instance = Instance::mono(tcx, *item);
+            let name = crate::utilis::function_name(tcx.symbol_name(instance));
+            if crate::libc::has_shim(&name) {
+                continue;
+            }
+            let sig = FnSig::sig_from_instance_(instance, tcx, cache)
+                .expect("Could not get the signature of a foreign function!");
+            let pinvoke = PInvoke::new(
+                lib_name.as_str().into(),
+                name.clone(),
+                CallConv::from_abi(module.abi),
+            );
+            asm.add_method(pinvoke_method(&name, sig, pinvoke));
+        }
+    }
+}
+/// Returns the name and calling convention `instance` should be exported to native code under, if it has a stable symbol name and a non-Rust ABI.
+pub(crate) fn unmanaged_export<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    instance: Instance<'tcx>,
+) -> Option<(IString, CallConv)> {
+    let def_id = instance.def_id();
+    if !matches!(tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn) {
+        return None;
+    }
+    let attrs = tcx.codegen_fn_attrs(def_id);
+    if !attrs.flags.contains(CodegenFnAttrFlags::NO_MANGLE) && attrs.export_name.is_none() {
+        return None;
+    }
+    let abi = tcx.fn_sig(def_id).skip_binder().abi();
+    if matches!(
+        abi,
+        TargetAbi::Rust | TargetAbi::RustCall | TargetAbi::RustIntrinsic
+    ) {
+        return None;
+    }
+    let name = crate::utilis::function_name(tcx.symbol_name(instance));
+    Some((name, CallConv::from_abi(abi)))
+}
+/// Appends `len` to `blob`, in the compressed form used by .NET metadata.
+fn push_compressed_len(blob: &mut Vec<u8>, len: usize) {
+    let len = u32::try_from(len).expect("Custom attribute string too long!");
+    match len {
+        0..=0x7F => blob.push(len as u8),
+        0x80..=0x3FFF => blob.extend((0x8000 | len as u16).to_be_bytes()),
+        _ => blob.extend((0xC000_0000 | len).to_be_bytes()),
+    }
+}
+/// Appends the length-prefixed UTF-8 string `string` to `blob`.
+fn push_ser_string(blob: &mut Vec<u8>, string: &str) {
+    push_compressed_len(blob, string.len());
+    blob.extend(string.as_bytes());
+}
+/// Returns the encoded arguments of `UnmanagedCallersOnlyAttribute`, exporting a method as `entry_point`, using the calling convention `call_conv`.
+/// The encoding is described in ECMA-335, II.23.3.
+#[must_use]
+pub fn unmanaged_callers_only_blob(entry_point: &str, call_conv: CallConv) -> Vec<u8> {
+    let call_conv = call_conv.modifier_type();
+    // Prolog, followed by the number of named arguments.
+    let mut blob = vec![0x01, 0x00, 1 + u8::from(call_conv.is_some()), 0x00];
+    // FIELD, of type `string`.
+    blob.extend([0x53, 0x0E]);
+    push_ser_string(&mut blob, "EntryPoint");
+    push_ser_string(&mut blob, entry_point);
+    if let Some(call_conv) = call_conv {
+        // FIELD, of type `System.Type[]`, with one element.
+        blob.extend([0x53, 0x1D, 0x50]);
+        push_ser_string(&mut blob, "CallConvs");
+        blob.extend(1_u32.to_le_bytes());
+        push_ser_string(&mut blob, &format!("{call_conv}, System.Runtime"));
+    }
+    blob
+}
+#[test]
+fn encodes_unmanaged_callers_only() {
+    let blob = unmanaged_callers_only_blob("add", CallConv::Winapi);
+    assert_eq!(
+        blob,
+        [
+            0x01, 0x00, 0x01, 0x00, 0x53, 0x0E, 10, b'E', b'n', b't', b'r', b'y', b'P', b'o', b'i',
+            b'n', b't', 3, b'a', b'd', b'd'
+        ]
+    );
+    let mut long = Vec::new();
+    push_compressed_len(&mut long, 0x100);
+    assert_eq!(long, [0x81, 0x00]);
+    let blob = unmanaged_callers_only_blob("add", CallConv::Cdecl);
+    assert_eq!(blob[2], 2);
+    assert!(blob.ends_with(b"CallConvCdecl, System.Runtime"));
+}
@@ -3,7 +3,7 @@ use crate::{
     assert_morphic,
     cil::{CILOp, FieldDescriptor},
     function_sig::FnSig,
-    r#type::{TyCache, Type},
+    r#type::Type,
 };
 use rustc_middle::{
     mir::PlaceElem,
@@ -18,36 +18,26 @@ pub fn local_adress(local: usize, method: &rustc_middle::mir::Body) -> CILOp {
         CILOp::LDArgA((local - 1) as u32)
     }
 }
+/// Returns the ops turning the address of the pointer of type `curr_type` on top of the stack into the address of the dereferenced place of type
+/// `target_type`. Wide pointers store the address of their data in the `data_address` field, unless the place is unsized too.
 pub fn address_last_dereference<'ctx>(
     target_type: Ty<'ctx>,
     curr_type: PlaceTy<'ctx>,
-    tycache: &mut TyCache,
-    tyctx: TyCtxt<'ctx>,
-    method: Instance<'ctx>,
 ) -> Vec<CILOp> {
     let curr_type = match curr_type {
         PlaceTy::Ty(curr_type) => curr_type,
         // Enums don't require any special handling
         PlaceTy::EnumVariant(_, _) => return vec![],
     };
-    //eprintln!("target_type:{target_type:?} curr_type:{curr_type:?}");
     // Get the type curr_type points to!
     let curr_points_to = super::pointed_type(curr_type.into());
-    let curr_type = tycache.type_from_cache(curr_type, tyctx, Some(method));
-
-    match (curr_points_to.kind(), target_type.kind()) {
-        (TyKind::Slice(_), TyKind::Slice(_)) => vec![],
-        (TyKind::Slice(_), _) => vec![CILOp::LDField(
-            FieldDescriptor::new(
-                curr_type.as_dotnet().unwrap(),
-                Type::Ptr(Type::Void.into()),
-                "data_address".into(),
-            )
-            .into(),
-        )],
-        _ => vec![],
+    if crate::fat_ptr::is_wide_pointee(curr_points_to)
+        && !crate::fat_ptr::is_wide_pointee(target_type)
+    {
+        vec![crate::fat_ptr::load_data_address()]
+    } else {
+        vec![]
     }
-    //println!("casting {source:?} source_pointed_to:{source_pointed_to:?} to {target:?} target_pointed_to:{target_pointed_to:?}. ops:{ops:?}");
 }
 pub fn place_elem_adress<'ctx>(
     place_elem: &PlaceElem<'ctx>,
@@ -61,9 +51,8 @@ pub fn place_elem_adress<'ctx>(
     let curr_type = curr_type.monomorphize(&method_instance, tyctx);
     assert_morphic!(curr_type);
     match place_elem {
-        PlaceElem::Deref => {
-            address_last_dereference(place_ty, curr_type, type_cache, tyctx, method_instance)
-        }
+        PlaceElem::Deref => address_last_dereference(place_ty, curr_type),
+        PlaceElem::Field(..) if super::is_zst_field(place_elem, method_instance, tyctx) => vec![],
         PlaceElem::Field(index, _) => match curr_type {
             PlaceTy::Ty(curr_type) => {
                 //TODO: Why was this commented out?
@@ -130,15 +119,7 @@ pub fn place_elem_adress<'ctx>(
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, tyctx);
                     let inner_type =
                         type_cache.type_from_cache(inner, tyctx, Some(method_instance));
-                    let slice = type_cache
-                        .slice_ty(inner, tyctx, Some(method_instance))
-                        .as_dotnet()
-                        .unwrap();
-                    let desc = FieldDescriptor::new(
-                        slice,
-                        Type::Ptr(Type::Void.into()),
-                        "data_address".into(),
-                    );
+                    let desc = crate::fat_ptr::data_address_field();
                     let _deref_op = super::deref_op(
                         super::PlaceTy::Ty(inner),
                         tyctx,
@@ -199,15 +180,7 @@ pub fn place_elem_adress<'ctx>(
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, tyctx);
                     let inner_type =
                         type_cache.type_from_cache(inner, tyctx, Some(method_instance));
-                    let slice = type_cache
-                        .slice_ty(inner, tyctx, Some(method_instance))
-                        .as_dotnet()
-                        .unwrap();
-                    let desc = FieldDescriptor::new(
-                        slice,
-                        Type::Ptr(Type::Void.into()),
-                        "data_address".into(),
-                    );
+                    let desc = crate::fat_ptr::data_address_field();
                     let derf_op = super::deref_op(
                         super::PlaceTy::Ty(inner),
                         tyctx,
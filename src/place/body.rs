@@ -44,6 +44,19 @@ pub fn place_elem_body<'ctx>(
                 )
             }
         }
+        PlaceElem::Field(_, field_type)
+            if super::is_zst_field(place_elem, method_instance, tyctx) =>
+        {
+            let field_type = crate::utilis::monomorphize(&method_instance, *field_type, tyctx);
+            if body_ty_is_by_adress(field_type) {
+                (field_type.into(), vec![])
+            } else {
+                let tpe = type_cache.type_from_cache(field_type, tyctx, Some(method_instance));
+                let mut ops = vec![CILOp::Pop];
+                ops.extend(crate::utilis::zst_value(tpe));
+                (field_type.into(), ops)
+            }
+        }
         PlaceElem::Field(index, field_type) => match curr_type {
             PlaceTy::Ty(curr_type) => {
                 //TODO: Why was this commented out?
@@ -118,15 +131,7 @@ pub fn place_elem_body<'ctx>(
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, tyctx);
                     let inner_type =
                         type_cache.type_from_cache(inner, tyctx, Some(method_instance));
-                    let slice = type_cache
-                        .slice_ty(inner, tyctx, Some(method_instance))
-                        .as_dotnet()
-                        .unwrap();
-                    let desc = FieldDescriptor::new(
-                        slice,
-                        Type::Ptr(Type::Void.into()),
-                        "data_address".into(),
-                    );
+                    let desc = crate::fat_ptr::data_address_field();
                     let deref_op = super::deref_op(
                         super::PlaceTy::Ty(inner),
                         tyctx,
@@ -206,15 +211,7 @@ pub fn place_elem_body<'ctx>(
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, tyctx);
                     let inner_type =
                         type_cache.type_from_cache(inner, tyctx, Some(method_instance));
-                    let slice = type_cache
-                        .slice_ty(inner, tyctx, Some(method_instance))
-                        .as_dotnet()
-                        .unwrap();
-                    let desc = FieldDescriptor::new(
-                        slice,
-                        Type::Ptr(Type::Void.into()),
-                        "data_address".into(),
-                    );
+                    let desc = crate::fat_ptr::data_address_field();
                     let derf_op = super::deref_op(
                         super::PlaceTy::Ty(inner),
                         tyctx,
@@ -1,4 +1,4 @@
-use crate::cil::{CILOp, FieldDescriptor};
+use crate::cil::CILOp;
 use crate::function_sig::FnSig;
 use crate::r#type::Type;
 
@@ -25,6 +25,13 @@ pub fn place_get<'a>(
     method_instance: Instance<'a>,
     type_cache: &mut crate::r#type::TyCache,
 ) -> Vec<CILOp> {
+    let place_ty = place.ty(method, ctx);
+    let place_ty = crate::utilis::monomorphize(&method_instance, place_ty, ctx).ty;
+    // ZSTs are not stored, so there is nothing to load.
+    if crate::utilis::is_zst(place_ty, ctx) {
+        let tpe = type_cache.type_from_cache(place_ty, ctx, Some(method_instance));
+        return crate::utilis::zst_value(tpe);
+    }
     let mut ops = Vec::with_capacity(place.projection.len());
 
     if place.projection.is_empty() {
@@ -36,13 +43,19 @@ pub fn place_get<'a>(
         let mut ty = ty.into();
         ops.push(op);
         let (head, body) = super::slice_head(place.projection);
+        let mut unaligned = false;
         for elem in body {
+            unaligned = super::elem_is_unaligned(unaligned, elem, ty);
             let (curr_ty, curr_ops) =
                 super::place_elem_body(elem, ty, ctx, method_instance, method, type_cache);
             ty = curr_ty.monomorphize(&method_instance, ctx);
-            ops.extend(curr_ops);
+            ops.extend(super::align_ops(curr_ops, unaligned));
         }
-        ops.extend(place_elem_get(head, ty, ctx, method_instance, type_cache));
+        unaligned = super::elem_is_unaligned(unaligned, head, ty);
+        ops.extend(super::align_ops(
+            place_elem_get(head, ty, ctx, method_instance, type_cache),
+            unaligned,
+        ));
         ops
     }
 }
@@ -104,15 +117,7 @@ fn place_elem_get<'a>(
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, tyctx);
                     let inner_type =
                         type_cache.type_from_cache(inner, tyctx, Some(method_instance));
-                    let slice = type_cache
-                        .slice_ty(inner, tyctx, Some(method_instance))
-                        .as_dotnet()
-                        .unwrap();
-                    let desc = FieldDescriptor::new(
-                        slice,
-                        Type::Ptr(Type::Void.into()),
-                        "data_address".into(),
-                    );
+                    let desc = crate::fat_ptr::data_address_field();
                     let deref_op = super::deref_op(
                         super::PlaceTy::Ty(inner),
                         tyctx,
@@ -170,15 +175,7 @@ fn place_elem_get<'a>(
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, tyctx);
                     let inner_type =
                         type_cache.type_from_cache(inner, tyctx, Some(method_instance));
-                    let slice = type_cache
-                        .slice_ty(inner, tyctx, Some(method_instance))
-                        .as_dotnet()
-                        .unwrap();
-                    let desc = FieldDescriptor::new(
-                        slice,
-                        Type::Ptr(Type::Void.into()),
-                        "data_address".into(),
-                    );
+                    let desc = crate::fat_ptr::data_address_field();
                     let derf_op = super::deref_op(
                         super::PlaceTy::Ty(inner),
                         tyctx,
@@ -3,7 +3,7 @@
 use crate::cil::CILOp;
 use crate::r#type::{DotnetTypeRef, Type};
 
-use rustc_middle::mir::Place;
+use rustc_middle::mir::{Place, PlaceElem};
 
 mod adress;
 mod body;
@@ -32,6 +32,62 @@ fn pointed_type(ty: PlaceTy) -> Ty {
         panic!("Can't dereference enum variant!");
     }
 }
+/// Checks if `ty` is a `#[repr(packed)]` ADT, whose fields may be unaligned.
+fn is_packed(ty: PlaceTy) -> bool {
+    match ty {
+        PlaceTy::Ty(ty) => matches!(ty.kind(), TyKind::Adt(def, _) if def.repr().packed()),
+        PlaceTy::EnumVariant(_, _) => false,
+    }
+}
+/// Checks if the memory accesses of `elem` may be unaligned, given that the preceding projections were(`unaligned`) or were not.
+/// Fields of packed ADTs may be unaligned, while a dereference always results in an aligned pointer.
+fn elem_is_unaligned(unaligned: bool, elem: &PlaceElem, curr_ty: PlaceTy) -> bool {
+    match elem {
+        PlaceElem::Deref => false,
+        PlaceElem::Field(_, _) => unaligned || is_packed(curr_ty),
+        _ => unaligned,
+    }
+}
+/// Prefixes all memory accesses within `ops` with `unaligned.`, if `unaligned` is true.
+fn align_ops(ops: Vec<CILOp>, unaligned: bool) -> Vec<CILOp> {
+    if !unaligned {
+        return ops;
+    }
+    let mut res = Vec::with_capacity(ops.len());
+    for op in ops {
+        if op.is_memory_access() {
+            res.push(CILOp::Unaligned(1));
+        }
+        res.push(op);
+    }
+    res
+}
+/// Prefixes all memory accesses within `ops` with `volatile.`, and with `unaligned.` too, if `unaligned` is true.
+pub(crate) fn volatile_ops(ops: Vec<CILOp>, unaligned: bool) -> Vec<CILOp> {
+    let mut res = Vec::with_capacity(ops.len());
+    for op in ops {
+        if op.is_memory_access() {
+            if unaligned {
+                res.push(CILOp::Unaligned(1));
+            }
+            res.push(CILOp::Volatile);
+        }
+        res.push(op);
+    }
+    res
+}
+/// Checks if `elem` selects a zero-sized field. ZST fields are not stored, so the address of their owner is used as theirs.
+fn is_zst_field<'tcx>(
+    elem: &PlaceElem<'tcx>,
+    method_instance: Instance<'tcx>,
+    tyctx: TyCtxt<'tcx>,
+) -> bool {
+    let PlaceElem::Field(_, field_ty) = elem else {
+        return false;
+    };
+    let field_ty = crate::utilis::monomorphize(&method_instance, *field_ty, tyctx);
+    crate::utilis::is_zst(field_ty, tyctx)
+}
 fn body_ty_is_by_adress(last_ty: Ty) -> bool {
     crate::assert_morphic!(last_ty);
     match *last_ty.kind() {
@@ -169,6 +225,10 @@ pub fn place_adress<'a>(
     let place_ty = place.ty(method, ctx);
     let place_ty = crate::utilis::monomorphize(&method_instance, place_ty, ctx).ty;
     if place.projection.is_empty() {
+        // ZST locals are removed, so they have no address.
+        if crate::utilis::is_zst(place_ty, ctx) {
+            return crate::utilis::zst_address(place_ty, ctx);
+        }
         ops.push(local_adress(place.local.as_usize(), method));
         ops
     } else {
@@ -177,11 +237,13 @@ pub fn place_adress<'a>(
         let mut ty = ty.into();
         ops.push(op);
         let (head, body) = slice_head(place.projection);
+        let mut unaligned = false;
         for elem in body {
+            unaligned = elem_is_unaligned(unaligned, elem, ty);
             let (curr_ty, curr_ops) =
                 place_elem_body(elem, ty, ctx, method_instance, method, type_cache);
             ty = curr_ty.monomorphize(&method_instance, ctx);
-            ops.extend(curr_ops);
+            ops.extend(align_ops(curr_ops, unaligned));
         }
         ops.extend(adress::place_elem_adress(
             head,
@@ -203,6 +265,14 @@ pub(crate) fn place_set<'a>(
     method_instance: Instance<'a>,
     type_cache: &mut crate::r#type::TyCache,
 ) -> Vec<CILOp> {
+    let place_ty = place.ty(method, ctx);
+    let place_ty = crate::utilis::monomorphize(&method_instance, place_ty, ctx).ty;
+    // ZSTs are not stored, so the value only needs to be calculated.
+    if crate::utilis::is_zst(place_ty, ctx) {
+        let mut ops = value_calc;
+        ops.push(CILOp::Pop);
+        return ops;
+    }
     let mut ops = Vec::with_capacity(place.projection.len());
     if place.projection.is_empty() {
         ops.extend(value_calc);
@@ -214,15 +284,21 @@ pub(crate) fn place_set<'a>(
         ty = ty.monomorphize(&method_instance, ctx);
         ops.push(op);
         let (head, body) = slice_head(place.projection);
+        let mut unaligned = false;
         for elem in body {
+            unaligned = elem_is_unaligned(unaligned, elem, ty);
             let (curr_ty, curr_ops) =
                 place_elem_body(elem, ty, ctx, method_instance, method, type_cache);
             ty = curr_ty.monomorphize(&method_instance, ctx);
-            ops.extend(curr_ops);
+            ops.extend(align_ops(curr_ops, unaligned));
         }
         ops.extend(value_calc);
         ty = ty.monomorphize(&method_instance, ctx);
-        ops.extend(place_elem_set(head, ty, ctx, method_instance, type_cache));
+        unaligned = elem_is_unaligned(unaligned, head, ty);
+        ops.extend(align_ops(
+            place_elem_set(head, ty, ctx, method_instance, type_cache),
+            unaligned,
+        ));
         ops
     }
 }
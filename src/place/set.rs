@@ -1,5 +1,5 @@
 use super::{pointed_type, PlaceTy};
-use crate::cil::{CILOp, FieldDescriptor};
+use crate::cil::CILOp;
 use crate::function_sig::FnSig;
 use crate::r#type::{DotnetTypeRef, Type};
 
@@ -65,15 +65,7 @@ pub fn place_elem_set<'a>(
                 TyKind::Slice(inner) => {
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, ctx);
                     let inner_type = type_cache.type_from_cache(inner, ctx, Some(method_instance));
-                    let slice = type_cache
-                        .slice_ty(inner, ctx, Some(method_instance))
-                        .as_dotnet()
-                        .unwrap();
-                    let desc = FieldDescriptor::new(
-                        slice,
-                        Type::Ptr(Type::Void.into()),
-                        "data_address".into(),
-                    );
+                    let desc = crate::fat_ptr::data_address_field();
                     let ptr_set_op =
                         ptr_set_op(super::PlaceTy::Ty(inner), ctx, &method_instance, type_cache);
                     let mut ops = vec![
@@ -129,15 +121,7 @@ pub fn place_elem_set<'a>(
                     let inner = crate::utilis::monomorphize(&method_instance, *inner, ctx);
 
                     let inner_type = type_cache.type_from_cache(inner, ctx, Some(method_instance));
-                    let slice = type_cache
-                        .slice_ty(inner, ctx, Some(method_instance))
-                        .as_dotnet()
-                        .unwrap();
-                    let desc = FieldDescriptor::new(
-                        slice,
-                        Type::Ptr(Type::Void.into()),
-                        "data_address".into(),
-                    );
+                    let desc = crate::fat_ptr::data_address_field();
                     let ptr_set_op =
                         ptr_set_op(super::PlaceTy::Ty(inner), ctx, &method_instance, type_cache);
                     let mut ops = vec![
@@ -200,7 +184,7 @@ pub fn place_elem_set<'a>(
     }
 }
 /// Returns a set of instructons to set a pointer to a `pointed_type` to a value from the stack.
-fn ptr_set_op<'ctx>(
+pub(crate) fn ptr_set_op<'ctx>(
     pointed_type: PlaceTy<'ctx>,
     tyctx: TyCtxt<'ctx>,
     method_instance: &Instance<'ctx>,
@@ -0,0 +1,124 @@
+//! Differences between the .NET runtimes the produced assemblies can run on.
+//!
+//! Every runtime is described by a [`RuntimeProfile`], selected using the [`runtime`](crate::config::BackendConfig::runtime) option.
+//! Code which behaves differently depending on the runtime(the exporter, the shims and the linker) should consult the profile instead
+//! of checking for a specific runtime, so that supporting a new runtime only requires implementing [`RuntimeProfile`] for it.
+use crate::config::DotnetApi;
+use std::path::Path;
+/// Describes the quirks of a .NET runtime.
+pub trait RuntimeProfile: Sync {
+    /// Human-readable name of the runtime.
+    fn name(&self) -> &'static str;
+    /// Returns true if the runtime supports `api`. Shims use this to choose fallbacks for missing APIs.
+    fn supports(&self, _api: DotnetApi) -> bool {
+        true
+    }
+    /// The command used to assemble the exported IL.
+    fn ilasm(&self) -> &'static str {
+        "ilasm"
+    }
+    /// Additional flags passed to [`ilasm`](RuntimeProfile::ilasm).
+    fn ilasm_flags(&self) -> &'static [&'static str] {
+        &[]
+    }
+    /// The command used to run an assembly on this runtime, if it can be run directly.
+    fn host(&self) -> Option<&'static str>;
+    /// Returns true if the runtime needs a `.runtimeconfig.json` file next to an assembly to run it.
+    fn needs_runtime_config(&self) -> bool {
+        false
+    }
+    /// Compiles the assembly at `path` ahead of time, if this runtime requires it. Called by the linker after the assembly is exported.
+    fn aot_compile(&self, _path: &Path) -> Result<(), String> {
+        Ok(())
+    }
+}
+/// The .NET(Core) runtime.
+pub struct CoreClrProfile;
+impl RuntimeProfile for CoreClrProfile {
+    fn name(&self) -> &'static str {
+        "CoreCLR"
+    }
+    fn host(&self) -> Option<&'static str> {
+        Some("dotnet")
+    }
+    // Makes ilasm use the short forms of branches(eg. `br.s`) when their targets are close enough.
+    fn ilasm_flags(&self) -> &'static [&'static str] {
+        &["-optimize"]
+    }
+    fn needs_runtime_config(&self) -> bool {
+        true
+    }
+}
+/// The Mono runtime. It does not support 128 bit integers.
+pub struct MonoProfile {
+    /// The `mono` flag selecting the AOT mode the linker compiles assemblies with(eg. `--aot=full`), or `None` if assemblies should be JIT-compiled.
+    pub aot: Option<&'static str>,
+}
+impl RuntimeProfile for MonoProfile {
+    fn name(&self) -> &'static str {
+        "Mono"
+    }
+    fn supports(&self, api: DotnetApi) -> bool {
+        !matches!(api, DotnetApi::Int128)
+    }
+    fn host(&self) -> Option<&'static str> {
+        Some("mono")
+    }
+    fn aot_compile(&self, path: &Path) -> Result<(), String> {
+        let Some(aot) = self.aot else {
+            return Ok(());
+        };
+        let out = std::process::Command::new("mono")
+            .arg(aot)
+            .arg("-O=all")
+            .arg(path)
+            .output()
+            .map_err(|err| format!("failed run mono AOT process: {err}"))?;
+        if out.stderr.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Could not run AOT: {stderr}",
+                stderr = String::from_utf8_lossy(&out.stderr)
+            ))
+        }
+    }
+}
+/// The .NET WebAssembly runtime. It is based on Mono, and assemblies are compiled to WebAssembly by the .NET SDK, not by the linker.
+pub struct WasmProfile;
+impl RuntimeProfile for WasmProfile {
+    fn name(&self) -> &'static str {
+        "WebAssembly"
+    }
+    fn supports(&self, api: DotnetApi) -> bool {
+        MonoProfile { aot: None }.supports(api)
+    }
+    fn host(&self) -> Option<&'static str> {
+        None
+    }
+}
+/// Contents of the runtime config file required by `dotnet` to run an assembly, targeting the framework selected by the `tfm` option.
+pub fn runtime_config() -> String {
+    let info = std::process::Command::new("dotnet")
+        .arg("--info")
+        .output()
+        .expect("Could not run `dotnet --info`");
+    let info = String::from_utf8(info.stdout).expect("Dotnet info not UTF8!");
+    let version_start = info.find("Host:").unwrap_or_default();
+    let version_start = version_start + info[version_start..].find("Version:").unwrap();
+    let version_start = version_start + "Version:".len();
+    let version_end = version_start + info[version_start..].find('\n').unwrap();
+    let version = info[version_start..version_end].trim();
+    let tfm = crate::config::config().tfm.name();
+    format!(
+        "{{
+  \"runtimeOptions\": {{
+    \"tfm\": \"{tfm}\",
+    \"framework\": {{
+      \"name\": \"Microsoft.NETCore.App\",
+      \"version\": \"{version}\"
+    }}
+  }}
+}}"
+    )
+}
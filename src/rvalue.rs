@@ -1,4 +1,4 @@
-use crate::cil::{CILOp, CallSite, FieldDescriptor};
+use crate::cil::{CILOp, CallSite};
 use crate::function_sig::FnSig;
 use crate::operand::handle_operand;
 use crate::place::deref_op;
@@ -47,39 +47,13 @@ pub fn handle_rvalue<'tcx>(
                 TyKind::Ref(_, inner, _) => *inner,
                 _ => panic!("Type is not ptr {target:?}."),
             };
-            let source_type = tycache.type_from_cache(source, tyctx, Some(method_instance));
-            //let target_type = tycache.type_from_cache(target, tyctx, Some(method_instance));
-
-            let ops = match (source_pointed_to.kind(), target_pointed_to.kind()) {
-                (TyKind::Slice(_) | TyKind::Str, TyKind::Slice(_) | TyKind::Str) => {
-                    let mut res = handle_operand(operand, tyctx, method, method_instance, tycache);
-                    res.push(CILOp::NewTMPLocal(source_type.into()));
-                    res.push(CILOp::SetTMPLocal);
-                    res.push(CILOp::LoadAddresOfTMPLocal);
-                    res.push(CILOp::FreeTMPLocal);
-                    res.extend(crate::place::deref_op(
-                        crate::place::PlaceTy::Ty(target),
-                        tyctx,
-                        &method_instance,
-                        tycache,
-                    ));
-                    res
-                }
-                (TyKind::Slice(_) | TyKind::Str, _) => {
-                    let mut res = handle_operand(operand, tyctx, method, method_instance, tycache);
-                    //println!("Slice!");
-                    res.push(CILOp::LDField(
-                        FieldDescriptor::new(
-                            source_type.as_dotnet().unwrap(),
-                            Type::Ptr(Type::Void.into()),
-                            "data_address".into(),
-                        )
-                        .into(),
-                    ));
-                    res
-                }
-                _ => handle_operand(operand, tyctx, method, method_instance, tycache),
-            };
+            let mut ops = handle_operand(operand, tyctx, method, method_instance, tycache);
+            // All wide pointers share one representation, so only casting a wide pointer to a thin one changes it.
+            if crate::fat_ptr::is_wide_pointee(source_pointed_to)
+                && !crate::fat_ptr::is_wide_pointee(target_pointed_to)
+            {
+                ops.push(crate::fat_ptr::load_data_address());
+            }
             //println!("casting {source:?} source_pointed_to:{source_pointed_to:?} to {target:?} target_pointed_to:{target_pointed_to:?}. ops:{ops:?}");
             ops
         }
@@ -89,7 +63,21 @@ pub fn handle_rvalue<'tcx>(
                 crate::utilis::monomorphize(&method_instance, operand.ty(method, tyctx), tyctx);
             let source_type = tycache.type_from_cache(source, tyctx, Some(method_instance));
             let target_type = tycache.type_from_cache(target, tyctx, Some(method_instance));
-            let target_dotnet = target_type.as_dotnet().unwrap();
+            let (source_tail, target_tail) = crate::vtable::unsize_pointees(source, target, tyctx);
+            if let TyKind::Dynamic(predicates, _, _) = target_tail.kind() {
+                if source_tail.is_trait() {
+                    todo!("Can't yet upcast {source_tail:?} to {target_tail:?}!");
+                }
+                let vtable = crate::vtable::vtable_ptr(source_tail, predicates.principal(), tyctx);
+                let mut ops = handle_operand(operand, tyctx, method, method_instance, tycache);
+                ops.extend(crate::vtable::unsize_to_dyn(
+                    source_type,
+                    target_type,
+                    vtable,
+                    tyctx,
+                ));
+                return ops;
+            }
             let derefed_source = match source.kind() {
                 TyKind::RawPtr(tpe) => tpe.ty,
                 TyKind::Ref(_, inner, _) => *inner,
@@ -100,39 +88,11 @@ pub fn handle_rvalue<'tcx>(
             } else {
                 panic!("Non array type:{source:?}")
             };
-            //let element_type = tycache.type_from_cache(*element, tyctx, Some(method_instance));
-            let mut res = handle_operand(operand, tyctx, method, method_instance, tycache);
-            println!("Unsize casting {target:?} to {target:?}");
-            res.extend([
-                CILOp::NewTMPLocal(source_type.clone().into()),
-                CILOp::SetTMPLocal,
-                // a:*[T;n] = stack_top;
-                CILOp::NewTMPLocal(target_type.into()),
-                // b:Slice = unint();
-                CILOp::LoadAddresOfTMPLocal,
-                CILOp::LoadUnderTMPLocal(1),
-                CILOp::STField(
-                    FieldDescriptor::new(
-                        target_dotnet.clone(),
-                        Type::Ptr(Type::Void.into()),
-                        "data_address".into(),
-                    )
-                    .into(),
-                ),
-                // b.data_address = (a as *mut RustVoid);
-                CILOp::LoadAddresOfTMPLocal,
-                CILOp::LdcI64(length as u64 as i64),
-                CILOp::ConvUSize(false),
-                CILOp::STField(
-                    FieldDescriptor::new(target_dotnet, Type::USize, "metadata".into()).into(),
-                ),
-                // b.metadata = (length_i64 as usize);
-                CILOp::LoadTMPLocal,
-                // stack_top = b;
-                CILOp::FreeTMPLocal,
-                CILOp::FreeTMPLocal,
-            ]);
-            res
+            // The length of the array becomes the metadata of the slice.
+            crate::fat_ptr::make_fat_ptr(
+                handle_operand(operand, tyctx, method, method_instance, tycache),
+                vec![CILOp::LdcI64(length as u64 as i64), CILOp::ConvUSize(false)],
+            )
             //todo!("Array to slice {res:?}!")
             //
         }
@@ -163,13 +123,14 @@ pub fn handle_rvalue<'tcx>(
             let src = operand.ty(&method.local_decls, tyctx);
             let src = crate::utilis::monomorphize(&method_instance, src, tyctx);
             let src = tycache.type_from_cache(src, tyctx, Some(method_instance));
-            [
-                handle_operand(operand, tyctx, method, method_instance, tycache),
-                crate::casts::int_to_int(src, target),
-            ]
-            .into_iter()
-            .flatten()
-            .collect()
+            let check = target == Type::Char && crate::config::config().check_assumptions;
+            let mut ops = handle_operand(operand, tyctx, method, method_instance, tycache);
+            ops.extend(crate::casts::int_to_int(src, target));
+            // `as char` casts can't produce invalid values, but are still checked if UB is suspected.
+            if check {
+                ops.push(crate::casts::check_char());
+            }
+            ops
         }
         Rvalue::Cast(CastKind::FloatToInt, operand, target) => {
             let target = crate::utilis::monomorphize(&method_instance, *target, tyctx);
@@ -238,6 +199,37 @@ pub fn handle_rvalue<'tcx>(
                 (Type::U16, Type::DotnetChar) => {
                     handle_operand(operand, tyctx, method, method_instance, tycache)
                 }
+                // `char` is an u32, so this is a NOP. Transmuting an invalid u32 into a char is UB, so it is checked. This is also how
+                // `char::from_u32` and `char::from_u32_unchecked` create chars.
+                (Type::U32 | Type::I32, Type::Char) => {
+                    let mut res = handle_operand(operand, tyctx, method, method_instance, tycache);
+                    res.push(crate::casts::check_char());
+                    res
+                }
+                (Type::Char, Type::U32 | Type::I32) | (Type::U32, Type::U32) => {
+                    handle_operand(operand, tyctx, method, method_instance, tycache)
+                }
+                // Interop: converting `char`s to and from .NET `char`s and `Rune`s.
+                (Type::Char, Type::DotnetChar) => {
+                    let mut res = handle_operand(operand, tyctx, method, method_instance, tycache);
+                    res.extend(crate::casts::char_to_dotnet_char());
+                    res
+                }
+                (Type::DotnetChar, Type::Char) => {
+                    let mut res = handle_operand(operand, tyctx, method, method_instance, tycache);
+                    res.extend(crate::casts::dotnet_char_to_char());
+                    res
+                }
+                (Type::Char, Type::DotnetType(dotnet)) if dotnet.is_rune() => {
+                    let mut res = handle_operand(operand, tyctx, method, method_instance, tycache);
+                    res.extend(crate::casts::char_to_rune());
+                    res
+                }
+                (Type::DotnetType(dotnet), Type::Char) if dotnet.is_rune() => {
+                    let mut res = handle_operand(operand, tyctx, method, method_instance, tycache);
+                    res.extend(crate::casts::rune_to_char());
+                    res
+                }
                 (Type::F64, Type::U64) => {
                     let mut res = handle_operand(operand, tyctx, method, method_instance, tycache);
                     res.extend([
@@ -347,26 +339,31 @@ pub fn handle_rvalue<'tcx>(
             }
             ops
         }
+        // Pointers to functions are obtained with `ldftn`. The function itself is a mono item, so it will be compiled on its own.
+        Rvalue::Cast(CastKind::PointerCoercion(PointerCoercion::ReifyFnPointer), operand, _) => {
+            let fn_ty =
+                crate::utilis::monomorphize(&method_instance, operand.ty(method, tyctx), tyctx);
+            let TyKind::FnDef(def_id, subst) = fn_ty.kind() else {
+                panic!("Only functions can be cast to function pointers, not {fn_ty:?}!");
+            };
+            let instance = Instance::resolve(tyctx, ParamEnv::reveal_all(), *def_id, subst)
+                .expect("Could not resolve a function cast to a pointer!")
+                .expect("Could not resolve a function cast to a pointer!");
+            let sig = FnSig::sig_from_instance_(instance, tyctx, tycache)
+                .expect("Could not get the signature of a function cast to a pointer!");
+            vec![CILOp::LDFtn(CallSite::boxed(
+                None,
+                crate::utilis::function_name(tyctx.symbol_name(instance)),
+                sig,
+                true,
+            ))]
+        }
         Rvalue::Cast(kind, _operand, _) => todo!("Unhandled cast kind {kind:?}, rvalue:{rvalue:?}"),
         Rvalue::Discriminant(place) => {
-            let mut ops =
-                crate::place::place_adress(place, tyctx, method, method_instance, tycache);
+            let addr = crate::place::place_adress(place, tyctx, method, method_instance, tycache);
             let owner_ty = place.ty(method, tyctx).ty;
             let owner_ty = crate::utilis::monomorphize(&method_instance, owner_ty, tyctx);
-            let owner = tycache.type_from_cache(owner_ty, tyctx, Some(method_instance));
-            //TODO: chose proper tag type based on variant count of `owner`
-            let discr_type = crate::r#type::Type::U8; //owner_ty
-            let owner = if let crate::r#type::Type::DotnetType(dotnet_type) = owner {
-                dotnet_type.as_ref().clone()
-            } else {
-                panic!();
-            };
-            ops.push(CILOp::LDField(Box::new(crate::cil::FieldDescriptor::new(
-                owner,
-                discr_type,
-                "_tag".into(),
-            ))));
-            ops
+            crate::discriminant::get_discr(addr, owner_ty, tyctx, method_instance, tycache)
         }
         Rvalue::Len(operand) => {
             let mut ops =
@@ -375,15 +372,7 @@ pub fn handle_rvalue<'tcx>(
             let ty = crate::utilis::monomorphize(&method_instance, ty, tyctx);
             // let tpe = tycache.type_from_cache(ty.ty, tyctx, Some(method_instance));
             match ty.ty.kind() {
-                TyKind::Slice(inner) => {
-                    let slice_tpe = tycache
-                        .slice_ty(*inner, tyctx, Some(method_instance))
-                        .as_dotnet()
-                        .unwrap();
-                    let descriptor =
-                        FieldDescriptor::new(slice_tpe, Type::USize, "metadata".into());
-                    ops.extend([CILOp::LDField(descriptor.into())]);
-                }
+                TyKind::Slice(_) => ops.push(crate::fat_ptr::load_metadata()),
                 _ => todo!("Get length of type {ty:?}"),
             }
             ops
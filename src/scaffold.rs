@@ -0,0 +1,80 @@
+//! Generates a minimal .NET project around an assembly produced by the linker, so that it can be used with `dotnet run` or referenced by other projects
+//! without any manual setup.
+//!
+//! For an executable `name.exe`, the project is a console application, which loads the assembly and runs its entrypoint.
+//! For a library `name.dll`, the project is a class library referencing the assembly.
+//! The project only compiles the files it generates, and C# bindings in `name.bindings.cs`, if they exist.
+use std::path::Path;
+/// Writes `{name}.csproj`(and `{name}.Program.cs`, for executables) into the directory containing `assembly`.
+pub fn scaffold(assembly: &Path, is_lib: bool) -> std::io::Result<()> {
+    let dir = assembly.parent().unwrap_or(Path::new("."));
+    let file_name = assembly
+        .file_name()
+        .expect("Scaffolded assembly has no name!")
+        .to_string_lossy();
+    let name = assembly
+        .file_stem()
+        .expect("Scaffolded assembly has no name!")
+        .to_string_lossy();
+    let tfm = crate::config::config().tfm;
+    if is_lib {
+        std::fs::write(
+            dir.join(format!("{name}.csproj")),
+            classlib_project(&name, &file_name, tfm.name()),
+        )
+    } else {
+        // .NET Standard is only usable by libraries, so executables need a real framework.
+        let tfm = tfm.max(crate::config::TargetFramework::Net7);
+        std::fs::write(dir.join(format!("{name}.Program.cs")), program(&file_name))?;
+        std::fs::write(
+            dir.join(format!("{name}.csproj")),
+            console_project(&name, &file_name, tfm.name()),
+        )
+    }
+}
+fn classlib_project(name: &str, file_name: &str, tfm: &str) -> String {
+    format!(
+        "<Project Sdk=\"Microsoft.NET.Sdk\">
+  <PropertyGroup>
+    <TargetFramework>{tfm}</TargetFramework>
+    <EnableDefaultCompileItems>false</EnableDefaultCompileItems>
+    <AllowUnsafeBlocks>true</AllowUnsafeBlocks>
+  </PropertyGroup>
+  <ItemGroup>
+    <Compile Include=\"{name}.bindings.cs\" Condition=\"Exists('{name}.bindings.cs')\" />
+    <Reference Include=\"{name}\">
+      <HintPath>{file_name}</HintPath>
+    </Reference>
+  </ItemGroup>
+</Project>
+"
+    )
+}
+fn console_project(name: &str, file_name: &str, tfm: &str) -> String {
+    format!(
+        "<Project Sdk=\"Microsoft.NET.Sdk\">
+  <PropertyGroup>
+    <OutputType>Exe</OutputType>
+    <TargetFramework>{tfm}</TargetFramework>
+    <AssemblyName>{name}.host</AssemblyName>
+    <EnableDefaultCompileItems>false</EnableDefaultCompileItems>
+    <AllowUnsafeBlocks>true</AllowUnsafeBlocks>
+  </PropertyGroup>
+  <ItemGroup>
+    <Compile Include=\"{name}.Program.cs\" />
+    <Compile Include=\"{name}.bindings.cs\" Condition=\"Exists('{name}.bindings.cs')\" />
+    <None Include=\"{file_name}\" CopyToOutputDirectory=\"PreserveNewest\" />
+  </ItemGroup>
+</Project>
+"
+    )
+}
+/// The entrypoint of the generated assembly is a global method, which C# can't call directly, so it is invoked using reflection.
+fn program(file_name: &str) -> String {
+    format!(
+        "var path = System.IO.Path.Combine(System.AppContext.BaseDirectory, \"{file_name}\");
+var assembly = System.Reflection.Assembly.LoadFrom(path);
+assembly.EntryPoint!.Invoke(null, null);
+"
+    )
+}
@@ -0,0 +1,299 @@
+//! The on-disk format of serialized assemblies(`.bc` files, and the `.bc` files within rlibs).
+//!
+//! A serialized assembly starts with [`MAGIC`], followed by the [`FORMAT_VERSION`] it was written with(as a little-endian `u32`), followed by
+//! a list of sections. Each section is a kind byte, followed by the length of its payload(as a little-endian `u64`), followed by the payload:
+//! * [`SECTION_METHOD`] sections hold a single method, spilled to disk during codegen(see [`crate::spill`]),
+//! * the last section is always an [`SECTION_ASSEMBLY`] section, holding everything else. The methods from the method sections are added to it.
+//!
+//! Versions before 2 had no sections: the assembly directly followed the version.
+//!
+//! Payloads are encoded as MessagePack. Enum variants and struct fields are encoded by *name*, not by position, so:
+//! * reordering the variants of an enum(eg. [`crate::cil::CILOp`]) or the fields of a struct does not change the format,
+//! * new variants and fields may be added: files written before they existed are still readable. Fields added later must be `#[serde(default)]`.
+//! * fields unknown to a reader are ignored, so older backends can read assemblies written by newer ones, as long as they don't use new variants.
+//!
+//! Removing or renaming a variant or a field is a breaking change, and requires bumping [`FORMAT_VERSION`].
+//!
+//! Files without the magic are assumed to be written by backends predating this format(which used `postcard`, encoding enum variants by their index),
+//! and are read using the legacy format.
+use crate::{assembly::Assembly, method::Method};
+use serde::{de::DeserializeOwned, Serialize};
+/// Bytes every serialized assembly starts with.
+pub const MAGIC: &[u8; 8] = b"RCCLRASM";
+/// Version of the format written by this backend. Bumped on every breaking change of the format.
+pub const FORMAT_VERSION: u32 = 2;
+/// Kind of a section holding a single method.
+pub const SECTION_METHOD: u8 = 0;
+/// Kind of the section holding the assembly itself.
+pub const SECTION_ASSEMBLY: u8 = 1;
+/// An error encountered while reading a serialized assembly.
+#[derive(Debug)]
+pub enum SerializationError {
+    /// The assembly was written using an unknown(newer) version of the format.
+    UnsupportedVersion(u32),
+    /// The assembly is malformed, or uses enum variants unknown to this backend.
+    Malformed(String),
+}
+impl std::fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "the assembly was serialized using format version {version}, but this backend only supports versions up to {FORMAT_VERSION}. Rebuild the assembly using this backend."
+            ),
+            Self::Malformed(msg) => write!(f, "the serialized assembly is malformed: {msg}"),
+        }
+    }
+}
+impl std::error::Error for SerializationError {}
+impl From<SerializationError> for std::io::Error {
+    fn from(err: SerializationError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+/// Serializes `asm`, using the current version of the format.
+#[must_use]
+pub fn serialize(asm: &Assembly) -> Vec<u8> {
+    let mut bytes = header().to_vec();
+    bytes.extend(section(SECTION_ASSEMBLY, &encode(asm)));
+    bytes
+}
+/// Returns the bytes every serialized assembly starts with: the magic, followed by the format version.
+pub(crate) fn header() -> [u8; 12] {
+    let mut header = [0; 12];
+    header[..8].copy_from_slice(MAGIC);
+    header[8..].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header
+}
+/// Returns a section of kind `kind`, holding `payload`.
+pub(crate) fn section(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut section = Vec::with_capacity(payload.len() + 9);
+    section.push(kind);
+    section.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    section.extend_from_slice(payload);
+    section
+}
+/// Deserializes an assembly serialized using either the current format, an older version of it, or the legacy format.
+pub fn deserialize(bytes: &[u8]) -> Result<Assembly, SerializationError> {
+    let Some(rest) = bytes.strip_prefix(MAGIC.as_slice()) else {
+        return postcard::from_bytes(bytes)
+            .map_err(|err| SerializationError::Malformed(err.to_string()));
+    };
+    if rest.len() < 4 {
+        return Err(SerializationError::Malformed(
+            "missing format version".into(),
+        ));
+    }
+    let (version, payload) = rest.split_at(4);
+    let version = u32::from_le_bytes(version.try_into().unwrap());
+    match version {
+        1 => decode(payload),
+        2 => read_sections(payload),
+        version => Err(SerializationError::UnsupportedVersion(version)),
+    }
+}
+/// Reads the sections of an assembly, and adds the methods from the method sections to it.
+fn read_sections(mut bytes: &[u8]) -> Result<Assembly, SerializationError> {
+    let mut methods: Vec<Method> = Vec::new();
+    loop {
+        if bytes.len() < 9 {
+            return Err(SerializationError::Malformed("truncated section".into()));
+        }
+        let (kind, rest) = bytes.split_at(1);
+        let (len, rest) = rest.split_at(8);
+        let len = u64::from_le_bytes(len.try_into().unwrap());
+        let Some(len) = usize::try_from(len).ok().filter(|len| *len <= rest.len()) else {
+            return Err(SerializationError::Malformed("truncated section".into()));
+        };
+        let (payload, rest) = rest.split_at(len);
+        bytes = rest;
+        match kind[0] {
+            SECTION_METHOD => methods.push(decode(payload)?),
+            SECTION_ASSEMBLY => {
+                if !bytes.is_empty() {
+                    return Err(SerializationError::Malformed(
+                        "data after the assembly section".into(),
+                    ));
+                }
+                let mut asm: Assembly = decode(payload)?;
+                // Methods are spilled by codegen units running in parallel, so they are sorted, to keep the order deterministic.
+                methods.sort_by(|a, b| a.name().cmp(b.name()));
+                asm.add_spilled_methods(methods);
+                return Ok(asm);
+            }
+            kind => {
+                return Err(SerializationError::Malformed(format!(
+                    "unknown section kind {kind}"
+                )))
+            }
+        }
+    }
+}
+/// Encodes `value` as MessagePack, with enum variants and struct fields encoded by name. Used for everything the backend stores on disk.
+pub(crate) fn encode<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+    rmp_serde::to_vec_named(value).expect("Could not serialize the assembly!")
+}
+/// Decodes a value encoded using [`encode`].
+pub(crate) fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, SerializationError> {
+    rmp_serde::from_slice(bytes).map_err(|err| SerializationError::Malformed(err.to_string()))
+}
+#[cfg(test)]
+fn test_assembly() -> Assembly {
+    use crate::{
+        access_modifier::AccessModifer, cil::CILOp, function_sig::FnSig, method::Method,
+        r#type::Type,
+    };
+    let mut asm = Assembly::empty();
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[Type::I32], &Type::I32),
+        "add_five",
+        vec![(Some("tmp".into()), Type::I32)],
+    );
+    method.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::LdcI32(5),
+        CILOp::Add,
+        CILOp::STLoc(0),
+        CILOp::LDLoc(0),
+        CILOp::Ret,
+    ]);
+    asm.add_method(method);
+    asm.add_static(Type::U64, "counter");
+    asm
+}
+#[test]
+fn round_trip() {
+    let asm = test_assembly();
+    let bytes = serialize(&asm);
+    assert!(bytes.starts_with(MAGIC));
+    let read = deserialize(&bytes).expect("Could not deserialize a serialized assembly!");
+    let methods: Vec<_> = asm.methods().collect();
+    let read_methods: Vec<_> = read.methods().collect();
+    assert_eq!(methods, read_methods);
+    assert_eq!(
+        asm.globals().collect::<Vec<_>>(),
+        read.globals().collect::<Vec<_>>()
+    );
+}
+#[test]
+fn spilled_methods() {
+    use crate::{access_modifier::AccessModifer, cil::CILOp, function_sig::FnSig, r#type::Type};
+    let asm = test_assembly();
+    let mut spilled = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[], &Type::Void),
+        "spilled",
+        vec![],
+    );
+    spilled.set_ops(vec![CILOp::Ret]);
+    let mut bytes = header().to_vec();
+    bytes.extend(section(SECTION_METHOD, &encode(&spilled)));
+    bytes.extend(section(SECTION_ASSEMBLY, &encode(&asm)));
+    let read = deserialize(&bytes).expect("Could not read an assembly with spilled methods!");
+    assert_eq!(read.methods().count(), asm.methods().count() + 1);
+    assert!(read.contains_fn_named("spilled"));
+    // A file cut short is rejected.
+    assert!(deserialize(&bytes[..bytes.len() - 1]).is_err());
+}
+#[test]
+fn reads_version_1() {
+    let asm = test_assembly();
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&1_u32.to_le_bytes());
+    bytes.extend(encode(&asm));
+    let read = deserialize(&bytes).expect("Could not read an assembly written using version 1!");
+    assert_eq!(
+        asm.methods().collect::<Vec<_>>(),
+        read.methods().collect::<Vec<_>>()
+    );
+}
+#[test]
+fn reads_legacy_format() {
+    let asm = test_assembly();
+    let legacy = postcard::to_stdvec(&asm).unwrap();
+    let read = deserialize(&legacy).expect("Could not read an assembly in the legacy format!");
+    assert_eq!(
+        asm.methods().collect::<Vec<_>>(),
+        read.methods().collect::<Vec<_>>()
+    );
+}
+#[test]
+fn rejects_newer_versions() {
+    let mut bytes = serialize(&test_assembly());
+    bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        deserialize(&bytes),
+        Err(SerializationError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+    ));
+}
+/// Checks that data survives the kinds of changes the format is meant to be resilient to, by encoding data using one "version" of a type and decoding it using another.
+#[test]
+fn cross_version() {
+    use serde::Deserialize;
+    /// Op enum, as defined by an "old" backend.
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum OldOp {
+        LdcI32(i32),
+        Add,
+        Ret,
+    }
+    /// The same enum after an upgrade: variants got reordered, and a new one was added.
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum NewOp {
+        Ret,
+        Nop,
+        Add,
+        LdcI32(i32),
+    }
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct OldMethod {
+        name: String,
+        ops: Vec<OldOp>,
+    }
+    /// The same struct after an upgrade: a field was added.
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct NewMethod {
+        #[serde(default)]
+        is_static: bool,
+        ops: Vec<NewOp>,
+        name: String,
+    }
+    // Old data read by a new backend.
+    let old = OldMethod {
+        name: "main".into(),
+        ops: vec![OldOp::LdcI32(2), OldOp::LdcI32(3), OldOp::Add, OldOp::Ret],
+    };
+    let new: NewMethod = decode(&encode(&old)).unwrap();
+    assert_eq!(
+        new,
+        NewMethod {
+            is_static: false,
+            ops: vec![NewOp::LdcI32(2), NewOp::LdcI32(3), NewOp::Add, NewOp::Ret],
+            name: "main".into(),
+        }
+    );
+    // New data read by an old backend: the new field is ignored.
+    let new = NewMethod {
+        is_static: true,
+        ops: vec![NewOp::LdcI32(-1), NewOp::Ret],
+        name: "neg".into(),
+    };
+    let old: OldMethod = decode(&encode(&new)).unwrap();
+    assert_eq!(
+        old,
+        OldMethod {
+            name: "neg".into(),
+            ops: vec![OldOp::LdcI32(-1), OldOp::Ret],
+        }
+    );
+    // New data using variants unknown to an old backend is rejected, instead of being misinterpreted.
+    let new = NewMethod {
+        is_static: true,
+        ops: vec![NewOp::Nop, NewOp::Ret],
+        name: "nop".into(),
+    };
+    assert!(decode::<OldMethod>(&encode(&new)).is_err());
+}
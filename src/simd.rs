@@ -0,0 +1,103 @@
+//! SIMD intrinsics, operating on `#[repr(simd)]` types lowered to `System.Runtime.Intrinsics.Vector64<T>`, `Vector128<T>` and `Vector256<T>`.
+//!
+//! | Rust        | .NET                  |
+//! |-------------|-----------------------|
+//! | `simd_add`  | `Vector128.Add`       |
+//! | `simd_sub`  | `Vector128.Subtract`  |
+//! | `simd_mul`  | `Vector128.Multiply`  |
+//! | `simd_eq`   | `Vector128.Equals`    |
+//!
+//! The methods are generic over the element type, and are static methods of the non-generic classes named like the vector types(`Vector64`,
+//! `Vector128` and `Vector256`). `Vector128.Equals` returns a vector of the same type as its arguments, with all bits of equal elements set, while
+//! `simd_eq` returns a vector of integers. So, if the element types differ(eg. when comparing floats), the result is reinterpreted using
+//! `Vector128.As`.
+//!
+//! Only vectors passed to intrinsics, or accessed trough pointers, are supported: fields of `#[repr(simd)]` types can't be accessed directly.
+use crate::{
+    cil::{CILOp, CallSite},
+    function_sig::FnSig,
+    r#type::{DotnetTypeRef, Type},
+};
+/// Returns the static class holding the helper methods for vectors `bits` wide(eg. `System.Runtime.Intrinsics.Vector128`).
+fn helper_class(bits: u64) -> DotnetTypeRef {
+    DotnetTypeRef::new(
+        Some("System.Runtime.Intrinsics"),
+        &format!("System.Runtime.Intrinsics.Vector{bits}"),
+    )
+    .with_valuetype(false)
+}
+/// Returns a call to the generic helper method `name`, taking `argc` vectors `bits` wide, and returning one, instantiated for vectors of `element`.
+fn helper_call(name: &str, bits: u64, argc: usize, element: &Type) -> CILOp {
+    let generic_vector =
+        Type::simd_vector(Type::MethodGenericArg(0), bits).expect("Not a SIMD vector size!");
+    let inputs = vec![generic_vector.clone(); argc];
+    CILOp::Call(
+        CallSite::new(
+            Some(helper_class(bits)),
+            name.into(),
+            FnSig::new(&inputs, &generic_vector),
+            true,
+        )
+        .with_generics(vec![element.clone()])
+        .into(),
+    )
+}
+/// Returns the op reinterpreting the vector on top of the stack, of elements of type `from`, as a vector of elements of type `to`.
+fn reinterpret(bits: u64, from: &Type, to: &Type) -> CILOp {
+    let vector = |idx| {
+        Type::simd_vector(Type::MethodGenericArg(idx), bits).expect("Not a SIMD vector size!")
+    };
+    CILOp::Call(
+        CallSite::new(
+            Some(helper_class(bits)),
+            "As".into(),
+            FnSig::new(&[vector(0)], &vector(1)),
+            true,
+        )
+        .with_generics(vec![from.clone(), to.clone()])
+        .into(),
+    )
+}
+/// Returns the ops calculating the result of the SIMD intrinsic `name`, of type `output`, with the arguments loaded by `args`, of type `vector`.
+/// Returns `None` if `name` is not supported, or `vector` is not a SIMD vector type.
+pub(crate) fn simd_intrinsic(
+    name: &str,
+    args: Vec<Vec<CILOp>>,
+    vector: &Type,
+    output: &Type,
+) -> Option<Vec<CILOp>> {
+    let (bits, element) = vector.simd_info()?;
+    let argc = args.len();
+    let mut ops: Vec<CILOp> = args.into_iter().flatten().collect();
+    match name {
+        "simd_add" => ops.push(helper_call("Add", bits, argc, element)),
+        "simd_sub" => ops.push(helper_call("Subtract", bits, argc, element)),
+        "simd_mul" => ops.push(helper_call("Multiply", bits, argc, element)),
+        "simd_eq" => {
+            ops.push(helper_call("Equals", bits, argc, element));
+            let (_, mask_element) = output.simd_info()?;
+            if mask_element != element {
+                ops.push(reinterpret(bits, element, mask_element));
+            }
+        }
+        _ => return None,
+    }
+    Some(ops)
+}
+#[test]
+fn float_eq_reinterpreted() {
+    let vector = Type::Vector128(Type::F32.into());
+    let mask = Type::Vector128(Type::I32.into());
+    let ops = simd_intrinsic(
+        "simd_eq",
+        vec![vec![CILOp::LDArg(0)], vec![CILOp::LDArg(1)]],
+        &vector,
+        &mask,
+    )
+    .expect("simd_eq is supported!");
+    assert_eq!(ops.len(), 4);
+    let equals = ops[2].to_string();
+    assert!(equals.contains("System.Runtime.Intrinsics.Vector128:: Equals<float32>("));
+    assert!(ops[3].to_string().contains(" As<float32,int32>("));
+    assert!(simd_intrinsic("simd_add", vec![], &Type::I32, &Type::I32).is_none());
+}
@@ -0,0 +1,210 @@
+//! Snapshot testing of the generated IL. The IL of small functions is normalized, and compared against snapshots checked into the repository.
+//! This catches changes to codegen which would otherwise go unnoticed.
+//!
+//! If a snapshot does not exist yet, it is created, and the check fails, so that it can be reviewed. If the codegen changed on purpose,
+//! snapshots can be updated by running the tests with [`BLESS_ENV`] set to `1`.
+#[cfg(test)]
+use std::path::Path;
+/// Environment variable which, when set to `1`, makes snapshot checks overwrite snapshots instead of comparing against them.
+pub const BLESS_ENV: &str = "RCCLR_BLESS";
+/// Replaces the parts of `il` which change between builds with placeholders, so that it can be compared against a snapshot.
+/// Currently, this replaces the hashes of mangled symbols (`h` followed by 16 hex digits) with `h<hash>`, and removes trailing whitespace and empty lines.
+#[must_use]
+pub fn normalize_il(il: &str) -> String {
+    let mut normalized = String::with_capacity(il.len());
+    for line in il.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        normalized.push_str(&strip_symbol_hashes(line));
+        normalized.push('\n');
+    }
+    normalized
+}
+/// Replaces all symbol hashes in `line` with `h<hash>`.
+fn strip_symbol_hashes(line: &str) -> String {
+    const HASH_LEN: usize = 16;
+    let mut res = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(idx) = rest.find("17h") {
+        let hash_start = idx + "17h".len();
+        let is_hash = rest
+            .get(hash_start..hash_start + HASH_LEN)
+            .is_some_and(|hash| hash.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        if is_hash {
+            res.push_str(&rest[..idx]);
+            res.push_str("17h<hash>");
+            rest = &rest[hash_start + HASH_LEN..];
+        } else {
+            res.push_str(&rest[..hash_start]);
+            rest = &rest[hash_start..];
+        }
+    }
+    res.push_str(rest);
+    res
+}
+/// Returns the last path segment of a legacy-mangled symbol(eg. `snap_add` for `_ZN5crate8snap_add17h0123456789abcdefE`), or `None` if `mangled` is not a mangled symbol.
+#[must_use]
+pub fn short_name(mangled: &str) -> Option<&str> {
+    let mut rest = mangled.strip_prefix("_ZN")?;
+    let mut last = None;
+    while let Some(len_end) = rest.find(|c: char| !c.is_ascii_digit()) {
+        if len_end == 0 {
+            break;
+        }
+        let len: usize = rest[..len_end].parse().ok()?;
+        let segment = rest.get(len_end..len_end + len)?;
+        // The hash is the last segment.
+        if segment.starts_with('h') && segment.len() == 17 {
+            break;
+        }
+        last = Some(segment);
+        rest = &rest[len_end + len..];
+    }
+    last
+}
+/// Compares `actual` with the snapshot at `snapshot`. If [`BLESS_ENV`] is set, or the snapshot does not exist, the snapshot is (over)written instead.
+/// Returns a description of the differences, if there are any, or if a new snapshot was created.
+#[cfg(test)]
+fn check_snapshot(snapshot: &Path, actual: &str) -> Result<(), String> {
+    let bless = std::env::var(BLESS_ENV).is_ok_and(|bless| bless == "1");
+    let expected = match std::fs::read_to_string(snapshot) {
+        Ok(expected) if !bless => expected,
+        Ok(_) => {
+            write_snapshot(snapshot, actual);
+            return Ok(());
+        }
+        Err(_) => {
+            write_snapshot(snapshot, actual);
+            if bless {
+                return Ok(());
+            }
+            return Err(format!(
+                "New snapshot {snapshot} created. Review it, and commit it if it is correct.",
+                snapshot = snapshot.display()
+            ));
+        }
+    };
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(format!(
+            "Snapshot {snapshot} does not match(run with {BLESS_ENV}=1 to update it):\n{diff}",
+            snapshot = snapshot.display(),
+            diff = crate::test_support::diff_lines(&expected, actual)
+        ))
+    }
+}
+#[cfg(test)]
+fn write_snapshot(snapshot: &Path, actual: &str) {
+    if let Some(dir) = snapshot.parent() {
+        std::fs::create_dir_all(dir).expect("Could not create the snapshot directory!");
+    }
+    std::fs::write(snapshot, actual).expect("Could not write the snapshot!");
+}
+/// Directory containing the snapshot test cases. Each `name.rs` file is compiled as a library, and the IL of all its functions with names starting with
+/// [`SNAPSHOT_PREFIX`] is compared against the snapshots in `name/`.
+#[cfg(test)]
+const SNAPSHOT_DIR: &str = "./test/snapshots/";
+/// Only functions with names starting with this prefix are snapshotted.
+#[cfg(test)]
+const SNAPSHOT_PREFIX: &str = "snap_";
+#[cfg(test)]
+fn run_snapshot_case(name: &str) -> Vec<String> {
+    let dump_dir = format!("out/{name}");
+    let _ = std::fs::remove_dir_all(Path::new(SNAPSHOT_DIR).join(&dump_dir));
+    let linker = format!(
+        "linker={}",
+        crate::compile_test::RUSTC_CODEGEN_CLR_LINKER.display()
+    );
+    let source = format!("./{name}.rs");
+    let output = format!("./out/lib{name}.rlib");
+    let out = std::process::Command::new("rustc")
+        .current_dir(SNAPSHOT_DIR)
+        .env("RCCLR_DUMP_IL", SNAPSHOT_PREFIX)
+        .env("RCCLR_DUMP_IL_DIR", &dump_dir)
+        .args([
+            "-O",
+            "--crate-type=lib",
+            "-Z",
+            crate::compile_test::backend_path(),
+            "-C",
+            &linker,
+            &source,
+            "-o",
+            &output,
+        ])
+        .output()
+        .expect("failed to execute process");
+    assert!(
+        out.status.success(),
+        "Could not compile snapshot case {name}:\n{stderr}",
+        stderr = String::from_utf8_lossy(&out.stderr)
+    );
+    let mut failures = Vec::new();
+    let dumps = std::fs::read_dir(Path::new(SNAPSHOT_DIR).join(&dump_dir))
+        .expect("No IL was dumped for a snapshot case!");
+    for dump in dumps {
+        let path = dump.expect("Could not read dumped IL!").path();
+        if path.extension().map_or(true, |ext| ext != "il") {
+            continue;
+        }
+        let mangled = path.file_stem().unwrap().to_string_lossy();
+        let Some(function) =
+            short_name(&mangled).filter(|fn_name| fn_name.starts_with(SNAPSHOT_PREFIX))
+        else {
+            continue;
+        };
+        let il = std::fs::read_to_string(&path).expect("Could not read dumped IL!");
+        let snapshot = Path::new(SNAPSHOT_DIR)
+            .join(name)
+            .join(format!("{function}.il"));
+        if let Err(err) = check_snapshot(&snapshot, &normalize_il(&il)) {
+            failures.push(err);
+        }
+    }
+    failures
+}
+#[cfg(test)]
+mod test {
+    #[test]
+    fn normalize_il() {
+        assert_eq!(
+            super::normalize_il(
+                "call void _ZN4core3ptr13drop_in_place17h0123456789abcdefE()  \n\n ret\n"
+            ),
+            "call void _ZN4core3ptr13drop_in_place17h<hash>E()\n ret\n"
+        );
+    }
+    #[test]
+    fn short_name() {
+        assert_eq!(
+            super::short_name("_ZN9snapshots8snap_add17h0123456789abcdefE"),
+            Some("snap_add")
+        );
+        assert_eq!(super::short_name("main"), None);
+    }
+    #[test]
+    fn snapshots() {
+        crate::compile_test::RUSTC_BUILD_STATUS
+            .as_ref()
+            .expect("Could not build rustc!");
+        let mut names: Vec<String> = std::fs::read_dir(super::SNAPSHOT_DIR)
+            .expect("Could not read the snapshot directory!")
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "rs" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_owned())
+            })
+            .collect();
+        names.sort();
+        let failures: Vec<_> = names
+            .iter()
+            .flat_map(|name| super::run_snapshot_case(name))
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
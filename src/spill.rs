@@ -0,0 +1,71 @@
+//! Spill mode: writing lowered methods to the output file during codegen, instead of keeping them in memory.
+//!
+//! Normally, every method of a crate stays in memory until the whole crate is lowered and serialized. When
+//! [`spill_methods`](crate::config::BackendConfig::spill_methods) is set, each method is optimized and written to the output `.bc` file
+//! as a separate section(see [`crate::serialization`]) as soon as its item is lowered. Only the types, statics and the static initializer
+//! stay in memory, and are written as the final section, once codegen finishes. So, the memory usage does not grow with the number of methods.
+//!
+//! Spilled methods are optimized in the context of the codegen unit they were lowered in, so they can only be inlined into methods of the same unit.
+use crate::method::Method;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+/// An output file, methods are spilled to. Shared by all codegen units.
+pub struct MethodSpill {
+    path: PathBuf,
+    file: Mutex<BufWriter<File>>,
+    /// Number of methods spilled so far.
+    spilled: AtomicUsize,
+}
+impl MethodSpill {
+    /// Creates the file at `path`, and writes the header of a serialized assembly to it.
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&crate::serialization::header())?;
+        Ok(Self {
+            path: path.to_owned(),
+            file: Mutex::new(file),
+            spilled: AtomicUsize::new(0),
+        })
+    }
+    /// Writes `method` to the file.
+    pub fn spill(&self, method: &Method) {
+        let section = crate::serialization::section(
+            crate::serialization::SECTION_METHOD,
+            &crate::serialization::encode(method),
+        );
+        self.file
+            .lock()
+            .expect("Method spill poisoned!")
+            .write_all(&section)
+            .expect("Could not spill a method!");
+        self.spilled.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Returns the path of the file methods are spilled to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Writes the assembly holding everything but the spilled methods, finishing the file.
+    pub fn finish(self, asm: &crate::assembly::Assembly) -> std::io::Result<()> {
+        let mut file = self.file.into_inner().expect("Method spill poisoned!");
+        file.write_all(&crate::serialization::section(
+            crate::serialization::SECTION_ASSEMBLY,
+            &crate::serialization::encode(asm),
+        ))?;
+        file.flush()?;
+        if crate::config::config().timings {
+            eprintln!(
+                "Spilled {spilled} methods to {path}.",
+                spilled = self.spilled.load(Ordering::Relaxed),
+                path = self.path.display()
+            );
+        }
+        Ok(())
+    }
+}
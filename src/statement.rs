@@ -21,12 +21,12 @@ pub fn handle_statement<'tcx>(
         StatementKind::Assign(palce_rvalue) => {
             let place = palce_rvalue.as_ref().0;
             let rvalue = &palce_rvalue.as_ref().1;
-            // Skip void assigments. Assigining to or from void type is a NOP.
-            if type_cache.type_from_cache(
-                crate::utilis::monomorphize(&method_instance, place.ty(method, tyctx).ty, tyctx),
-                tyctx,
-                Some(method_instance),
-            ) == crate::r#type::Type::Void
+            // Skip void and ZST assigments. ZSTs are not stored, so assigining to or from them is a NOP.
+            let place_ty =
+                crate::utilis::monomorphize(&method_instance, place.ty(method, tyctx).ty, tyctx);
+            if crate::utilis::is_zst(place_ty, tyctx)
+                || type_cache.type_from_cache(place_ty, tyctx, Some(method_instance))
+                    == crate::r#type::Type::Void
             {
                 return vec![];
             }
@@ -46,7 +46,7 @@ pub fn handle_statement<'tcx>(
                 method_instance,
                 type_cache,
             );
-            if crate::TRACE_STATEMENTS {
+            if crate::codegen_context::trace_statements() {
                 use crate::r#type::Type;
                 rustc_middle::ty::print::with_no_trimmed_paths! {res.extend(CILOp::debug_msg(&format!("{statement:?}")))};
                 let place_ty = type_cache.type_from_cache(
@@ -115,9 +115,40 @@ pub fn handle_statement<'tcx>(
             };
             res
         }
+        StatementKind::SetDiscriminant {
+            place,
+            variant_index,
+        } => {
+            let addr =
+                crate::place::place_adress(place, tyctx, method, method_instance, type_cache);
+            let owner_ty = place.ty(method, tyctx).ty;
+            let owner_ty = crate::utilis::monomorphize(&method_instance, owner_ty, tyctx);
+            crate::discriminant::set_discr(
+                addr,
+                owner_ty,
+                *variant_index,
+                tyctx,
+                method_instance,
+                type_cache,
+            )
+        }
         StatementKind::Intrinsic(non_diverging_intirinsic) => {
             match non_diverging_intirinsic.as_ref() {
-                NonDivergingIntrinsic::Assume(_) => vec![],
+                NonDivergingIntrinsic::Assume(assumption) => {
+                    if crate::config::config().check_assumptions {
+                        let mut ops = crate::operand::handle_operand(
+                            assumption,
+                            tyctx,
+                            method,
+                            method_instance,
+                            type_cache,
+                        );
+                        ops.push(crate::utilis::check_assumption());
+                        ops
+                    } else {
+                        vec![]
+                    }
+                }
                 NonDivergingIntrinsic::CopyNonOverlapping(CopyNonOverlapping {
                     src,
                     dst,
@@ -156,7 +187,7 @@ pub fn handle_statement<'tcx>(
                     res.push(CILOp::SizeOf(pointed));
                     res.push(CILOp::Mul);
                     res.push(CILOp::CpBlk);
-                    if crate::TRACE_STATEMENTS {
+                    if crate::codegen_context::trace_statements() {
                         rustc_middle::ty::print::with_no_trimmed_paths! {res.extend(CILOp::debug_msg(&format!("{statement:?}")))};
                     }
                     res
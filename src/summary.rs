@@ -0,0 +1,215 @@
+//! Machine-readable summary of the structure of an assembly, for use by external tools(eg. dashboards tracking the generated code).
+//!
+//! The summary is serialized as JSON. Its schema is defined by [`AssemblySummary`], and is versioned using [`SUMMARY_SCHEMA_VERSION`]:
+//! fields may be added without changing the version, but removing or changing the meaning of a field bumps it.
+//! All lists are sorted, so summaries of the same assembly are always identical.
+use crate::{
+    assembly::Assembly,
+    assembly_exporter::ilasm_op::{dotnet_type_ref_cli, type_cil},
+    cil::{CILOp, CallSite},
+    method::Method,
+    r#type::TypeDef,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+/// Version of the summary schema.
+pub const SUMMARY_SCHEMA_VERSION: u32 = 1;
+/// Summary of a whole assembly.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AssemblySummary {
+    /// Version of the schema this summary follows. Always [`SUMMARY_SCHEMA_VERSION`] for summaries created by this version of the crate.
+    pub schema_version: u32,
+    /// Name of the entrypoint of the assembly, if it has one.
+    pub entrypoint: Option<String>,
+    /// Types defined within the assembly.
+    pub types: Vec<TypeSummary>,
+    /// Methods defined within the assembly, outside of any type.
+    pub methods: Vec<MethodSummary>,
+    /// Static fields of the assembly.
+    pub statics: Vec<FieldSummary>,
+    /// Calls between methods. Each edge is listed once, no matter how many times the caller calls the callee.
+    pub call_edges: Vec<CallEdge>,
+}
+/// Summary of a type definition.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TypeSummary {
+    pub name: String,
+    /// Number of generic arguments of the type.
+    pub generic_args: u32,
+    /// Does the type use explicit field offsets?
+    pub explicit_layout: bool,
+    pub fields: Vec<FieldSummary>,
+    pub methods: Vec<MethodSummary>,
+    pub inner_types: Vec<TypeSummary>,
+}
+/// Summary of a field or a static.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldSummary {
+    pub name: String,
+    /// Type of the field, as written in CIL.
+    pub tpe: String,
+}
+/// Summary of a method.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MethodSummary {
+    pub name: String,
+    pub is_static: bool,
+    /// Types of the arguments, as written in CIL.
+    pub inputs: Vec<String>,
+    /// Return type, as written in CIL.
+    pub output: String,
+    /// Number of local variables.
+    pub locals: usize,
+    /// Number of ops in the method.
+    pub op_count: usize,
+    /// Number of ops of each kind, keyed by the name of the op(eg. `LdcI32`).
+    pub op_histogram: BTreeMap<String, usize>,
+}
+/// A call from method `caller` to `callee`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CallEdge {
+    pub caller: String,
+    /// The type `caller` belongs to, or `None` if it is defined outside of any type.
+    pub caller_class: Option<String>,
+    pub callee: String,
+    /// The class `callee` belongs to, or `None` if it is defined outside of any class.
+    pub callee_class: Option<String>,
+}
+impl AssemblySummary {
+    /// Summarizes `asm`.
+    #[must_use]
+    pub fn new(asm: &Assembly) -> Self {
+        let mut types: Vec<_> = asm.types().map(TypeSummary::new).collect();
+        types.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut methods: Vec<_> = asm.methods().map(MethodSummary::new).collect();
+        methods.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut statics: Vec<_> = asm
+            .globals()
+            .map(|(name, tpe)| FieldSummary {
+                name: name.to_string(),
+                tpe: type_cil(tpe).into(),
+            })
+            .collect();
+        statics.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut call_edges: Vec<_> = asm
+            .methods()
+            .flat_map(|method| CallEdge::from_method(method, None))
+            .collect();
+        for tpe in asm.types() {
+            CallEdge::add_type_edges(tpe, &mut call_edges);
+        }
+        call_edges.sort();
+        call_edges.dedup();
+        Self {
+            schema_version: SUMMARY_SCHEMA_VERSION,
+            entrypoint: asm.entrypoint().map(|entry| entry.name().into()),
+            types,
+            methods,
+            statics,
+            call_edges,
+        }
+    }
+}
+impl TypeSummary {
+    fn new(tpe: &TypeDef) -> Self {
+        Self {
+            name: tpe.name().into(),
+            generic_args: tpe.gargc(),
+            explicit_layout: tpe.explicit_offsets().is_some(),
+            fields: tpe
+                .fields()
+                .iter()
+                .map(|(name, tpe)| FieldSummary {
+                    name: name.to_string(),
+                    tpe: type_cil(tpe).into(),
+                })
+                .collect(),
+            methods: tpe.methods().map(MethodSummary::new).collect(),
+            inner_types: tpe.inner_types().iter().map(Self::new).collect(),
+        }
+    }
+}
+impl MethodSummary {
+    fn new(method: &Method) -> Self {
+        let mut op_histogram = BTreeMap::new();
+        for op in method.get_ops() {
+            *op_histogram.entry(op_name(op)).or_insert(0) += 1;
+        }
+        Self {
+            name: method.name().into(),
+            is_static: method.is_static(),
+            inputs: method
+                .sig()
+                .inputs()
+                .iter()
+                .map(|input| type_cil(input).into())
+                .collect(),
+            output: type_cil(method.sig().output()).into(),
+            locals: method.locals().len(),
+            op_count: method.get_ops().len(),
+            op_histogram,
+        }
+    }
+}
+impl CallEdge {
+    fn new(caller: &Method, caller_class: Option<&str>, callee: &CallSite) -> Self {
+        Self {
+            caller: caller.name().into(),
+            caller_class: caller_class.map(Into::into),
+            callee: callee.name().into(),
+            callee_class: callee.class().map(dotnet_type_ref_cli),
+        }
+    }
+    /// Returns the calls made by `caller`, which belongs to the type `caller_class`.
+    fn from_method<'a>(
+        caller: &'a Method,
+        caller_class: Option<&'a str>,
+    ) -> impl Iterator<Item = Self> + 'a {
+        caller
+            .get_ops()
+            .iter()
+            .filter_map(CILOp::call)
+            .map(move |callee| Self::new(caller, caller_class, callee))
+    }
+    /// Adds the calls made by the methods of `tpe`, and of the types nested within it, to `edges`.
+    fn add_type_edges(tpe: &TypeDef, edges: &mut Vec<Self>) {
+        edges.extend(
+            tpe.methods()
+                .flat_map(|method| Self::from_method(method, Some(tpe.name()))),
+        );
+        for inner in tpe.inner_types() {
+            Self::add_type_edges(inner, edges);
+        }
+    }
+}
+/// Returns the name of the kind of `op`(the name of its variant, eg. `LdcI32` for `CILOp::LdcI32(5)`).
+pub(crate) fn op_name(op: &CILOp) -> String {
+    let debug = format!("{op:?}");
+    debug
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or_default()
+        .into()
+}
+#[test]
+fn call_edges_include_type_methods() {
+    use crate::{access_modifier::AccessModifer, function_sig::FnSig, r#type::Type};
+    let callee = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[], &Type::I32),
+        "init_value",
+        vec![],
+    );
+    let mut asm = Assembly::empty();
+    let mut tpe = TypeDef::nameonly("Statics");
+    tpe.extend_cctor([CILOp::Call(callee.call_site().into()), CILOp::Pop]);
+    asm.add_typedef(tpe);
+    asm.add_method(callee);
+    let summary = AssemblySummary::new(&asm);
+    assert_eq!(summary.call_edges.len(), 1);
+    let edge = &summary.call_edges[0];
+    assert_eq!(edge.caller, ".cctor");
+    assert_eq!(edge.caller_class.as_deref(), Some("Statics"));
+    assert_eq!(edge.callee, "init_value");
+}
@@ -0,0 +1,127 @@
+//! Marking self-recursive calls in return position with the `tail.` prefix.
+//!
+//! The CLR stack is much smaller than a native one, so recursive Rust code(eg. a recursive descent parser) overflows it easily. A call prefixed with
+//! `tail.` replaces the frame of its caller, so recursion in return position uses constant stack space. Only calls of a method to itself are
+//! marked: they are the common case, and can't be made cheaper by inlining.
+//!
+//! A call is in return position if its result(if any) is returned right away: it is directly followed by a `ret`, or stored in a local, which is
+//! then loaded and returned, possibly after jumps. The frame of the caller is gone after a tail call, so methods taking the address of any of
+//! their locals or arguments, using `localloc`, or having exception handlers are never changed.
+use crate::{assembly::Assembly, cil::CILOp, method::Method};
+/// Checks if tail calls can be made from `method` without leaving dangling pointers to its frame.
+fn can_tail_call(method: &Method) -> bool {
+    method.is_static()
+        && method.handlers().is_empty()
+        && !method.get_ops().iter().any(|op| {
+            matches!(
+                op,
+                CILOp::LDLocA(_) | CILOp::LDArgA(_) | CILOp::LocAlloc | CILOp::LoadAddresOfTMPLocal
+            )
+        })
+}
+/// Checks if the result of the call at `call` in `ops` is returned right away. `returns_value` is false for calls returning nothing.
+fn is_return_position(ops: &[CILOp], call: usize, returns_value: bool) -> bool {
+    let mut pos = call + 1;
+    // The local the result was stored in, and whether it was loaded back.
+    let mut stored = None;
+    let mut loaded = false;
+    // Bounds the number of followed jumps, in case they form a loop.
+    for _ in 0..ops.len() {
+        let Some(op) = ops.get(pos) else {
+            return false;
+        };
+        pos += 1;
+        match op {
+            CILOp::Nop | CILOp::Comment(_) | CILOp::SourceSpan(_) | CILOp::Label(_) => (),
+            CILOp::STLoc(local) if returns_value && stored.is_none() => stored = Some(*local),
+            CILOp::LDLoc(local) if stored == Some(*local) && !loaded => loaded = true,
+            CILOp::GoTo(target) => {
+                let Some(label) = ops.iter().position(|op| *op == CILOp::Label(*target)) else {
+                    return false;
+                };
+                pos = label + 1;
+            }
+            CILOp::Ret => return stored.is_none() || loaded,
+            _ => return false,
+        }
+    }
+    false
+}
+/// Marks the self-recursive calls in return position of `method` with the `tail.` prefix. Returns the number of marked calls.
+pub fn mark_tail_calls(method: &mut Method) -> usize {
+    if !can_tail_call(method) {
+        return 0;
+    }
+    let site = method.call_site();
+    let returns_value = *site.signature().output() != crate::r#type::Type::Void;
+    let ops = method.get_ops();
+    let calls: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(idx, op)| {
+            matches!(op, CILOp::Call(called) if **called == site)
+                && !matches!(idx.checked_sub(1).map(|prev| &ops[prev]), Some(CILOp::Tail))
+                && is_return_position(ops, *idx, returns_value)
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+    if calls.is_empty() {
+        return 0;
+    }
+    let mut new_ops = Vec::with_capacity(ops.len() + calls.len() * 2);
+    for (idx, op) in ops.iter().enumerate() {
+        if calls.contains(&idx) {
+            // A `tail.` call must be followed by a `ret`. The ops after it are left in place, since they may be jumped to.
+            new_ops.extend([CILOp::Tail, op.clone(), CILOp::Ret]);
+        } else {
+            new_ops.push(op.clone());
+        }
+    }
+    method.set_ops(new_ops);
+    calls.len()
+}
+/// Marks the self-recursive calls in return position of all methods of `asm`. Returns the number of marked calls.
+pub fn mark_all_tail_calls(asm: &mut Assembly) -> usize {
+    asm.methods_mut().map(mark_tail_calls).sum()
+}
+#[test]
+fn marks_recursion_in_return_position() {
+    use crate::{access_modifier::AccessModifer, function_sig::FnSig, r#type::Type};
+    let mut method = Method::new(
+        AccessModifer::Public,
+        true,
+        FnSig::new(&[Type::I32], &Type::I32),
+        "countdown",
+        vec![(None, Type::I32)],
+    );
+    let call = CILOp::Call(method.call_site().into());
+    method.set_ops(vec![
+        CILOp::LDArg(0),
+        CILOp::BZero(1),
+        // Not in return position: the result is used.
+        CILOp::LDArg(0),
+        CILOp::LdcI32(1),
+        CILOp::Sub,
+        call.clone(),
+        CILOp::LdcI32(1),
+        CILOp::Add,
+        CILOp::Pop,
+        // In return position, after a jump.
+        CILOp::LDArg(0),
+        CILOp::LdcI32(1),
+        CILOp::Sub,
+        call.clone(),
+        CILOp::STLoc(0),
+        CILOp::GoTo(2),
+        CILOp::Label(1),
+        CILOp::LdcI32(0),
+        CILOp::STLoc(0),
+        CILOp::Label(2),
+        CILOp::LDLoc(0),
+        CILOp::Ret,
+    ]);
+    assert_eq!(mark_tail_calls(&mut method), 1);
+    assert_eq!(method.get_ops()[12..15], [CILOp::Tail, call, CILOp::Ret]);
+    // Already marked calls are not marked again.
+    assert_eq!(mark_tail_calls(&mut method), 0);
+}
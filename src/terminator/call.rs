@@ -1,6 +1,7 @@
 use crate::call_info::CallInfo;
 use crate::cil::FieldDescriptor;
 use crate::interop::AssemblyRef;
+use crate::place::PlaceTy;
 use crate::r#type::Type;
 use crate::utilis::garg_to_string;
 use crate::{
@@ -13,11 +14,17 @@ use crate::{
     utilis::MANAGED_CALL_FN_NAME,
     utilis::MANAGED_CALL_VIRT_FN_NAME,
 };
+use rustc_abi::FieldIdx;
 use rustc_middle::ty::InstanceDef;
 use rustc_middle::{
-    mir::{Body, Operand, Place, SwitchTargets, Terminator, TerminatorKind},
+    mir::{
+        interpret::{GlobalAlloc, Scalar},
+        Body, ConstValue, Operand, Place, Rvalue, StatementKind, SwitchTargets, Terminator,
+        TerminatorKind,
+    },
     ty::{GenericArg, Instance, ParamEnv, Ty, TyCtxt, TyKind},
 };
+use rustc_span::{Span, Symbol};
 fn decode_interop_call<'ctx>(
     function_name: &str,
     prefix: &str,
@@ -103,6 +110,10 @@ fn call_managed<'ctx>(
             signature.clone(),
             is_static,
         )));
+        // Managed code is not guaranteed to return a bool which is exactly 0 or 1.
+        if *signature.output() == crate::r#type::Type::Bool {
+            call.extend(crate::utilis::normalize_bool());
+        }
         if *signature.output() == crate::r#type::Type::Void {
             call
         } else {
@@ -180,12 +191,21 @@ fn callvirt_managed<'ctx>(
                 type_cache,
             ));
         }
+        // Virtual methods can't be called on value types directly: `this` is a managed pointer to the value, which `constrained.` resolves
+        // the method for, without boxing it.
+        if is_valuetype && !is_static {
+            call.push(CILOp::Constrained(Box::new(tpe.clone().into())));
+        }
         call.push(CILOp::CallVirt(CallSite::boxed(
             Some(tpe.clone()),
             managed_fn_name.into(),
             signature.clone(),
             is_static,
         )));
+        // Managed code is not guaranteed to return a bool which is exactly 0 or 1.
+        if *signature.output() == crate::r#type::Type::Bool {
+            call.extend(crate::utilis::normalize_bool());
+        }
         if *signature.output() == crate::r#type::Type::Void {
             call
         } else {
@@ -305,13 +325,127 @@ pub fn call_closure<'tyctx>(
             type_cache,
         ));
     }
+    call.extend(untuple_arg(
+        last_arg,
+        tyctx,
+        body,
+        method_instance,
+        type_cache,
+    ));
+    //panic!("Last arg:{last_arg:?}last_arg_type:{last_arg_type:?}");
+    //assert_eq!(args.len(),signature.inputs().len(),"CALL SIGNATURE ARG COUNT MISMATCH!");
+    let is_void = matches!(sig.output(), crate::r#type::Type::Void);
+    call.push(CILOp::Call(CallSite::boxed(
+        None,
+        function_name.into(),
+        sig,
+        true,
+    )));
+    // Hande
+    if is_void {
+        call
+    } else {
+        crate::place::place_set(destination, tyctx, call, body, method_instance, type_cache)
+    }
+}
+/// Returns the ops calling the trait method `instance` on a trait object, trough slot `idx` of its vtable, and writing the result to
+/// `destination`.
+fn call_virtual<'tyctx>(
+    instance: Instance<'tyctx>,
+    idx: usize,
+    args: &[Operand<'tyctx>],
+    destination: &Place<'tyctx>,
+    body: &'tyctx Body<'tyctx>,
+    tyctx: TyCtxt<'tyctx>,
+    method_instance: Instance<'tyctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    let call_info = CallInfo::sig_from_instance_(instance, tyctx, type_cache)
+        .expect("Could not resolve the signature of a virtual call!");
+    // The signature of a virtual call already takes a thin pointer as `self`.
+    let signature = call_info.sig().clone();
+    let (receiver, other_args) = args
+        .split_first()
+        .expect("Virtual calls must have a receiver!");
+    let receiver_ty = monomorphize(&method_instance, receiver.ty(body, tyctx), tyctx);
+    if receiver_ty.is_trait() {
+        todo!("Can't yet call methods taking trait objects by value({receiver_ty:?})!");
+    }
+    let receiver_type = type_cache.type_from_cache(receiver_ty, tyctx, Some(method_instance));
+    // The receiver(a pointer to a trait object, possibly wrapped in a smart pointer) is stored in a temporary, so that both the data pointer
+    // and the vtable can be read from it.
+    let mut call = vec![CILOp::NewTMPLocal(receiver_type.into())];
+    call.extend(handle_operand(
+        receiver,
+        tyctx,
+        body,
+        method_instance,
+        type_cache,
+    ));
+    call.push(CILOp::SetTMPLocal);
+    call.extend(crate::vtable::dyn_data_ptr(&[CILOp::LoadAddresOfTMPLocal]));
+    match other_args.split_last() {
+        Some((last_arg, other_args)) if call_info.split_last_tuple() => {
+            for arg in other_args {
+                call.extend(handle_operand(
+                    arg,
+                    tyctx,
+                    body,
+                    method_instance,
+                    type_cache,
+                ));
+            }
+            call.extend(untuple_arg(
+                last_arg,
+                tyctx,
+                body,
+                method_instance,
+                type_cache,
+            ));
+        }
+        _ => {
+            for arg in other_args {
+                call.extend(handle_operand(
+                    arg,
+                    tyctx,
+                    body,
+                    method_instance,
+                    type_cache,
+                ));
+            }
+        }
+    }
+    call.extend(crate::vtable::vtable_slot(
+        &[CILOp::LoadAddresOfTMPLocal],
+        idx,
+        tyctx,
+    ));
+    let is_void = matches!(signature.output(), Type::Void);
+    call.push(CILOp::CallI(signature.into()));
+    call.push(CILOp::FreeTMPLocal);
+    if is_void {
+        call
+    } else {
+        crate::place::place_set(destination, tyctx, call, body, method_instance, type_cache)
+    }
+}
+/// Returns the ops loading the elements of the tuple `last_arg` one by one. Used to pass the last argument of functions using the `rust_call`
+/// calling convention, which expect the elements of their last argument as separate arguments.
+fn untuple_arg<'tyctx>(
+    last_arg: &Operand<'tyctx>,
+    tyctx: TyCtxt<'tyctx>,
+    body: &'tyctx Body<'tyctx>,
+    method_instance: Instance<'tyctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    let mut ops = Vec::new();
     let last_arg_type =
         crate::utilis::monomorphize(&method_instance, last_arg.ty(body, tyctx), tyctx);
     match last_arg_type.kind() {
         TyKind::Tuple(elements) => {
             if elements.is_empty() {
             } else {
-                call.extend(crate::operand::handle_operand(
+                ops.extend(crate::operand::handle_operand(
                     last_arg,
                     tyctx,
                     body,
@@ -320,10 +454,10 @@ pub fn call_closure<'tyctx>(
                 ));
                 let tuple_type =
                     type_cache.type_from_cache(last_arg_type, tyctx, Some(method_instance));
-                call.push(CILOp::NewTMPLocal(tuple_type.clone().into()));
-                call.push(CILOp::SetTMPLocal);
+                ops.push(CILOp::NewTMPLocal(tuple_type.clone().into()));
+                ops.push(CILOp::SetTMPLocal);
                 for (index, element) in elements.iter().enumerate() {
-                    call.push(CILOp::LoadAddresOfTMPLocal);
+                    ops.push(CILOp::LoadAddresOfTMPLocal);
                     let element_type =
                         type_cache.type_from_cache(element, tyctx, Some(method_instance));
                     let tuple_element_name = format!("Item{}", index + 1);
@@ -332,30 +466,559 @@ pub fn call_closure<'tyctx>(
                         element_type,
                         tuple_element_name.into(),
                     );
-                    call.push(CILOp::LDField(field_descriptor));
+                    ops.push(CILOp::LDField(field_descriptor));
                 }
-                call.push(CILOp::FreeTMPLocal);
+                ops.push(CILOp::FreeTMPLocal);
                 //todo!("Can't unbox tupels yet!")
             }
         }
         _ => panic!("Can't unbox type {last_arg_type:?}!"),
     }
-    //panic!("Last arg:{last_arg:?}last_arg_type:{last_arg_type:?}");
-    //assert_eq!(args.len(),signature.inputs().len(),"CALL SIGNATURE ARG COUNT MISMATCH!");
-    let is_void = matches!(sig.output(), crate::r#type::Type::Void);
-    call.push(CILOp::Call(CallSite::boxed(
-        None,
-        function_name.into(),
-        sig,
-        true,
-    )));
-    // Hande
+    ops
+}
+/// Name of the foreign function `core::panicking::panic_fmt` uses to call the panic handler.
+const PANIC_IMPL_NAME: &str = "panic_impl";
+/// Name under which the `#[panic_handler]` function is exported.
+const PANIC_HANDLER_NAME: &str = "rust_begin_unwind";
+/// Name of the function a panic runtime exports to start a panic, once the panic handler has run.
+const START_PANIC_NAME: &str = "__rust_start_panic";
+/// Returns the ops storing the message of the panic started by calling `core::panicking::panic_fmt` with `fmt_args`. The message is formatted
+/// by `core::fmt::write`, into a managed writer(see [`crate::panic_fmt`]).
+fn record_panic_message<'ctx>(
+    fmt_args: &Operand<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    body: &'ctx Body<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    // The message of a previous panic(eg. one caught by `catch_unwind`) is discarded.
+    let mut ops = vec![
+        CILOp::LdNull,
+        CILOp::STStaticField(crate::ffi::panic_message_static()),
+    ];
+    ops.extend(crate::fat_ptr::make_fat_ptr(
+        vec![CILOp::LdcI32(0), CILOp::ConvUSize(false)],
+        vec![CILOp::LDStaticField(crate::panic_fmt::writer_vtable())],
+    ));
+    ops.extend(handle_operand(
+        fmt_args,
+        tyctx,
+        body,
+        method_instance,
+        type_cache,
+    ));
+    ops.extend([
+        CILOp::Call(crate::panic_fmt::fmt_write_site(tyctx, type_cache)),
+        CILOp::Pop,
+    ]);
+    ops
+}
+/// Returns the ops loading a reference to a `core::panic::Location` describing `span`. Used for calling `#[track_caller]` functions.
+fn caller_location<'ctx>(
+    span: Span,
+    tyctx: TyCtxt<'ctx>,
+    body: &'ctx Body<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    // Use the location of the outermost macro invocation, so that `panic!` points to user code, and not into `core`.
+    let topmost = span.ctxt().outer_expn().expansion_cause().unwrap_or(span);
+    let caller = tyctx.sess.source_map().lookup_char_pos(topmost.lo());
+    let location = tyctx.const_caller_location((
+        Symbol::intern(&caller.file.name.prefer_remapped().to_string_lossy()),
+        caller.line as u32,
+        caller.col_display as u32 + 1,
+    ));
+    crate::constant::load_const_value(
+        location,
+        tyctx.caller_location_ty(),
+        tyctx,
+        body,
+        method_instance,
+        type_cache,
+    )
+}
+/// Returns the function the function pointer `operand` points to, if it is known at compile time: if the pointer is a constant, or a local
+/// assigned a function(or a function cast to a pointer).
+fn fn_ptr_target<'ctx>(
+    operand: &Operand<'ctx>,
+    body: &Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    method_instance: Instance<'ctx>,
+) -> Option<Instance<'ctx>> {
+    match operand {
+        Operand::Constant(constant) => {
+            let ty = monomorphize(&method_instance, constant.ty(), tyctx);
+            if let TyKind::FnDef(def_id, subst) = ty.kind() {
+                return Instance::resolve(tyctx, ParamEnv::reveal_all(), *def_id, subst)
+                    .ok()
+                    .flatten();
+            }
+            let value = monomorphize(&method_instance, constant.const_, tyctx)
+                .eval(tyctx, ParamEnv::reveal_all(), None)
+                .ok()?;
+            let ConstValue::Scalar(Scalar::Ptr(ptr, _)) = value else {
+                return None;
+            };
+            match tyctx.global_alloc(ptr.into_parts().0.alloc_id()) {
+                GlobalAlloc::Function(instance) => Some(instance),
+                _ => None,
+            }
+        }
+        Operand::Copy(place) | Operand::Move(place) => {
+            let local = place.as_local()?;
+            body.basic_blocks
+                .iter()
+                .flat_map(|block| &block.statements)
+                .find_map(|statement| {
+                    let StatementKind::Assign(assign) = &statement.kind else {
+                        return None;
+                    };
+                    let (target, Rvalue::Use(source) | Rvalue::Cast(_, source, _)) = &**assign
+                    else {
+                        return None;
+                    };
+                    if target.as_local() != Some(local) {
+                        return None;
+                    }
+                    fn_ptr_target(source, body, tyctx, method_instance)
+                })
+        }
+    }
+}
+/// Returns the ops calling the function behind the function pointer `fn_ptr` using `calli`, and writing the result to `destination`.
+pub fn call_fn_ptr<'ctx>(
+    fn_ptr: &Operand<'ctx>,
+    body: &'ctx Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    args: &[Operand<'ctx>],
+    destination: &Place<'ctx>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Vec<CILOp> {
+    let fn_ptr_ty = monomorphize(&method_instance, fn_ptr.ty(body, tyctx), tyctx);
+    let sig = FnSig::sig_from_fn_ptr(fn_ptr_ty, tyctx, type_cache, method_instance);
+    assert_eq!(
+        args.len(),
+        sig.inputs().len(),
+        "Argument count of a call trough {fn_ptr_ty:?} does not match its signature {sig:?}!"
+    );
+    let mut call = Vec::new();
+    for arg in args {
+        call.extend(handle_operand(
+            arg,
+            tyctx,
+            body,
+            method_instance,
+            type_cache,
+        ));
+    }
+    call.extend(handle_operand(
+        fn_ptr,
+        tyctx,
+        body,
+        method_instance,
+        type_cache,
+    ));
+    let is_void = matches!(sig.output(), Type::Void);
+    call.push(CILOp::CallI(sig.into()));
     if is_void {
         call
     } else {
         crate::place::place_set(destination, tyctx, call, body, method_instance, type_cache)
     }
 }
+/// Returns the value of `operand`, if it is an integer constant.
+fn const_uint<'ctx>(
+    operand: &Operand<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    method_instance: Instance<'ctx>,
+) -> Option<u128> {
+    let constant = operand.constant()?;
+    let value = monomorphize(&method_instance, constant.const_, tyctx)
+        .eval(tyctx, ParamEnv::reveal_all(), None)
+        .ok()?;
+    let ConstValue::Scalar(Scalar::Int(int)) = value else {
+        return None;
+    };
+    int.try_to_uint(int.size()).ok()
+}
+/// Handles calls to intrinsics which can't be called like ordinary functions. Returns `None` if the intrinsic needs no special handling.
+fn call_intrinsic<'ctx>(
+    instance: Instance<'ctx>,
+    args: &[Operand<'ctx>],
+    destination: &Place<'ctx>,
+    body: &'ctx Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    method_instance: Instance<'ctx>,
+    fn_span: Span,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Option<Vec<CILOp>> {
+    match tyctx.item_name(instance.def_id()).as_str() {
+        "abort" => Some(CILOp::fail_fast("The Rust program aborted.").into()),
+        "assume" => {
+            if !crate::config::config().check_assumptions {
+                return Some(vec![]);
+            }
+            let mut ops = handle_operand(&args[0], tyctx, body, method_instance, type_cache);
+            ops.push(crate::utilis::check_assumption());
+            Some(ops)
+        }
+        // Used by UB checks in debug builds. At runtime, the last argument(`called_at_rt`) is always called, with the fields of the first argument(a tuple) as its arguments.
+        "const_eval_select" => {
+            let tuple_ty = monomorphize(&method_instance, args[0].ty(body, tyctx), tyctx);
+            let TyKind::Tuple(elements) = tuple_ty.kind() else {
+                panic!("const_eval_select arguments must be a tuple, not {tuple_ty:?}!");
+            };
+            let rt_fn = monomorphize(&method_instance, args[2].ty(body, tyctx), tyctx);
+            let Some(tuple_place) = args[0].place() else {
+                // A constant tuple of arguments(eg. `()`) has no place to read the fields from, so it gets untupled on the stack, and the
+                // runtime function is called directly.
+                let TyKind::FnDef(def_id, subst) = rt_fn.kind() else {
+                    panic!("The runtime function of const_eval_select must be a function item, not {rt_fn:?}!");
+                };
+                let rt_fn = Instance::resolve(tyctx, ParamEnv::reveal_all(), *def_id, subst)
+                    .expect("Could not resolve the runtime function of const_eval_select!")
+                    .expect("Could not resolve the runtime function of const_eval_select!");
+                let sig = FnSig::sig_from_instance_(rt_fn, tyctx, type_cache).expect(
+                    "Could not get the signature of the runtime function of const_eval_select!",
+                );
+                let mut ops = untuple_arg(&args[0], tyctx, body, method_instance, type_cache);
+                // The function is `#[track_caller]`, so it expects the location of the caller as a hidden last argument.
+                if elements.len() < sig.inputs().len() {
+                    ops.extend(caller_location(
+                        fn_span,
+                        tyctx,
+                        body,
+                        method_instance,
+                        type_cache,
+                    ));
+                }
+                let is_void = matches!(sig.output(), Type::Void);
+                ops.push(CILOp::Call(CallSite::boxed(
+                    None,
+                    crate::utilis::function_name(tyctx.symbol_name(rt_fn)),
+                    sig,
+                    true,
+                )));
+                return Some(if is_void {
+                    ops
+                } else {
+                    crate::place::place_set(
+                        destination,
+                        tyctx,
+                        ops,
+                        body,
+                        method_instance,
+                        type_cache,
+                    )
+                });
+            };
+            let rt_args: Vec<_> = elements
+                .iter()
+                .enumerate()
+                .map(|(index, element)| {
+                    Operand::Copy(tyctx.mk_place_field(
+                        tuple_place,
+                        FieldIdx::from_usize(index),
+                        element,
+                    ))
+                })
+                .collect();
+            Some(call(
+                rt_fn,
+                body,
+                tyctx,
+                &rt_args,
+                destination,
+                method_instance,
+                fn_span,
+                type_cache,
+            ))
+        }
+        // Calls `try_fn(data)`, and `catch_fn(data, payload)` if it panics. Used by `catch_unwind`. Panics can't be caught yet, so a panic
+        // propagates as an exception, and `catch_fn` is never called.
+        "try" => {
+            let try_fn = fn_ptr_target(&args[0], body, tyctx, method_instance).expect(
+                "The function passed to the `try` intrinsic must be known at compile time!",
+            );
+            let sig = FnSig::sig_from_instance_(try_fn, tyctx, type_cache)
+                .expect("Could not get the signature of a function passed to `try`!");
+            let mut ops = handle_operand(&args[1], tyctx, body, method_instance, type_cache);
+            ops.extend([
+                CILOp::Call(CallSite::boxed(
+                    None,
+                    crate::utilis::function_name(tyctx.symbol_name(try_fn)),
+                    sig,
+                    true,
+                )),
+                CILOp::LdcI32(0),
+            ]);
+            Some(crate::place::place_set(
+                destination,
+                tyctx,
+                ops,
+                body,
+                method_instance,
+                type_cache,
+            ))
+        }
+        // Sets `count` values of type `T` behind `dst` to `val` bytes. `MaybeUninit::zeroed`(used by `mem::zeroed`) writes a single zeroed value.
+        name @ ("write_bytes" | "volatile_set_memory") => {
+            let ptr_ty = monomorphize(&method_instance, args[0].ty(body, tyctx), tyctx);
+            let TyKind::RawPtr(pointee) = ptr_ty.kind() else {
+                panic!("{name} must operate on a raw pointer, not {ptr_ty:?}!");
+            };
+            let pointee = type_cache.type_from_cache(pointee.ty, tyctx, Some(method_instance));
+            if pointee == Type::Void {
+                return Some(vec![]);
+            }
+            let mut ops = handle_operand(&args[0], tyctx, body, method_instance, type_cache);
+            let zeroes_one = const_uint(&args[1], tyctx, method_instance) == Some(0)
+                && const_uint(&args[2], tyctx, method_instance) == Some(1);
+            if zeroes_one && name == "write_bytes" {
+                ops.push(CILOp::InitObj(pointee.into()));
+                return Some(ops);
+            }
+            ops.extend(handle_operand(
+                &args[1],
+                tyctx,
+                body,
+                method_instance,
+                type_cache,
+            ));
+            ops.extend(handle_operand(
+                &args[2],
+                tyctx,
+                body,
+                method_instance,
+                type_cache,
+            ));
+            ops.extend([
+                CILOp::SizeOf(pointee.into()),
+                CILOp::Mul,
+                CILOp::ConvU32(false),
+            ]);
+            if name == "volatile_set_memory" {
+                ops.push(CILOp::Volatile);
+            }
+            ops.push(CILOp::InitBlk);
+            Some(ops)
+        }
+        // Used by `ptr::read_volatile` and `ptr::write_volatile`. The `unaligned_` variants may access misaligned pointers, so they are also `unaligned.`.
+        name @ ("volatile_load" | "unaligned_volatile_load") => {
+            let ptr_ty = monomorphize(&method_instance, args[0].ty(body, tyctx), tyctx);
+            let TyKind::RawPtr(pointee) = ptr_ty.kind() else {
+                panic!("{name} must operate on a raw pointer, not {ptr_ty:?}!");
+            };
+            let mut ops = handle_operand(&args[0], tyctx, body, method_instance, type_cache);
+            ops.extend(crate::place::volatile_ops(
+                crate::place::deref_op(
+                    PlaceTy::Ty(pointee.ty),
+                    tyctx,
+                    &method_instance,
+                    type_cache,
+                ),
+                name == "unaligned_volatile_load",
+            ));
+            Some(crate::place::place_set(
+                destination,
+                tyctx,
+                ops,
+                body,
+                method_instance,
+                type_cache,
+            ))
+        }
+        name @ ("volatile_store" | "unaligned_volatile_store") => {
+            let ptr_ty = monomorphize(&method_instance, args[0].ty(body, tyctx), tyctx);
+            let TyKind::RawPtr(pointee) = ptr_ty.kind() else {
+                panic!("{name} must operate on a raw pointer, not {ptr_ty:?}!");
+            };
+            let mut ops = handle_operand(&args[0], tyctx, body, method_instance, type_cache);
+            ops.extend(handle_operand(
+                &args[1],
+                tyctx,
+                body,
+                method_instance,
+                type_cache,
+            ));
+            ops.extend(crate::place::volatile_ops(
+                crate::place::ptr_set_op(
+                    PlaceTy::Ty(pointee.ty),
+                    tyctx,
+                    &method_instance,
+                    type_cache,
+                ),
+                name == "unaligned_volatile_store",
+            ));
+            Some(ops)
+        }
+        // `TypeId::of` and `any::type_name`. A `TypeId` is a hash of the Rust type, which must be equal to the one computed during const evaluation,
+        // so it can't be derived from the runtime handle of the .NET type(`CILOp::LDTypeToken`). Both are constants known at compile time.
+        "type_id" | "type_name" => {
+            let value = tyctx
+                .const_eval_instance(ParamEnv::reveal_all(), instance, Some(fn_span))
+                .expect("Could not evaluate a type intrinsic!");
+            let dst_ty = monomorphize(&method_instance, destination.ty(body, tyctx).ty, tyctx);
+            let ops = crate::constant::load_const_value(
+                value,
+                dst_ty,
+                tyctx,
+                body,
+                method_instance,
+                type_cache,
+            );
+            Some(crate::place::place_set(
+                destination,
+                tyctx,
+                ops,
+                body,
+                method_instance,
+                type_cache,
+            ))
+        }
+        // Division known to have no remainder, used by size and capacity computations.
+        "exact_div" => {
+            let ops = crate::binop::binop_unchecked(
+                rustc_middle::mir::BinOp::Div,
+                &args[0],
+                &args[1],
+                tyctx,
+                body,
+                method_instance,
+                type_cache,
+            );
+            Some(crate::place::place_set(
+                destination,
+                tyctx,
+                ops,
+                body,
+                method_instance,
+                type_cache,
+            ))
+        }
+        // Size and alignment of the value behind a(possibly wide) pointer. Used by `mem::size_of_val`, `Layout::for_value` and `Box` deallocation.
+        name @ ("size_of_val" | "min_align_of_val") => {
+            let ptr_ty = monomorphize(&method_instance, args[0].ty(body, tyctx), tyctx);
+            let TyKind::RawPtr(pointee) = ptr_ty.kind() else {
+                panic!("{name} must operate on a raw pointer, not {ptr_ty:?}!");
+            };
+            let ptr = handle_operand(&args[0], tyctx, body, method_instance, type_cache);
+            let ops = crate::fat_ptr::size_or_align_of_val(
+                ptr,
+                pointee.ty,
+                name == "min_align_of_val",
+                tyctx,
+            );
+            Some(crate::place::place_set(
+                destination,
+                tyctx,
+                ops,
+                body,
+                method_instance,
+                type_cache,
+            ))
+        }
+        // Distance between two pointers, in elements. Used by slice iterators, eg. to get the remaining length.
+        "ptr_offset_from" | "ptr_offset_from_unsigned" => {
+            let ptr_ty = monomorphize(&method_instance, args[0].ty(body, tyctx), tyctx);
+            let TyKind::RawPtr(pointee) = ptr_ty.kind() else {
+                panic!("ptr_offset_from must operate on raw pointers, not {ptr_ty:?}!");
+            };
+            let pointee = type_cache.type_from_cache(pointee.ty, tyctx, Some(method_instance));
+            let mut ops = handle_operand(&args[0], tyctx, body, method_instance, type_cache);
+            ops.extend(handle_operand(
+                &args[1],
+                tyctx,
+                body,
+                method_instance,
+                type_cache,
+            ));
+            ops.extend([CILOp::Sub, CILOp::SizeOf(pointee.into()), CILOp::Div]);
+            Some(crate::place::place_set(
+                destination,
+                tyctx,
+                ops,
+                body,
+                method_instance,
+                type_cache,
+            ))
+        }
+        name @ ("ctlz" | "ctlz_nonzero" | "cttz" | "cttz_nonzero" | "ctpop" | "bswap"
+        | "rotate_left" | "rotate_right") => {
+            let int_ty = monomorphize(&method_instance, args[0].ty(body, tyctx), tyctx);
+            let tpe = type_cache.type_from_cache(int_ty, tyctx, Some(method_instance));
+            let args = args
+                .iter()
+                .map(|arg| handle_operand(arg, tyctx, body, method_instance, type_cache))
+                .collect();
+            let ops = crate::bit_ops::bit_intrinsic(name, args, &tpe)?;
+            Some(crate::place::place_set(
+                destination,
+                tyctx,
+                ops,
+                body,
+                method_instance,
+                type_cache,
+            ))
+        }
+        name @ ("simd_add" | "simd_sub" | "simd_mul" | "simd_eq") => {
+            let vector_ty = monomorphize(&method_instance, args[0].ty(body, tyctx), tyctx);
+            let vector = type_cache.type_from_cache(vector_ty, tyctx, Some(method_instance));
+            let dst_ty = monomorphize(&method_instance, destination.ty(body, tyctx).ty, tyctx);
+            let output = type_cache.type_from_cache(dst_ty, tyctx, Some(method_instance));
+            let args = args
+                .iter()
+                .map(|arg| handle_operand(arg, tyctx, body, method_instance, type_cache))
+                .collect();
+            let ops = crate::simd::simd_intrinsic(name, args, &vector, &output)?;
+            Some(crate::place::place_set(
+                destination,
+                tyctx,
+                ops,
+                body,
+                method_instance,
+                type_cache,
+            ))
+        }
+        name if name.starts_with("atomic_") => {
+            // All atomic intrinsics, except fences, take a pointer to the value they operate on as their first argument.
+            let tpe = match args.first() {
+                Some(ptr) => {
+                    let ptr_ty = monomorphize(&method_instance, ptr.ty(body, tyctx), tyctx);
+                    let TyKind::RawPtr(pointee) = ptr_ty.kind() else {
+                        panic!("Atomic intrinsic {name} must operate on a raw pointer, not {ptr_ty:?}!");
+                    };
+                    type_cache.type_from_cache(pointee.ty, tyctx, Some(method_instance))
+                }
+                None => Type::Void,
+            };
+            let mut args: Vec<_> = args
+                .iter()
+                .map(|arg| handle_operand(arg, tyctx, body, method_instance, type_cache))
+                .collect();
+            if let Some(ptr) = args.first_mut() {
+                *ptr = crate::atomic::volatile_statics(std::mem::take(ptr));
+            }
+            let ops = crate::atomic::atomic_intrinsic(name, args, &tpe)?;
+            let dst_ty = monomorphize(&method_instance, destination.ty(body, tyctx).ty, tyctx);
+            if type_cache.type_from_cache(dst_ty, tyctx, Some(method_instance)) == Type::Void {
+                Some(ops)
+            } else {
+                Some(crate::place::place_set(
+                    destination,
+                    tyctx,
+                    ops,
+                    body,
+                    method_instance,
+                    type_cache,
+                ))
+            }
+        }
+        _ => None,
+    }
+}
 /// Calls `fn_type` with `args`, placing the return value in destination.
 pub fn call<'ctx>(
     fn_type: Ty<'ctx>,
@@ -364,6 +1027,7 @@ pub fn call<'ctx>(
     args: &[Operand<'ctx>],
     destination: &Place<'ctx>,
     method_instance: Instance<'ctx>,
+    fn_span: Span,
     type_cache: &mut crate::r#type::TyCache,
 ) -> Vec<CILOp> {
     let fn_type = crate::utilis::monomorphize(&method_instance, fn_type, tyctx);
@@ -388,12 +1052,62 @@ pub fn call<'ctx>(
     } else {
         todo!("Trying to call a type which is not a function definition!");
     };
+    if let InstanceDef::Intrinsic(_) = instance.def {
+        if let Some(ops) = call_intrinsic(
+            instance,
+            args,
+            destination,
+            body,
+            tyctx,
+            method_instance,
+            fn_span,
+            type_cache,
+        ) {
+            return ops;
+        }
+    }
+    if let InstanceDef::Virtual(_, idx) = instance.def {
+        return call_virtual(
+            instance,
+            idx,
+            args,
+            destination,
+            body,
+            tyctx,
+            method_instance,
+            type_cache,
+        );
+    }
     let call_info = CallInfo::sig_from_instance_(instance, tyctx, type_cache)
         .expect("Could not resolve function sig");
 
-    let signature = call_info.sig().clone();
-    let function_name = crate::utilis::function_name(tyctx.symbol_name(instance));
+    let mut signature = call_info.sig().clone();
+    let mut function_name = crate::utilis::function_name(tyctx.symbol_name(instance));
+    // `panic_impl` is a foreign item, implemented by the `#[panic_handler]` function, which is always named `rust_begin_unwind`.
+    if &*function_name == PANIC_IMPL_NAME {
+        function_name = PANIC_HANDLER_NAME.into();
+    }
+    if crate::libc::has_shim(&function_name) {
+        let args = args
+            .iter()
+            .map(|arg| handle_operand(arg, tyctx, body, method_instance, type_cache))
+            .collect();
+        let ops = crate::libc::call_shim(&function_name, args);
+        return crate::place::place_set(destination, tyctx, ops, body, method_instance, type_cache);
+    }
+    // The panic runtime(`panic_abort` or `panic_unwind`) starts a panic using the native unwinder, which does not exist here.
+    // So, the panic is started in a way matching the panic strategy: by aborting, or by throwing an exception.
+    if &*function_name == START_PANIC_NAME {
+        let mut ops = vec![CILOp::Call(crate::ffi::panic_message())];
+        ops.extend(super::panic_with_msg(tyctx));
+        // Never returns, but the place still needs a value.
+        ops.push(CILOp::LdcI32(0));
+        return crate::place::place_set(destination, tyctx, ops, body, method_instance, type_cache);
+    }
     // Checks if function is "magic"
+    if function_name.contains(crate::inline_il::INLINE_IL_FN_NAME) {
+        return crate::inline_il::inline_il(subst_ref, tyctx, fn_span);
+    }
     if function_name.contains(CTOR_FN_NAME) {
         assert!(
             !call_info.split_last_tuple(),
@@ -457,8 +1171,12 @@ pub fn call<'ctx>(
             type_cache,
         );
     }
-
-    let mut call = Vec::new();
+    // `panic_fmt` starts all panics carrying a message, so the message is recorded here, to be thrown once the panic handler has run.
+    let mut call = if tyctx.lang_items().panic_fmt() == Some(instance.def_id()) {
+        record_panic_message(&args[0], tyctx, body, method_instance, type_cache)
+    } else {
+        Vec::new()
+    };
     for arg in args {
         call.extend(crate::operand::handle_operand(
             arg,
@@ -474,13 +1192,30 @@ pub fn call<'ctx>(
         // let arg_len = args.len();
         //assert_eq!(args.len() + 1,signature.inputs().len(),"ERROR: mismatched argument count. \nsignature inputs:{:?} \narguments:{args:?}\narg_len:{arg_len}\n",signature.inputs());
         // assert_eq!(signature.inputs()[signature.inputs().len() - 1],tpe);
-        //FIXME:This assembles a panic location from uninitialized memory. This WILL lead to bugs once unwinding is added. The fields `file`,`col`, and `line` should be set there.
-        call.extend([
-            CILOp::NewTMPLocal(Box::new(tpe)),
-            CILOp::LoadTMPLocal,
-            CILOp::FreeTMPLocal,
-        ]);
-        //panic!("Call with PanicLocation!");
+        // The function is `#[track_caller]`, so it expects the location of the caller as a hidden last argument.
+        assert_eq!(
+            tpe,
+            type_cache.type_from_cache(tyctx.caller_location_ty(), tyctx, Some(method_instance)),
+            "ERROR: mismatched argument count, and the missing argument is not a caller location."
+        );
+        call.extend(caller_location(
+            fn_span,
+            tyctx,
+            body,
+            method_instance,
+            type_cache,
+        ));
+    }
+    // Calls to variadic functions list the types of the additional arguments in the call site signature.
+    if signature.is_vararg() && args.len() > signature.inputs().len() {
+        let varargs = args[signature.inputs().len()..]
+            .iter()
+            .map(|arg| {
+                let ty = crate::utilis::monomorphize(&method_instance, arg.ty(body, tyctx), tyctx);
+                type_cache.type_from_cache(ty, tyctx, Some(method_instance))
+            })
+            .collect();
+        signature = signature.with_varargs(varargs);
     }
     //assert_eq!(args.len(),signature.inputs().len(),"CALL SIGNATURE ARG COUNT MISMATCH!");
     let is_void = matches!(signature.output(), crate::r#type::Type::Void);
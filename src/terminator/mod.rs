@@ -5,7 +5,7 @@ use crate::{
     cil::{CILOp, CallSite},
     function_sig::FnSig,
     operand::handle_operand,
-    r#type::DotnetTypeRef,
+    r#type::{DotnetTypeRef, Type},
     utilis::monomorphize,
     utilis::CTOR_FN_NAME,
     utilis::MANAGED_CALL_FN_NAME,
@@ -14,9 +14,10 @@ use crate::{
 use rustc_middle::ty::InstanceDef;
 use rustc_middle::{
     mir::{Body, Operand, Place, SwitchTargets, Terminator, TerminatorKind},
-    ty::{GenericArg, Instance, ParamEnv, Ty, TyCtxt, TyKind},
+    ty::{GenericArg, Instance, IntTy, ParamEnv, Ty, TyCtxt, TyKind, UintTy},
 };
 mod call;
+mod range;
 
 pub fn handle_terminator<'ctx>(
     terminator: &Terminator<'ctx>,
@@ -34,7 +35,7 @@ pub fn handle_terminator<'ctx>(
             target,
             unwind: _,
             call_source: _,
-            fn_span: _,
+            fn_span,
         } => {
             let mut ops = Vec::new();
             match func {
@@ -45,6 +46,21 @@ pub fn handle_terminator<'ctx>(
                         "fn_ty{fn_ty:?} in call is not a function type!"
                     );
                     let fn_ty = monomorphize(&method_instance, fn_ty, tyctx);
+                    if let Some(range_ops) = range::range_next(
+                        fn_ty,
+                        body,
+                        tyctx,
+                        args,
+                        destination,
+                        *target,
+                        method_instance,
+                        type_cache,
+                    ) {
+                        ops.extend(range_ops);
+                        // `range_next` returns ops only if the call has a target.
+                        ops.push(CILOp::GoTo(target.unwrap().as_u32()));
+                        return ops;
+                    }
                     //let fn_instance = Instance::resolve(tyctx,ParamEnv::reveal_all,fn_ty.did,List::empty());
                     let call_ops = call::call(
                         fn_ty,
@@ -53,11 +69,21 @@ pub fn handle_terminator<'ctx>(
                         args,
                         destination,
                         method_instance,
+                        *fn_span,
                         type_cache,
                     );
                     ops.extend(call_ops);
                 }
-                _ => panic!("called func must be const!"),
+                // Calls trough function pointers.
+                Operand::Copy(_) | Operand::Move(_) => ops.extend(call::call_fn_ptr(
+                    func,
+                    body,
+                    tyctx,
+                    args,
+                    destination,
+                    method_instance,
+                    type_cache,
+                )),
             }
             if let Some(target) = target {
                 ops.push(CILOp::GoTo(target.as_u32()));
@@ -66,10 +92,14 @@ pub fn handle_terminator<'ctx>(
         }
         TerminatorKind::Return => {
             let ret = crate::utilis::monomorphize(&method_instance, method.return_ty(), tyctx);
-            if type_cache.type_from_cache(ret, tyctx, Some(method_instance))
-                == crate::r#type::Type::Void
-            {
+            let ret_tpe = type_cache.type_from_cache(ret, tyctx, Some(method_instance));
+            if ret_tpe == crate::r#type::Type::Void {
                 vec![CILOp::Ret]
+            } else if crate::utilis::is_zst(ret, tyctx) {
+                // The return value is a ZST, so it is not stored in the local 0.
+                let mut ops = crate::utilis::zst_value(ret_tpe);
+                ops.push(CILOp::Ret);
+                ops
             } else {
                 vec![CILOp::LDLoc(0), CILOp::Ret]
             }
@@ -81,27 +111,29 @@ pub fn handle_terminator<'ctx>(
             handle_switch(ty, &discr, targets)
         }
         TerminatorKind::Assert {
-            cond: _,
-            expected: _,
-            msg: _,
+            cond,
+            expected,
+            msg,
             target,
             unwind: _,
         } => {
-            //let mut ops = handle_operand(cond, tyctx, method, method_instance, type_cache);
-            //ops.push(CILOp::LdcI32(i32::from(*expected)));
-            //ops.push(CILOp::BEq(target.as_u32()));
-            //ops.extend(throw_assert_msg(
-            //msg,
-            //tyctx,
-            //method,
-            //method_instance,
-            //type_cache,
-            //));
-            //ops
-            let _ = throw_assert_msg;
-            vec![CILOp::GoTo(target.as_u32())]
+            let mut ops = handle_operand(cond, tyctx, method, method_instance, type_cache);
+            if *expected {
+                ops.push(CILOp::BTrue(target.as_u32()));
+            } else {
+                ops.push(CILOp::BZero(target.as_u32()));
+            }
+            ops.extend(throw_assert_msg(
+                msg,
+                tyctx,
+                method,
+                method_instance,
+                type_cache,
+            ));
+            ops
         }
         TerminatorKind::Goto { target } => vec![CILOp::GoTo((*target).into())],
+        // With `-C panic=abort`, cleanup blocks are never lowered, so this is only reachable when unwinding.
         TerminatorKind::UnwindResume => {
             eprintln!("WARNING: stack unwiniding is not supported yet in rustc_codegen_clr!");
             vec![CILOp::Comment(
@@ -120,6 +152,35 @@ pub fn handle_terminator<'ctx>(
             if let InstanceDef::DropGlue(_, None) = drop_instance.def {
                 //Empty drop, nothing needs to happen.
                 vec![]
+            } else if let InstanceDef::Virtual(_, idx) = drop_instance.def {
+                // Trait objects are dropped by the drop glue from their vtable. The address of the place is a pointer to a trait object.
+                let sig = FnSig::sig_from_instance_(drop_instance, tyctx, type_cache).unwrap();
+                let fat_ptr = type_cache.type_from_cache(
+                    Ty::new_mut_ptr(tyctx, ty),
+                    tyctx,
+                    Some(method_instance),
+                );
+                let mut call = vec![CILOp::NewTMPLocal(fat_ptr.into())];
+                call.extend(crate::place::place_adress(
+                    place,
+                    tyctx,
+                    method,
+                    method_instance,
+                    type_cache,
+                ));
+                call.push(CILOp::SetTMPLocal);
+                call.extend(crate::vtable::dyn_data_ptr(&[CILOp::LoadAddresOfTMPLocal]));
+                call.extend(crate::vtable::vtable_slot(
+                    &[CILOp::LoadAddresOfTMPLocal],
+                    idx,
+                    tyctx,
+                ));
+                call.extend([
+                    CILOp::CallI(sig.into()),
+                    CILOp::FreeTMPLocal,
+                    CILOp::GoTo(target.as_u32()),
+                ]);
+                call
             } else {
                 let sig = FnSig::sig_from_instance_(drop_instance, tyctx, type_cache).unwrap();
 
@@ -134,35 +195,40 @@ pub fn handle_terminator<'ctx>(
             }
         }
         TerminatorKind::Unreachable => {
-            /*
-            let string_type = crate::r#type::Type::DotnetType(Box::new(DotnetTypeRef::new(
-                Some("System.Runtime"),
-                "System.String",
-            )));
-            let exception = DotnetTypeRef::new(Some("System.Runtime"), "System.Exception");
-            let sig = FnSig::new(&[string_type], &crate::r#type::Type::Void);
-            vec![
-                CILOp::LdStr("Undefined behaviour! Unreachable terminator reached!".into()),
-                CILOp::NewObj(CallSite::boxed(Some(exception), ".ctor".into(), sig, false)),
-                CILOp::Throw,
-            ]*/
-            vec![]
-        }
-        TerminatorKind::InlineAsm {
-            template,
-            operands,
-            options,
-            line_spans,
-            destination,
-            unwind,
-        } => {
-            eprintln!("Inline assembly is not yet supported!");
-            CILOp::throw_msg("Inline assembly is not yet supported!").to_vec()
+            CILOp::throw_msg("Undefined behaviour! Unreachable terminator reached!").into()
         }
+        // Reported as an unsupported terminator by `Assembly::terminator_to_ops`, and replaced with a throw.
+        TerminatorKind::InlineAsm { .. } => panic!("inline assembly is not supported"),
 
         _ => todo!("Unhandled terminator kind {kind:?}", kind = terminator.kind),
     }
 }
+/// Returns the ops raising a panic with message `msg`. With `-C panic=abort`, the process is terminated using `System.Environment.FailFast`.
+/// Otherwise, the panic is raised as an exception.
+pub(crate) fn panic_ops(msg: &str, tyctx: TyCtxt) -> Vec<CILOp> {
+    let mut ops = vec![CILOp::LdStr(msg.into())];
+    ops.extend(panic_with_msg(tyctx));
+    ops
+}
+/// Like [`panic_ops`], but the message is the string on top of the stack.
+pub(crate) fn panic_with_msg(tyctx: TyCtxt) -> Vec<CILOp> {
+    if crate::utilis::panic_aborts(tyctx) {
+        let mut ops = vec![CILOp::fail_fast_with_msg()];
+        // `FailFast` never returns, but the method must not seem to fall through, so an (unreachable) `throw` ends the block.
+        ops.extend([CILOp::LdNull, CILOp::Throw]);
+        ops
+    } else {
+        CILOp::throw_with_msg().into()
+    }
+}
+/// A piece of the message of a failed assertion.
+enum MsgPiece<'a, 'ctx> {
+    Text(&'static str),
+    /// The value of an operand, formatted using its `ToString` method.
+    Value(&'a Operand<'ctx>),
+}
+/// Returns the ops raising the panic a failed assertion with message `msg` raises. Like in rustc, the operands of the message(eg. the index and
+/// length of a bounds check) are formatted into it.
 fn throw_assert_msg<'ctx>(
     msg: &rustc_middle::mir::AssertMessage<'ctx>,
     tyctx: TyCtxt<'ctx>,
@@ -170,277 +236,259 @@ fn throw_assert_msg<'ctx>(
     method_instance: Instance<'ctx>,
     type_cache: &mut crate::r#type::TyCache,
 ) -> Vec<CILOp> {
-    use rustc_middle::mir::AssertKind;
-    // Assertion messages cause miscomplations.
-    if true {
-        return vec![CILOp::LdNull, CILOp::Throw];
+    use rustc_middle::mir::{AssertKind, BinOp};
+    use MsgPiece::{Text, Value};
+    let pieces = match msg {
+        AssertKind::BoundsCheck { len, index } => vec![
+            Text("index out of bounds: the len is "),
+            Value(len),
+            Text(" but the index is "),
+            Value(index),
+        ],
+        AssertKind::Overflow(BinOp::Shl, _, rhs) => vec![
+            Text("attempt to shift left by `"),
+            Value(rhs),
+            Text("`, which would overflow"),
+        ],
+        AssertKind::Overflow(BinOp::Shr, _, rhs) => vec![
+            Text("attempt to shift right by `"),
+            Value(rhs),
+            Text("`, which would overflow"),
+        ],
+        AssertKind::Overflow(binop, lhs, rhs) => {
+            let binop = match binop {
+                BinOp::Add => " + ",
+                BinOp::Sub => " - ",
+                BinOp::Mul => " * ",
+                BinOp::Div => " / ",
+                BinOp::Rem => " % ",
+                _ => panic!("{binop:?} can't overflow!"),
+            };
+            vec![
+                Text("attempt to compute `"),
+                Value(lhs),
+                Text(binop),
+                Value(rhs),
+                Text("`, which would overflow"),
+            ]
+        }
+        AssertKind::OverflowNeg(value) => vec![
+            Text("attempt to negate `"),
+            Value(value),
+            Text("`, which would overflow"),
+        ],
+        AssertKind::DivisionByZero(value) => {
+            vec![Text("attempt to divide `"), Value(value), Text("` by zero")]
+        }
+        AssertKind::RemainderByZero(value) => vec![
+            Text("attempt to calculate the remainder of `"),
+            Value(value),
+            Text("` with a divisor of zero"),
+        ],
+        AssertKind::MisalignedPointerDereference { required, found } => vec![
+            Text("misaligned pointer dereference: address must be a multiple of "),
+            Value(required),
+            Text(" but is "),
+            Value(found),
+        ],
+        _ => return panic_ops(msg.description(), tyctx),
     };
-    match msg {
-        AssertKind::BoundsCheck { len, index } => {
-            let mut ops = Vec::with_capacity(8);
-            ops.push(CILOp::LdStr("index out of bounds: the len is ".into()));
-            ops.extend(handle_operand(
-                len,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-            let usize_class = crate::utilis::usize_class();
-            let string_class = crate::utilis::string_class();
-            let string_type = crate::r#type::Type::DotnetType(Box::new(string_class.clone()));
-            let sig = FnSig::new(&[], &string_type);
-            let usize_to_string = CallSite::boxed(Some(usize_class), "ToString".into(), sig, false);
-            ops.push(CILOp::Call(usize_to_string.clone()));
-            ops.push(CILOp::LdStr(" but the index is".into()));
-            ops.extend(handle_operand(
-                index,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-            ops.push(CILOp::Call(usize_to_string.clone()));
-
-            let sig = FnSig::new(
-                &[
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                ],
-                &string_type.clone(),
-            );
-            let out_of_range_exception =
-                DotnetTypeRef::new(Some("System.Runtime"), "System.IndexOutOfRangeException");
-            ops.push(CILOp::Call(CallSite::boxed(
-                Some(string_class),
-                "Concat".into(),
-                sig,
-                true,
-            )));
-            let sig = FnSig::new(&[string_type], &crate::r#type::Type::Void);
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(out_of_range_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
-            ops
+    let string: Type = crate::utilis::string_class().into();
+    let concat = CallSite::boxed(
+        Some(crate::utilis::string_class()),
+        "Concat".into(),
+        FnSig::new(&[string.clone(), string.clone()], &string),
+        true,
+    );
+    let mut ops = Vec::new();
+    for (idx, piece) in pieces.into_iter().enumerate() {
+        match piece {
+            Text(text) => ops.push(CILOp::LdStr(text.into())),
+            Value(operand) => {
+                let ty = monomorphize(&method_instance, operand.ty(method, tyctx), tyctx);
+                let tpe = type_cache.type_from_cache(ty, tyctx, Some(method_instance));
+                ops.extend(handle_operand(
+                    operand,
+                    tyctx,
+                    method,
+                    method_instance,
+                    type_cache,
+                ));
+                ops.extend(CILOp::value_to_string(&tpe));
+            }
         }
-        AssertKind::DivisionByZero(_operand) => {
-            let mut ops = Vec::with_capacity(8);
-
-            let sig = FnSig::new(&[], &crate::r#type::Type::Void);
-            let div_by_zero_exception =
-                DotnetTypeRef::new(Some("System.Runtime"), "System.DivideByZeroException");
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(div_by_zero_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
-            ops
+        if idx != 0 {
+            ops.push(CILOp::Call(concat.clone()));
         }
-        AssertKind::RemainderByZero(_operand) => {
-            let mut ops = Vec::with_capacity(8);
-
-            let sig = FnSig::new(&[], &crate::r#type::Type::Void);
-            let div_by_zero_exception =
-                DotnetTypeRef::new(Some("System.Runtime"), "System.DivideByZeroException");
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(div_by_zero_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
-            ops
+    }
+    ops.extend(panic_with_msg(tyctx));
+    ops
+}
+/// Maximal size of a `switch` jump table.
+const MAX_SWITCH_TABLE: u128 = 256;
+/// Maximal number of tests a switch is lowered to as a chain. Switches with more tests are lowered to a binary search over their values, so
+/// that finding the target takes a logarithmic, and not linear, number of comparisons.
+const MAX_LINEAR_TESTS: usize = 4;
+/// A test of a switch: either a single value, or a dense run of values handled by a jump table.
+enum SwitchTest {
+    /// Jumps to the target if the discriminant has the value(bits).
+    Value(u128, u32),
+    /// Jumps to the targets, if the discriminant is within `first..(first + targets.len())`. `first` is the value on the evaluation stack.
+    Table { first: i64, targets: Vec<u32> },
+}
+fn handle_switch(ty: Ty, discr: &[CILOp], switch: &SwitchTargets) -> Vec<CILOp> {
+    let mut values: Vec<(u128, u32)> = switch
+        .iter()
+        .map(|(value, target)| (value, target.as_u32()))
+        .collect();
+    let otherwise = switch.otherwise().as_u32();
+    // Only an `otherwise` arm, so no comparisons are needed.
+    if values.is_empty() {
+        return vec![CILOp::GoTo(otherwise)];
+    }
+    // 128 bit values are not primitives, so they have to be compared using `op_Equality`.
+    if let TyKind::Int(IntTy::I128) | TyKind::Uint(UintTy::U128) = ty.kind() {
+        let (tpe, class) = match ty.kind() {
+            TyKind::Int(_) => (Type::I128, DotnetTypeRef::int_128()),
+            _ => (Type::U128, DotnetTypeRef::uint_128()),
+        };
+        let eq = CallSite::boxed(
+            Some(class),
+            "op_Equality".into(),
+            FnSig::new(&[tpe.clone(), tpe], &Type::Bool),
+            true,
+        );
+        let mut ops = Vec::new();
+        for (value, target) in values {
+            ops.extend(discr.iter().cloned());
+            ops.extend(switch_value(ty, value));
+            ops.push(CILOp::Call(eq.clone()));
+            ops.push(CILOp::BTrue(target));
         }
-        AssertKind::Overflow(binop, a, b) => {
-            let mut ops = Vec::with_capacity(8);
-            let string_class = crate::utilis::string_class();
-            ops.push(CILOp::LdStr(
-                format!("attempt to {binop:?} with overflow lhs:").into(),
-            ));
-            ops.extend(handle_operand(
-                a,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-            let usize_class = crate::utilis::usize_class();
-            let string_type = crate::r#type::Type::DotnetType(Box::new(string_class.clone()));
-            let sig = FnSig::new(&[], &string_type);
-            let usize_to_string = CallSite::boxed(Some(usize_class), "ToString".into(), sig, false);
-            ops.push(CILOp::Call(usize_to_string.clone()));
-            ops.push(CILOp::LdStr("rhs:".into()));
-            ops.extend(handle_operand(
-                b,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-            ops.push(CILOp::Call(usize_to_string.clone()));
-
-            let sig = FnSig::new(
-                &[
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                ],
-                &string_type.clone(),
-            );
-            ops.push(CILOp::Call(CallSite::boxed(
-                Some(string_class),
-                "Concat".into(),
-                sig,
-                true,
-            )));
-            let sig = FnSig::new(&[string_type], &crate::r#type::Type::Void);
-            let ovefow_exception =
-                DotnetTypeRef::new(Some("System.Runtime"), "System.ArithmeticException");
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(ovefow_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
-            ops
+        ops.push(CILOp::GoTo(otherwise));
+        return ops;
+    }
+    // Values which fit in an int32 can use jump tables.
+    let is_small = match ty.kind() {
+        TyKind::Int(int) => matches!(int, IntTy::I8 | IntTy::I16 | IntTy::I32),
+        TyKind::Uint(uint) => matches!(uint, UintTy::U8 | UintTy::U16 | UintTy::U32),
+        TyKind::Bool | TyKind::Char => true,
+        _ => false,
+    };
+    // Sort by the value on the evaluation stack, so that dense ranges of values are next to each other, and values can be binary searched.
+    values.sort_by_key(|(value, _)| switch_key(ty, *value));
+    let mut tests = Vec::new();
+    let mut start = 0;
+    while start < values.len() {
+        // Find the longest run of values dense enough to be worth a jump table.
+        let first = switch_key(ty, values[start].0);
+        let mut end = start + 1;
+        while is_small && end < values.len() {
+            let span = (switch_key(ty, values[end].0) - first) as u128 + 1;
+            if span > MAX_SWITCH_TABLE || span > 2 * (end - start + 1) as u128 {
+                break;
+            }
+            end += 1;
         }
-        AssertKind::MisalignedPointerDereference {
-            required: _,
-            found: _,
-        } => {
-            /*
-            let mut ops = Vec::with_capacity(8);
-            let string_class = crate::utilis::string_class();
-            ops.push(CILOp::LdStr(
-                "Missaligned pointer dereference. required: "
-                    .to_string()
-                    .into(),
-            ));
-            ops.extend(handle_operand(
-                required,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-            let usize_class = crate::utilis::usize_class();
-            let string_type = crate::r#type::Type::DotnetType(string_class.clone().into());
-            let sig = FnSig::new(&[], &string_type);
-            let usize_to_string = CallSite::boxed(Some(usize_class), "ToString".into(), sig, false);
-            ops.push(CILOp::Call(usize_to_string.clone()));
-            ops.push(CILOp::LdStr(" found: ".into()));
-            ops.extend(handle_operand(
-                found,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-            ops.push(CILOp::Call(usize_to_string.clone()));
-
-            let sig = FnSig::new(
-                &[
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                ],
-                &string_type.clone(),
+        if end - start >= 3 {
+            let last = switch_key(ty, values[end - 1].0);
+            let mut targets = vec![otherwise; (last - first) as usize + 1];
+            for (value, target) in &values[start..end] {
+                targets[(switch_key(ty, *value) - first) as usize] = *target;
+            }
+            tests.push(SwitchTest::Table {
+                first: first as i64,
+                targets,
+            });
+        } else {
+            tests.extend(
+                values[start..end]
+                    .iter()
+                    .map(|(value, target)| SwitchTest::Value(*value, *target)),
             );
-            ops.push(CILOp::Call(CallSite::boxed(
-                Some(string_class),
-                "Concat".into(),
-                sig,
-                true,
-            )));
-            let sig = FnSig::new(&[string_type], &crate::r#type::Type::Void);
-            let ovefow_exception = DotnetTypeRef::new(Some("System.Runtime"), "System.Exception");
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(ovefow_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
-            ops*/
-            vec![]
         }
-        AssertKind::OverflowNeg(value) => {
-            let mut ops = Vec::with_capacity(8);
-            let string_class = crate::utilis::string_class();
-            ops.push(CILOp::LdStr(
-                "attempt to neg with overflow value:".to_string().into(),
-            ));
-            ops.extend(handle_operand(
-                value,
-                tyctx,
-                method,
-                method_instance,
-                type_cache,
-            ));
-            let usize_class = crate::utilis::usize_class();
-            let string_type = crate::r#type::Type::DotnetType(Box::new(string_class.clone()));
-            let sig = FnSig::new(&[], &string_type);
-            let usize_to_string = CallSite::boxed(Some(usize_class), "ToString".into(), sig, false);
-            ops.push(CILOp::Call(usize_to_string.clone()));
-            ops.push(CILOp::LdStr("rhs:".into()));
-
-            let sig = FnSig::new(
-                &[
-                    string_type.clone(),
-                    string_type.clone(),
-                    string_type.clone(),
-                ],
-                &string_type.clone(),
-            );
-            ops.push(CILOp::Call(CallSite::boxed(
-                Some(string_class),
-                "Concat".into(),
-                sig,
-                true,
-            )));
-            let sig = FnSig::new(&[string_type], &crate::r#type::Type::Void);
-            let ovefow_exception =
-                DotnetTypeRef::new(Some("System.Runtime"), "System.ArithmeticException");
-            ops.push(CILOp::NewObj(CallSite::boxed(
-                Some(ovefow_exception),
-                ".ctor".into(),
-                sig,
-                false,
-            )));
-            ops.push(CILOp::Throw);
-            ops
+        start = end;
+    }
+    let mut ops = Vec::new();
+    switch_search(ty, discr, &tests, otherwise, &mut ops);
+    ops
+}
+/// Appends to `ops` the ops jumping to the target of the test the discriminant matches, or to `otherwise` if it matches none. `tests` must be
+/// sorted. If there are more than [`MAX_LINEAR_TESTS`] of them, the discriminant is compared with the middle test, and only the half it may
+/// match is searched further.
+fn switch_search(
+    ty: Ty,
+    discr: &[CILOp],
+    tests: &[SwitchTest],
+    otherwise: u32,
+    ops: &mut Vec<CILOp>,
+) {
+    if tests.len() <= MAX_LINEAR_TESTS {
+        for test in tests {
+            ops.extend(discr.iter().cloned());
+            match test {
+                SwitchTest::Value(value, target) => {
+                    ops.extend(switch_value(ty, *value));
+                    ops.push(CILOp::BEq(*target));
+                }
+                // `switch` falls trough if the value is outside of the table.
+                SwitchTest::Table { first, targets } => {
+                    ops.push(CILOp::LdcI32(*first as i32));
+                    ops.push(CILOp::Sub);
+                    ops.push(CILOp::Switch(targets.clone().into()));
+                }
+            }
         }
-        _ => todo!("unsuported assertion message:{msg:?}"),
+        ops.push(CILOp::GoTo(otherwise));
+        return;
+    }
+    let (lower, upper) = tests.split_at(tests.len() / 2);
+    let lower_label = crate::codegen_context::fresh_label();
+    ops.extend(discr.iter().cloned());
+    match &upper[0] {
+        SwitchTest::Value(value, _) => ops.extend(switch_value(ty, *value)),
+        SwitchTest::Table { first, .. } => ops.push(CILOp::LdcI32(*first as i32)),
     }
+    ops.push(switch_less_than(ty, lower_label));
+    switch_search(ty, discr, upper, otherwise, ops);
+    ops.push(CILOp::Label(lower_label));
+    switch_search(ty, discr, lower, otherwise, ops);
 }
-fn handle_switch(ty: Ty, discr: &[CILOp], switch: &SwitchTargets) -> Vec<CILOp> {
-    let mut ops = Vec::new();
-    for (value, target) in switch.iter() {
-        ops.extend(discr.iter().cloned());
-        ops.extend(match ty.kind() {
-            TyKind::Int(int) => crate::constant::load_const_int(value, int),
-            TyKind::Uint(uint) => crate::constant::load_const_uint(value, uint),
-            TyKind::Bool => vec![CILOp::LdcI32(
-                u8::try_from(value)
-                    .expect("Bool value outside of range 0-255. Should be either 0 OR 1.")
-                    as i32,
-            )],
-            _ => todo!("Unsuported switch discriminant type {ty:?}"),
-        });
-        //ops.push(CILOp::LdcI64(value as i64));
-        ops.push(CILOp::BEq(target.into()));
+/// Returns the key switch values are sorted by: the value on the evaluation stack, ordered like [`switch_less_than`] orders them.
+fn switch_key(ty: Ty, value: u128) -> i128 {
+    match ty.kind() {
+        TyKind::Uint(UintTy::U64 | UintTy::Usize) => i128::from(value as u64),
+        _ => i128::from(stack_value(ty, value)),
+    }
+}
+/// Returns the op jumping to `target` if the discriminant of type `ty` is lower than the value on top of the stack.
+fn switch_less_than(ty: Ty, target: u32) -> CILOp {
+    match ty.kind() {
+        TyKind::Uint(UintTy::U64 | UintTy::Usize) => CILOp::BLtUn(target),
+        _ => CILOp::BLt(target),
+    }
+}
+/// Returns the value a switch discriminant of type `ty` with bits `value` has, once it is loaded on the evaluation stack.
+fn stack_value(ty: Ty, value: u128) -> i64 {
+    match ty.kind() {
+        TyKind::Int(IntTy::I8) => i64::from(value as u8 as i8),
+        TyKind::Int(IntTy::I16) => i64::from(value as u16 as i16),
+        TyKind::Int(IntTy::I32) => i64::from(value as u32 as i32),
+        // Unsigned values are treated as int32 on the evaluation stack, so u32 values above `i32::MAX` are negative.
+        TyKind::Uint(UintTy::U32) | TyKind::Char => i64::from(value as u32 as i32),
+        _ => value as i64,
+    }
+}
+/// Returns the ops loading the switch discriminant value `value` of type `ty`.
+fn switch_value(ty: Ty, value: u128) -> Vec<CILOp> {
+    match ty.kind() {
+        TyKind::Int(int) => crate::constant::load_const_int(value, int),
+        TyKind::Uint(uint) => crate::constant::load_const_uint(value, uint),
+        TyKind::Bool => vec![CILOp::LdcI32(
+            u8::try_from(value).expect("Bool value outside of range 0-255. Should be either 0 OR 1.")
+                as i32,
+        )],
+        TyKind::Char => vec![CILOp::LdcI32(value as u32 as i32)],
+        _ => todo!("Unsuported switch discriminant type {ty:?}"),
     }
-    ops.push(CILOp::GoTo(switch.otherwise().into()));
-    ops
 }
@@ -0,0 +1,149 @@
+//! Specialization of `Range` iteration into simple counted loops.
+//!
+//! `for i in start..end` calls `<Range<A> as Iterator>::next` on every iteration, which goes through `Step`, `mem::replace` and a few more
+//! layers of generic helpers. Mono's interpreter does not inline them, so such loops are very slow. At optimization level 2 or higher(see
+//! [`level_rank`](crate::opt::peephole::level_rank)), calls to `next` of a range of integers are replaced by inline ops, doing the same as the
+//! original function:
+//! ```text
+//! if self.start < self.end { let n = self.start; self.start = n + 1; Some(n) } else { None }
+//! ```
+//! Together with the back-edge branch of the loop, this gives a plain counter loop. Other adapters are not specialized: `StepBy<Range<A>>` keeps
+//! its state in a form specific to the version of `core`(the remaining number of steps, not the end of the range), so it calls the generic `next`.
+use crate::cil::CILOp;
+use rustc_index::IndexVec;
+use rustc_middle::{
+    mir::{interpret::Scalar, AggregateKind, BasicBlock, BinOp, Body, Operand, Place},
+    ty::{Instance, InstanceDef, ParamEnv, Ty, TyCtxt, TyKind},
+};
+use rustc_span::DUMMY_SP;
+use rustc_target::abi::{FieldIdx, VariantIdx};
+/// Returns the integer type `A`, if `instance` is `<Range<A> as Iterator>::next`, and `A` is an integer.
+fn range_next_elem<'ctx>(
+    instance: Instance<'ctx>,
+    range: Ty<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+) -> Option<Ty<'ctx>> {
+    let InstanceDef::Item(def_id) = instance.def else {
+        return None;
+    };
+    if tyctx.item_name(def_id).as_str() != "next" {
+        return None;
+    }
+    let iterator = tyctx.lang_items().iterator_trait()?;
+    let impl_id = tyctx.impl_of_method(def_id)?;
+    if tyctx.trait_id_of_impl(impl_id) != Some(iterator) {
+        return None;
+    }
+    let TyKind::Adt(adt, subst) = range.kind() else {
+        return None;
+    };
+    if Some(adt.did()) != tyctx.lang_items().range_struct() {
+        return None;
+    }
+    let elem = subst.type_at(0);
+    matches!(elem.kind(), TyKind::Int(_) | TyKind::Uint(_)).then_some(elem)
+}
+/// Returns the ops replacing the call to function `fn_type` with `args`, if it is a call to `next` of a range of integers, which can be specialized.
+/// The ops jump to `target` if the range yielded a value, and fall through otherwise. Both paths leave the result in `destination`.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn range_next<'ctx>(
+    fn_type: Ty<'ctx>,
+    body: &'ctx Body<'ctx>,
+    tyctx: TyCtxt<'ctx>,
+    args: &[Operand<'ctx>],
+    destination: &Place<'ctx>,
+    target: Option<BasicBlock>,
+    method_instance: Instance<'ctx>,
+    type_cache: &mut crate::r#type::TyCache,
+) -> Option<Vec<CILOp>> {
+    if crate::opt::peephole::level_rank(tyctx.sess.opts.optimize) < 2 {
+        return None;
+    }
+    let target = target?;
+    let TyKind::FnDef(def_id, subst) = fn_type.kind() else {
+        return None;
+    };
+    let instance = Instance::resolve(tyctx, ParamEnv::reveal_all(), *def_id, subst).ok()??;
+    // `next` takes `&mut Range<A>`.
+    let [Operand::Copy(range_ref) | Operand::Move(range_ref)] = args else {
+        return None;
+    };
+    let range_ref_ty =
+        crate::utilis::monomorphize(&method_instance, range_ref.ty(body, tyctx).ty, tyctx);
+    let TyKind::Ref(_, range, _) = range_ref_ty.kind() else {
+        return None;
+    };
+    let elem = range_next_elem(instance, *range, tyctx)?;
+    let none_label = crate::codegen_context::fresh_label();
+    let range_place = tyctx.mk_place_deref(*range_ref);
+    let start_place = tyctx.mk_place_field(range_place, FieldIdx::from_u32(0), elem);
+    let start = Operand::Copy(start_place);
+    let end = Operand::Copy(tyctx.mk_place_field(range_place, FieldIdx::from_u32(1), elem));
+    let size = tyctx
+        .layout_of(ParamEnv::reveal_all().and(elem))
+        .expect("Could not get the layout of a range element!")
+        .size;
+    let one = Operand::const_from_scalar(tyctx, elem, Scalar::from_uint(1_u128, size), DUMMY_SP);
+    let option = tyctx
+        .lang_items()
+        .option_type()
+        .expect("`Option` is missing!");
+    let option_of = |variant: u32| {
+        AggregateKind::Adt(
+            option,
+            VariantIdx::from_u32(variant),
+            tyctx.mk_args(&[elem.into()]),
+            None,
+            None,
+        )
+    };
+    let mut ops = crate::binop::binop_unchecked(
+        BinOp::Lt,
+        &start,
+        &end,
+        tyctx,
+        body,
+        method_instance,
+        type_cache,
+    );
+    ops.push(CILOp::BZero(none_label));
+    // `Some(self.start)` is written before `self.start` is incremented, so no temporary is needed.
+    ops.extend(crate::aggregate::handle_aggregate(
+        tyctx,
+        destination,
+        body,
+        &option_of(1),
+        &IndexVec::from_raw(vec![start.clone()]),
+        method_instance,
+        type_cache,
+    ));
+    let incremented = crate::binop::binop_unchecked(
+        BinOp::Add,
+        &start,
+        &one,
+        tyctx,
+        body,
+        method_instance,
+        type_cache,
+    );
+    ops.extend(crate::place::place_set(
+        &start_place,
+        tyctx,
+        incremented,
+        body,
+        method_instance,
+        type_cache,
+    ));
+    ops.push(CILOp::GoTo(target.as_u32()));
+    ops.push(CILOp::Label(none_label));
+    ops.extend(crate::aggregate::handle_aggregate(
+        tyctx,
+        destination,
+        body,
+        &option_of(0),
+        &IndexVec::new(),
+        method_instance,
+        type_cache,
+    ));
+    Some(ops)
+}
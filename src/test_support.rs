@@ -0,0 +1,205 @@
+//! Directory-driven test runner. Every `name.rs` file in [`CASES_DIR`] is a test case. The runner compiles it with this backend, runs the produced
+//! assembly, and compares the results with the expectations stored next to the source:
+//! * `name.stdout` - the expected stdout of the program. Required.
+//! * `name.exit_code` - the expected exit code of the program, or `failure` if it must exit unsuccessfully(eg. because of an unhandled
+//!   exception, on which `dotnet` aborts, and `mono` exits with code 1). Optional, defaults to 0.
+//! * `name.stderr` - text the stderr of the program must contain. Optional.
+//!
+//! The runtime used to execute the test cases can be selected using the [`TEST_RUNTIME_ENV`] environment variable(`dotnet` or `mono`).
+//! If it is not set, `dotnet` is used if it is present, and `mono` otherwise.
+//! To add a new test case, add its source and expected output to [`CASES_DIR`]. Running `cargo test cases` runs all of them.
+#![cfg(test)]
+use crate::{compile_test::compile_checked, differential::ProgramOutput};
+use std::path::Path;
+/// Directory containing the test cases.
+pub(crate) const CASES_DIR: &str = "./test/cases/";
+/// Environment variable selecting the runtime used to run test programs.
+pub(crate) const TEST_RUNTIME_ENV: &str = "RCCLR_TEST_RUNTIME";
+/// A .NET runtime able to execute test programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TestRuntime {
+    Dotnet,
+    Mono,
+}
+impl TestRuntime {
+    /// Returns the runtime selected by [`TEST_RUNTIME_ENV`], or the first runtime present on this system.
+    pub(crate) fn from_env() -> Self {
+        match std::env::var(TEST_RUNTIME_ENV).as_deref() {
+            Ok("dotnet") => Self::Dotnet,
+            Ok("mono") => Self::Mono,
+            Ok(other) => panic!("Unknown test runtime {other:?}. Expected `dotnet` or `mono`."),
+            Err(_) if *crate::compile_test::IS_DOTNET_PRESENT => Self::Dotnet,
+            Err(_) if *crate::compile_test::IS_MONO_PRESENT => Self::Mono,
+            Err(_) => panic!("You must have either mono or dotnet runtime installed to run tests."),
+        }
+    }
+    /// Runs the assembly `{exec_path}.exe`, with `test_dir` as the working directory.
+    pub(crate) fn run(self, exec_path: &str, test_dir: &str) -> std::process::Output {
+        let runtime = match self {
+            Self::Dotnet => {
+                let config_path = format!("{test_dir}/{exec_path}.runtimeconfig.json");
+                std::fs::write(config_path, crate::compile_test::RUNTIME_CONFIG.as_bytes())
+                    .expect("Could not write runtime config");
+                "dotnet"
+            }
+            Self::Mono => "mono",
+        };
+        std::process::Command::new(runtime)
+            .current_dir(test_dir)
+            .arg(format!("{exec_path}.exe"))
+            .output()
+            .expect("failed to run test assebmly!")
+    }
+}
+/// A single test case: a program and its expected behaviour.
+struct TestCase {
+    name: String,
+    expected: ProgramOutput,
+    /// Text the stderr of the program must contain.
+    stderr: Option<String>,
+}
+impl TestCase {
+    /// Loads the expectations of the test case `name` from `dir`.
+    fn load(dir: &Path, name: &str) -> Self {
+        let stdout = std::fs::read_to_string(dir.join(format!("{name}.stdout")))
+            .unwrap_or_else(|_| panic!("Test case {name} has no `{name}.stdout` file!"));
+        // `None` stands for any unsuccessful exit.
+        let exit_code = match std::fs::read_to_string(dir.join(format!("{name}.exit_code"))) {
+            Ok(code) if code.trim() == "failure" => None,
+            Ok(code) => Some(
+                code.trim()
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid exit code of test case {name}: {code:?}")),
+            ),
+            Err(_) => Some(0),
+        };
+        Self {
+            name: name.into(),
+            expected: ProgramOutput { stdout, exit_code },
+            stderr: std::fs::read_to_string(dir.join(format!("{name}.stderr"))).ok(),
+        }
+    }
+    /// Builds and runs the test case, returning a description of the differences between the expected and actual results, if there are any.
+    fn run(&self, optimize: bool, runtime: TestRuntime) -> Option<String> {
+        let name = &self.name;
+        let mode = if optimize { "release" } else { "debug" };
+        let exec_name = format!("case_{mode}_{name}");
+        let linker = format!(
+            "linker={}",
+            crate::compile_test::RUSTC_CODEGEN_CLR_LINKER.display()
+        );
+        let source = format!("./{name}.rs");
+        let output = format!("./{exec_name}.exe");
+        let mut args = if optimize { vec!["-O"] } else { vec![] };
+        args.extend([
+            "-Z",
+            crate::compile_test::backend_path(),
+            "-C",
+            linker.as_str(),
+            source.as_str(),
+            "-o",
+            output.as_str(),
+        ]);
+        compile_checked(CASES_DIR, &args);
+        let out = runtime.run(&format!("../{exec_name}"), CASES_DIR);
+        let stderr = String::from_utf8_lossy(&out.stderr).into_owned();
+        let actual: ProgramOutput = out.into();
+        let exit_ok = match self.expected.exit_code {
+            Some(code) => actual.exit_code == Some(code),
+            None => actual.exit_code != Some(0),
+        };
+        let stderr_ok = self
+            .stderr
+            .as_ref()
+            .map_or(true, |expected| stderr.contains(expected.trim()));
+        if exit_ok && stderr_ok && actual.stdout == self.expected.stdout {
+            return None;
+        }
+        let mut report = format!("{name}({mode}, {runtime:?}):\n");
+        if !exit_ok {
+            report.push_str(&format!(
+                "  exit code: expected {expected:?}, got {actual:?}\n",
+                expected = self.expected.exit_code,
+                actual = actual.exit_code
+            ));
+        }
+        if !stderr_ok {
+            report.push_str(&format!(
+                "  stderr: expected it to contain {expected:?}, got {stderr:?}\n",
+                expected = self.stderr.as_deref().unwrap_or_default().trim()
+            ));
+        }
+        if actual.stdout != self.expected.stdout {
+            report.push_str(&diff_lines(&self.expected.stdout, &actual.stdout));
+        }
+        Some(report)
+    }
+}
+/// Returns a line-by-line diff of `expected` and `actual`. Lines only in `expected` are prefixed with `-`, and lines only in `actual` with `+`.
+pub(crate) fn diff_lines(expected: &str, actual: &str) -> String {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+    let mut diff = String::new();
+    for idx in 0..expected.len().max(actual.len()) {
+        match (expected.get(idx), actual.get(idx)) {
+            (Some(expected), Some(actual)) if expected == actual => {
+                diff.push_str(&format!("   {expected}\n"));
+            }
+            (expected, actual) => {
+                if let Some(expected) = expected {
+                    diff.push_str(&format!("  -{expected}\n"));
+                }
+                if let Some(actual) = actual {
+                    diff.push_str(&format!("  +{actual}\n"));
+                }
+            }
+        }
+    }
+    diff
+}
+/// Runs all test cases in [`CASES_DIR`], panicking with a report of all failures.
+fn run_cases(optimize: bool) {
+    crate::compile_test::RUSTC_BUILD_STATUS
+        .as_ref()
+        .expect("Could not build rustc!");
+    let runtime = TestRuntime::from_env();
+    let dir = Path::new(CASES_DIR);
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .expect("Could not read the test case directory!")
+        .filter_map(|entry| {
+            let path = entry.expect("Could not read a test case!").path();
+            if path.extension()? != "rs" {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_owned())
+        })
+        .collect();
+    names.sort();
+    let failures: Vec<_> = names
+        .iter()
+        .filter_map(|name| TestCase::load(dir, name).run(optimize, runtime))
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{count} out of {total} test cases failed:\n{failures}",
+        count = failures.len(),
+        total = names.len(),
+        failures = failures.join("\n")
+    );
+}
+mod cases {
+    /// Ensures no two compilations of the same test case run at the same time.
+    static COMPILE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    #[test]
+    fn release() {
+        let lock = COMPILE_LOCK.lock();
+        super::run_cases(true);
+        drop(lock);
+    }
+    #[test]
+    fn debug() {
+        let lock = COMPILE_LOCK.lock();
+        super::run_cases(false);
+        drop(lock);
+    }
+}
@@ -0,0 +1,117 @@
+//! Measuring the throughput of the codegen. Used by the benchmarks, and by the backend itself when [`timings`](crate::config::BackendConfig::timings) is set.
+use std::time::{Duration, Instant};
+/// A single measured phase of the codegen.
+struct Phase {
+    name: &'static str,
+    elapsed: Duration,
+    ops: usize,
+}
+/// Time spent in each phase of the codegen, and the number of ops each phase processed.
+#[derive(Default)]
+pub struct Timings {
+    phases: Vec<Phase>,
+    /// Should the start and end of each phase be printed?
+    progress: bool,
+}
+impl Timings {
+    /// Creates an empty set of timings.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Creates an empty set of timings, which prints the start and end of each phase timed using [`Self::time`] to stderr.
+    /// Useful for showing progress of long-running work.
+    #[must_use]
+    pub fn with_progress() -> Self {
+        Self {
+            phases: Vec::new(),
+            progress: true,
+        }
+    }
+    /// Runs `f`, recording the time it took as phase `name`, which processed `ops` ops.
+    pub fn time<T>(&mut self, name: &'static str, ops: usize, f: impl FnOnce() -> T) -> T {
+        if self.progress {
+            eprintln!("{name}...");
+        }
+        let start = Instant::now();
+        let res = f();
+        let elapsed = start.elapsed();
+        if self.progress {
+            eprintln!(
+                "{name} finished in {ms:.3} ms",
+                ms = elapsed.as_secs_f64() * 1000.0
+            );
+        }
+        self.record(name, elapsed, ops);
+        res
+    }
+    /// Records that phase `name` took `elapsed`, and processed `ops` ops. Used when the number of ops is only known after the phase finishes.
+    /// Phases recorded multiple times are added together.
+    pub fn record(&mut self, name: &'static str, elapsed: Duration, ops: usize) {
+        match self.phases.iter_mut().find(|phase| phase.name == name) {
+            Some(phase) => {
+                phase.elapsed += elapsed;
+                phase.ops += ops;
+            }
+            None => self.phases.push(Phase { name, elapsed, ops }),
+        }
+    }
+    /// Returns a human-readable report of the recorded timings, the throughput of each phase, and the peak memory usage.
+    #[must_use]
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for phase in &self.phases {
+            let secs = phase.elapsed.as_secs_f64();
+            let ops_per_sec = if secs > 0.0 {
+                phase.ops as f64 / secs
+            } else {
+                0.0
+            };
+            report.push_str(&format!(
+                "{name:<16} {ms:>10.3} ms {ops:>10} ops {ops_per_sec:>14.0} ops/s\n",
+                name = phase.name,
+                ms = secs * 1000.0,
+                ops = phase.ops
+            ));
+        }
+        match peak_rss_kib() {
+            Some(rss) => report.push_str(&format!("peak RSS: {rss} KiB\n")),
+            None => report.push_str("peak RSS: unknown\n"),
+        }
+        report
+    }
+    /// Returns a machine-readable JSON breakdown of the recorded timings: the name, duration(in milliseconds) and number of ops of each phase,
+    /// and the peak memory usage(in KiB, or `null` if unknown).
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let phases: Vec<_> = self
+            .phases
+            .iter()
+            .map(|phase| {
+                serde_json::json!({
+                    "name": phase.name,
+                    "ms": phase.elapsed.as_secs_f64() * 1000.0,
+                    "ops": phase.ops,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "phases": phases,
+            "peak_rss_kib": peak_rss_kib(),
+        })
+        .to_string()
+    }
+}
+/// Returns the peak resident set size of this process, in KiB. Only supported on Linux.
+#[must_use]
+pub fn peak_rss_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
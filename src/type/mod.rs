@@ -27,15 +27,22 @@ pub fn mangle(tpe: &Type) -> std::borrow::Cow<'static, str> {
         Type::F32 => "f32".into(),
         Type::F64 => "f64".into(),
         Type::Ptr(inner) => format!("p{inner}", inner = mangle(inner)).into(),
+        Type::ManagedReference(inner) => format!("r{inner}", inner = mangle(inner)).into(),
         Type::DotnetType(tpe) => {
-            assert!(
-                tpe.generics().is_empty(),
-                "Arrays of generic .NET types not supported yet"
-            );
-            tpe.name_path().replace(".", "_").into()
+            let mut mangled = tpe.name_path().replace(".", "_").replace('`', "_g");
+            for generic in tpe.generics() {
+                mangled.push('_');
+                mangled.push_str(&mangle(generic));
+            }
+            mangled.into()
         }
         Type::DotnetArray(arr) => format!("a{}{}", arr.dimensions, mangle(&arr.element)).into(),
         Type::DotnetChar => "c".into(),
+        Type::Char => "ch".into(),
+        Type::Vector64(element) | Type::Vector128(element) | Type::Vector256(element) => {
+            let bits = tpe.simd_info().expect("Not a SIMD vector type!").0;
+            format!("v{bits}{element}", element = mangle(element)).into()
+        }
         Type::GenericArg(_) => todo!("Can't mangle generic type arg"),
         Type::FnDef(name) => format!("fn{}{}", name.len(), name).into(),
         Type::Unresolved => "un".into(),
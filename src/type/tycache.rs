@@ -3,42 +3,111 @@ use crate::{
     access_modifier::AccessModifer,
     function_sig::FnSig,
     r#type::{closure_typedef, escape_field_name},
-    utilis::enum_tag_size,
     IString,
 };
+use indexmap::{IndexMap, IndexSet};
 use rustc_middle::ty::{
-    AdtDef, AdtKind, GenericArg, Instance, List, ParamEnv, Ty, TyCtxt, TyKind, UintTy,
+    AdtDef, AdtKind, GenericArgs, GenericParamDefKind, Instance, List, ParamEnv, Ty, TyCtxt,
+    TyKind, TypeAndMut, TypeVisitableExt,
 };
 use rustc_span::def_id::DefId;
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::{OnceLock, RwLock};
+/// Handle to an interned type reference. Fields, call sites and statics refer to the types they belong to trough those handles, so comparing
+/// and hashing them does not have to look at the names of the types.
+/// Handles are only valid within one process: they are serialized as the type reference they point to, and interned again when deserialized.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeIdx(u32);
+/// The interned type references, in the order they were interned in. They are never freed, so handles can give out `'static` references.
+static TYPE_REFS: OnceLock<RwLock<IndexSet<&'static DotnetTypeRef>>> = OnceLock::new();
+impl TypeIdx {
+    /// Returns the handle of `tpe`, interning it if needed.
+    pub fn intern(tpe: &DotnetTypeRef) -> Self {
+        let refs = TYPE_REFS.get_or_init(Default::default);
+        if let Some(idx) = refs.read().unwrap().get_index_of(tpe) {
+            return Self(idx as u32);
+        }
+        let mut refs = refs.write().unwrap();
+        // Another thread may have interned `tpe` in the meantime.
+        let idx = match refs.get_index_of(tpe) {
+            Some(idx) => idx,
+            None => refs.insert_full(Box::leak(Box::new(tpe.clone()))).0,
+        };
+        Self(idx as u32)
+    }
+    /// Returns the type reference behind this handle.
+    #[must_use]
+    pub fn get(self) -> &'static DotnetTypeRef {
+        TYPE_REFS
+            .get()
+            .and_then(|refs| refs.read().unwrap().get_index(self.0 as usize).copied())
+            .expect("Invalid type handle!")
+    }
+}
+impl std::fmt::Debug for TypeIdx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.get().fmt(f)
+    }
+}
+impl Serialize for TypeIdx {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for TypeIdx {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DotnetTypeRef::deserialize(deserializer).map(|tpe| Self::intern(&tpe))
+    }
+}
+impl From<DotnetTypeRef> for TypeIdx {
+    fn from(tpe: DotnetTypeRef) -> Self {
+        Self::intern(&tpe)
+    }
+}
 // CAN'T BE SERAILIZED!
 pub struct TyCache {
-    type_def_cache: HashMap<IString, TypeDef>,
+    /// Type definitions, keyed by name, in the order they were registered in.
+    type_def_cache: IndexMap<IString, TypeDef>,
+    /// Number of definitions already returned by [`Self::take_new_defs`].
+    taken: usize,
     cycle_prevention: Vec<IString>,
-    ptr_components: Option<DefId>,
+    /// ADTs lowered to CLR generic types: the name of their generic type definition, and the indices of their type parameters. `None` marks
+    /// ADTs which can't be generic.
+    generic_adts: IndexMap<DefId, Option<(IString, Vec<u32>)>>,
 }
 impl TyCache {
     #[must_use]
     pub fn empty() -> Self {
         Self {
-            type_def_cache: HashMap::new(),
+            type_def_cache: IndexMap::new(),
+            taken: 0,
             cycle_prevention: vec![],
-            ptr_components: None,
+            generic_adts: IndexMap::new(),
         }
     }
     pub fn defs(&self) -> impl Iterator<Item = &TypeDef> {
         self.type_def_cache.values()
     }
+    /// Returns the definition of the type named `name`, if it was already created.
+    pub fn get(&self, name: &str) -> Option<&TypeDef> {
+        self.type_def_cache.get(name)
+    }
+    /// Returns the handle of the type named `name`, if it was already created.
+    pub fn idx_of(&self, name: &str) -> Option<TypeIdx> {
+        self.type_def_cache
+            .contains_key(name)
+            .then(|| TypeIdx::intern(&DotnetTypeRef::new(None, name)))
+    }
+    /// Returns the definition behind `idx`, if it was created by this cache.
     #[must_use]
-    /// Gets the definition ID of the `PtrComponents` type.
-    /// # Panics
-    /// Will panic if the `PtrComponents` type is missing.
-    pub fn ptr_components(&mut self, tyctx: TyCtxt) -> DefId {
-        if self.ptr_components.is_none() {
-            self.ptr_components = Some(try_find_ptr_components(tyctx));
-        }
-        self.ptr_components
-            .expect("Could not find `PtrComponents`.")
+    pub fn def(&self, idx: TypeIdx) -> Option<&TypeDef> {
+        self.type_def_cache.get(idx.get().name_path())
+    }
+    /// Returns the definitions created since the last call, in the order they were created in.
+    pub fn take_new_defs(&mut self) -> impl Iterator<Item = &TypeDef> {
+        let start = self.taken;
+        self.taken = self.type_def_cache.len();
+        self.type_def_cache.values().skip(start)
     }
     fn adt<'tyctx>(
         &mut self,
@@ -68,6 +137,174 @@ impl TyCache {
         self.cycle_prevention.pop();
         DotnetTypeRef::new(None, name)
     }
+    /// Returns the name of the generic type definition of `adt`, and the indices of its type parameters, registering the definition if needed.
+    /// Returns `None` if `adt` can't be represented by a CLR generic type.
+    fn generic_adt<'tyctx>(
+        &mut self,
+        adt: AdtDef<'tyctx>,
+        tyctx: TyCtxt<'tyctx>,
+    ) -> Option<(IString, Vec<u32>)> {
+        if let Some(generic) = self.generic_adts.get(&adt.did()) {
+            return generic.clone();
+        }
+        let Some(params) = generic_params(adt, tyctx) else {
+            self.generic_adts.insert(adt.did(), None);
+            return None;
+        };
+        let path = rustc_middle::ty::print::with_no_trimmed_paths! {tyctx.def_path_str(adt.did())};
+        let name: IString = format!(
+            "{path}`{argc}",
+            path = crate::utilis::escape_class_name(&path),
+            argc = params.len()
+        )
+        .into();
+        // The ADT may refer to itself(eg. trough a pointer), so it is marked as generic while its fields are lowered. If any of them can't be
+        // expressed using CLR generics, everything registered in the meantime is rolled back, since it may refer to the generic definition.
+        let (defs, generic_adts) = (self.type_def_cache.len(), self.generic_adts.len());
+        self.generic_adts
+            .insert(adt.did(), Some((name.clone(), params.clone())));
+        let identity = GenericArgs::identity_for_item(tyctx, adt.did());
+        let param_env = tyctx.param_env(adt.did());
+        let fields: Option<Vec<_>> = adt
+            .all_fields()
+            .map(|field| {
+                let field_ty = field.ty(tyctx, identity);
+                // ZST fields are not stored, so the definition can't have them. Fields which are only ZSTs for some generic arguments are
+                // checked by `generic_instance`.
+                if is_zst_in(field_ty, param_env, tyctx) {
+                    return None;
+                }
+                let tpe = self.open_type(field_ty, &params, param_env, tyctx)?;
+                Some((escape_field_name(&field.name.to_string()), tpe))
+            })
+            .collect();
+        let Some(fields) = fields else {
+            self.type_def_cache.truncate(defs);
+            self.generic_adts.truncate(generic_adts);
+            self.generic_adts.insert(adt.did(), None);
+            return None;
+        };
+        let gargc = u32::try_from(params.len()).expect("More than 2^32 generic arguments.");
+        self.type_def_cache.insert(
+            name.clone(),
+            TypeDef::new(
+                AccessModifer::Public,
+                name.clone(),
+                vec![],
+                fields,
+                vec![],
+                None,
+                gargc,
+                None,
+            ),
+        );
+        Some((name, params))
+    }
+    /// Lowers the type `ty` of a field of a generic ADT, whose type parameters have the indices `params`, to a type referring to the
+    /// generic arguments of its definition(eg. `!0`). Returns `None` if `ty` can't be expressed using CLR generics.
+    fn open_type<'tyctx>(
+        &mut self,
+        ty: Ty<'tyctx>,
+        params: &[u32],
+        param_env: ParamEnv<'tyctx>,
+        tyctx: TyCtxt<'tyctx>,
+    ) -> Option<Type> {
+        if !ty.has_param() {
+            let ty = tyctx.normalize_erasing_regions(ParamEnv::reveal_all(), ty);
+            return Some(self.type_from_cache(ty, tyctx, None));
+        }
+        match ty.kind() {
+            TyKind::Param(param) => params
+                .iter()
+                .position(|idx| *idx == param.index)
+                .map(|arg| Type::GenericArg(arg as u32)),
+            // Pointers to types which may be unsized are either thin or wide, depending on the generic arguments.
+            TyKind::RawPtr(TypeAndMut { ty: pointee, .. }) | TyKind::Ref(_, pointee, _) => {
+                if !pointee.is_sized(tyctx, param_env) {
+                    return None;
+                }
+                let pointee = self.open_type(*pointee, params, param_env, tyctx)?;
+                Some(Type::Ptr(pointee.into()))
+            }
+            TyKind::Adt(def, subst) => {
+                // Like in `generic_instance`, instances with ZST fields can't use the generic definition.
+                if def
+                    .all_fields()
+                    .any(|field| is_zst_in(field.ty(tyctx, subst), param_env, tyctx))
+                {
+                    return None;
+                }
+                let (name, def_params) = self.generic_adt(*def, tyctx)?;
+                let generics = def_params
+                    .iter()
+                    .map(|idx| {
+                        let arg = subst[*idx as usize].expect_ty();
+                        let arg = self.open_type(arg, params, param_env, tyctx)?;
+                        is_generic_arg(&arg).then_some(arg)
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                let mut tref = DotnetTypeRef::new(None, &name);
+                tref.set_generics(generics);
+                Some(tref.into())
+            }
+            // Arrays, tuples and closures are named after their element types, and associated types depend on trait implementations.
+            _ => None,
+        }
+    }
+    /// Returns the instance of the generic type definition of `adt` with the generic arguments `subst`. Returns `None` if `adt` is not generic,
+    /// or any of the arguments can't be used as a CLR generic argument.
+    fn generic_instance<'tyctx>(
+        &mut self,
+        adt: AdtDef<'tyctx>,
+        subst: &'tyctx List<rustc_middle::ty::GenericArg<'tyctx>>,
+        tyctx: TyCtxt<'tyctx>,
+        method: Option<Instance<'tyctx>>,
+    ) -> Option<Type> {
+        // Whether a field is zero-sized(and so, not stored) may depend on the generic arguments, so ADTs with ZST fields are not generic.
+        if adt.all_fields().any(|field| {
+            let mut field_ty = field.ty(tyctx, subst);
+            method
+                .inspect(|method| field_ty = crate::utilis::monomorphize(method, field_ty, tyctx));
+            crate::utilis::is_zst(field_ty, tyctx)
+        }) {
+            return None;
+        }
+        let (name, params) = self.generic_adt(adt, tyctx)?;
+        let generics = params
+            .iter()
+            .map(|idx| {
+                let mut arg = subst[*idx as usize].expect_ty();
+                method.inspect(|method| arg = crate::utilis::monomorphize(method, arg, tyctx));
+                let arg = self.type_from_cache(arg, tyctx, method);
+                is_generic_arg(&arg).then_some(arg)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let mut tref = DotnetTypeRef::new(None, &name);
+        tref.set_generics(generics);
+        Some(tref.into())
+    }
+    /// Returns the type of the field `name` of the type `owner`, as declared by its definition. For instances of generic type definitions, this is
+    /// the type before the generic arguments are substituted(eg. `!0`), which is the one field references must use. Otherwise, returns
+    /// `instantiated`.
+    #[must_use]
+    pub fn declared_field_type(
+        &self,
+        owner: &DotnetTypeRef,
+        name: &str,
+        instantiated: Type,
+    ) -> Type {
+        if owner.generics().is_empty() || owner.asm().is_some() {
+            return instantiated;
+        }
+        self.type_def_cache
+            .get(owner.name_path())
+            .and_then(|def| {
+                def.fields()
+                    .iter()
+                    .find(|(field, _)| field.as_ref() == name)
+            })
+            .map_or(instantiated, |(_, tpe)| tpe.clone())
+    }
     pub fn recover_from_panic(&mut self) {
         self.cycle_prevention.clear();
     }
@@ -80,19 +317,52 @@ impl TyCache {
         method: Option<Instance<'tyctx>>,
     ) -> TypeDef {
         let mut fields = Vec::new();
-        for field in adt.all_fields() {
+        // Indices of the fields which are stored.
+        let mut stored = Vec::new();
+        for (idx, field) in adt.all_fields().enumerate() {
             let name = escape_field_name(&field.name.to_string());
             let mut field_ty = field.ty(tyctx, subst);
             method.inspect(|method_instance| {
                 field_ty = crate::utilis::monomorphize(method_instance, field_ty, tyctx);
             });
+            // ZST fields would take up at least a byte in .NET, changing the layout of the struct.
+            if crate::utilis::is_zst(field_ty, tyctx) {
+                continue;
+            }
             let field_ty = self.type_from_cache(field_ty, tyctx, method);
             fields.push((name, field_ty));
+            stored.push(idx);
         }
 
         let access = AccessModifer::Public;
-
-        TypeDef::new(access, name.into(), vec![], fields, vec![], None, 0, None)
+        // Fields of packed structs are not naturally aligned, so their offsets must match the ones calculated by rustc.
+        let explicit_offsets = if adt.repr().packed() {
+            let ty = Ty::new_adt(tyctx, adt, subst);
+            let layout = tyctx
+                .layout_of(ParamEnv::reveal_all().and(ty))
+                .expect("Can't get the layout of a packed struct.");
+            Some(
+                stored
+                    .iter()
+                    .map(|idx| {
+                        u32::try_from(layout.fields.offset(*idx).bytes())
+                            .expect("Packed struct field offset over 2^32.")
+                    })
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        TypeDef::new(
+            access,
+            name.into(),
+            vec![],
+            fields,
+            vec![],
+            explicit_offsets,
+            0,
+            None,
+        )
     }
     fn union_<'tyctx>(
         &mut self,
@@ -103,20 +373,35 @@ impl TyCache {
         method: Option<Instance<'tyctx>>,
     ) -> TypeDef {
         let mut fields = Vec::new();
-        for field in adt.all_fields() {
+        let mut stored = Vec::new();
+        for (idx, field) in adt.all_fields().enumerate() {
             let name = escape_field_name(&field.name.to_string());
             let mut field_ty = field.ty(tyctx, subst);
             method.inspect(|method_instance| {
                 field_ty = crate::utilis::monomorphize(method_instance, field_ty, tyctx);
             });
+            if crate::utilis::is_zst(field_ty, tyctx) {
+                continue;
+            }
             let field_ty = self.type_from_cache(field_ty, tyctx, method);
             fields.push((name, field_ty));
+            stored.push(idx);
         }
 
         let access = AccessModifer::Public;
-        let offsets = adt.all_fields().map(|_| 0).collect();
-
-        TypeDef::new(
+        // All fields of a union overlap. Their offsets, and the size of the union(which includes padding) come from the layout calculated by rustc.
+        let ty = Ty::new_adt(tyctx, adt, subst);
+        let layout = tyctx
+            .layout_of(ParamEnv::reveal_all().and(ty))
+            .expect("Can't get the layout of a union.");
+        let offsets = stored
+            .iter()
+            .map(|idx| {
+                u32::try_from(layout.fields.offset(*idx).bytes())
+                    .expect("Union field offset over 2^32.")
+            })
+            .collect();
+        let mut def = TypeDef::new(
             access,
             name.into(),
             vec![],
@@ -125,7 +410,9 @@ impl TyCache {
             Some(offsets),
             0,
             None,
-        )
+        );
+        def.set_explicit_size(u32::try_from(layout.size.bytes()).expect("Union size over 2^32."));
+        def
     }
     fn enum_<'tyctx>(
         &mut self,
@@ -136,46 +423,51 @@ impl TyCache {
         method: Option<Instance<'tyctx>>,
     ) -> TypeDef {
         let access = AccessModifer::Public;
-        let mut explicit_offsets: Vec<u32> = vec![0];
-
-        let tag_size = enum_tag_size(adt.variants().len() as u64);
-        let mut fields = vec![(
-            "_tag".into(),
-            crate::utilis::tag_from_enum_variants(adt.variants().len() as u64),
-        )];
-        explicit_offsets.extend(adt.variants().iter().map(|_| tag_size));
-        //let mut inner_types = vec![];
-        let mut variants = vec![];
-        for variant in adt.variants() {
+        // The tag, and the fields of all variants, are placed where rustc placed them, so niches and reinterpreting enums as bytes work.
+        let layout = crate::discriminant::enum_layout(Ty::new_adt(tyctx, adt, subst), tyctx);
+        let mut explicit_offsets: Vec<u32> = vec![];
+        let mut fields = vec![];
+        if let Some((offset, tag)) = crate::discriminant::tag_field(&layout) {
+            fields.push((crate::discriminant::TAG_FIELD.into(), tag));
+            explicit_offsets.push(offset);
+        }
+        let mut inner_types = vec![];
+        for (variant_idx, variant) in adt.variants().iter_enumerated() {
             let variant_name: IString = variant.name.to_string().into();
             let mut variant_fields = vec![];
-            for field in &variant.fields {
+            // Variants which can never be active are not a part of the layout, so their fields are never used.
+            let offsets = crate::discriminant::variant_field_offsets(&layout, variant_idx)
+                .unwrap_or_else(|| vec![0; variant.fields.len()]);
+            let mut variant_offsets = vec![];
+            for (field, offset) in variant.fields.iter().zip(offsets) {
+                let field_ty = field.ty(tyctx, subst);
+                if crate::utilis::is_zst(field_ty, tyctx) {
+                    continue;
+                }
                 let name = escape_field_name(&field.name.to_string());
-                let field_ty = self.type_from_cache(field.ty(tyctx, subst), tyctx, method);
+                let field_ty = self.type_from_cache(field_ty, tyctx, method);
                 variant_fields.push((name, field_ty));
+                variant_offsets.push(offset);
             }
-            variants.push((variant_name, variant_fields));
-        }
-        let mut inner_types = vec![];
-
-        for (variant_name, field_list) in variants {
             let inner = TypeDef::new(
                 access,
                 variant_name.clone(),
                 vec![],
-                field_list,
+                variant_fields,
                 vec![],
-                None,
+                Some(variant_offsets),
                 0,
                 None,
             );
             let dref = DotnetTypeRef::new(None, &format!("{enum_name}/{variant_name}"));
             let variant_name: IString = format!("v_{variant_name}").into();
             fields.push((variant_name, dref.into()));
+            // The offsets of variant fields are relative to the start of the enum.
+            explicit_offsets.push(0);
             inner_types.push(inner);
         }
 
-        TypeDef::new(
+        let mut def = TypeDef::new(
             access,
             enum_name.into(),
             inner_types,
@@ -184,15 +476,18 @@ impl TyCache {
             Some(explicit_offsets),
             0,
             None,
-        )
+        );
+        def.set_explicit_size(u32::try_from(layout.size.bytes()).expect("Enum size over 2^32."));
+        def
     }
-    pub fn slice_ty<'tyctx>(
-        &mut self,
-        inner: Ty<'tyctx>,
-        tyctx: TyCtxt<'tyctx>,
-        method: Option<Instance<'tyctx>>,
-    ) -> Type {
-        slice_ref_to(tyctx, self, Ty::new_slice(tyctx, inner), method)
+    /// Returns the type of wide pointers(pointers to slices, `str` and trait objects), registering its definition if needed.
+    pub fn fat_ptr(&mut self) -> Type {
+        let name = crate::fat_ptr::FAT_PTR_NAME;
+        if !self.type_def_cache.contains_key(name) {
+            self.type_def_cache
+                .insert(name.into(), crate::fat_ptr::fat_ptr_def());
+        }
+        Type::DotnetType(crate::fat_ptr::fat_ptr_type().into())
     }
     /// Converts a [`Ty`] to a dotnet-compatible [`Type`]. It is cached.
     /// # Panics
@@ -203,14 +498,14 @@ impl TyCache {
         tyctx: TyCtxt<'tyctx>,
         method: Option<Instance<'tyctx>>,
     ) -> Type {
-        if crate::PRINT_TY_CONVERTION {
+        if crate::config::config().print_ty_convertion {
             eprintln!("ty:{ty:?} method:{method:?}");
         }
         match ty.kind() {
             TyKind::Bool => Type::Bool,
             TyKind::Int(int) => int.into(),
             TyKind::Uint(uint) => uint.into(),
-            TyKind::Char => Type::U32,
+            TyKind::Char => Type::Char,
             TyKind::Float(float) => float.into(),
             TyKind::Tuple(types) => {
                 let types: Vec<_> = types
@@ -254,45 +549,47 @@ impl TyCache {
                 DotnetTypeRef::new(None, &name).into()
             }
             TyKind::Never => Type::Void,
-            TyKind::RawPtr(type_and_mut) => match type_and_mut.ty.kind() {
-                TyKind::Slice(inner) => {
-                    let inner = if let Some(method) = method {
-                        crate::utilis::monomorphize(&method, *inner, tyctx)
-                    } else {
-                        *inner
-                    };
-                    slice_ref_to(tyctx, self, Ty::new_slice(tyctx, inner), method)
-                }
-                TyKind::Str => {
-                    slice_ref_to(tyctx, self, Ty::new_slice(tyctx, u8_ty(tyctx)), method)
+            TyKind::RawPtr(type_and_mut) => {
+                if crate::fat_ptr::is_wide_pointee(type_and_mut.ty) {
+                    self.fat_ptr()
+                } else {
+                    Type::Ptr(self.type_from_cache(type_and_mut.ty, tyctx, method).into())
                 }
-                _ => Type::Ptr(self.type_from_cache(type_and_mut.ty, tyctx, method).into()),
-            },
+            }
             TyKind::Adt(def, subst) => {
                 let name = crate::utilis::adt_name(*def, tyctx, subst);
                 if super::is_name_magic(name.as_ref()) {
                     return super::magic_type(name.as_ref(), def, subst, tyctx);
                 }
+                // `#[repr(simd)]` types of the sizes supported by .NET become its SIMD vectors.
+                if def.repr().simd() {
+                    let (lanes, element) = ty.simd_size_and_type(tyctx);
+                    let bits = lanes * crate::utilis::compiletime_sizeof(element, tyctx) as u64 * 8;
+                    let element = self.type_from_cache(element, tyctx, method);
+                    if let Some(vector) = Type::simd_vector(element, bits) {
+                        return vector;
+                    }
+                }
+                // Enums with no variants(eg. `enum Never{}`) can't be constructed, so they are handled just like `!`.
+                if def.is_enum() && def.variants().is_empty() {
+                    return Type::Void;
+                }
+                if let Some(generic) = self.generic_instance(*def, subst, tyctx, method) {
+                    return generic;
+                }
                 self.adt(&name, *def, subst, tyctx, method).into()
             }
             TyKind::Dynamic(trait_, _, dyn_kind) => {
                 println!("trait:{trait_:?} dyn_kind:{dyn_kind:?}");
                 Type::Unresolved
             }
-            TyKind::Ref(_region, inner, _mut) => match inner.kind() {
-                TyKind::Slice(inner) => {
-                    let inner = if let Some(method) = method {
-                        crate::utilis::monomorphize(&method, *inner, tyctx)
-                    } else {
-                        *inner
-                    };
-                    slice_ref_to(tyctx, self, Ty::new_slice(tyctx, inner), method)
-                }
-                TyKind::Str => {
-                    slice_ref_to(tyctx, self, Ty::new_slice(tyctx, u8_ty(tyctx)), method)
+            TyKind::Ref(_region, inner, _mut) => {
+                if crate::fat_ptr::is_wide_pointee(*inner) {
+                    self.fat_ptr()
+                } else {
+                    Type::Ptr(self.type_from_cache(*inner, tyctx, method).into())
                 }
-                _ => Type::Ptr(self.type_from_cache(*inner, tyctx, method).into()),
-            },
+            }
             TyKind::Foreign(foregin) => {
                 println!("foregin:{foregin:?}");
                 Type::Foreign
@@ -337,83 +634,37 @@ impl TyCache {
         }
     }
 }
-fn slice_ref_to<'tyctx>(
-    tyctx: TyCtxt<'tyctx>,
-    cache: &mut TyCache,
-    mut inner: Ty<'tyctx>,
-    method: Option<Instance<'tyctx>>,
-) -> Type {
-    method.inspect(|method| inner = crate::utilis::monomorphize(method, inner, tyctx));
-    let inner = ty_generic_arg(inner);
-    // TODO: ensure this function call is valid.
-    let list = tyctx.mk_args(&[inner]);
-
-    let ptr_components = cache.ptr_components(tyctx);
-    //std::process::exit(-1);
-    let adt_def = tyctx.adt_def(ptr_components);
-    let ty = Ty::new(tyctx, TyKind::Adt(adt_def, list));
-    cache.type_from_cache(ty, tyctx, method)
-}
-fn u8_ty(tyctx: TyCtxt) -> Ty {
-    Ty::new(tyctx, TyKind::Uint(UintTy::U8))
-}
-/// Turns a `ty` into a `generic_arg`
-pub fn ty_generic_arg(ty: Ty) -> GenericArg {
-    // Shit version, ok only cause type tag is 0b00
-    unsafe { std::mem::transmute(ty) }
-    // Good version
-    /*
-    rustc_middle::ty::GenericArgKind::Type(ty).pack()
-    */
-}
-// WARING: This function is hacky as shit. It assumes the index of defid of PtrComponents is smaller than the index of the last public function. This *should* almost always be the case,
-// but it might not be.
-
-fn try_find_ptr_components(ctx: TyCtxt) -> DefId {
-    use rustc_middle::middle::exported_symbols::ExportedSymbol;
-    let mut core = None;
-    for krate in ctx.crates(()) {
-        let name = ctx.crate_name(*krate);
-        if name.as_str() == "core" {
-            core = Some(krate);
-            break;
-        }
-    }
-    let core = core.expect("Could not find core!");
-    let core_symbols = ctx.exported_symbols(*core);
-    let mut max_index = 0;
-    for symbol in core_symbols {
-        match symbol.0 {
-            ExportedSymbol::ThreadLocalShim(def_id)
-            | ExportedSymbol::Generic(def_id, _)
-            | ExportedSymbol::NonGeneric(def_id) => {
-                max_index = max_index.max(def_id.index.as_u32());
-            }
-            _ => (),
-        }
+/// Returns the indices of the type parameters of `adt`, if it may be represented by a CLR generic type: a struct with type parameters, no const
+/// parameters, and a layout .NET calculates on its own.
+fn generic_params(adt: AdtDef, tyctx: TyCtxt) -> Option<Vec<u32>> {
+    if !crate::config::config().generic_types
+        || !adt.is_struct()
+        || adt.repr().packed()
+        || adt.repr().simd()
+        || super::is_name_magic(tyctx.item_name(adt.did()).as_str())
+    {
+        return None;
     }
-    let mut ptr_components = None;
-    for index in 0..max_index {
-        let did = DefId {
-            index: index.into(),
-            krate: *core,
-        };
-        let name = format!("{did:?}");
-        if name.contains("ptr::metadata::PtrComponents")
-            && !name.contains("PtrComponents::data_address")
-            && !name.contains("PtrComponents::metadata")
-            && !name.contains("PtrComponents::T")
-        {
-            //println!("did:{did:?}");
-            assert!(
-                ptr_components.is_none(),
-                "Found more than one defintin of PtrComponents"
-            );
-            ptr_components = Some(did);
+    let mut params = Vec::new();
+    for param in &tyctx.generics_of(adt.did()).params {
+        match param.kind {
+            GenericParamDefKind::Lifetime => (),
+            GenericParamDefKind::Type { .. } => params.push(param.index),
+            GenericParamDefKind::Const { .. } => return None,
         }
-
-        //44548
     }
-    //todo!("core:{core:?} max_index:{max_index:?} ptr_components:{ptr_components:?}");
-    ptr_components.expect("Could not find core::ptr::metadata::PtrComponents")
+    (!params.is_empty()).then_some(params)
+}
+/// Checks if `ty` is known to be zero-sized in `param_env`. Types whose size depends on generic arguments are not.
+fn is_zst_in<'tyctx>(ty: Ty<'tyctx>, param_env: ParamEnv<'tyctx>, tyctx: TyCtxt<'tyctx>) -> bool {
+    tyctx
+        .layout_of(param_env.and(ty))
+        .is_ok_and(|layout| layout.is_sized() && layout.is_zst())
+}
+/// Checks if `tpe` may be used as a generic argument. The CLR does not allow pointers, or `void`, to be generic arguments.
+fn is_generic_arg(tpe: &Type) -> bool {
+    !matches!(
+        tpe,
+        Type::Void | Type::Ptr(_) | Type::ManagedReference(_) | Type::Unresolved | Type::Foreign
+    )
 }
@@ -32,15 +32,28 @@ pub enum Type {
     DotnetArray(Box<DotnetArray>),
     // Pointer to a type
     Ptr(Box<Self>),
+    /// Managed reference(`ref`) to a type. Used only to call .NET methods taking `ref` arguments.
+    ManagedReference(Box<Self>),
     // Speical type marking an unresoved type. This is a work around some issues with corelib types. Nothing can ever interact directly with this type.
     Unresolved,
     /// Foregin type. Will never be interacted with directly
     Foreign,
     /// Generic argument
     GenericArg(u32),
+    /// Generic argument of a generic method(`!!n` in ILASM). Appears only in signatures of generic .NET methods, and is replaced by the
+    /// generic arguments of a [`crate::cil::CallSite`] calling such a method.
+    MethodGenericArg(u32),
     DotnetChar,
+    /// Rust `char`: a Unicode scalar value, stored as an u32. Only values up to `char::MAX`, outside of the surrogate range, are valid.
+    Char,
     /// Rust FnDefs
     FnDef(IString),
+    /// A 64 bit SIMD vector of values of the element type(`System.Runtime.Intrinsics.Vector64<T>`).
+    Vector64(Box<Self>),
+    /// A 128 bit SIMD vector of values of the element type(`System.Runtime.Intrinsics.Vector128<T>`).
+    Vector128(Box<Self>),
+    /// A 256 bit SIMD vector of values of the element type(`System.Runtime.Intrinsics.Vector256<T>`).
+    Vector256(Box<Self>),
 }
 #[derive(Serialize, Deserialize, PartialEq, Clone, Eq, Hash, Debug)]
 pub struct DotnetArray {
@@ -58,12 +71,25 @@ pub struct DotnetTypeRef {
 impl DotnetTypeRef {
     #[must_use]
     pub fn int_128() -> Self {
+        crate::config::config().require(crate::config::DotnetApi::Int128);
         Self::new(Some("System.Runtime"), "System.Int128")
     }
     #[must_use]
     pub fn uint_128() -> Self {
+        crate::config::config().require(crate::config::DotnetApi::Int128);
         Self::new(Some("System.Runtime"), "System.UInt128")
     }
+    /// Returns a reference to `System.Text.Rune`: a Unicode scalar value, just like a Rust `char`.
+    #[must_use]
+    pub fn rune() -> Self {
+        crate::config::config().require(crate::config::DotnetApi::Rune);
+        Self::new(Some("System.Runtime"), RUNE_NAME)
+    }
+    /// Checks if this is a reference to `System.Text.Rune`.
+    #[must_use]
+    pub fn is_rune(&self) -> bool {
+        self.name_path() == RUNE_NAME
+    }
     #[must_use]
     pub fn usize_type() -> Self {
         Self::new(Some("System.Runtime"), "System.UIntPtr")
@@ -155,9 +181,78 @@ impl Type {
                 dref.set_generics(dref_generics?);
                 Some(Self::DotnetType(dref))
             }
+            Self::Ptr(inner) => Some(Self::Ptr(inner.map_generic(generics)?.into())),
             _ => Some(self.clone()),
         }
     }
+    /// Returns the SIMD vector type `bits` wide, with elements of type `element`. Returns `None` if .NET has no vectors of that size.
+    #[must_use]
+    pub fn simd_vector(element: Self, bits: u64) -> Option<Self> {
+        match bits {
+            64 => Some(Self::Vector64(element.into())),
+            128 => Some(Self::Vector128(element.into())),
+            256 => Some(Self::Vector256(element.into())),
+            _ => None,
+        }
+    }
+    /// Returns the size in bits, and the element type, of this SIMD vector type. Returns `None` if this is not a SIMD vector type.
+    #[must_use]
+    pub fn simd_info(&self) -> Option<(u64, &Self)> {
+        match self {
+            Self::Vector64(element) => Some((64, element)),
+            Self::Vector128(element) => Some((128, element)),
+            Self::Vector256(element) => Some((256, element)),
+            _ => None,
+        }
+    }
+    /// Returns the generic .NET struct representing this SIMD vector type(eg. ``System.Runtime.Intrinsics.Vector128`1<int32>``).
+    #[must_use]
+    pub fn simd_class(&self) -> Option<DotnetTypeRef> {
+        let (bits, element) = self.simd_info()?;
+        let mut class = DotnetTypeRef::new(
+            Some("System.Runtime.Intrinsics"),
+            &format!("System.Runtime.Intrinsics.Vector{bits}`1"),
+        );
+        class.set_generics([element.clone()]);
+        Some(class)
+    }
+    /// Replaces the method generic arguments within this type with the corresponding types in `generics`.
+    #[must_use]
+    pub fn map_method_generic(&self, generics: &[Type]) -> Type {
+        match self {
+            Self::MethodGenericArg(arg) => generics
+                .get(*arg as usize)
+                .cloned()
+                .unwrap_or_else(|| panic!("Method generic argument {arg} out of range!")),
+            Self::Ptr(inner) => Self::Ptr(inner.map_method_generic(generics).into()),
+            Self::ManagedReference(inner) => {
+                Self::ManagedReference(inner.map_method_generic(generics).into())
+            }
+            Self::DotnetArray(array) => Self::DotnetArray(
+                DotnetArray {
+                    element: array.element.map_method_generic(generics),
+                    dimensions: array.dimensions,
+                }
+                .into(),
+            ),
+            Self::DotnetType(dref) => {
+                let mut dref = dref.clone();
+                let dref_generics = dref
+                    .generics()
+                    .iter()
+                    .map(|gtype| gtype.map_method_generic(generics))
+                    .collect();
+                dref.set_generics(dref_generics);
+                Self::DotnetType(dref)
+            }
+            Self::Vector64(_) | Self::Vector128(_) | Self::Vector256(_) => {
+                let (bits, element) = self.simd_info().expect("Not a SIMD vector type!");
+                Self::simd_vector(element.map_method_generic(generics), bits)
+                    .expect("Not a SIMD vector size!")
+            }
+            _ => self.clone(),
+        }
+    }
     #[must_use]
     pub fn ref_to(&self) -> Self {
         match self {
@@ -168,10 +263,9 @@ impl Type {
     #[must_use]
     pub fn metadata(&self) -> Self {
         match self {
-            Self::DotnetType(dotnet) => match dotnet.name_path() {
-                "PtrComponents" => Type::USize,
-                _ => Type::Void,
-            },
+            Self::DotnetType(dotnet) if dotnet.name_path() == crate::fat_ptr::FAT_PTR_NAME => {
+                Type::USize
+            }
             _ => Self::Void,
         }
     }
@@ -190,7 +284,10 @@ impl From<&IntTy> for Type {
             IntTy::I16 => Self::I16,
             IntTy::I32 => Self::I32,
             IntTy::I64 => Self::I64,
-            IntTy::I128 => Self::I128,
+            IntTy::I128 => {
+                crate::config::config().require(crate::config::DotnetApi::Int128);
+                Self::I128
+            }
             IntTy::Isize => Self::ISize,
         }
     }
@@ -202,7 +299,10 @@ impl From<&UintTy> for Type {
             UintTy::U16 => Self::U16,
             UintTy::U32 => Self::U32,
             UintTy::U64 => Self::U64,
-            UintTy::U128 => Self::U128,
+            UintTy::U128 => {
+                crate::config::config().require(crate::config::DotnetApi::Int128);
+                Self::U128
+            }
             UintTy::Usize => Self::USize,
         }
     }
@@ -230,10 +330,12 @@ impl From<DotnetTypeRef> for Type {
         Self::DotnetType(Box::new(value))
     }
 }
+const RUNE_NAME: &str = "System.Text.Rune";
 const INTEROP_CLASS_TPE_NAME: &str = "RustcCLRInteropManagedClass";
 const INTEROP_STRUCT_TPE_NAME: &str = "RustcCLRInteropManagedStruct";
 const INTEROP_CHR_TPE_NAME: &str = "RustcCLRInteropManagedChar";
 const INTEROP_ARR_TPE_NAME: &str = "RustcCLRInteropManagedArray";
+const INTEROP_RUNE_TPE_NAME: &str = "RustcCLRInteropManagedRune";
 #[must_use]
 /// Checks if a type is a magic interop type.
 pub fn is_name_magic(name: &str) -> bool {
@@ -297,6 +399,8 @@ pub fn magic_type<'tyctx>(
         todo!()
     } else if name.contains(INTEROP_CHR_TPE_NAME) {
         Type::DotnetChar
+    } else if name.contains(INTEROP_RUNE_TPE_NAME) {
+        DotnetTypeRef::rune().into()
     } else {
         todo!("Interop type {name:?} is not yet supported!")
     }
@@ -1,7 +1,8 @@
 use crate::{
     access_modifier::AccessModifer,
-    cil::FieldDescriptor,
-    method::Method,
+    cil::{CILOp, FieldDescriptor, StaticFieldDescriptor},
+    function_sig::FnSig,
+    method::{ImplFlags, Method},
     r#type::{DotnetTypeRef, Type},
     IString,
 };
@@ -17,6 +18,11 @@ pub struct TypeDef {
     explicit_offsets: Option<Vec<u32>>,
     gargc: u32,
     extends: Option<DotnetTypeRef>,
+    #[serde(default)]
+    static_fields: Vec<(IString, Type)>,
+    /// The size of this type, if it must differ from the one calculated from its fields(eg. a union padded to its alignment).
+    #[serde(default)]
+    explicit_size: Option<u32>,
 }
 impl TypeDef {
     #[must_use]
@@ -69,12 +75,79 @@ impl TypeDef {
     pub fn explicit_offsets(&self) -> Option<&Vec<u32>> {
         self.explicit_offsets.as_ref()
     }
+    /// Returns the size of this type, if it is set explicitly.
+    #[must_use]
+    pub fn explicit_size(&self) -> Option<u32> {
+        self.explicit_size
+    }
+    /// Sets the size of this type to `size` bytes. Only used by types with explicit field offsets.
+    pub fn set_explicit_size(&mut self, size: u32) {
+        self.explicit_size = Some(size);
+    }
     pub fn add_method(&mut self, method: Method) {
         self.functions.push(method);
     }
+    /// Returns the static fields of this type.
+    #[must_use]
+    pub fn static_fields(&self) -> &[(IString, Type)] {
+        &self.static_fields
+    }
+    /// Adds a static field named `name`, of type `tpe`, to this type.
+    pub fn add_static_field(&mut self, name: IString, tpe: Type) {
+        self.static_fields.push((name, tpe));
+    }
+    /// Returns the static constructor(`.cctor`) of this type, if it has one.
+    #[must_use]
+    pub fn cctor(&self) -> Option<&Method> {
+        self.functions
+            .iter()
+            .find(|method| method.name() == ".cctor")
+    }
+    /// Appends `new_ops` to the static constructor(`.cctor`) of this type, creating it if needed. The static constructor runs before the first
+    /// access to a static field of this type, so `new_ops` should initialize them.
+    pub fn extend_cctor(&mut self, new_ops: impl IntoIterator<Item = CILOp>) {
+        let cctor = match self
+            .functions
+            .iter()
+            .position(|method| method.name() == ".cctor")
+        {
+            Some(idx) => &mut self.functions[idx],
+            None => {
+                self.functions.push(Method::new(
+                    AccessModifer::Private,
+                    true,
+                    FnSig::new(&[], &Type::Void),
+                    ".cctor",
+                    vec![],
+                ));
+                self.functions.last_mut().expect("A method was just added!")
+            }
+        };
+        let ops = cctor.ops_mut();
+        if ops.last() == Some(&CILOp::Ret) {
+            ops.pop();
+        }
+        ops.extend(new_ops);
+        ops.push(CILOp::Ret);
+    }
+    /// Adds a static field named `name`, of type `tpe`, initialized by the static constructor to the value `init` leaves on the stack.
+    pub fn add_static_field_with_init(
+        &mut self,
+        name: IString,
+        tpe: Type,
+        init: impl IntoIterator<Item = CILOp>,
+    ) {
+        let field = StaticFieldDescriptor::boxed(Some((&*self).into()), tpe.clone(), name.clone());
+        self.add_static_field(name, tpe);
+        self.extend_cctor(init.into_iter().chain([CILOp::STStaticField(field)]));
+    }
     pub fn methods(&self) -> impl Iterator<Item = &Method> {
         self.functions.iter()
     }
+    /// Returns an iterator over mutable references to the methods of this type.
+    pub fn methods_mut(&mut self) -> impl Iterator<Item = &mut Method> {
+        self.functions.iter_mut()
+    }
     #[must_use]
     pub fn nameonly(name: &str) -> Self {
         Self {
@@ -86,6 +159,8 @@ impl TypeDef {
             gargc: 0,
             extends: None,
             explicit_offsets: None,
+            static_fields: vec![],
+            explicit_size: None,
         }
     }
     #[must_use]
@@ -108,6 +183,8 @@ impl TypeDef {
             explicit_offsets,
             gargc,
             extends,
+            static_fields: vec![],
+            explicit_size: None,
         }
     }
 }
@@ -230,7 +307,7 @@ pub fn tuple_typedef(elements: &[Type]) -> TypeDef {
 }
 #[must_use]
 pub fn get_array_type(element_count: usize, element: Type) -> TypeDef {
-    use crate::cil::CILOp;
+    use crate::cil::CilBuilder;
     let name = arr_name(element_count, &element);
     let mut fields = Vec::with_capacity(element_count);
     for field in 0..element_count {
@@ -245,7 +322,10 @@ pub fn get_array_type(element_count: usize, element: Type) -> TypeDef {
         explicit_offsets: None,
         gargc: 0,
         extends: None,
+        static_fields: vec![],
+        explicit_size: None,
     };
+    let first_field = FieldDescriptor::new((&def).into(), element.clone(), "f_0".into());
     // set_Item(usize offset, G0 value)
     let mut set_usize = Method::new(
         AccessModifer::Public,
@@ -257,20 +337,17 @@ pub fn get_array_type(element_count: usize, element: Type) -> TypeDef {
         "set_Item",
         vec![],
     );
-    let ops = vec![
-        CILOp::LDArg(0),
-        CILOp::LDFieldAdress(FieldDescriptor::boxed(
-            (&def).into(),
-            element.clone(),
-            "f_0".to_string().into(),
-        )),
-        CILOp::LDArg(1),
-        CILOp::Add,
-        CILOp::LDArg(2),
-        CILOp::STObj(element.clone().into()),
-        CILOp::Ret,
-    ];
+    let ops = CilBuilder::new()
+        .ldarg(0)
+        .ldflda(first_field.clone())
+        .ldarg(1)
+        .add()
+        .ldarg(2)
+        .stobj(element.clone())
+        .ret()
+        .build();
     set_usize.set_ops(ops);
+    set_usize.set_impl_flags(ImplFlags::AggressiveInlining);
     def.add_method(set_usize);
     // get_Address(usize offset)
     let mut get_adress_usize = Method::new(
@@ -283,18 +360,15 @@ pub fn get_array_type(element_count: usize, element: Type) -> TypeDef {
         "get_Address",
         vec![],
     );
-    let ops = vec![
-        CILOp::LDArg(0),
-        CILOp::LDFieldAdress(FieldDescriptor::boxed(
-            (&def).into(),
-            element.clone(),
-            "f_0".to_string().into(),
-        )),
-        CILOp::LDArg(1),
-        CILOp::Add,
-        CILOp::Ret,
-    ];
+    let ops = CilBuilder::new()
+        .ldarg(0)
+        .ldflda(first_field.clone())
+        .ldarg(1)
+        .add()
+        .ret()
+        .build();
     get_adress_usize.set_ops(ops);
+    get_adress_usize.set_impl_flags(ImplFlags::AggressiveInlining);
     def.add_method(get_adress_usize);
     // get_Item
     let mut get_item_usize = Method::new(
@@ -304,19 +378,16 @@ pub fn get_array_type(element_count: usize, element: Type) -> TypeDef {
         "get_Item",
         vec![],
     );
-    let ops = vec![
-        CILOp::LDArg(0),
-        CILOp::LDFieldAdress(FieldDescriptor::boxed(
-            (&def).into(),
-            element.clone(),
-            "f_0".to_string().into(),
-        )),
-        CILOp::LDArg(1),
-        CILOp::Add,
-        CILOp::LdObj(element.clone().into()),
-        CILOp::Ret,
-    ];
+    let ops = CilBuilder::new()
+        .ldarg(0)
+        .ldflda(first_field)
+        .ldarg(1)
+        .add()
+        .ldobj(element.clone())
+        .ret()
+        .build();
     get_item_usize.set_ops(ops);
+    get_item_usize.set_impl_flags(ImplFlags::AggressiveInlining);
     def.add_method(get_item_usize);
     def
 }
@@ -1,8 +1,9 @@
 use crate::r#type::tycache::TyCache;
 use rustc_middle::mir::{Operand, UnOp};
-use rustc_middle::ty::{Instance, TyCtxt};
+use rustc_middle::ty::{Instance, TyCtxt, TyKind};
 
-use crate::cil::CILOp;
+use crate::cil::{CILOp, CallSite};
+use crate::function_sig::FnSig;
 pub fn unop<'ctx>(
     unnop: UnOp,
     operand: &Operand<'ctx>,
@@ -12,10 +13,28 @@ pub fn unop<'ctx>(
     tycache: &mut TyCache,
 ) -> Vec<CILOp> {
     let mut ops = crate::operand::handle_operand(operand, tcx, method, method_instance, tycache);
-    let _ty = operand.ty(&method.local_decls, tcx);
+    let ty = operand.ty(&method.local_decls, tcx);
+    if let Some(class) = crate::binop::int128_class(ty) {
+        let tpe = tycache.type_from_cache(ty, tcx, Some(method_instance));
+        let name = match unnop {
+            UnOp::Neg => "op_UnaryNegation",
+            UnOp::Not => "op_OnesComplement",
+        };
+        ops.push(CILOp::Call(CallSite::boxed(
+            Some(class),
+            name.into(),
+            FnSig::new(&[tpe.clone()], &tpe),
+            true,
+        )));
+        return ops;
+    }
     match unnop {
         UnOp::Neg => ops.push(CILOp::Neg),
-        UnOp::Not => ops.push(CILOp::Not),
+        // `not` flips all the bits of an int32, which would turn a `bool` into something that is neither 0 nor 1.
+        UnOp::Not => match ty.kind() {
+            TyKind::Bool => ops.extend([CILOp::LdcI32(0), CILOp::Eq]),
+            _ => ops.push(CILOp::Not),
+        },
     };
     ops
 }
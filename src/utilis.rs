@@ -199,6 +199,7 @@ pub fn field_descrptor<'tyctx>(
         .type_from_cache(owner_ty, tyctx, Some(method_instance))
         .as_dotnet()
         .expect("Error: tried to set a field of a non-object type!");
+    let field_ty = type_cache.declared_field_type(&owner_ty, &field_name, field_ty);
     FieldDescriptor::new(owner_ty, field_ty, field_name)
     /*
     let def = type_cache.type_def_from_cache(owner_ty, ctx, Some(method_instance)); //TypeDef::from_ty(owner_ty, ctx, &method_instance);
@@ -209,24 +210,6 @@ pub fn field_descrptor<'tyctx>(
         .expect("Field owner not a dotnet type!");
     def.field_desc_from_rust_field_idx(type_ref, field_idx)*/
 }
-/// Returns the size of a tag of an enum with `variants` variants.
-pub fn enum_tag_size(variants: u64) -> u32 {
-    u32::try_from(((u64::from(u64::BITS) - u64::from((variants).leading_zeros())) + 8 - 1) / 8)
-        .expect("Enum variant over 2^4294967296")
-}
-/// Gets the type of the tag of enum with `variants` varinats.
-pub fn tag_from_enum_variants(variants: u64) -> crate::r#type::Type {
-    let var_size = enum_tag_size(variants);
-    // println!("variants:{variants}tag_size:{var_size}");
-    match var_size {
-        0 => Type::Void,
-        1 => Type::U8,
-        2 => Type::U16,
-        4 => Type::U32,
-        8 => Type::U64,
-        _ => todo!("Can't yet have {var_size} byte wide enum tag!"),
-    }
-}
 /// Tires to get the value of Const `size` as usize.
 pub fn try_resolve_const_size(size: Const) -> Result<usize, &'static str> {
     let scalar = match size.try_to_scalar() {
@@ -286,7 +269,7 @@ pub fn garag_to_bool<'tyctx>(garg: GenericArg<'tyctx>, _ctx: TyCtxt<'tyctx>) ->
         _ => todo!("Can't convert generic arg of const kind {kind:?} to string!"),
     }
 }
-/// This function returns the size of a type at the compile time. This should be used ONLY for handling constants. It currently assumes a 64 bit env
+/// This function returns the size of a type at the compile time. This should be used ONLY for handling constants. Pointer-sized types use the pointer size of the target.
 pub fn compiletime_sizeof<'tyctx>(ty: Ty<'tyctx>, tyctx: TyCtxt<'tyctx>) -> usize {
     use rustc_middle::ty::{IntTy, UintTy};
     match ty.kind() {
@@ -296,10 +279,7 @@ pub fn compiletime_sizeof<'tyctx>(ty: Ty<'tyctx>, tyctx: TyCtxt<'tyctx>) -> usiz
             IntTy::I32 => std::mem::size_of::<i32>(),
             IntTy::I64 => std::mem::size_of::<i64>(),
             IntTy::I128 => std::mem::size_of::<i128>(),
-            IntTy::Isize => {
-                eprintln!("WARNING: Assuming sizeof::<isize>() == 8!");
-                8
-            }
+            IntTy::Isize => tyctx.data_layout.pointer_size.bytes() as usize,
         },
         TyKind::Uint(int) => match int {
             UintTy::U8 => std::mem::size_of::<u8>(),
@@ -307,16 +287,14 @@ pub fn compiletime_sizeof<'tyctx>(ty: Ty<'tyctx>, tyctx: TyCtxt<'tyctx>) -> usiz
             UintTy::U32 => std::mem::size_of::<u32>(),
             UintTy::U64 => std::mem::size_of::<u64>(),
             UintTy::U128 => std::mem::size_of::<u128>(),
-            UintTy::Usize => {
-                eprintln!("WARNING: Assuming sizeof::<usize>() == 8!");
-                8
-            }
+            UintTy::Usize => tyctx.data_layout.pointer_size.bytes() as usize,
         },
         TyKind::Float(float_ty) => match float_ty {
             FloatTy::F32 => std::mem::size_of::<f32>(),
             FloatTy::F64 => std::mem::size_of::<f64>(),
         },
         TyKind::Bool => std::mem::size_of::<u8>(),
+        TyKind::Char => std::mem::size_of::<u32>(),
         TyKind::Adt(def, subst) => match def.adt_kind() {
             AdtKind::Struct => def
                 .all_fields()
@@ -347,10 +325,7 @@ pub fn compiletime_sizeof<'tyctx>(ty: Ty<'tyctx>, tyctx: TyCtxt<'tyctx>) -> usiz
                 rustc_middle::ty::print::with_no_trimmed_paths! {todo!("Can't compute compiletime sizeof *[{inner:?}]")}
             }
             TyKind::Str => todo!("Can't compute compiletime sizeof *str"),
-            _ => {
-                eprintln!("WARNING: Assuming sizeof::<*T>() == sizeof::<isize>() == 8!");
-                8
-            }
+            _ => tyctx.data_layout.pointer_size.bytes() as usize,
         },
         _ => todo!("Can't compute compiletime sizeof {ty:?}"),
     }
@@ -378,6 +353,38 @@ pub fn string_class() -> DotnetTypeRef {
     string.set_valuetype(false);
     string
 }
+/// Returns a [`DotnetTypeRef`] describing the `System.Object` class.
+pub fn object_class() -> DotnetTypeRef {
+    let mut object = DotnetTypeRef::new(Some("System.Runtime"), "System.Object");
+    object.set_valuetype(false);
+    object
+}
+/// Returns a [`DotnetTypeRef`] describing the `System.Type` class.
+pub fn type_class() -> DotnetTypeRef {
+    let mut type_class = DotnetTypeRef::new(Some("System.Runtime"), "System.Type");
+    type_class.set_valuetype(false);
+    type_class
+}
+/// Returns a [`DotnetTypeRef`] describing the `System.RuntimeTypeHandle` struct, loaded by `ldtoken`.
+pub fn runtime_type_handle() -> DotnetTypeRef {
+    DotnetTypeRef::new(Some("System.Runtime"), "System.RuntimeTypeHandle")
+}
+/// Returns a [`DotnetTypeRef`] describing the `System.Exception` class.
+pub fn exception_class() -> DotnetTypeRef {
+    let mut exception = DotnetTypeRef::new(Some("System.Runtime"), "System.Exception");
+    exception.set_valuetype(false);
+    exception
+}
+/// Returns a [`DotnetTypeRef`] describing the `System.Environment` class.
+pub fn environment_class() -> DotnetTypeRef {
+    let mut environment = DotnetTypeRef::new(Some("System.Runtime"), "System.Environment");
+    environment.set_valuetype(false);
+    environment
+}
+/// Checks if panics abort the process(`-C panic=abort`), instead of unwinding the stack(`-C panic=unwind`).
+pub fn panic_aborts(tcx: TyCtxt) -> bool {
+    tcx.sess.panic_strategy() == rustc_target::spec::PanicStrategy::Abort
+}
 /// Returns a [`DotnetTypeRef`] describing the usize class.
 pub fn usize_class() -> DotnetTypeRef {
     let mut string = DotnetTypeRef::new(Some("System.Runtime"), "System.UIntPtr");
@@ -409,12 +416,92 @@ pub fn check_debugable(
                 eprintln!("{index}:\t{op:?} changed stack by {diff}, to {stack}");
             }
         }
-        assert!(crate::ALLOW_MISCOMPILATIONS, "Miscompiled  {debugable:?}.");
+        assert!(crate::config::config().allow_miscompilations, "Miscompiled  {debugable:?}.");
     }
 }
 pub(crate) fn alloc_id_to_u64(alloc_id: AllocId) -> u64 {
     unsafe { std::mem::transmute(alloc_id) }
 }
+/// Checks if `ty` is a zero-sized type(eg. `()`, `PhantomData<T>` or an empty struct). ZSTs hold no data, so ZST locals and fields are removed,
+/// and loads and stores of ZST places are elided.
+pub(crate) fn is_zst<'tcx>(ty: Ty<'tcx>, tyctx: TyCtxt<'tcx>) -> bool {
+    tyctx
+        .layout_of(ParamEnv::reveal_all().and(ty))
+        .is_ok_and(|layout| layout.is_sized() && layout.is_zst())
+}
+/// Returns the ops loading a value of the zero-sized type `tpe`. ZSTs hold no data, so the default value of `tpe` is as good as any other.
+pub(crate) fn zst_value(tpe: Type) -> Vec<CILOp> {
+    vec![
+        CILOp::NewTMPLocal(tpe.into()),
+        CILOp::LoadTMPLocal,
+        CILOp::FreeTMPLocal,
+    ]
+}
+/// Returns the ops loading the address of a ZST of type `ty`, which is not stored anywhere. Just like `NonNull::dangling`, the address is equal to
+/// the alignment of `ty`, so it is never null, and always aligned.
+pub(crate) fn zst_address<'tcx>(ty: Ty<'tcx>, tyctx: TyCtxt<'tcx>) -> Vec<CILOp> {
+    let align = tyctx
+        .layout_of(ParamEnv::reveal_all().and(ty))
+        .expect("Could not get the layout of a ZST!")
+        .align
+        .abi
+        .bytes();
+    vec![CILOp::LdcI64(align as i64), CILOp::ConvUSize(false)]
+}
+/// Returns the op checking if the `bool` on top of the stack is true, throwing an exception otherwise. Used for checking `assume`.
+pub(crate) fn check_assumption() -> CILOp {
+    CILOp::Call(crate::cil::CallSite::boxed(
+        None,
+        "check_assumption".into(),
+        crate::function_sig::FnSig::new(&[Type::Bool], &Type::Void),
+        true,
+    ))
+}
+/// Ops converting an int32 truth value on top of the stack into a normalized `bool`(0 or 1).
+pub(crate) fn normalize_bool() -> [CILOp; 4] {
+    [CILOp::LdcI32(0), CILOp::Eq, CILOp::LdcI32(0), CILOp::Eq]
+}
+/// Part of micompilatio detection. Warns about values which may not be 0 or 1 being written into a `bool` location.
+pub(crate) fn check_bool_normalization(method: &crate::method::Method) {
+    let ops = method.get_ops();
+    for (index, op) in ops.iter().enumerate().skip(1) {
+        let is_bool_store = match op {
+            CILOp::STLoc(local) => method
+                .locals()
+                .get(*local as usize)
+                .is_some_and(|(_, tpe)| *tpe == Type::Bool),
+            CILOp::STArg(arg) => method
+                .sig()
+                .inputs()
+                .get(*arg as usize)
+                .is_some_and(|tpe| *tpe == Type::Bool),
+            CILOp::STField(desc) => *desc.tpe() == Type::Bool,
+            _ => false,
+        };
+        if !is_bool_store {
+            continue;
+        }
+        let not_normalized = match &ops[index - 1] {
+            CILOp::LdcI32(value) => *value != 0 && *value != 1,
+            CILOp::LdcI64(_)
+            | CILOp::Not
+            | CILOp::Neg
+            | CILOp::Add
+            | CILOp::Sub
+            | CILOp::Mul
+            | CILOp::Shl
+            | CILOp::Shr => true,
+            _ => false,
+        };
+        if not_normalized {
+            eprintln!(
+                "Propable miscompilation: method {name} stores a non-normalized value into a bool at op {index}:{op:?}, produced by {prev:?}.",
+                name = method.name(),
+                prev = ops[index - 1]
+            );
+        }
+    }
+}
 /// Part of micompilatio detection.
 pub(crate) fn verify_locals_within_range(ops: &[CILOp], argc: u32, locc: u32) -> bool {
     ops.iter().all(|op| match op {
@@ -0,0 +1,643 @@
+//! Verification of the evaluation stack of methods, before they are exported.
+//!
+//! Invalid IL(eg. a branch reached with different stack contents from two places, or an `add` of an `int32` and an `int64`) is normally only
+//! discovered by ILASM, or by the runtime when the method is first called, far away from the code which produced it. [`verify_method`] simulates
+//! the evaluation stack of a method, tracking the verification type(see ECMA-335 III.1.5) of each value, and reports the first problem it finds,
+//! together with the index of the offending op.
+//!
+//! The check is a linear pass over the ops. The stack state at each label is recorded the first time the label is reached(by a branch, or by
+//! falling trough), and every other path reaching it must agree with it. Code right after an unconditional jump, only reached by backward branches,
+//! starts with an empty stack, as required by ECMA-335 III.1.7.5. Verification is less strict than the one of the runtime: native pointers and
+//! managed references, as well as `int32` and `native int` are interchangeable, since the codegen relies on the runtime accepting this.
+//! Types of values which can't be known(eg. ones produced by [`CILOp::InlineIL`]) match anything.
+use crate::{
+    assembly::Assembly,
+    cil::CILOp,
+    method::{HandlerKind, Method},
+    r#type::Type,
+};
+use std::collections::HashMap;
+/// The type of a value on the evaluation stack.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackType {
+    /// `int32`. Also used for all smaller integers, `bool` and `char`.
+    Int32,
+    /// `int64`
+    Int64,
+    /// `native int`. Also used for unmanaged pointers.
+    NativeInt,
+    /// `F`: the native-size float, used for both `float32` and `float64`.
+    Float,
+    /// `O`: a reference to an object.
+    ObjRef,
+    /// `&`: a managed reference.
+    ManagedPtr,
+    /// An instance of a value type.
+    Value(Box<Type>),
+    /// A value of an unknown type. Matches all types.
+    Unknown,
+}
+impl StackType {
+    /// Returns the stack type of a value of type `tpe`, or `None` if `tpe` is `void`.
+    #[must_use]
+    pub fn from_type(tpe: &Type) -> Option<Self> {
+        Some(match tpe {
+            Type::Void => return None,
+            Type::Bool
+            | Type::I8
+            | Type::U8
+            | Type::I16
+            | Type::U16
+            | Type::I32
+            | Type::U32
+            | Type::Char
+            | Type::DotnetChar => Self::Int32,
+            Type::I64 | Type::U64 => Self::Int64,
+            Type::ISize | Type::USize | Type::Ptr(_) => Self::NativeInt,
+            Type::F32 | Type::F64 => Self::Float,
+            Type::ManagedReference(_) => Self::ManagedPtr,
+            Type::DotnetArray(_) => Self::ObjRef,
+            Type::DotnetType(class) if !class.is_valuetype() => Self::ObjRef,
+            Type::DotnetType(_)
+            | Type::I128
+            | Type::U128
+            | Type::Vector64(_)
+            | Type::Vector128(_)
+            | Type::Vector256(_) => Self::Value(tpe.clone().into()),
+            Type::Unresolved
+            | Type::Foreign
+            | Type::GenericArg(_)
+            | Type::MethodGenericArg(_)
+            | Type::FnDef(_) => Self::Unknown,
+        })
+    }
+    /// Checks if a value of type `self` may be used where a value of type `other` is expected, and vice versa.
+    fn matches(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Unknown, _) | (_, Self::Unknown) => true,
+            (Self::Int32 | Self::ManagedPtr, Self::NativeInt)
+            | (Self::NativeInt, Self::Int32 | Self::ManagedPtr) => true,
+            _ => self == other,
+        }
+    }
+    fn is_int(&self) -> bool {
+        matches!(
+            self,
+            Self::Int32 | Self::Int64 | Self::NativeInt | Self::Unknown
+        )
+    }
+    fn is_pointer(&self) -> bool {
+        matches!(self, Self::NativeInt | Self::ManagedPtr | Self::Unknown)
+    }
+}
+/// A problem found in the ops of a method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationError {
+    method: String,
+    op_index: usize,
+    message: String,
+}
+impl VerificationError {
+    /// Returns the name of the method containing the invalid op.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+    /// Returns the index of the invalid op within the ops of the method.
+    pub fn op_index(&self) -> usize {
+        self.op_index
+    }
+    /// Returns the description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+impl std::fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid IL in method {method} at op {index}: {message}",
+            method = self.method,
+            index = self.op_index,
+            message = self.message
+        )
+    }
+}
+/// The simulated evaluation stack.
+struct Stack(Vec<StackType>);
+impl Stack {
+    fn pop(&mut self) -> Result<StackType, String> {
+        self.0.pop().ok_or_else(|| "stack underflow".to_string())
+    }
+    /// Pops a value, which must be of type `expected`.
+    fn pop_expecting(&mut self, expected: &StackType) -> Result<StackType, String> {
+        let value = self.pop()?;
+        if value.matches(expected) {
+            Ok(value)
+        } else {
+            Err(format!(
+                "expected a value of type {expected:?}, found {value:?}"
+            ))
+        }
+    }
+    /// Pops a value, which must be an integer.
+    fn pop_int(&mut self) -> Result<StackType, String> {
+        let value = self.pop()?;
+        if value.is_int() || value == StackType::ManagedPtr {
+            Ok(value)
+        } else {
+            Err(format!("expected an integer, found {value:?}"))
+        }
+    }
+    /// Pops a value, which must be a pointer or a managed reference.
+    fn pop_pointer(&mut self) -> Result<StackType, String> {
+        let value = self.pop()?;
+        if value.is_pointer() {
+            Ok(value)
+        } else {
+            Err(format!("expected a pointer, found {value:?}"))
+        }
+    }
+    /// Pops the arguments of a call with inputs `inputs`, checking their types.
+    fn pop_args(&mut self, inputs: &[Type]) -> Result<(), String> {
+        for input in inputs.iter().rev() {
+            if let Some(expected) = StackType::from_type(input) {
+                self.pop_expecting(&expected)?;
+            }
+        }
+        Ok(())
+    }
+    /// Pushes a value of type `tpe`, unless it is `void`.
+    fn push_type(&mut self, tpe: &Type) {
+        self.0.extend(StackType::from_type(tpe));
+    }
+}
+/// Returns the type of the result of an arithmetic op with operands of types `a` and `b`. `allows_floats` is false for ops only defined for integers.
+fn binary_result(a: &StackType, b: &StackType, allows_floats: bool) -> Result<StackType, String> {
+    match (a, b) {
+        (StackType::Unknown, _) | (_, StackType::Unknown) => Ok(StackType::Unknown),
+        (StackType::Int32, StackType::Int32) => Ok(StackType::Int32),
+        (StackType::Int64, StackType::Int64) => Ok(StackType::Int64),
+        (StackType::Float, StackType::Float) if allows_floats => Ok(StackType::Float),
+        (
+            StackType::Int32 | StackType::NativeInt | StackType::ManagedPtr,
+            StackType::Int32 | StackType::NativeInt | StackType::ManagedPtr,
+        ) => Ok(StackType::NativeInt),
+        _ => Err(format!("mismatched operands {a:?} and {b:?}")),
+    }
+}
+/// Simulates the effect of `op` on `stack`. `tmp_locals` holds the types of temporary locals, if they were not allocated yet.
+fn apply(
+    op: &CILOp,
+    stack: &mut Stack,
+    method: &Method,
+    tmp_locals: &mut Vec<Type>,
+) -> Result<(), String> {
+    let local = |index: u32| -> Result<StackType, String> {
+        let (_, tpe) = method
+            .locals()
+            .get(index as usize)
+            .ok_or_else(|| format!("local {index} does not exist"))?;
+        Ok(StackType::from_type(tpe).unwrap_or(StackType::Unknown))
+    };
+    let arg = |index: u32| -> Result<StackType, String> {
+        let tpe = method
+            .sig()
+            .inputs()
+            .get(index as usize)
+            .ok_or_else(|| format!("argument {index} does not exist"))?;
+        Ok(StackType::from_type(tpe).unwrap_or(StackType::Unknown))
+    };
+    let tmp_local = |tmp_locals: &Vec<Type>, under: u8| -> Result<StackType, String> {
+        let tpe = tmp_locals
+            .iter()
+            .rev()
+            .nth(under as usize)
+            .ok_or_else(|| "temporary local does not exist".to_string())?;
+        Ok(StackType::from_type(tpe).unwrap_or(StackType::Unknown))
+    };
+    match op {
+        CILOp::Nop
+        | CILOp::Comment(_)
+        | CILOp::SourceSpan(_)
+        | CILOp::Label(_)
+        | CILOp::GoTo(_)
+        | CILOp::InlineIL(..)
+        | CILOp::Unaligned(_)
+        | CILOp::Volatile
+        | CILOp::Tail
+        | CILOp::Constrained(_) => (),
+        CILOp::BZero(_) | CILOp::BTrue(_) => {
+            let value = stack.pop()?;
+            if value == StackType::Float {
+                return Err("a float can't be used as a condition".into());
+            }
+        }
+        CILOp::Switch(_) => {
+            stack.pop_int()?;
+        }
+        CILOp::BEq(_)
+        | CILOp::BNe(_)
+        | CILOp::BLt(_)
+        | CILOp::BGe(_)
+        | CILOp::BLe(_)
+        | CILOp::BLtUn(_)
+        | CILOp::BGeUn(_)
+        | CILOp::BLeUn(_) => {
+            let b = stack.pop()?;
+            let a = stack.pop()?;
+            if !(a == StackType::ObjRef && b == StackType::ObjRef) {
+                binary_result(&a, &b, true)?;
+            }
+        }
+        CILOp::Eq | CILOp::Lt | CILOp::Gt | CILOp::LtUn | CILOp::GtUn => {
+            let b = stack.pop()?;
+            let a = stack.pop()?;
+            if !(a == StackType::ObjRef && b == StackType::ObjRef) {
+                binary_result(&a, &b, true)?;
+            }
+            stack.0.push(StackType::Int32);
+        }
+        CILOp::Add
+        | CILOp::Sub
+        | CILOp::Mul
+        | CILOp::Div
+        | CILOp::Rem
+        | CILOp::AddOvf
+        | CILOp::AddOvfUn
+        | CILOp::SubOvf
+        | CILOp::SubOvfUn
+        | CILOp::MulOvf
+        | CILOp::And
+        | CILOp::Or
+        | CILOp::XOr => {
+            let allows_floats = matches!(
+                op,
+                CILOp::Add | CILOp::Sub | CILOp::Mul | CILOp::Div | CILOp::Rem
+            );
+            let b = stack.pop()?;
+            let a = stack.pop()?;
+            stack.0.push(binary_result(&a, &b, allows_floats)?);
+        }
+        CILOp::Shl | CILOp::Shr => {
+            let amount = stack.pop()?;
+            if !matches!(
+                amount,
+                StackType::Int32 | StackType::NativeInt | StackType::Unknown
+            ) {
+                return Err(format!("invalid shift amount {amount:?}"));
+            }
+            let value = stack.pop_int()?;
+            stack.0.push(value);
+        }
+        CILOp::Neg => {
+            let value = stack.pop()?;
+            if !(value.is_int() || value == StackType::Float) {
+                return Err(format!("can't negate {value:?}"));
+            }
+            stack.0.push(value);
+        }
+        CILOp::Not => {
+            let value = stack.pop_int()?;
+            stack.0.push(value);
+        }
+        CILOp::ConvI8(_)
+        | CILOp::ConvI16(_)
+        | CILOp::ConvI32(_)
+        | CILOp::ConvU8(_)
+        | CILOp::ConvU16(_)
+        | CILOp::ConvU32(_)
+        | CILOp::ConvI64(_)
+        | CILOp::ConvU64(_)
+        | CILOp::ConvISize(_)
+        | CILOp::ConvUSize(_)
+        | CILOp::ConvF32(_)
+        | CILOp::ConvF64(_)
+        | CILOp::ConvRUn => {
+            let value = stack.pop()?;
+            if !(value.is_int() || value == StackType::Float || value == StackType::ManagedPtr) {
+                return Err(format!("can't convert {value:?}"));
+            }
+            if *op == CILOp::ConvRUn && value == StackType::Float {
+                return Err("`conv.r.un` expects an integer".into());
+            }
+            stack.0.push(match op {
+                CILOp::ConvI64(_) | CILOp::ConvU64(_) => StackType::Int64,
+                CILOp::ConvISize(_) | CILOp::ConvUSize(_) => StackType::NativeInt,
+                CILOp::ConvF32(_) | CILOp::ConvF64(_) | CILOp::ConvRUn => StackType::Float,
+                _ => StackType::Int32,
+            });
+        }
+        CILOp::LdcI32(_) | CILOp::SizeOf(_) => stack.0.push(StackType::Int32),
+        CILOp::LdcI64(_) => stack.0.push(StackType::Int64),
+        CILOp::LdcF32(_) | CILOp::LdcF64(_) => stack.0.push(StackType::Float),
+        CILOp::LdStr(_) | CILOp::LdNull => stack.0.push(StackType::ObjRef),
+        CILOp::LoadGlobalAllocPtr { .. } | CILOp::LDFtn(_) => stack.0.push(StackType::NativeInt),
+        CILOp::LDVirtFtn(_) => {
+            stack.pop_expecting(&StackType::ObjRef)?;
+            stack.0.push(StackType::NativeInt);
+        }
+        CILOp::LDTypeToken(_) => stack.0.push(StackType::Value(Box::new(
+            crate::utilis::runtime_type_handle().into(),
+        ))),
+        CILOp::LDLoc(index) => stack.0.push(local(*index)?),
+        CILOp::LDArg(index) => stack.0.push(arg(*index)?),
+        CILOp::LDLocA(index) => {
+            local(*index)?;
+            stack.0.push(StackType::ManagedPtr);
+        }
+        CILOp::LDArgA(index) => {
+            arg(*index)?;
+            stack.0.push(StackType::ManagedPtr);
+        }
+        CILOp::STLoc(index) => {
+            stack.pop_expecting(&local(*index)?)?;
+        }
+        CILOp::STArg(index) => {
+            stack.pop_expecting(&arg(*index)?)?;
+        }
+        CILOp::NewTMPLocal(tpe) => tmp_locals.push(tpe.as_ref().clone()),
+        CILOp::FreeTMPLocal => {
+            tmp_locals
+                .pop()
+                .ok_or_else(|| "freed a temporary local which does not exist".to_string())?;
+        }
+        CILOp::LoadTMPLocal => stack.0.push(tmp_local(tmp_locals, 0)?),
+        CILOp::LoadUnderTMPLocal(under) => stack.0.push(tmp_local(tmp_locals, *under)?),
+        CILOp::LoadAddresOfTMPLocal => {
+            tmp_local(tmp_locals, 0)?;
+            stack.0.push(StackType::ManagedPtr);
+        }
+        CILOp::LoadAdressUnderTMPLocal(under) => {
+            tmp_local(tmp_locals, *under)?;
+            stack.0.push(StackType::ManagedPtr);
+        }
+        CILOp::SetTMPLocal => {
+            let expected = tmp_local(tmp_locals, 0)?;
+            stack.pop_expecting(&expected)?;
+        }
+        CILOp::LDIndI8
+        | CILOp::LDIndI16
+        | CILOp::LDIndI32
+        | CILOp::LDIndI64
+        | CILOp::LDIndISize
+        | CILOp::LDIndF32
+        | CILOp::LDIndF64
+        | CILOp::LDIndRef => {
+            stack.pop_pointer()?;
+            stack.0.push(match op {
+                CILOp::LDIndI64 => StackType::Int64,
+                CILOp::LDIndISize => StackType::NativeInt,
+                CILOp::LDIndF32 | CILOp::LDIndF64 => StackType::Float,
+                CILOp::LDIndRef => StackType::ObjRef,
+                _ => StackType::Int32,
+            });
+        }
+        CILOp::STIndI8
+        | CILOp::STIndI16
+        | CILOp::STIndI32
+        | CILOp::STIndI64
+        | CILOp::STIndISize
+        | CILOp::STIndF32
+        | CILOp::STIndF64 => {
+            stack.pop_expecting(&match op {
+                CILOp::STIndI64 => StackType::Int64,
+                CILOp::STIndISize => StackType::NativeInt,
+                CILOp::STIndF32 | CILOp::STIndF64 => StackType::Float,
+                _ => StackType::Int32,
+            })?;
+            stack.pop_pointer()?;
+        }
+        CILOp::LdObj(tpe) => {
+            stack.pop_pointer()?;
+            stack.push_type(tpe);
+        }
+        CILOp::STObj(tpe) => {
+            if let Some(expected) = StackType::from_type(tpe) {
+                stack.pop_expecting(&expected)?;
+            }
+            stack.pop_pointer()?;
+        }
+        CILOp::InitObj(_) => {
+            stack.pop_pointer()?;
+        }
+        CILOp::CpBlk | CILOp::InitBlk => {
+            stack.pop_int()?;
+            stack.pop()?;
+            stack.pop_pointer()?;
+        }
+        CILOp::LocAlloc => {
+            stack.pop_int()?;
+            stack.0.push(StackType::NativeInt);
+        }
+        CILOp::LDField(desc) => {
+            stack.pop()?;
+            stack.push_type(desc.tpe());
+        }
+        CILOp::LDFieldAdress(_) => {
+            stack.pop()?;
+            stack.0.push(StackType::ManagedPtr);
+        }
+        CILOp::STField(desc) => {
+            if let Some(expected) = StackType::from_type(desc.tpe()) {
+                stack.pop_expecting(&expected)?;
+            }
+            stack.pop()?;
+        }
+        CILOp::LDStaticField(desc) => stack.push_type(desc.tpe()),
+        CILOp::STStaticField(desc) => {
+            if let Some(expected) = StackType::from_type(desc.tpe()) {
+                stack.pop_expecting(&expected)?;
+            }
+        }
+        CILOp::Call(site) | CILOp::CallVirt(site) => {
+            let signature = site.instantiated_signature();
+            // Variadic arguments are pushed last.
+            stack.pop_args(site.signature().varargs())?;
+            stack.pop_args(signature.inputs())?;
+            stack.push_type(signature.output());
+        }
+        CILOp::NewObj(site) => {
+            stack.pop_args(site.explicit_inputs())?;
+            match site.class() {
+                Some(class) => stack.push_type(&Type::DotnetType(Box::new(class.clone()))),
+                None => stack.0.push(StackType::Unknown),
+            }
+        }
+        CILOp::CallI(sig) => {
+            stack.pop_pointer()?;
+            stack.pop_args(sig.inputs())?;
+            stack.push_type(sig.output());
+        }
+        CILOp::Pop => {
+            stack.pop()?;
+        }
+        CILOp::Dup => {
+            let value = stack.pop()?;
+            stack.0.extend([value.clone(), value]);
+        }
+        CILOp::Box(_) => {
+            stack.pop()?;
+            stack.0.push(StackType::ObjRef);
+        }
+        CILOp::UnboxAny(tpe) => {
+            stack.pop_expecting(&StackType::ObjRef)?;
+            stack.push_type(tpe);
+        }
+        CILOp::IsInst(_) | CILOp::CastClass(_) => {
+            stack.pop_expecting(&StackType::ObjRef)?;
+            stack.0.push(StackType::ObjRef);
+        }
+        CILOp::Throw => {
+            stack.pop_expecting(&StackType::ObjRef)?;
+        }
+        CILOp::Ret => {
+            if let Some(expected) = StackType::from_type(method.sig().output()) {
+                stack.pop_expecting(&expected)?;
+            }
+            if !stack.0.is_empty() {
+                return Err(format!(
+                    "{len} values left on the stack",
+                    len = stack.0.len()
+                ));
+            }
+        }
+        CILOp::Rethrow | CILOp::EndFinally | CILOp::Leave(_) => stack.0.clear(),
+    }
+    Ok(())
+}
+/// Verifies the evaluation stack of `method`, as described in the [module documentation](self). Returns the first problem found.
+pub fn verify_method(method: &Method) -> Result<(), VerificationError> {
+    let error = |op_index: usize, message: String| VerificationError {
+        method: method.name().into(),
+        op_index,
+        message,
+    };
+    // The stack at the start of a handler holds only the caught exception, if any.
+    let mut labels: HashMap<u32, Vec<StackType>> = method
+        .handlers()
+        .iter()
+        .map(|handler| {
+            let stack = match handler.kind() {
+                HandlerKind::Catch(_) => vec![StackType::ObjRef],
+                HandlerKind::Finally | HandlerKind::Fault => vec![],
+            };
+            (handler.handler_range().0, stack)
+        })
+        .collect();
+    // Methods without a body(eg. ones implemented by the runtime) have nothing to verify.
+    if method.get_ops().is_empty() {
+        return Ok(());
+    }
+    let mut tmp_locals = Vec::new();
+    // `None` if the current op is unreachable from the previous one.
+    let mut stack = Some(Stack(Vec::new()));
+    for (index, op) in method.get_ops().iter().enumerate() {
+        if let CILOp::Label(id) = op {
+            match (&stack, labels.get(id)) {
+                (Some(current), Some(recorded)) => {
+                    check_merge(&current.0, recorded).map_err(|msg| error(index, msg))?;
+                }
+                (Some(current), None) => {
+                    labels.insert(*id, current.0.clone());
+                }
+                (None, recorded) => stack = Some(Stack(recorded.cloned().unwrap_or_default())),
+            }
+        }
+        let Some(current) = &mut stack else {
+            continue;
+        };
+        apply(op, current, method, &mut tmp_locals).map_err(|msg| error(index, msg))?;
+        for target in op.branch_targets() {
+            match labels.get(target) {
+                Some(recorded) => {
+                    check_merge(&current.0, recorded).map_err(|msg| error(index, msg))?;
+                }
+                None => {
+                    labels.insert(*target, current.0.clone());
+                }
+            }
+        }
+        if op.is_diverging() {
+            stack = None;
+        }
+    }
+    if stack.is_some() {
+        return Err(error(
+            method.get_ops().len(),
+            "control falls trough the end of the method".into(),
+        ));
+    }
+    Ok(())
+}
+/// Checks if the stack `current` agrees with the stack `recorded` at a label it reaches.
+fn check_merge(current: &[StackType], recorded: &[StackType]) -> Result<(), String> {
+    if current.len() == recorded.len() && current.iter().zip(recorded).all(|(a, b)| a.matches(b)) {
+        Ok(())
+    } else {
+        Err(format!(
+            "stack {current:?} does not match the stack {recorded:?} at a branch target"
+        ))
+    }
+}
+/// Verifies all methods of `asm`. Returns all the problems found, at most one per method.
+pub fn verify_assembly(asm: &Assembly) -> Vec<VerificationError> {
+    asm.methods()
+        .filter_map(|method| verify_method(method).err())
+        .collect()
+}
+#[test]
+fn finds_invalid_stacks() {
+    use crate::{access_modifier::AccessModifer, function_sig::FnSig};
+    let method = |ops: Vec<CILOp>| {
+        let mut method = Method::new(
+            AccessModifer::Public,
+            true,
+            FnSig::new(&[Type::I32], &Type::I64),
+            "tested",
+            vec![(None, Type::I64)],
+        );
+        method.set_ops(ops);
+        method
+    };
+    let valid = method(vec![
+        CILOp::LDArg(0),
+        CILOp::BZero(1),
+        CILOp::LdcI64(1),
+        CILOp::GoTo(2),
+        CILOp::Label(1),
+        CILOp::LDArg(0),
+        CILOp::ConvI64(false),
+        CILOp::Label(2),
+        CILOp::STLoc(0),
+        CILOp::LDLoc(0),
+        CILOp::Ret,
+    ]);
+    assert_eq!(verify_method(&valid), Ok(()));
+    let underflow = method(vec![CILOp::LdcI64(1), CILOp::Add, CILOp::Ret]);
+    assert_eq!(verify_method(&underflow).unwrap_err().op_index(), 1);
+    let mismatched_operands = method(vec![
+        CILOp::LDArg(0),
+        CILOp::LdcI64(1),
+        CILOp::Add,
+        CILOp::Ret,
+    ]);
+    assert_eq!(
+        verify_method(&mismatched_operands).unwrap_err().op_index(),
+        2
+    );
+    // The two paths reach label 2 with an `int64` and an `int32`.
+    let mismatched_branches = method(vec![
+        CILOp::LDArg(0),
+        CILOp::BZero(1),
+        CILOp::LdcI64(1),
+        CILOp::GoTo(2),
+        CILOp::Label(1),
+        CILOp::LDArg(0),
+        CILOp::Label(2),
+        CILOp::Ret,
+    ]);
+    assert_eq!(
+        verify_method(&mismatched_branches).unwrap_err().op_index(),
+        6
+    );
+}
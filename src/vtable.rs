@@ -0,0 +1,98 @@
+//! Trait objects: pointers to `dyn Trait` values, and their vtables.
+//!
+//! A pointer to a trait object(eg. `&dyn Trait` or `*mut dyn Trait`) is a wide pointer(see [`crate::fat_ptr`]): the address of the value,
+//! followed by the address of the vtable of its type. Vtables are laid out by rustc: the drop glue, the size and alignment of the type, and then pointers to
+//! each method of the trait. Each vtable is a static allocation(one per type and trait), so it is emitted just like any other allocation, with
+//! function pointers patched in by the static constructor.
+//!
+//! Unsizing a pointer(eg. `&T` to `&dyn Trait`, or `Box<T>` to `Box<dyn Trait>`) copies the thin pointer, and appends the vtable of `T`.
+//! A call to a trait method on a trait object loads the function pointer from the right slot of the vtable, and calls it using `calli`, passing
+//! a thin pointer to the value as `self`.
+use crate::{cil::CILOp, r#type::Type};
+use rustc_middle::ty::{ParamEnv, PolyExistentialTraitRef, Ty, TyCtxt, TyKind, TypeAndMut};
+/// Returns the types behind the pointers `source` and `target`, where `source` is unsized into `target`. Smart pointers(like `Box` or `Rc`)
+/// are looked trough, and structs with an unsized last field are reduced to that field.
+pub(crate) fn unsize_pointees<'tcx>(
+    source: Ty<'tcx>,
+    target: Ty<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> (Ty<'tcx>, Ty<'tcx>) {
+    match (source.kind(), target.kind()) {
+        (
+            TyKind::Ref(_, source, _) | TyKind::RawPtr(TypeAndMut { ty: source, .. }),
+            TyKind::Ref(_, target, _) | TyKind::RawPtr(TypeAndMut { ty: target, .. }),
+        ) => tcx.struct_lockstep_tails_erasing_lifetimes(*source, *target, ParamEnv::reveal_all()),
+        // Smart pointers have exactly one field which is not zero-sized: the pointer which gets unsized.
+        (TyKind::Adt(def, source_subst), TyKind::Adt(_, target_subst)) => {
+            let (source, target) = def
+                .non_enum_variant()
+                .fields
+                .iter()
+                .map(|field| (field.ty(tcx, source_subst), field.ty(tcx, target_subst)))
+                .find(|(source, target)| source != target)
+                .expect("Unsized smart pointer has no field to unsize!");
+            unsize_pointees(source, target, tcx)
+        }
+        _ => panic!("Can't unsize {source:?} into {target:?}!"),
+    }
+}
+/// Returns the op loading the address of the vtable of `ty`, for the trait `trait_ref`(only the drop glue, size and alignment if `None`).
+pub(crate) fn vtable_ptr<'tcx>(
+    ty: Ty<'tcx>,
+    trait_ref: Option<PolyExistentialTraitRef<'tcx>>,
+    tcx: TyCtxt<'tcx>,
+) -> CILOp {
+    let alloc_id = tcx.vtable_allocation((ty, trait_ref));
+    CILOp::LoadGlobalAllocPtr {
+        alloc_id: crate::utilis::alloc_id_to_u64(alloc_id),
+    }
+}
+/// Returns the ops turning the pointer of type `source` on top of the stack into a pointer to a trait object of type `target`, using the vtable
+/// loaded by `vtable`. `source` may also be a smart pointer(eg. `Box<T>`), in which case `target` is the matching smart pointer to a trait
+/// object. In both cases, the thin pointer is at offset 0, and the fat one is a thin pointer followed by the vtable.
+pub(crate) fn unsize_to_dyn(source: Type, target: Type, vtable: CILOp, tcx: TyCtxt) -> Vec<CILOp> {
+    let ptr_size = tcx.data_layout.pointer_size.bytes();
+    vec![
+        CILOp::NewTMPLocal(source.into()),
+        CILOp::SetTMPLocal,
+        CILOp::NewTMPLocal(target.into()),
+        // target.data_address = source
+        CILOp::LoadAddresOfTMPLocal,
+        CILOp::LoadAdressUnderTMPLocal(1),
+        CILOp::LDIndISize,
+        CILOp::STIndISize,
+        // target.metadata = vtable
+        CILOp::LoadAddresOfTMPLocal,
+        CILOp::LdcI64(ptr_size as i64),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        vtable,
+        CILOp::STIndISize,
+        CILOp::LoadTMPLocal,
+        CILOp::FreeTMPLocal,
+        CILOp::FreeTMPLocal,
+    ]
+}
+/// Returns the ops loading the thin pointer to the value behind the pointer to a trait object, whose address is loaded by `fat_ptr`.
+pub(crate) fn dyn_data_ptr(fat_ptr: &[CILOp]) -> Vec<CILOp> {
+    let mut ops = fat_ptr.to_vec();
+    ops.push(CILOp::LDIndISize);
+    ops
+}
+/// Returns the ops loading the function pointer from slot `idx` of the vtable of the pointer to a trait object, whose address is loaded by
+/// `fat_ptr`.
+pub(crate) fn vtable_slot(fat_ptr: &[CILOp], idx: usize, tcx: TyCtxt) -> Vec<CILOp> {
+    let ptr_size = tcx.data_layout.pointer_size.bytes();
+    let mut ops = fat_ptr.to_vec();
+    ops.extend([
+        CILOp::LdcI64(ptr_size as i64),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        CILOp::LDIndISize,
+        CILOp::LdcI64((idx as u64 * ptr_size) as i64),
+        CILOp::ConvISize(false),
+        CILOp::Add,
+        CILOp::LDIndISize,
+    ]);
+    ops
+}
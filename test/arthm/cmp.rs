@@ -0,0 +1,25 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+fn main(){
+    // Unsigned values with the highest bit set must not compare as negative.
+    test_eq!(black_box(u32::MAX) > black_box(1), true);
+    test_eq!(black_box(u32::MAX) < black_box(1), false);
+    test_eq!(black_box(u64::MAX) >= black_box(1), true);
+    test_eq!(black_box(u64::MAX) <= black_box(1), false);
+    test_eq!(black_box(usize::MAX) > black_box(0), true);
+    test_eq!(black_box(-1_i32) < black_box(1), true);
+    test_eq!(black_box(-1_i64) >= black_box(1), false);
+    // Every comparison involving NaN is false.
+    let nan = black_box(f32::NAN);
+    test_eq!(nan < 1.0, false);
+    test_eq!(nan > 1.0, false);
+    test_eq!(nan <= 1.0, false);
+    test_eq!(nan >= 1.0, false);
+    let nan = black_box(f64::NAN);
+    test_eq!(nan <= 1.0, false);
+    test_eq!(nan >= 1.0, false);
+    test_eq!(black_box(2.0_f64) >= 1.0, true);
+    test_eq!(black_box(1.0_f64) <= 1.0, true);
+}
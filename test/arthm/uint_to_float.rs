@@ -0,0 +1,14 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+fn main(){
+    // Values with the highest bit set must not be converted as negative numbers.
+    test_eq!(black_box(u32::MAX) as f64, 4294967295.0);
+    test_eq!(black_box(u64::MAX) as f64, 18446744073709551615.0);
+    test_eq!(black_box(u64::MAX) as f32, 18446744073709551615.0);
+    test_eq!(black_box(1_u64 << 63) as f64, 9223372036854775808.0);
+    test_eq!(black_box(usize::MAX) as f64 > 0.0, true);
+    test_eq!(black_box(u8::MAX) as f32, 255.0);
+    test_eq!(black_box(-1_i64) as f64, -1.0);
+}
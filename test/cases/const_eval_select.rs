@@ -0,0 +1,24 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,const_eval_select)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+include!("../diff/output.rs");
+use core::intrinsics::const_eval_select;
+const fn answer_ct() -> i64 {
+    0
+}
+fn answer_rt() -> i64 {
+    42
+}
+const fn double_ct(val: i64) -> i64 {
+    0
+}
+fn double_rt(val: i64) -> i64 {
+    val * 2
+}
+fn main() {
+    // The arguments are a constant `()`.
+    put_i64(unsafe { const_eval_select((), answer_ct, answer_rt) });
+    // The arguments are a tuple in a local.
+    put_i64(unsafe { const_eval_select((black_box(21),), double_ct, double_rt) });
+}
@@ -0,0 +1,19 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+include!("../diff/output.rs");
+fn fib(n: i64) -> i64 {
+    if n < 2 {
+        n
+    } else {
+        fib(n - 1) + fib(n - 2)
+    }
+}
+fn main() {
+    put_i64(fib(black_box(0)));
+    put_i64(fib(black_box(1)));
+    put_i64(fib(black_box(2)));
+    put_i64(fib(black_box(10)));
+    put_i64(fib(black_box(20)));
+}
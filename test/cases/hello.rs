@@ -0,0 +1,7 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+fn main() {
+    unsafe { puts("Hello from .NET!\0".as_ptr()) };
+}
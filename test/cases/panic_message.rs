@@ -0,0 +1,22 @@
+#![feature(lang_items,start,core_intrinsics)]
+#![allow(internal_features)]
+#![no_std]
+#[lang = "eh_personality"]
+fn rust_eh_personality() {}
+extern "C" {
+    fn puts(msg: *const u8);
+}
+extern "Rust" {
+    // Implemented by the backend: starts a panic with the message recorded by `panic_fmt`.
+    fn __rust_start_panic(payload: *mut u8) -> u32;
+}
+#[panic_handler]
+fn panic(_panic: &core::panic::PanicInfo<'_>) -> ! {
+    unsafe { __rust_start_panic(core::ptr::null_mut()) };
+    core::intrinsics::abort();
+}
+#[start]
+fn start(argc: isize, _argv: *const *const u8) -> isize {
+    unsafe { puts("Panicking...\0".as_ptr()) };
+    panic!("Oh no, {argc} is not a valid argument count!");
+}
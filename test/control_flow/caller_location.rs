@@ -0,0 +1,14 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+#[track_caller]
+fn location()->&'static core::panic::Location<'static>{
+    core::panic::Location::caller()
+}
+fn main(){
+    let loc = black_box(location());
+    test_eq!(loc.line(),10);
+    test_eq!(loc.column(),25);
+    test!(loc.file().ends_with("caller_location.rs"));
+}
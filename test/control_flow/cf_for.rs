@@ -9,4 +9,25 @@ fn main(){
         rustc_clr_interop_managed_call1_::<"System.Console","System.Console",false,"WriteLine",true,(),i32>(i);
     }
     test_eq!(black_box(i),1234);
+    // Ranges of other integer types, including empty ones and ones ending at the maximum value.
+    let mut sum = 0_u64;
+    for j in black_box(5_u64)..black_box(10){
+        sum += j;
+    }
+    test_eq!(black_box(sum),35);
+    let mut count = 0;
+    for _ in black_box(10_i8)..black_box(-10){
+        count += 1;
+    }
+    test_eq!(black_box(count),0);
+    for j in black_box(-128_i8)..black_box(127){
+        count += 1;
+        test_eq!(black_box(j),(-129 + count) as i8);
+    }
+    test_eq!(black_box(count),255);
+    let mut last = 0;
+    for j in black_box(usize::MAX - 3)..black_box(usize::MAX){
+        last = j;
+    }
+    test_eq!(black_box(last),usize::MAX - 1);
 }
@@ -0,0 +1,27 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+fn mul(a: u32, b: u32) -> u32 {
+    a * b
+}
+static mut CALLS: u32 = 0;
+fn count() {
+    unsafe { CALLS += 1 };
+}
+fn apply(op: fn(u32, u32) -> u32, a: u32, b: u32) -> u32 {
+    op(a, b)
+}
+fn main() {
+    let ops: [fn(u32, u32) -> u32; 2] = [add, mul];
+    test_eq!(black_box(ops[black_box(0)])(3, 4), 7);
+    test_eq!(black_box(ops[black_box(1)])(3, 4), 12);
+    test_eq!(apply(black_box(mul), 5, 6), 30);
+    let callback: fn() = black_box(count);
+    callback();
+    callback();
+    test_eq!(unsafe { CALLS }, 2);
+}
@@ -0,0 +1,107 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+fn dense(val:u8)->u8{
+    match val{
+        0=>10,
+        1=>11,
+        2=>12,
+        3=>13,
+        5=>15,
+        _=>0,
+    }
+}
+fn sparse(val:i32)->i32{
+    match val{
+        -1000=>1,
+        7=>2,
+        1_000_000=>3,
+        _=>0,
+    }
+}
+// Enough sparse values to be binary searched, mixed with a dense run handled by a jump table.
+fn many_sparse(val:i32)->i32{
+    match val{
+        -70_000=>1,
+        -5=>2,
+        0=>3,
+        1=>4,
+        2=>5,
+        3=>6,
+        40=>7,
+        900=>8,
+        12_345=>9,
+        1_000_000=>10,
+        i32::MAX=>11,
+        _=>0,
+    }
+}
+fn many_sparse_wide(val:u64)->u8{
+    match val{
+        1=>1,
+        1_000=>2,
+        1_000_000=>3,
+        1_000_000_000=>4,
+        0x8000_0000_0000_0000=>5,
+        u64::MAX=>6,
+        _=>0,
+    }
+}
+fn signed(val:i8)->i8{
+    match val{
+        -2=>1,
+        -1=>2,
+        0=>3,
+        1=>4,
+        _=>0,
+    }
+}
+fn wide(val:u128)->u8{
+    match val{
+        0=>1,
+        0xFFFF_FFFF_FFFF_FFFF_FFFF=>2,
+        _=>0,
+    }
+}
+fn letter(val:char)->u8{
+    match val{
+        'a'=>1,
+        'b'=>2,
+        'c'=>3,
+        '🦀'=>4,
+        _=>0,
+    }
+}
+fn main(){
+    test_eq!(dense(black_box(2)),12);
+    test_eq!(dense(black_box(4)),0);
+    test_eq!(dense(black_box(5)),15);
+    test_eq!(dense(black_box(200)),0);
+    test_eq!(sparse(black_box(-1000)),1);
+    test_eq!(sparse(black_box(1_000_000)),3);
+    test_eq!(sparse(black_box(8)),0);
+    test_eq!(many_sparse(black_box(-70_000)),1);
+    test_eq!(many_sparse(black_box(-5)),2);
+    test_eq!(many_sparse(black_box(2)),5);
+    test_eq!(many_sparse(black_box(40)),7);
+    test_eq!(many_sparse(black_box(12_345)),9);
+    test_eq!(many_sparse(black_box(i32::MAX)),11);
+    test_eq!(many_sparse(black_box(41)),0);
+    test_eq!(many_sparse(black_box(-6)),0);
+    test_eq!(many_sparse(black_box(i32::MIN)),0);
+    test_eq!(many_sparse_wide(black_box(1)),1);
+    test_eq!(many_sparse_wide(black_box(1_000_000_000)),4);
+    test_eq!(many_sparse_wide(black_box(0x8000_0000_0000_0000)),5);
+    test_eq!(many_sparse_wide(black_box(u64::MAX)),6);
+    test_eq!(many_sparse_wide(black_box(2)),0);
+    test_eq!(many_sparse_wide(black_box(u64::MAX - 1)),0);
+    test_eq!(signed(black_box(-2)),1);
+    test_eq!(signed(black_box(1)),4);
+    test_eq!(signed(black_box(-128)),0);
+    test_eq!(wide(black_box(0xFFFF_FFFF_FFFF_FFFF_FFFF)),2);
+    test_eq!(wide(black_box(0xFFFF_FFFF_FFFF_FFFF)),0);
+    test_eq!(letter(black_box('c')),3);
+    test_eq!(letter(black_box('🦀')),4);
+    test_eq!(letter(black_box('d')),0);
+}
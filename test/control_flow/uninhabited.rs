@@ -0,0 +1,28 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start,never_type)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code,unreachable_code)]
+#![no_std]
+include!("../common.rs");
+enum Empty{}
+fn diverge()->!{
+    core::intrinsics::abort();
+}
+fn take_empty(empty:Empty)->u32{
+    match empty{}
+}
+fn maybe_empty(val:Result<u32,Empty>)->u32{
+    match val{
+        Ok(val)=>val,
+        Err(empty)=>take_empty(empty),
+    }
+}
+fn main(){
+    test_eq!(maybe_empty(Ok(black_box(5))),5);
+    if black_box(false){
+        diverge();
+    }
+    let val = match black_box(Some(1_u8)){
+        Some(val) => val,
+        None => unsafe{core::hint::unreachable_unchecked()},
+    };
+    test_eq!(val,1);
+}
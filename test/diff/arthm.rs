@@ -0,0 +1,29 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+include!("output.rs");
+fn main() {
+    let a = black_box(1234_i64);
+    let b = black_box(-56_i64);
+    put_i64(a + b);
+    put_i64(a - b);
+    put_i64(a * b);
+    put_i64(a / b);
+    put_i64(a % b);
+    put_i64(a << 3);
+    put_i64(b >> 2);
+    put_i64(a & b);
+    put_i64(a | b);
+    put_i64(a ^ b);
+    let c = black_box(200_u8);
+    put_i64(c.wrapping_add(100) as i64);
+    put_i64(c.wrapping_mul(3) as i64);
+    put_i64((c as i8) as i64);
+    let d = black_box(-7_i32);
+    put_i64(d.wrapping_neg() as i64);
+    put_i64((d as u32) as i64);
+    put_i64(d.rem_euclid(3) as i64);
+    put_i64(u16::MAX.wrapping_add(black_box(2)) as i64);
+    put_i64(black_box(i64::MAX).wrapping_add(1));
+}
@@ -0,0 +1,44 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+include!("output.rs");
+struct Point {
+    x: i32,
+    y: i32,
+}
+impl Point {
+    fn manhattan(&self) -> i64 {
+        (self.x.abs() + self.y.abs()) as i64
+    }
+}
+fn checked_div(a: i32, b: i32) -> Option<i32> {
+    if b == 0 {
+        None
+    } else {
+        Some(a / b)
+    }
+}
+fn main() {
+    let point = black_box(Point { x: -3, y: 7 });
+    put_i64(point.manhattan());
+    let values = black_box([5_i64, -2, 9, 4]);
+    let mut max = i64::MIN;
+    for val in values.iter() {
+        if *val > max {
+            max = *val;
+        }
+    }
+    put_i64(max);
+    put_i64(values.len() as i64);
+    match checked_div(black_box(10), black_box(0)) {
+        Some(val) => put_i64(val as i64),
+        None => put_i64(-1),
+    }
+    put_i64(checked_div(black_box(10), black_box(3)).unwrap_or(0) as i64);
+    let tuple = black_box((1_u8, 2_i16, 3_u32));
+    put_i64(tuple.0 as i64 + tuple.1 as i64 + tuple.2 as i64);
+    let text = black_box("differential");
+    put_i64(text.len() as i64);
+    put_i64(text.as_bytes()[3] as i64);
+}
@@ -0,0 +1,47 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+include!("output.rs");
+fn collatz(mut n: u64) -> i64 {
+    let mut steps = 0;
+    while n != 1 {
+        n = if n % 2 == 0 { n / 2 } else { 3 * n + 1 };
+        steps += 1;
+    }
+    steps
+}
+fn classify(val: u32) -> i64 {
+    match val {
+        0 => 100,
+        1 | 2 => 200,
+        3..=9 => 300,
+        10 => 400,
+        _ => 500,
+    }
+}
+fn fib(n: u32) -> i64 {
+    if n < 2 {
+        n as i64
+    } else {
+        fib(n - 1) + fib(n - 2)
+    }
+}
+fn main() {
+    put_i64(collatz(black_box(27)));
+    for val in 0..12 {
+        put_i64(classify(black_box(val)));
+    }
+    put_i64(fib(black_box(15)));
+    let mut sum = 0;
+    for i in 0..black_box(100_i64) {
+        if i % 3 == 0 {
+            continue;
+        }
+        if i > 90 {
+            break;
+        }
+        sum += i;
+    }
+    put_i64(sum);
+}
@@ -0,0 +1,22 @@
+/// Prints `val` followed by a new line. Uses only `puts`, so it behaves the same when compiled natively and for the CLR.
+#[allow(dead_code)]
+fn put_i64(val: i64) {
+    // The last byte is the nul terminator.
+    let mut buff = [0_u8; 24];
+    let mut idx = buff.len() - 1;
+    let negative = val < 0;
+    let mut val = val.unsigned_abs();
+    loop {
+        idx -= 1;
+        buff[idx] = b'0' + (val % 10) as u8;
+        val /= 10;
+        if val == 0 {
+            break;
+        }
+    }
+    if negative {
+        idx -= 1;
+        buff[idx] = b'-';
+    }
+    unsafe { puts(buff[idx..].as_ptr()) }
+}
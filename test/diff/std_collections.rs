@@ -0,0 +1,31 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+fn main() {
+    let mut primes = Vec::new();
+    for candidate in 2..100_u32 {
+        if primes.iter().all(|prime| candidate % prime != 0) {
+            primes.push(candidate);
+        }
+    }
+    println!("{} primes below 100, the last one is {:?}", primes.len(), primes.last());
+    let words = "the quick brown fox jumps over the lazy dog the end";
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for word in words.split_whitespace() {
+        *counts.entry(word).or_default() += 1;
+    }
+    for (word, count) in &counts {
+        println!("{word}: {count}");
+    }
+    let letters: BTreeSet<char> = words.chars().filter(|c| c.is_alphabetic()).collect();
+    println!("{} distinct letters", letters.len());
+    let mut queue: VecDeque<i32> = (1..=5).collect();
+    queue.rotate_left(2);
+    queue.push_front(0);
+    println!("{queue:?}");
+    let mut sorted = vec![5, -3, 12, 0, 7, -8];
+    sorted.sort_unstable_by(|a: &i32, b| b.abs().cmp(&a.abs()));
+    println!("{sorted:?}");
+    let joined: String = primes.iter().take(5).map(u32::to_string).collect::<Vec<_>>().join(",");
+    println!("{joined}");
+    // Exit codes must match too.
+    std::process::exit(primes.len() as i32 % 7);
+}
@@ -0,0 +1,25 @@
+#[derive(Debug, Clone, Default)]
+struct Config {
+    name: String,
+    retries: u8,
+    ratio: f32,
+    tags: Vec<&'static str>,
+}
+fn main() {
+    let config = Config {
+        name: "diff".into(),
+        retries: 3,
+        ratio: 0.25,
+        tags: vec!["a", "b"],
+    };
+    println!("{config:?}");
+    println!("{config:#?}");
+    println!("{:?}", Config::default());
+    println!("[{:>8}] [{:<8}] [{:^8}] [{:08.3}]", "right", "left", "mid", -3.14159_f64);
+    println!("{:x} {:X} {:o} {:b} {:#x} {:+}", 255_u8, 48879_u32, 8_i64, 5_u16, 4096_usize, 17_i32);
+    println!("{} {} {}", i128::MIN, u64::MAX, f64::EPSILON);
+    println!("{:e} {:.0} {}", 1234.5_f64, 2.5_f32, 1.0e-7_f64);
+    let text = format!("{}-{}", 'x', true);
+    println!("{text} has {} bytes", text.len());
+    eprintln!("stderr is not compared");
+}
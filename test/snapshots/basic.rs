@@ -0,0 +1,21 @@
+#![no_std]
+// Small functions whose IL is snapshotted. Only functions with names starting with `snap_` are checked.
+pub fn snap_add(a: i32, b: i32) -> i32 {
+    a.wrapping_add(b)
+}
+pub fn snap_mul_add(a: u64, b: u64, c: u64) -> u64 {
+    a.wrapping_mul(b).wrapping_add(c)
+}
+pub fn snap_max(a: i64, b: i64) -> i64 {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+pub fn snap_is_zero(a: u8) -> bool {
+    a == 0
+}
+pub fn snap_swap(pair: (u32, u16)) -> (u16, u32) {
+    (pair.1, pair.0)
+}
@@ -0,0 +1,27 @@
+#[repr(align(64))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Aligned(u8);
+fn main() {
+    let mut vec = Vec::new();
+    for idx in 0..1000_u32 {
+        vec.push(idx);
+    }
+    assert_eq!(vec.len(), 1000);
+    assert_eq!(vec.iter().sum::<u32>(), 499_500);
+    // Reallocating must preserve the alignment.
+    let mut aligned = Vec::with_capacity(1);
+    for idx in 0..100 {
+        aligned.push(Aligned(idx));
+        assert_eq!(aligned.as_ptr() as usize % 64, 0);
+    }
+    assert_eq!(aligned[99], Aligned(99));
+    let zeroed = vec![0_u64; 256];
+    assert!(zeroed.iter().all(|value| *value == 0));
+    let mut string = String::new();
+    for _ in 0..100 {
+        string.push_str("Hello");
+    }
+    assert_eq!(string.len(), 500);
+    let msg = format!("{} {}", string.len(), vec[999]);
+    assert_eq!(msg, "500 999");
+}
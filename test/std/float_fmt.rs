@@ -0,0 +1,28 @@
+fn main() {
+    // The expected strings are the output of the same code compiled with the default backend.
+    assert_eq!(format!("{}", 0.1_f64), "0.1");
+    assert_eq!(format!("{}", 1.0_f64), "1");
+    assert_eq!(format!("{:?}", 1.0_f64), "1.0");
+    assert_eq!(format!("{}", -2.5_f32), "-2.5");
+    assert_eq!(format!("{}", 0.3_f32), "0.3");
+    assert_eq!(format!("{}", 1e21_f64), "1000000000000000000000");
+    assert_eq!(format!("{:?}", 1e21_f64), "1e21");
+    assert_eq!(format!("{:?}", 1.5e-7_f64), "1.5e-7");
+    assert_eq!(format!("{}", f64::MAX).len(), 309);
+    assert_eq!(format!("{:?}", f64::MIN_POSITIVE), "2.2250738585072014e-308");
+    assert_eq!(format!("{:?}", f32::EPSILON), "1.1920929e-7");
+    assert_eq!(format!("{:.3}", core::f64::consts::PI), "3.142");
+    assert_eq!(format!("{:e}", 1234.5_f64), "1.2345e3");
+    assert_eq!(format!("{}", f64::NAN), "NaN");
+    assert_eq!(format!("{}", f64::NEG_INFINITY), "-inf");
+    assert_eq!(format!("{:?}", -0.0_f64), "-0.0");
+    // Bit manipulation intrinsics, used while formatting.
+    assert_eq!(1_u64.leading_zeros(), 63);
+    assert_eq!(0_u8.leading_zeros(), 8);
+    assert_eq!(0_u16.trailing_zeros(), 16);
+    assert_eq!(0x8000_u16.trailing_zeros(), 15);
+    assert_eq!(0xF0_u8.count_ones(), 4);
+    assert_eq!(0x12_34_u16.swap_bytes(), 0x34_12);
+    assert_eq!(0x81_u8.rotate_left(1), 0x03);
+    assert_eq!(0x1234_5678_u32.rotate_right(8), 0x7812_3456);
+}
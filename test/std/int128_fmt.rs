@@ -0,0 +1,27 @@
+fn main() {
+    assert_eq!(format!("{}", u128::MAX), "340282366920938463463374607431768211455");
+    assert_eq!(format!("{}", i128::MIN), "-170141183460469231731687303715884105728");
+    assert_eq!(format!("{}", i128::MAX), "170141183460469231731687303715884105727");
+    assert_eq!(format!("{}", 0_u128), "0");
+    assert_eq!(format!("{}", -1_i128), "-1");
+    assert_eq!(format!("{}", 10_000_000_000_000_000_000_u128), "10000000000000000000");
+    assert_eq!(format!("{:x}", u128::MAX >> 4), "fffffffffffffffffffffffffffffff");
+    assert_eq!(format!("{:b}", 5_u128 << 100).len(), 103);
+    assert_eq!(format!("{:?}", -12345_i128), "-12345");
+    assert_eq!("340282366920938463463374607431768211455".parse::<u128>(), Ok(u128::MAX));
+    assert_eq!("-170141183460469231731687303715884105728".parse::<i128>(), Ok(i128::MIN));
+    assert_eq!("123456789012345678901234567890".parse::<i128>(), Ok(123456789012345678901234567890));
+    assert!("340282366920938463463374607431768211456".parse::<u128>().is_err());
+    assert!("-1".parse::<u128>().is_err());
+    // The arithmetic formatting and parsing relies on.
+    let big = 123456789012345678901234567890_u128;
+    assert_eq!(big / 10_000_000_000_000_000_000, 12345678901);
+    assert_eq!(big % 10_000_000_000_000_000_000, 2345678901234567890);
+    assert_eq!(-(big as i128) * -1, big as i128);
+    assert_eq!(!0_u128, u128::MAX);
+    assert!(u128::MAX > big && big >= 1 << 96);
+    assert_eq!((1_u128 << 100).leading_zeros(), 27);
+    assert_eq!((1_u128 << 100).trailing_zeros(), 100);
+    assert_eq!(u128::MAX.count_ones(), 128);
+    assert_eq!(1_u128.swap_bytes(), 1 << 120);
+}
@@ -0,0 +1,21 @@
+fn main() {
+    // Classification, using the lookup tables of `core::unicode`.
+    assert!('a'.is_alphabetic());
+    assert!('ß'.is_alphabetic());
+    assert!('中'.is_alphabetic());
+    assert!(!'1'.is_alphabetic());
+    assert!('٣'.is_numeric());
+    assert!('\u{2003}'.is_whitespace());
+    assert!('Ж'.is_uppercase());
+    assert!('ж'.is_lowercase());
+    assert!(!'\u{301}'.is_alphanumeric());
+    // Case mapping, including characters mapping to multiple characters.
+    assert_eq!('ж'.to_uppercase().to_string(), "Ж");
+    assert_eq!('ß'.to_uppercase().to_string(), "SS");
+    assert_eq!('İ'.to_lowercase().to_string(), "i\u{307}");
+    assert_eq!("Hello, Wörld!".to_uppercase(), "HELLO, WÖRLD!");
+    assert_eq!("ΣΑΣ".to_lowercase(), "σας");
+    assert_eq!("straße".to_ascii_uppercase(), "STRAßE");
+    assert_eq!("  trim me\u{3000}".trim(), "trim me");
+    assert_eq!("a1 b2".split_whitespace().count(), 2);
+}
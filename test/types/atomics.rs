@@ -0,0 +1,89 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+use core::sync::atomic::{AtomicBool, AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicU16, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+static FLAG: AtomicBool = AtomicBool::new(false);
+static SMALL: AtomicU8 = AtomicU8::new(0);
+static SIZE: AtomicUsize = AtomicUsize::new(8);
+static SIGNED: AtomicI32 = AtomicI32::new(-5);
+static WIDE: AtomicI64 = AtomicI64::new(-(1 << 40));
+// 8 and 16 bit atomics share their 4 byte word with their neighbours, which must stay untouched.
+static BYTES: [AtomicU8; 4] = [AtomicU8::new(1), AtomicU8::new(2), AtomicU8::new(3), AtomicU8::new(4)];
+static HALF: AtomicU16 = AtomicU16::new(0x8001);
+static SIGNED_BYTE: AtomicI8 = AtomicI8::new(-100);
+static SIGNED_HALF: AtomicI16 = AtomicI16::new(-30000);
+fn main() {
+    test_eq!(COUNTER.fetch_add(5, Ordering::SeqCst), 0);
+    test_eq!(COUNTER.load(Ordering::Acquire), 5);
+    test_eq!(COUNTER.fetch_sub(2, Ordering::Relaxed), 5);
+    test_eq!(COUNTER.load(Ordering::Relaxed), 3);
+    COUNTER.store(10, Ordering::Release);
+    test_eq!(COUNTER.swap(11, Ordering::AcqRel), 10);
+    test_eq!(COUNTER.compare_exchange(11, 12, Ordering::SeqCst, Ordering::SeqCst), Ok(11));
+    test_eq!(COUNTER.compare_exchange(11, 13, Ordering::SeqCst, Ordering::SeqCst), Err(12));
+    test_eq!(COUNTER.fetch_and(4, Ordering::SeqCst), 12);
+    test_eq!(COUNTER.fetch_or(1, Ordering::SeqCst), 4);
+    test_eq!(COUNTER.load(Ordering::SeqCst), 5);
+    core::sync::atomic::fence(Ordering::SeqCst);
+    test_eq!(FLAG.compare_exchange(false, true, Ordering::SeqCst, Ordering::Relaxed), Ok(false));
+    test!(FLAG.load(Ordering::SeqCst));
+    test_eq!(SMALL.fetch_add(200, Ordering::SeqCst), 0);
+    test_eq!(SMALL.fetch_add(100, Ordering::SeqCst), 200);
+    test_eq!(SMALL.load(Ordering::SeqCst), 44);
+    test_eq!(SIZE.fetch_add(8, Ordering::SeqCst), 8);
+    test_eq!(SIZE.swap(1, Ordering::SeqCst), 16);
+    // Operations lowered to `CompareExchange` loops.
+    test_eq!(COUNTER.fetch_xor(0b110, Ordering::SeqCst), 5);
+    test_eq!(COUNTER.fetch_nand(0b1, Ordering::SeqCst), 3);
+    test_eq!(COUNTER.load(Ordering::SeqCst), u32::MAX - 1);
+    test_eq!(COUNTER.fetch_min(7, Ordering::SeqCst), u32::MAX - 1);
+    test_eq!(COUNTER.fetch_max(9, Ordering::SeqCst), 7);
+    test_eq!(COUNTER.fetch_max(8, Ordering::SeqCst), 9);
+    test_eq!(COUNTER.load(Ordering::SeqCst), 9);
+    test_eq!(SIGNED.fetch_max(3, Ordering::SeqCst), -5);
+    test_eq!(SIGNED.fetch_min(-7, Ordering::SeqCst), 3);
+    test_eq!(SIGNED.fetch_max(-8, Ordering::SeqCst), -7);
+    test_eq!(SIGNED.load(Ordering::SeqCst), -7);
+    test_eq!(WIDE.fetch_max(1, Ordering::SeqCst), -(1 << 40));
+    test_eq!(WIDE.fetch_min(-(1 << 41), Ordering::SeqCst), 1);
+    test_eq!(WIDE.load(Ordering::SeqCst), -(1 << 41));
+    test_eq!(SIZE.fetch_xor(3, Ordering::SeqCst), 1);
+    test_eq!(SIZE.fetch_max(usize::MAX, Ordering::SeqCst), 2);
+    test_eq!(SIZE.fetch_min(4, Ordering::SeqCst), usize::MAX);
+    test_eq!(SIZE.load(Ordering::SeqCst), 4);
+    test_eq!(SMALL.fetch_max(200, Ordering::SeqCst), 44);
+    test_eq!(SMALL.fetch_min(100, Ordering::SeqCst), 200);
+    test_eq!(SMALL.load(Ordering::SeqCst), 100);
+    // Values with the highest bit set.
+    test_eq!(SMALL.swap(200, Ordering::SeqCst), 100);
+    test_eq!(SMALL.compare_exchange(200, 250, Ordering::SeqCst, Ordering::SeqCst), Ok(200));
+    test_eq!(SMALL.compare_exchange(200, 1, Ordering::SeqCst, Ordering::SeqCst), Err(250));
+    test_eq!(SMALL.compare_exchange_weak(250, 128, Ordering::SeqCst, Ordering::SeqCst), Ok(250));
+    test_eq!(SMALL.fetch_sub(1, Ordering::SeqCst), 128);
+    test_eq!(SMALL.fetch_xor(0xFF, Ordering::SeqCst), 127);
+    test_eq!(SMALL.load(Ordering::SeqCst), 128);
+    test_eq!(BYTES[2].compare_exchange(3, 255, Ordering::SeqCst, Ordering::SeqCst), Ok(3));
+    test_eq!(BYTES[1].fetch_add(254, Ordering::SeqCst), 2);
+    test_eq!(BYTES[3].swap(0x80, Ordering::SeqCst), 4);
+    test_eq!(BYTES[0].fetch_nand(0, Ordering::SeqCst), 1);
+    test_eq!(BYTES[0].load(Ordering::SeqCst), 255);
+    test_eq!(BYTES[1].load(Ordering::SeqCst), 0);
+    test_eq!(BYTES[2].load(Ordering::SeqCst), 255);
+    test_eq!(BYTES[3].load(Ordering::SeqCst), 0x80);
+    test_eq!(HALF.compare_exchange(0x8001, 0xFFFF, Ordering::SeqCst, Ordering::SeqCst), Ok(0x8001));
+    test_eq!(HALF.compare_exchange(0x8001, 0, Ordering::SeqCst, Ordering::SeqCst), Err(0xFFFF));
+    test_eq!(HALF.fetch_add(0x8001, Ordering::SeqCst), 0xFFFF);
+    test_eq!(HALF.swap(0x9000, Ordering::SeqCst), 0x8000);
+    test_eq!(HALF.fetch_max(0x8FFF, Ordering::SeqCst), 0x9000);
+    test_eq!(HALF.fetch_min(0x8FFF, Ordering::SeqCst), 0x9000);
+    test_eq!(HALF.load(Ordering::SeqCst), 0x8FFF);
+    test_eq!(SIGNED_BYTE.compare_exchange(-100, -128, Ordering::SeqCst, Ordering::SeqCst), Ok(-100));
+    test_eq!(SIGNED_BYTE.fetch_max(-1, Ordering::SeqCst), -128);
+    test_eq!(SIGNED_BYTE.fetch_add(-2, Ordering::SeqCst), -1);
+    test_eq!(SIGNED_BYTE.load(Ordering::SeqCst), -3);
+    test_eq!(SIGNED_HALF.fetch_min(-32768, Ordering::SeqCst), -30000);
+    test_eq!(SIGNED_HALF.compare_exchange(-32768, 5, Ordering::SeqCst, Ordering::SeqCst), Ok(-32768));
+    test_eq!(SIGNED_HALF.load(Ordering::SeqCst), 5);
+}
@@ -0,0 +1,24 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+fn main(){
+    let a = black_box('a');
+    test_eq!(a as u32,97);
+    test_eq!(a,'a');
+    test_ne!(a,'b');
+    test!(a < 'b');
+    test!('\u{10FFFF}' > a);
+    let from_byte = black_box(98_u8) as char;
+    test_eq!(from_byte,'b');
+    test_eq!(char::from_u32(black_box(0x61)),Some('a'));
+    test_eq!(char::from_u32(black_box(0xD800)),None);
+    test_eq!(char::from_u32(black_box(0x110000)),None);
+    let crab = black_box('🦀');
+    test_eq!(crab as u32,0x1F980);
+    test_eq!(crab.len_utf8(),4);
+    test_eq!(unsafe{char::from_u32_unchecked(black_box(0x1F980))},crab);
+    test_eq!(char::try_from(black_box(0xDFFF_u32)).is_err(),true);
+    test_eq!(u32::from(black_box('\u{D7FF}')),0xD7FF);
+    test_eq!(char::from_digit(black_box(7),10),Some('7'));
+}
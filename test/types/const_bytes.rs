@@ -0,0 +1,89 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+struct Mixed {
+    a: u8,
+    b: u32,
+    c: u16,
+    d: i64,
+    e: f32,
+}
+const MIXED: Mixed = Mixed {
+    a: 0x01,
+    b: 0x0504_0302,
+    c: 0x0706,
+    d: -2,
+    e: 1.5,
+};
+const TUPLE: (u8, u64, i16) = (0x11, 0x1918_1716_1514_1312, -3);
+// `Padded` is 16 bytes big, but its fields only take up 9 of them, so nested fields must be sliced using its real size.
+struct Padded {
+    wide: u64,
+    narrow: u8,
+}
+struct Nested {
+    first: Padded,
+    tail: u32,
+    array: [Padded; 2],
+    pair: (Padded, u16),
+}
+const NESTED: Nested = Nested {
+    first: Padded {
+        wide: 0x0102_0304_0506_0708,
+        narrow: 0x09,
+    },
+    tail: 0x0A0B_0C0D,
+    array: [
+        Padded {
+            wide: 0x1112_1314_1516_1718,
+            narrow: 0x19,
+        },
+        Padded {
+            wide: 0x2122_2324_2526_2728,
+            narrow: 0x29,
+        },
+    ],
+    pair: (
+        Padded {
+            wide: 0x3132_3334_3536_3738,
+            narrow: 0x39,
+        },
+        0x3A3B,
+    ),
+};
+fn main() {
+    let mixed = black_box(MIXED);
+    test_eq!(mixed.a, 0x01);
+    test_eq!(mixed.b, 0x0504_0302);
+    test_eq!(mixed.c, 0x0706);
+    test_eq!(mixed.d, -2);
+    test_eq!(mixed.e, 1.5);
+    let b: [u8; 4] = unsafe { core::mem::transmute(mixed.b) };
+    test_eq!(b[0], 0x02);
+    test_eq!(b[1], 0x03);
+    test_eq!(b[2], 0x04);
+    test_eq!(b[3], 0x05);
+    let c: [u8; 2] = unsafe { core::mem::transmute(mixed.c) };
+    test_eq!(c[0], 0x06);
+    test_eq!(c[1], 0x07);
+    let tuple = black_box(TUPLE);
+    test_eq!(tuple.0, 0x11);
+    test_eq!(tuple.1, 0x1918_1716_1514_1312);
+    test_eq!(tuple.2, -3);
+    let bytes: [u8; 8] = unsafe { core::mem::transmute(tuple.1) };
+    for (idx, byte) in bytes.iter().enumerate() {
+        test_eq!(*byte, 0x12 + idx as u8);
+    }
+    let nested = black_box(NESTED);
+    test_eq!(nested.first.wide, 0x0102_0304_0506_0708);
+    test_eq!(nested.first.narrow, 0x09);
+    test_eq!(nested.tail, 0x0A0B_0C0D);
+    test_eq!(nested.array[0].wide, 0x1112_1314_1516_1718);
+    test_eq!(nested.array[0].narrow, 0x19);
+    test_eq!(nested.array[1].wide, 0x2122_2324_2526_2728);
+    test_eq!(nested.array[1].narrow, 0x29);
+    test_eq!(nested.pair.0.wide, 0x3132_3334_3536_3738);
+    test_eq!(nested.pair.0.narrow, 0x39);
+    test_eq!(nested.pair.1, 0x3A3B);
+}
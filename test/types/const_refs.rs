@@ -0,0 +1,25 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+struct Named {
+    name: &'static str,
+    value: u32,
+}
+static FIRST: u32 = 7;
+static SECOND: u32 = 11;
+static BOTH: [&u32; 2] = [&FIRST, &SECOND];
+static NAMED: Named = Named {
+    name: "hello",
+    value: 3,
+};
+static NESTED: &&u32 = &&FIRST;
+fn main() {
+    test_eq!(*BOTH[0], 7);
+    test_eq!(*BOTH[1], 11);
+    test_eq!(NAMED.value, 3);
+    test_eq!(NAMED.name.len(), 5);
+    test_eq!(NAMED.name.as_bytes()[0], b'h');
+    test_eq!(NAMED.name.as_bytes()[4], b'o');
+    test_eq!(**NESTED, 7);
+}
@@ -0,0 +1,55 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+trait Shape{
+    fn area(&self)->u32;
+    fn scale(&mut self,by:u32);
+}
+struct Square(u32);
+struct Rect{w:u32,h:u32}
+impl Shape for Square{
+    fn area(&self)->u32{
+        self.0 * self.0
+    }
+    fn scale(&mut self,by:u32){
+        self.0 *= by;
+    }
+}
+impl Shape for Rect{
+    fn area(&self)->u32{
+        self.w * self.h
+    }
+    fn scale(&mut self,by:u32){
+        self.w *= by;
+        self.h *= by;
+    }
+}
+fn total_area(shapes:&[&dyn Shape])->u32{
+    let mut total = 0;
+    let mut idx = 0;
+    while idx < shapes.len(){
+        total += shapes[idx].area();
+        idx += 1;
+    }
+    total
+}
+fn call_fn(f:&dyn Fn(u32)->u32,arg:u32)->u32{
+    f(arg)
+}
+fn main(){
+    let square = Square(black_box(3));
+    let mut rect = Rect{w:black_box(2),h:4};
+    let shape:&dyn Shape = black_box(&square);
+    test_eq!(shape.area(),9);
+    // A pointer to a trait object is the data pointer, followed by the vtable.
+    test_eq!(core::mem::size_of::<&dyn Shape>(),2 * core::mem::size_of::<usize>());
+    {
+        let shape:&mut dyn Shape = black_box(&mut rect);
+        shape.scale(2);
+    }
+    test_eq!(rect.area(),32);
+    test_eq!(total_area(&[&square,&rect]),41);
+    let offset = black_box(5);
+    test_eq!(call_fn(&|x|x + offset,2),7);
+}
@@ -0,0 +1,26 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+fn mul(a: u32, b: u32) -> u32 {
+    a * b
+}
+static OPS: [fn(u32, u32) -> u32; 2] = [add, mul];
+struct Callbacks {
+    on_value: fn(u32, u32) -> u32,
+    value: u32,
+}
+static CALLBACKS: Callbacks = Callbacks {
+    on_value: mul,
+    value: 6,
+};
+fn main() {
+    test_ne!(OPS[0] as usize, 0);
+    test_ne!(OPS[1] as usize, 0);
+    test_ne!(OPS[0] as usize, OPS[1] as usize);
+    test_eq!(OPS[1] as usize, CALLBACKS.on_value as usize);
+    test_eq!(CALLBACKS.value, 6);
+}
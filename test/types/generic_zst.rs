@@ -0,0 +1,34 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+// Compiled with `generic_types`: generic structs with ZST fields must fall back to a type per instance.
+use core::marker::PhantomData;
+#[derive(Clone,Copy)]
+struct Tagged<T>{
+    value:u32,
+    marker:PhantomData<T>,
+}
+#[derive(Clone,Copy)]
+struct Pair<A,B>{
+    first:A,
+    second:B,
+}
+#[derive(Clone,Copy)]
+struct Holder<T>{
+    // A ZST only for some arguments.
+    pair:Pair<T,PhantomData<T>>,
+    tagged:Tagged<T>,
+}
+fn main(){
+    let tagged:Tagged<u64> = black_box(Tagged{value:7,marker:PhantomData});
+    test_eq!(tagged.value,7);
+    let pair:Pair<u32,u16> = black_box(Pair{first:1,second:2});
+    test_eq!(pair.first,1);
+    test_eq!(pair.second,2);
+    let zst_pair:Pair<u32,()> = black_box(Pair{first:3,second:()});
+    test_eq!(zst_pair.first,3);
+    let holder:Holder<i8> = black_box(Holder{pair:Pair{first:-4,second:PhantomData},tagged:Tagged{value:5,marker:PhantomData}});
+    test_eq!(holder.pair.first,-4);
+    test_eq!(holder.tagged.value,5);
+}
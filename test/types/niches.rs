@@ -0,0 +1,52 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+#[derive(Clone,Copy,PartialEq,Eq)]
+enum Wide{
+    A = 1000,
+    B = -5,
+}
+#[derive(Clone,Copy)]
+enum Shape{
+    Point,
+    Circle(f32),
+    Rect(f32,f32),
+}
+// Stored in the niche of the `bool`.
+#[derive(Clone,Copy,PartialEq,Eq)]
+enum Flag{
+    On(bool),
+    Unset,
+    Unknown,
+}
+const UNKNOWN:Flag = Flag::Unknown;
+const CIRCLE:Shape = Shape::Circle(2.0);
+fn area(shape:Shape)->f32{
+    match shape{
+        Shape::Point=>0.0,
+        Shape::Circle(r)=>r * r * 3.0,
+        Shape::Rect(w,h)=>w * h,
+    }
+}
+fn main(){
+    let value = 8_u32;
+    // The null value of the reference is used as the tag of `None`.
+    test_eq!(core::mem::size_of::<Option<&u32>>(),core::mem::size_of::<&u32>());
+    let some = black_box(Some(&value));
+    test_eq!(*some.unwrap(),8);
+    let none:Option<&u32> = black_box(None);
+    test!(none.is_none());
+    test_eq!(unsafe{core::mem::transmute::<Option<&u32>,usize>(none)},0);
+    test_eq!(core::mem::size_of::<Flag>(),1);
+    test!(black_box(Flag::On(true)) == Flag::On(true));
+    test!(black_box(Flag::On(false)) != Flag::Unset);
+    test!(black_box(UNKNOWN) == Flag::Unknown);
+    test!(black_box(Flag::Unset) != Flag::Unknown);
+    // Explicit discriminants, which don't fit in a byte.
+    test_eq!(black_box(Wide::A) as i32,1000);
+    test_eq!(black_box(Wide::B) as i32,-5);
+    test_eq!(area(black_box(Shape::Rect(2.0,3.0))),6.0);
+    test_eq!(area(black_box(CIRCLE)),12.0);
+    test_eq!(area(black_box(Shape::Point)),0.0);
+}
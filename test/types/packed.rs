@@ -0,0 +1,36 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+#[repr(packed)]
+#[derive(Clone,Copy)]
+struct Packed{
+    tag:u8,
+    value:u64,
+    other:u32,
+}
+#[derive(Clone,Copy)]
+struct Inner{
+    a:u16,
+    b:u64,
+}
+#[repr(packed)]
+#[derive(Clone,Copy)]
+struct Outer{
+    tag:u8,
+    inner:Inner,
+}
+fn main(){
+    let mut packed = black_box(Packed{tag:1,value:0xDEAD_BEEF_CAFE,other:7});
+    test_eq!({packed.value},0xDEAD_BEEF_CAFE);
+    packed.value += 1;
+    test_eq!({packed.value},0xDEAD_BEEF_CAFF);
+    test_eq!({packed.other},7);
+    let ptr = black_box(core::ptr::addr_of_mut!(packed));
+    unsafe{(*ptr).other = 8};
+    test_eq!(unsafe{(*ptr).other},8);
+    let mut outer = black_box(Outer{tag:2,inner:Inner{a:3,b:4}});
+    outer.inner.b = 5;
+    test_eq!({outer.inner.b},5);
+    test_eq!({outer.inner.a},3);
+}
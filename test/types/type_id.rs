@@ -0,0 +1,16 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+use core::any::TypeId;
+struct Marker;
+fn id_of<T: 'static>() -> TypeId {
+    black_box(TypeId::of::<T>())
+}
+fn main(){
+    test_eq!(id_of::<u32>() == id_of::<u32>(), true);
+    test_eq!(id_of::<u32>() == id_of::<i32>(), false);
+    test_eq!(id_of::<Marker>() == TypeId::of::<Marker>(), true);
+    test_eq!(id_of::<&'static str>() == id_of::<*const u8>(), false);
+    test_eq!(core::any::type_name::<u32>().len(), 3);
+}
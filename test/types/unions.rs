@@ -0,0 +1,28 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+#[derive(Clone,Copy)]
+union IntOrFloat{
+    int:u32,
+    float:f32,
+}
+#[derive(Clone,Copy)]
+union Padded{
+    small:u8,
+    big:[u8;3],
+    wide:u16,
+}
+const ONE:IntOrFloat = IntOrFloat{float:1.0};
+fn main(){
+    let mut value = black_box(IntOrFloat{float:1.0});
+    test_eq!(unsafe{value.int},0x3F80_0000);
+    value.int = 0x4000_0000;
+    test_eq!(unsafe{value.float},2.0);
+    test_eq!(unsafe{black_box(ONE).int},0x3F80_0000);
+    // The size of a union includes the padding needed by its alignment.
+    test_eq!(core::mem::size_of::<Padded>(),4);
+    let mut padded = black_box(Padded{big:[1,2,3]});
+    padded.small = 4;
+    test_eq!(unsafe{padded.big},[4,2,3]);
+}
@@ -0,0 +1,30 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+#[repr(packed)]
+struct Packed {
+    tag: u8,
+    value: u64,
+}
+fn main(){
+    let mut byte = 7_u8;
+    let mut wide = 0x1234_5678_9abc_def0_u64;
+    let mut pair = (1_u32, 2.5_f32);
+    unsafe {
+        test_eq!(core::ptr::read_volatile(&byte), 7);
+        core::ptr::write_volatile(&mut byte, 9);
+        test_eq!(byte, 9);
+        test_eq!(core::ptr::read_volatile(&wide), 0x1234_5678_9abc_def0);
+        core::ptr::write_volatile(&mut wide, u64::MAX);
+        test_eq!(wide, u64::MAX);
+        core::ptr::write_volatile(&mut pair, (3, 4.5));
+        let read = core::ptr::read_volatile(&pair);
+        test_eq!(read.0, 3);
+        test_eq!(read.1, 4.5);
+        let mut packed = Packed { tag: 1, value: 2 };
+        let value = core::ptr::addr_of_mut!(packed.value);
+        core::intrinsics::unaligned_volatile_store(value, 0xdead_beef);
+        test_eq!(core::intrinsics::unaligned_volatile_load(value), 0xdead_beef);
+    }
+}
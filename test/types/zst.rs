@@ -0,0 +1,41 @@
+#![feature(lang_items,adt_const_params,associated_type_defaults,core_intrinsics,start)]
+#![allow(internal_features,incomplete_features,unused_variables,dead_code)]
+#![no_std]
+include!("../common.rs");
+use core::marker::PhantomData;
+#[derive(Clone,Copy)]
+struct Empty;
+#[derive(Clone,Copy)]
+struct Tagged<T>{
+    value:u32,
+    marker:PhantomData<T>,
+    empty:Empty,
+    other:u8,
+}
+#[derive(Clone,Copy)]
+enum WithZst{
+    Some(Empty,u64),
+    None,
+}
+fn make_empty()->Empty{
+    Empty
+}
+fn main(){
+    let mut tagged:Tagged<u64> = black_box(Tagged{value:7,marker:PhantomData,empty:Empty,other:8});
+    test_eq!(core::mem::size_of::<Tagged<u64>>(),8);
+    test_eq!(tagged.value,7);
+    test_eq!(tagged.other,8);
+    tagged.value += 1;
+    tagged.empty = make_empty();
+    test_eq!(tagged.value,8);
+    let empty = black_box(&tagged.empty);
+    test!(!(empty as *const Empty).is_null());
+    let local = black_box(Empty);
+    let local_ptr = &local as *const Empty;
+    test_eq!(local_ptr as usize % core::mem::align_of::<Empty>(),0);
+    let with_zst = black_box(WithZst::Some(Empty,9));
+    match with_zst{
+        WithZst::Some(_,value)=>test_eq!(value,9),
+        WithZst::None=>core::intrinsics::abort(),
+    }
+}
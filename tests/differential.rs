@@ -0,0 +1,56 @@
+//! Differential tests: every program in `test/diff` is compiled with this backend and with the default(LLVM) backend, and both builds must
+//! write the same stdout and exit with the same code.
+//!
+//! The CLR builds run on `dotnet`, or on `mono` if `RCCLR_TEST_RUNTIME=mono` is set.
+use rustc_codegen_clr::{
+    config::Runtime,
+    differential::{compare_with_native, Toolchain},
+};
+use std::path::Path;
+/// Directory containing the test programs.
+const DIFF_DIR: &str = "./test/diff/";
+/// Ensures no two compilations of the same program run at the same time.
+static COMPILE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+fn compare(name: &str, optimize: bool) {
+    let lock = COMPILE_LOCK.lock();
+    // Cargo builds the linker for integration tests, next to the backend library.
+    let toolchain = Toolchain::in_dir(
+        Path::new(env!("CARGO_BIN_EXE_linker"))
+            .parent()
+            .expect("The linker is not inside a directory!"),
+    );
+    let runtime = match std::env::var("RCCLR_TEST_RUNTIME").as_deref() {
+        Ok("mono") => Runtime::Mono,
+        _ => Runtime::CoreCLR,
+    };
+    let result = compare_with_native(
+        &toolchain,
+        runtime.profile(),
+        Path::new(DIFF_DIR),
+        name,
+        optimize,
+    );
+    drop(lock);
+    if let Err(err) = result {
+        panic!("{err}");
+    }
+}
+macro_rules! diff_test {
+    ($name:ident) => {
+        mod $name {
+            #[test]
+            fn release() {
+                super::compare(stringify!($name), true);
+            }
+            #[test]
+            fn debug() {
+                super::compare(stringify!($name), false);
+            }
+        }
+    };
+}
+diff_test! {arthm}
+diff_test! {control_flow}
+diff_test! {basics}
+diff_test! {std_collections}
+diff_test! {std_fmt}